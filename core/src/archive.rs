@@ -0,0 +1,434 @@
+//! Archival of sealed log segments to object storage
+//! Each sealed segment is uploaded unchanged (still encrypted, if the log
+//! is) under `segments/NNNNNN.log`, then recorded with its SHA-256 in
+//! `MANIFEST.json`. Retention keeps a segment on disk until it has been
+//! archived, and a ledger can be restored from the archive alone.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::backup::MANIFEST_FILE;
+use crate::event_log::{segment_file_name, segment_number};
+use crate::{Ledger, LedgerError, LedgerOptions, SegmentSource};
+
+const ARCHIVE_MANIFEST_VERSION: u32 = 1;
+/// Highest segment number uploaded by this ledger.
+const ARCHIVED_KEY: &[u8] = b"archive/segment";
+
+/// Flat key/value object storage. [`DirStore`] covers local and mounted
+/// directories; `CloudStore` (feature `cloud`) covers S3 and GCS.
+pub trait ObjectStore: Send + Sync {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), LedgerError>;
+    /// `None` when there is no object under `key`.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, LedgerError>;
+}
+
+/// Objects as files under a directory.
+#[derive(Debug, Clone)]
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DirStore { dir: dir.into() }
+    }
+}
+
+impl ObjectStore for DirStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> Result<(), LedgerError> {
+        let path = self.dir.join(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write aside and rename so readers never see a partial object.
+        let tmp = path.with_extension("partial");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path).map_err(LedgerError::from)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, LedgerError> {
+        match fs::read(self.dir.join(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Store handle carried by options and the ledger.
+#[derive(Clone)]
+pub(crate) struct SharedStore(pub Arc<dyn ObjectStore>);
+
+impl fmt::Debug for SharedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ObjectStore")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedSegment {
+    pub number: u64,
+    pub bytes: u64,
+    /// Hex SHA-256 of the segment file.
+    pub sha256: String,
+    pub first_lsn: u64,
+    pub events: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    /// Oldest first.
+    pub segments: Vec<ArchivedSegment>,
+}
+
+/// Read side of an archive: the manifest and checksummed segments. Also a
+/// [`SegmentSource`], so a follower can replicate straight from a bucket.
+#[derive(Clone)]
+pub struct Archive {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl Archive {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
+        Archive { store }
+    }
+
+    /// The manifest, or an empty one if nothing has been archived yet.
+    pub fn manifest(&self) -> Result<ArchiveManifest, LedgerError> {
+        let Some(raw) = self.store.get(MANIFEST_FILE)? else {
+            return Ok(ArchiveManifest {
+                version: ARCHIVE_MANIFEST_VERSION,
+                segments: Vec::new(),
+            });
+        };
+        let manifest: ArchiveManifest = serde_json::from_slice(&raw)
+            .map_err(|e| LedgerError::Corruption(format!("invalid archive manifest: {}", e)))?;
+        if manifest.version != ARCHIVE_MANIFEST_VERSION {
+            return Err(LedgerError::Corruption(format!(
+                "unsupported archive manifest version {}",
+                manifest.version
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// Download an archived segment and check it against the manifest.
+    pub fn read(&self, entry: &ArchivedSegment) -> Result<Vec<u8>, LedgerError> {
+        let name = segment_file_name(entry.number);
+        let raw = self
+            .store
+            .get(&segment_key(entry.number))?
+            .ok_or_else(|| LedgerError::Corruption(format!("archived {} is missing", name)))?;
+        if raw.len() as u64 != entry.bytes || sha256(&raw) != entry.sha256 {
+            return Err(LedgerError::Corruption(format!(
+                "archived {} does not match its checksum",
+                name
+            )));
+        }
+        Ok(raw)
+    }
+}
+
+impl SegmentSource for Archive {
+    fn segments(&self) -> Result<Vec<u64>, LedgerError> {
+        Ok(self.manifest()?.segments.iter().map(|s| s.number).collect())
+    }
+
+    fn fetch(&self, n: u64) -> Result<Vec<u8>, LedgerError> {
+        let manifest = self.manifest()?;
+        let entry = manifest
+            .segments
+            .iter()
+            .find(|s| s.number == n)
+            .ok_or_else(|| {
+                LedgerError::InvalidArgument(format!("segment {} is not archived", n))
+            })?;
+        self.read(entry)
+    }
+}
+
+fn segment_key(n: u64) -> String {
+    format!("segments/{}", segment_file_name(n))
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+impl Ledger {
+    /// Upload sealed segments not archived yet; returns their numbers.
+    /// Runs on every rotation; call it to retry after a failed upload.
+    pub fn archive_sealed(&self) -> Result<Vec<u64>, LedgerError> {
+        self.ensure_writable()?;
+        let _guard = self.write_lock.lock()?;
+        self.archive_segments()
+    }
+
+    /// Caller must hold the write lock.
+    pub(crate) fn archive_segments(&self) -> Result<Vec<u64>, LedgerError> {
+        let Some(store) = &self.archive else {
+            return Ok(Vec::new());
+        };
+        let archive = Archive::new(store.0.clone());
+        let mut manifest = archive.manifest()?;
+        let archived = self.archived_through()?;
+        let mut next_lsn = manifest
+            .segments
+            .last()
+            .map_or(self.pruned_prefix()?.events, |s| s.first_lsn + s.events);
+        let mut uploaded = Vec::new();
+        for path in self.log.sealed_segments()? {
+            let Some(n) = segment_number(&path).filter(|&n| n > archived) else {
+                continue;
+            };
+            let raw = fs::read(&path)?;
+            let events = self.log.decode_segment(&path, &raw)?.len() as u64;
+            // A crash between the manifest and the marker leaves it listed.
+            if manifest.segments.iter().all(|s| s.number != n) {
+                store.0.put(&segment_key(n), &raw)?;
+                manifest.segments.push(ArchivedSegment {
+                    number: n,
+                    bytes: raw.len() as u64,
+                    sha256: sha256(&raw),
+                    first_lsn: next_lsn,
+                    events,
+                });
+                store
+                    .0
+                    .put(MANIFEST_FILE, &serde_json::to_vec_pretty(&manifest)?)?;
+                uploaded.push(n);
+            }
+            next_lsn += events;
+            self.db.put(ARCHIVED_KEY, n.to_be_bytes())?;
+        }
+        Ok(uploaded)
+    }
+
+    /// Segments numbered up to this have been archived; `u64::MAX` when no
+    /// archive is configured, so retention is unconstrained.
+    pub(crate) fn archived_through(&self) -> Result<u64, LedgerError> {
+        if self.archive.is_none() {
+            return Ok(u64::MAX);
+        }
+        match self.db.get(ARCHIVED_KEY)? {
+            Some(raw) => raw
+                .as_slice()
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| LedgerError::Corruption("corrupt archive/segment".to_string())),
+            None => Ok(0),
+        }
+    }
+
+    /// Rebuild a ledger in the empty `target_dir` from the segments in
+    /// `store`, checking each against its checksum and the whole log
+    /// against its hash chain. The archive must reach back to LSN 0.
+    pub fn restore_from_archive<P: AsRef<Path>>(
+        store: Arc<dyn ObjectStore>,
+        target_dir: P,
+        options: &LedgerOptions,
+    ) -> Result<Ledger, LedgerError> {
+        let target_dir = target_dir.as_ref();
+        if target_dir.exists() && fs::read_dir(target_dir)?.next().is_some() {
+            return Err(LedgerError::InvalidArgument(format!(
+                "restore target {} is not empty",
+                target_dir.display()
+            )));
+        }
+        let archive = Archive::new(store);
+        let manifest = archive.manifest()?;
+        check_contiguous(&manifest)?;
+        let segments_dir = target_dir.join("segments");
+        fs::create_dir_all(&segments_dir)?;
+        for entry in &manifest.segments {
+            fs::write(
+                segments_dir.join(segment_file_name(entry.number)),
+                archive.read(entry)?,
+            )?;
+        }
+
+        // Opening a fresh DB over the log backfills centroids and history.
+        let ledger = Ledger::with_options(target_dir, options)?;
+        ledger.rebuild_projections()?;
+        ledger.verify_chain()?;
+        if let Some(last) = manifest.segments.last() {
+            ledger.db.put(ARCHIVED_KEY, last.number.to_be_bytes())?;
+        }
+        Ok(ledger)
+    }
+}
+
+/// Fail unless the manifest's segments run from LSN 0 with no gaps.
+fn check_contiguous(manifest: &ArchiveManifest) -> Result<(), LedgerError> {
+    let mut next_lsn = 0;
+    for segment in &manifest.segments {
+        if segment.first_lsn != next_lsn {
+            return Err(LedgerError::Corruption(format!(
+                "archived segment {} starts at LSN {}, expected {}",
+                segment.number, segment.first_lsn, next_lsn
+            )));
+        }
+        next_lsn += segment.events;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "cloud")]
+mod cloud {
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use object_store::path::Path;
+    use tokio::runtime::Runtime;
+
+    use crate::LedgerError;
+
+    /// S3 or GCS bucket, addressed as `s3://bucket/prefix` or
+    /// `gs://bucket/prefix`. Credentials and region come from the usual
+    /// `AWS_*` / `GOOGLE_*` environment variables.
+    pub struct CloudStore {
+        runtime: Runtime,
+        store: Box<dyn object_store::ObjectStore>,
+        prefix: String,
+    }
+
+    fn cloud_error(e: object_store::Error) -> LedgerError {
+        LedgerError::Internal(format!("object store: {}", e))
+    }
+
+    impl CloudStore {
+        /// Call it outside any async runtime; the store drives its own.
+        pub fn from_url(url: &str) -> Result<Self, LedgerError> {
+            let invalid =
+                || LedgerError::InvalidArgument(format!("unsupported archive URL {}", url));
+            let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+            let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+            let store: Box<dyn object_store::ObjectStore> = match scheme {
+                "s3" => Box::new(
+                    AmazonS3Builder::from_env()
+                        .with_bucket_name(bucket)
+                        .build()
+                        .map_err(cloud_error)?,
+                ),
+                "gs" => Box::new(
+                    GoogleCloudStorageBuilder::from_env()
+                        .with_bucket_name(bucket)
+                        .build()
+                        .map_err(cloud_error)?,
+                ),
+                _ => return Err(invalid()),
+            };
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            Ok(CloudStore {
+                runtime,
+                store,
+                prefix: prefix.trim_matches('/').to_string(),
+            })
+        }
+
+        fn path(&self, key: &str) -> Path {
+            if self.prefix.is_empty() {
+                Path::from(key)
+            } else {
+                Path::from(format!("{}/{}", self.prefix, key))
+            }
+        }
+    }
+
+    impl super::ObjectStore for CloudStore {
+        fn put(&self, key: &str, bytes: &[u8]) -> Result<(), LedgerError> {
+            self.runtime
+                .block_on(self.store.put(&self.path(key), bytes.to_vec().into()))
+                .map(|_| ())
+                .map_err(cloud_error)
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>, LedgerError> {
+            self.runtime
+                .block_on(async {
+                    match self.store.get(&self.path(key)).await {
+                        Ok(object) => object.bytes().await.map(|b| Some(b.to_vec())),
+                        Err(object_store::Error::NotFound { .. }) => Ok(None),
+                        Err(e) => Err(e),
+                    }
+                })
+                .map_err(cloud_error)
+        }
+    }
+}
+
+#[cfg(feature = "cloud")]
+pub use cloud::CloudStore;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Follower, RetentionPolicy};
+
+    #[test]
+    fn sealed_segments_archive_and_restore_without_local_history() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store: Arc<dyn ObjectStore> = Arc::new(DirStore::new(tmp.path().join("bucket")));
+        let options = LedgerOptions::new()
+            .archive_store(store.clone())
+            .retention(RetentionPolicy::new().max_sealed_segments(1));
+        let ledger = Ledger::with_options(tmp.path().join("primary"), &options).unwrap();
+        for entity in 1..=3 {
+            ledger.anchor_batch(entity, &[(2, 2)]).unwrap();
+            ledger.log.seal().unwrap();
+        }
+        ledger.delete_entity(2).unwrap();
+        ledger.log.seal().unwrap();
+        assert_eq!(ledger.archive_sealed().unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(ledger.archive_sealed().unwrap(), Vec::<u64>::new());
+        ledger.apply_retention().unwrap();
+        assert_eq!(ledger.log.sealed_segments().unwrap().len(), 1);
+
+        let manifest = Archive::new(store.clone()).manifest().unwrap();
+        let lsns = manifest.segments.iter().map(|s| s.first_lsn);
+        assert!(lsns.eq([0, 1, 2, 3]));
+
+        let restored =
+            Ledger::restore_from_archive(store.clone(), tmp.path().join("restored"), &options)
+                .unwrap();
+        assert_eq!(restored.verify_chain().unwrap(), 4);
+        let head = |l: &Ledger| l.log.last_event().unwrap().unwrap().event_hash;
+        assert_eq!(head(&restored), head(&ledger));
+        assert_eq!(restored.current_exponents(3, &[2]).unwrap(), vec![Some(2)]);
+        assert!(restored.is_deleted(2).unwrap());
+        assert_eq!(restored.next_lsn().unwrap(), 4);
+
+        let follower =
+            Follower::open(tmp.path().join("follower"), Archive::new(store.clone())).unwrap();
+        assert_eq!(follower.poll().unwrap(), 4);
+
+        std::fs::write(tmp.path().join("bucket/segments/000002.log"), "{}\n").unwrap();
+        assert!(matches!(
+            Ledger::restore_from_archive(
+                Arc::new(DirStore::new(tmp.path().join("bucket"))),
+                tmp.path().join("again"),
+                &options
+            ),
+            Err(LedgerError::Corruption(_))
+        ));
+
+        let mut gapped = manifest.clone();
+        gapped.segments.remove(1);
+        store
+            .put(MANIFEST_FILE, &serde_json::to_vec(&gapped).unwrap())
+            .unwrap();
+        assert!(matches!(
+            Ledger::restore_from_archive(store, tmp.path().join("gapped"), &options),
+            Err(LedgerError::Corruption(msg)) if msg.contains("expected 1")
+        ));
+        assert!(!tmp.path().join("gapped").exists());
+    }
+}
@@ -0,0 +1,81 @@
+//! Tokio-friendly wrapper: RocksDB writes and log appends run on the
+//! blocking pool so async callers never stall the runtime.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crossbeam_channel::Receiver;
+
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+#[derive(Clone)]
+pub struct AsyncLedger {
+    inner: Arc<Ledger>,
+}
+
+impl AsyncLedger {
+    pub async fn open<P: Into<PathBuf>>(base_path: P) -> Result<Self, LedgerError> {
+        let base_path = base_path.into();
+        let ledger = blocking(move || Ledger::new(base_path)).await?;
+        Ok(AsyncLedger::from(ledger))
+    }
+
+    pub async fn anchor_batch(
+        &self,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let ledger = Arc::clone(&self.inner);
+        blocking(move || ledger.anchor_batch(entity, &commands)).await
+    }
+
+    pub async fn validate_batch(
+        &self,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let ledger = Arc::clone(&self.inner);
+        blocking(move || ledger.validate_batch(entity, &commands)).await
+    }
+
+    pub fn subscribe(&self) -> Receiver<LedgerEvent> {
+        self.inner.subscribe()
+    }
+
+    /// Synchronous access for cheap reads.
+    pub fn ledger(&self) -> &Ledger {
+        &self.inner
+    }
+}
+
+impl From<Ledger> for AsyncLedger {
+    fn from(ledger: Ledger) -> Self {
+        AsyncLedger {
+            inner: Arc::new(ledger),
+        }
+    }
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, LedgerError>
+where
+    F: FnOnce() -> Result<T, LedgerError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| LedgerError::Internal(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn anchors_without_blocking_the_runtime() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = AsyncLedger::open(tmp.path()).await.unwrap();
+        let events = ledger.anchor_batch(1, vec![(2, 2)]).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(ledger.ledger().verify_chain().unwrap(), 1);
+    }
+}
@@ -0,0 +1,305 @@
+//! Online backups: RocksDB checkpoint + sealed log segments + manifest
+
+use std::fs;
+use std::path::Path;
+
+use rocksdb::checkpoint::Checkpoint;
+use serde::{Deserialize, Serialize};
+
+use crate::event_log;
+use crate::{Ledger, LedgerError, LedgerOptions};
+
+pub const MANIFEST_FILE: &str = "MANIFEST.json";
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SegmentEntry {
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// Describes the contents of a backup directory.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: u64,
+    pub segments: Vec<SegmentEntry>,
+}
+
+impl Ledger {
+    /// Write a consistent backup into `dir` (which must not exist yet).
+    /// Writers are paused only while the active segment is sealed and the
+    /// RocksDB checkpoint is hard-linked; segment copies happen afterwards.
+    pub fn create_checkpoint<P: AsRef<Path>>(&self, dir: P) -> Result<BackupManifest, LedgerError> {
+        self.ensure_writable()?;
+        let dir = dir.as_ref();
+        if dir.exists() {
+            return Err(LedgerError::InvalidArgument(format!(
+                "backup target {} already exists",
+                dir.display()
+            )));
+        }
+        fs::create_dir_all(dir)?;
+
+        let segments = {
+            let _guard = self.write_lock.lock()?;
+            self.log.seal()?;
+            Checkpoint::new(&*self.db).and_then(|cp| cp.create_checkpoint(dir.join("db")))?;
+            self.log.sealed_segments()?
+        };
+
+        let segments_dir = dir.join("segments");
+        fs::create_dir_all(&segments_dir)?;
+        let mut entries = Vec::with_capacity(segments.len());
+        for src in &segments {
+            let name = file_name(src)?;
+            let bytes = fs::copy(src, segments_dir.join(&name))?;
+            entries.push(SegmentEntry { name, bytes });
+        }
+
+        // Compaction snapshots are needed to rebuild once the log is truncated.
+        let snapshots = self.log.base_dir().join("snapshots");
+        if snapshots.exists() {
+            copy_dir(&snapshots, &dir.join("snapshots"))?;
+        }
+
+        let manifest = BackupManifest {
+            version: MANIFEST_VERSION,
+            created_at: self.clock.now_millis(),
+            segments: entries,
+        };
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        fs::write(dir.join(MANIFEST_FILE), json)?;
+        Ok(manifest)
+    }
+}
+
+impl Ledger {
+    /// Restore a backup made by [`Ledger::create_checkpoint`] into `target_dir`.
+    ///
+    /// The manifest is validated against the files on disk before anything is
+    /// copied. Segments dropped into the backup after it was taken (and a
+    /// trailing `event.log`) form the log tail: they are copied across and
+    /// replayed on top of the checkpointed column families.
+    pub fn restore<P: AsRef<Path>, Q: AsRef<Path>>(
+        backup_dir: P,
+        target_dir: Q,
+    ) -> Result<Ledger, LedgerError> {
+        Ledger::restore_with_options(backup_dir, target_dir, &LedgerOptions::default())
+    }
+
+    /// [`Ledger::restore`], opening the restored ledger with `options`;
+    /// needed to read the log tail of an encrypted backup.
+    pub fn restore_with_options<P: AsRef<Path>, Q: AsRef<Path>>(
+        backup_dir: P,
+        target_dir: Q,
+        options: &LedgerOptions,
+    ) -> Result<Ledger, LedgerError> {
+        let backup_dir = backup_dir.as_ref();
+        let target_dir = target_dir.as_ref();
+
+        let manifest = read_manifest(backup_dir)?;
+        validate_backup(backup_dir, &manifest)?;
+        if target_dir.exists() && fs::read_dir(target_dir)?.next().is_some() {
+            return Err(LedgerError::InvalidArgument(format!(
+                "restore target {} is not empty",
+                target_dir.display()
+            )));
+        }
+
+        let listed = manifest
+            .segments
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        let tail_segments = event_log::list_segments(&backup_dir.join("segments"))?
+            .into_iter()
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| !listed.contains(&n))
+            })
+            .collect::<Vec<_>>();
+
+        copy_dir(&backup_dir.join("db"), &target_dir.join("db"))?;
+        let segments_dir = target_dir.join("segments");
+        fs::create_dir_all(&segments_dir)?;
+        for entry in &manifest.segments {
+            fs::copy(
+                backup_dir.join("segments").join(&entry.name),
+                segments_dir.join(&entry.name),
+            )?;
+        }
+        for src in &tail_segments {
+            fs::copy(src, segments_dir.join(file_name(src)?))?;
+        }
+        let active_tail = backup_dir.join("event.log");
+        if active_tail.exists() {
+            fs::copy(&active_tail, target_dir.join("event.log"))?;
+        }
+        if backup_dir.join("snapshots").exists() {
+            copy_dir(&backup_dir.join("snapshots"), &target_dir.join("snapshots"))?;
+        }
+
+        let ledger = Ledger::with_options(target_dir, options)?;
+        let mut tail = Vec::new();
+        for src in &tail_segments {
+            tail.extend(ledger.log.read_segment(src)?);
+        }
+        if active_tail.exists() {
+            tail.extend(ledger.log.read_segment(&active_tail)?);
+        }
+        ledger.replay_events(&tail)?;
+        Ok(ledger)
+    }
+}
+
+pub fn read_manifest(dir: &Path) -> Result<BackupManifest, LedgerError> {
+    let raw = fs::read(dir.join(MANIFEST_FILE))
+        .map_err(|e| LedgerError::Corruption(format!("cannot read backup manifest: {}", e)))?;
+    let manifest: BackupManifest = serde_json::from_slice(&raw)
+        .map_err(|e| LedgerError::Corruption(format!("invalid backup manifest: {}", e)))?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(LedgerError::Corruption(format!(
+            "unsupported backup manifest version {}",
+            manifest.version
+        )));
+    }
+    Ok(manifest)
+}
+
+fn validate_backup(dir: &Path, manifest: &BackupManifest) -> Result<(), LedgerError> {
+    if !dir.join("db").join("CURRENT").exists() {
+        return Err(LedgerError::Corruption(format!(
+            "backup {} has no RocksDB checkpoint",
+            dir.display()
+        )));
+    }
+    for entry in &manifest.segments {
+        let path = dir.join("segments").join(&entry.name);
+        let bytes = fs::metadata(&path)
+            .map_err(|_| {
+                LedgerError::Corruption(format!("backup segment {} is missing", entry.name))
+            })?
+            .len();
+        if bytes != entry.bytes {
+            return Err(LedgerError::Corruption(format!(
+                "backup segment {} is {} bytes, manifest says {}",
+                entry.name, bytes, entry.bytes
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> Result<(), LedgerError> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        let target = dst.join(entry.file_name());
+        if path.is_dir() {
+            copy_dir(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<String, LedgerError> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| LedgerError::Corruption(format!("invalid segment path {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AsOf;
+
+    #[test]
+    fn checkpoint_seals_log_and_writes_manifest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path().join("ledger")).unwrap();
+        ledger.anchor_batch(7, &[(2, 2), (3, 1)]).unwrap();
+
+        let backup = tmp.path().join("backup");
+        let manifest = ledger.create_checkpoint(&backup).unwrap();
+
+        assert_eq!(manifest.segments.len(), 1);
+        assert!(backup.join("db").join("CURRENT").exists());
+        assert!(backup
+            .join("segments")
+            .join(&manifest.segments[0].name)
+            .exists());
+        assert!(backup.join(MANIFEST_FILE).exists());
+        assert!(ledger.create_checkpoint(&backup).is_err());
+    }
+
+    #[test]
+    fn restore_replays_log_tail() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path().join("ledger")).unwrap();
+        ledger.anchor_batch(7, &[(2, 2)]).unwrap();
+        let backup = tmp.path().join("backup");
+        ledger.create_checkpoint(&backup).unwrap();
+
+        // Events committed after the checkpoint reach the backup as a tail.
+        ledger.anchor_batch(7, &[(2, 4)]).unwrap();
+        fs::copy(
+            tmp.path().join("ledger").join("event.log"),
+            backup.join("event.log"),
+        )
+        .unwrap();
+        drop(ledger);
+
+        let restored = Ledger::restore(&backup, tmp.path().join("restored")).unwrap();
+        assert_eq!(restored.current_exponent(7, 2).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn restored_tail_reaches_history_and_stats() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path().join("ledger")).unwrap();
+        ledger.anchor_batch(7, &[(2, 2)]).unwrap();
+        let backup = tmp.path().join("backup");
+        ledger.create_checkpoint(&backup).unwrap();
+
+        let tail = ledger.anchor_batch(8, &[(2, 4), (3, 1)]).unwrap();
+        fs::copy(
+            tmp.path().join("ledger").join("event.log"),
+            backup.join("event.log"),
+        )
+        .unwrap();
+        let expected = ledger.stats().unwrap();
+        drop(ledger);
+
+        let restored = Ledger::restore(&backup, tmp.path().join("restored")).unwrap();
+        assert_eq!(
+            restored
+                .get_exponent_at(8, 2, AsOf::Lsn(tail[0].lsn))
+                .unwrap(),
+            Some(4)
+        );
+        let stats = restored.stats().unwrap();
+        assert_eq!(stats.entities, expected.entities);
+        assert_eq!(stats.events_per_prime, expected.events_per_prime);
+        assert_eq!(stats.last_lsn, expected.last_lsn);
+        assert!(restored.quaternion_state(8).unwrap().is_some());
+    }
+
+    #[test]
+    fn restore_rejects_truncated_segment() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path().join("ledger")).unwrap();
+        ledger.anchor_batch(7, &[(2, 2)]).unwrap();
+        let backup = tmp.path().join("backup");
+        let manifest = ledger.create_checkpoint(&backup).unwrap();
+
+        let seg = backup.join("segments").join(&manifest.segments[0].name);
+        fs::write(&seg, b"").unwrap();
+        assert!(Ledger::restore(&backup, tmp.path().join("restored")).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Floor/ceiling limits on stored exponents
+//! Set for the whole ledger with [`crate::LedgerOptions::exponent_bounds`]
+//! or per prime with [`crate::LedgerOptions::prime_bounds`]; exponents are
+//! unbounded by default. Anchored batches, transfers and bulk loads apply
+//! the [`BoundsPolicy`]; CRDT merges always reject, since adjusting a merged
+//! exponent would stop writers converging.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::{Ledger, LedgerError};
+
+/// What happens to an exponent outside its bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoundsPolicy {
+    /// Fail with [`LedgerError::ExponentOutOfBounds`].
+    #[default]
+    Reject,
+    /// Store the nearest bound instead.
+    Clamp,
+    /// Wrap around into the range, modulo its width.
+    Wrap,
+}
+
+impl FromStr for BoundsPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(BoundsPolicy::Reject),
+            "clamp" => Ok(BoundsPolicy::Clamp),
+            "wrap" => Ok(BoundsPolicy::Wrap),
+            _ => Err(format!("unknown bounds policy {:?}", s)),
+        }
+    }
+}
+
+/// Inclusive exponent range and the policy enforcing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExponentBounds {
+    pub floor: i32,
+    pub ceiling: i32,
+    pub policy: BoundsPolicy,
+}
+
+impl ExponentBounds {
+    pub fn new(floor: i32, ceiling: i32, policy: BoundsPolicy) -> Self {
+        ExponentBounds {
+            floor,
+            ceiling,
+            policy,
+        }
+    }
+
+    /// `exponent` brought within bounds, or `None` if it is rejected.
+    fn apply(&self, exponent: i64, policy: BoundsPolicy) -> Option<i32> {
+        let (floor, ceiling) = (i64::from(self.floor), i64::from(self.ceiling));
+        let bounded = match policy {
+            _ if (floor..=ceiling).contains(&exponent) => exponent,
+            BoundsPolicy::Reject => return None,
+            BoundsPolicy::Clamp => exponent.clamp(floor, ceiling),
+            BoundsPolicy::Wrap => floor + (exponent - floor).rem_euclid(ceiling - floor + 1),
+        };
+        i32::try_from(bounded).ok()
+    }
+}
+
+/// Ledger-wide bounds plus per-prime overrides.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BoundsConfig {
+    pub(crate) default: Option<ExponentBounds>,
+    pub(crate) per_prime: BTreeMap<u32, ExponentBounds>,
+}
+
+impl BoundsConfig {
+    fn for_prime(&self, prime: u32) -> Option<&ExponentBounds> {
+        self.per_prime.get(&prime).or(self.default.as_ref())
+    }
+
+    /// Fail on a range with its floor above its ceiling.
+    pub(crate) fn check(&self) -> Result<(), LedgerError> {
+        for b in self.default.iter().chain(self.per_prime.values()) {
+            if b.floor > b.ceiling {
+                return Err(LedgerError::InvalidArgument(format!(
+                    "exponent floor {} is above ceiling {}",
+                    b.floor, b.ceiling
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Ledger {
+    /// `exponent` for `prime` on `entity` after the configured policy.
+    pub(crate) fn bound_exponent(
+        &self,
+        entity: u64,
+        prime: u32,
+        exponent: i64,
+    ) -> Result<i32, LedgerError> {
+        self.apply_bounds(entity, prime, exponent, None)
+    }
+
+    /// Like [`Ledger::bound_exponent`], but rejecting whatever the policy.
+    pub(crate) fn check_exponent(
+        &self,
+        entity: u64,
+        prime: u32,
+        exponent: i64,
+    ) -> Result<i32, LedgerError> {
+        self.apply_bounds(entity, prime, exponent, Some(BoundsPolicy::Reject))
+    }
+
+    fn apply_bounds(
+        &self,
+        entity: u64,
+        prime: u32,
+        exponent: i64,
+        policy: Option<BoundsPolicy>,
+    ) -> Result<i32, LedgerError> {
+        let (floor, ceiling) = match self.bounds.for_prime(prime) {
+            Some(b) => match b.apply(exponent, policy.unwrap_or(b.policy)) {
+                Some(bounded) => return Ok(bounded),
+                None => (b.floor, b.ceiling),
+            },
+            None => match i32::try_from(exponent) {
+                Ok(exponent) => return Ok(exponent),
+                Err(_) => (i32::MIN, i32::MAX),
+            },
+        };
+        Err(LedgerError::ExponentOutOfBounds {
+            entity,
+            prime,
+            exponent,
+            floor,
+            ceiling,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LedgerOptions;
+
+    #[test]
+    fn policies_reject_clamp_or_wrap() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = LedgerOptions::new()
+            .exponent_bounds(ExponentBounds::new(0, 5, BoundsPolicy::Reject))
+            .prime_bounds(3, ExponentBounds::new(0, 5, BoundsPolicy::Clamp))
+            .prime_bounds(5, ExponentBounds::new(2, 5, BoundsPolicy::Wrap));
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+
+        assert!(matches!(
+            ledger.anchor_batch(1, &[(2, 6)]),
+            Err(LedgerError::ExponentOutOfBounds {
+                prime: 2,
+                exponent: 6,
+                ceiling: 5,
+                ..
+            })
+        ));
+        ledger.anchor_batch(1, &[(3, 7)]).unwrap();
+        ledger.anchor_batch(1, &[(5, 0)]).unwrap();
+        assert_eq!(
+            ledger.current_exponents(1, &[3, 5]).unwrap(),
+            vec![Some(5), Some(4)]
+        );
+
+        assert!(ledger.bulk_load([(2, 2, -1)]).is_err());
+        ledger.bulk_load([(2, 3, -4)]).unwrap();
+        assert_eq!(ledger.current_exponents(2, &[3]).unwrap(), vec![Some(0)]);
+
+        let inverted =
+            LedgerOptions::new().prime_bounds(2, ExponentBounds::new(3, 1, BoundsPolicy::Clamp));
+        assert!(Ledger::with_options(tmp.path().join("bad"), &inverted).is_err());
+    }
+}
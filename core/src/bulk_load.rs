@@ -0,0 +1,141 @@
+//! Bulk import of pre-computed exponents through SST ingestion
+//! Entries are sorted in chunks, written to SST files for the factors and
+//! postings column families, and ingested directly, skipping the WriteBatch
+//! and event log path entirely.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use rocksdb::{IngestExternalFileOptions, Options, SstFileWriter};
+
+use crate::keys;
+use crate::scan::FactorEntry;
+use crate::{Ledger, LedgerError};
+
+const BULK_DIR: &str = "bulk_load";
+/// Entries sorted and ingested per round; bounds memory use.
+const CHUNK: usize = 1_000_000;
+
+impl Ledger {
+    /// Set the exponent of every `(entity, prime, exponent)` in `entries`,
+    /// overwriting what is stored. Later entries for the same key win.
+    ///
+    /// Loaded values are not logged as events, so neither the hash chain
+    /// nor `rebuild_projections` knows about them until the next
+    /// [`Ledger::compact`] folds them into a snapshot; call it once the
+    /// load is done. Returns the number of entries read.
+    pub fn bulk_load<I>(&self, entries: I) -> Result<u64, LedgerError>
+    where
+        I: IntoIterator<Item = FactorEntry>,
+    {
+        self.ensure_writable()?;
+        let _guard = self.write_lock.lock()?;
+        let dir = self.log.base_dir().join(BULK_DIR);
+        fs::create_dir_all(&dir)?;
+        let loaded = self.load_chunks(&dir, entries.into_iter());
+        self.exponent_cache.clear();
+        let cleanup = fs::remove_dir_all(&dir);
+        let loaded = loaded?;
+        cleanup?;
+        Ok(loaded)
+    }
+
+    fn load_chunks(
+        &self,
+        dir: &Path,
+        mut entries: impl Iterator<Item = FactorEntry>,
+    ) -> Result<u64, LedgerError> {
+        let mut live = HashSet::new();
+        let mut total = 0u64;
+        for round in 0.. {
+            let mut chunk = BTreeMap::new();
+            for (entity, prime, exp) in entries.by_ref().take(CHUNK) {
+                self.registry
+                    .prime_to_node(prime)
+                    .ok_or(LedgerError::UnknownPrime(prime))?;
+                if live.insert(entity) {
+                    self.ensure_live(entity)?;
+                }
+                let exp = self.bound_exponent(entity, prime, i64::from(exp))?;
+                chunk.insert((entity, prime), exp);
+                total += 1;
+            }
+            if chunk.is_empty() {
+                break;
+            }
+
+            let factors = dir.join(format!("factors-{}.sst", round));
+            write_sst(
+                &factors,
+                chunk.iter().map(|(&(entity, prime), &exp)| {
+                    (keys::factor_key(entity, prime), keys::encode_exponent(exp))
+                }),
+            )?;
+            let mut postings: Vec<_> = chunk
+                .iter()
+                .map(|(&(entity, prime), &exp)| {
+                    (keys::posting_key(prime, entity), keys::encode_exponent(exp))
+                })
+                .collect();
+            postings.sort_unstable_by_key(|(key, _)| *key);
+            let postings_path = dir.join(format!("postings-{}.sst", round));
+            write_sst(&postings_path, postings.into_iter())?;
+
+            let mut opts = IngestExternalFileOptions::default();
+            opts.set_move_files(true);
+            self.db
+                .ingest_external_file_cf_opts(self.cf("factors")?, &opts, vec![factors])?;
+            self.db.ingest_external_file_cf_opts(
+                self.cf("postings")?,
+                &opts,
+                vec![postings_path],
+            )?;
+        }
+        Ok(total)
+    }
+}
+
+/// Write sorted, de-duplicated `entries` to a new SST file at `path`.
+fn write_sst<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+    path: &Path,
+    entries: impl Iterator<Item = (K, V)>,
+) -> Result<(), LedgerError> {
+    let opts = Options::default();
+    let mut writer = SstFileWriter::create(&opts);
+    writer.open(path)?;
+    for (key, value) in entries {
+        writer.put(key, value)?;
+    }
+    writer.finish().map_err(LedgerError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bulk_loaded_exponents_are_visible_and_survive_compaction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+
+        let loaded = ledger
+            .bulk_load(vec![(9, 5, 3), (1, 2, 6), (9, 3, -1), (9, 5, 4)])
+            .unwrap();
+        assert_eq!(loaded, 4);
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(6));
+        assert_eq!(ledger.current_exponent(9, 5).unwrap(), Some(4));
+        assert!(ledger.bulk_load(vec![(9, 4, 1)]).is_err());
+        assert!(!tmp.path().join(BULK_DIR).exists());
+
+        // Merges from later anchors apply on top of ingested values.
+        ledger.anchor_batch(9, &[(3, 1)]).unwrap();
+        assert_eq!(ledger.current_exponent(9, 3).unwrap(), Some(1));
+
+        ledger.compact().unwrap();
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.current_exponent(9, 5).unwrap(), Some(4));
+        assert_eq!(ledger.current_exponent(9, 3).unwrap(), Some(1));
+    }
+}
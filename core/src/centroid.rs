@@ -1,9 +1,200 @@
-pub type CentroidDigit = u8; // 0 or 1
+//! Centroid state machine
+//! Each entity carries a [`Centroid`]: the current digit, how many times a
+//! via-C routing has flipped it, and what changed it last. A fresh entity is
+//! seeded per [`CentroidSeed`]; after that only via-C events move it.
 
-pub fn centroid_now(ts_ms: u64) -> CentroidDigit {
-    (ts_ms % 2) as u8
-}
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Clock, LedgerError, LedgerEvent};
+
+pub type CentroidDigit = u8; // 0 or 1
 
 pub fn flip_digit(d: CentroidDigit) -> CentroidDigit {
     1 - d
 }
+
+/// How an entity's first centroid digit is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CentroidSeed {
+    /// [`Clock::centroid_digit`] of the batch timestamp: its parity,
+    /// unless the clock pins it.
+    #[default]
+    Clock,
+    /// Low bit of SHA-256 over the entity and LSN (both BE), so the same
+    /// command stream seeds the same digits on every machine and replay.
+    Content,
+}
+
+impl CentroidSeed {
+    /// Seed for `entity`'s event at `lsn`, committed at `ts_ms` by `clock`.
+    pub fn digit(self, clock: &dyn Clock, entity: u64, lsn: u64, ts_ms: u64) -> CentroidDigit {
+        match self {
+            CentroidSeed::Clock => clock.centroid_digit(ts_ms),
+            CentroidSeed::Content => {
+                let hash = Sha256::new()
+                    .chain_update(entity.to_be_bytes())
+                    .chain_update(lsn.to_be_bytes())
+                    .finalize();
+                hash[hash.len() - 1] & 1
+            }
+        }
+    }
+}
+
+impl FromStr for CentroidSeed {
+    type Err = String;
+
+    /// `clock` or `content`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "clock" => Ok(CentroidSeed::Clock),
+            "content" => Ok(CentroidSeed::Content),
+            other => Err(format!("unknown centroid seed {:?}", other)),
+        }
+    }
+}
+
+/// What last set a centroid's digit.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CentroidCause {
+    /// The entity's first event picked the digit.
+    Seeded,
+    /// `prime` was routed via C in the event at `lsn`.
+    ViaC { lsn: u64, prime: u32 },
+}
+
+/// An entity's centroid. Serialises as a struct; a bare digit, as written
+/// by older snapshots, reads as a seeded centroid that never flipped.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(from = "StoredCentroid")]
+pub struct Centroid {
+    pub digit: CentroidDigit,
+    pub flips: u64,
+    pub cause: CentroidCause,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredCentroid {
+    Digit(CentroidDigit),
+    Full {
+        digit: CentroidDigit,
+        flips: u64,
+        cause: CentroidCause,
+    },
+}
+
+impl From<StoredCentroid> for Centroid {
+    fn from(stored: StoredCentroid) -> Self {
+        match stored {
+            StoredCentroid::Digit(digit) => Centroid::seeded(digit),
+            StoredCentroid::Full {
+                digit,
+                flips,
+                cause,
+            } => Centroid {
+                digit,
+                flips,
+                cause,
+            },
+        }
+    }
+}
+
+/// One flip, as recovered from the log.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CentroidTransition {
+    pub lsn: u64,
+    pub timestamp: u64,
+    pub prime: u32,
+    pub from: CentroidDigit,
+    pub to: CentroidDigit,
+}
+
+/// Value tags in the `centroids` column family.
+const SEEDED: u8 = 0;
+const VIA_C: u8 = 1;
+
+impl Centroid {
+    pub fn seeded(digit: CentroidDigit) -> Self {
+        Centroid {
+            digit,
+            flips: 0,
+            cause: CentroidCause::Seeded,
+        }
+    }
+
+    /// The centroid an entity had before `evt`, its first event.
+    pub fn before(evt: &LedgerEvent) -> Self {
+        if evt.via_c {
+            Centroid::seeded(flip_digit(evt.centroid_digit))
+        } else {
+            Centroid::seeded(evt.centroid_digit)
+        }
+    }
+
+    /// Move past `evt`, returning the flip it made, if any.
+    pub fn apply(&mut self, evt: &LedgerEvent) -> Option<CentroidTransition> {
+        let from = self.digit;
+        self.digit = evt.centroid_digit;
+        if !evt.via_c {
+            return None;
+        }
+        self.flips += 1;
+        self.cause = CentroidCause::ViaC {
+            lsn: evt.lsn,
+            prime: evt.prime,
+        };
+        Some(CentroidTransition {
+            lsn: evt.lsn,
+            timestamp: evt.timestamp,
+            prime: evt.prime,
+            from,
+            to: evt.centroid_digit,
+        })
+    }
+
+    /// Digit, flips (BE), then the cause tag and, for via-C, LSN and prime.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut out = vec![self.digit];
+        out.extend(self.flips.to_be_bytes());
+        match self.cause {
+            CentroidCause::Seeded => out.push(SEEDED),
+            CentroidCause::ViaC { lsn, prime } => {
+                out.push(VIA_C);
+                out.extend(lsn.to_be_bytes());
+                out.extend(prime.to_be_bytes());
+            }
+        }
+        out
+    }
+
+    /// Also reads the single digit byte stored before flips were tracked.
+    pub(crate) fn from_bytes(raw: &[u8]) -> Result<Self, LedgerError> {
+        let corrupt = || LedgerError::Corruption("malformed centroid value".to_string());
+        match raw {
+            [digit] => Ok(Centroid::seeded(*digit)),
+            [digit, rest @ ..] if rest.len() >= 9 => {
+                let (flips, rest) = rest.split_at(8);
+                let flips = u64::from_be_bytes(flips.try_into().map_err(|_| corrupt())?);
+                let cause = match rest {
+                    [SEEDED] => CentroidCause::Seeded,
+                    [VIA_C, tail @ ..] if tail.len() == 12 => CentroidCause::ViaC {
+                        lsn: u64::from_be_bytes(tail[..8].try_into().map_err(|_| corrupt())?),
+                        prime: u32::from_be_bytes(tail[8..].try_into().map_err(|_| corrupt())?),
+                    },
+                    _ => return Err(corrupt()),
+                };
+                Ok(Centroid {
+                    digit: *digit,
+                    flips,
+                    cause,
+                })
+            }
+            _ => Err(corrupt()),
+        }
+    }
+}
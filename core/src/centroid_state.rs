@@ -0,0 +1,321 @@
+//! Per-entity centroid state, persisted across batches
+//! The `centroids` column family maps entity (BE) to the [`Centroid`] left
+//! by its most recent event; a batch starts from its digit instead of the
+//! clock. [`Ledger::audit_centroid`] checks the digits recorded in the log
+//! against each other.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+use rocksdb::{IteratorMode, WriteBatch};
+use serde::Serialize;
+
+use crate::centroid::{flip_digit, Centroid, CentroidDigit, CentroidTransition};
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const CENTROIDS_CF: &str = "centroids";
+
+/// An event whose centroid digit does not follow from the one before it.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CentroidMismatch {
+    pub lsn: u64,
+    pub via_c: bool,
+    /// The previous digit, flipped if `via_c`.
+    pub expected: CentroidDigit,
+    pub found: CentroidDigit,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CentroidAudit {
+    /// The entity's events after the latest snapshot.
+    pub events_checked: u64,
+    pub flips: u64,
+    /// The first inconsistent event; the walk stops there.
+    pub mismatch: Option<CentroidMismatch>,
+}
+
+impl CentroidAudit {
+    pub fn is_clean(&self) -> bool {
+        self.mismatch.is_none()
+    }
+}
+
+/// Fold `evt` into per-entity centroids; tombstones drop the entity.
+pub(crate) fn fold_centroid(centroids: &mut BTreeMap<u64, Centroid>, evt: &LedgerEvent) {
+    if evt.tombstone {
+        centroids.remove(&evt.entity_id);
+    } else {
+        centroids
+            .entry(evt.entity_id)
+            .or_insert_with(|| Centroid::before(evt))
+            .apply(evt);
+    }
+}
+
+impl Ledger {
+    /// Centroid digit of `entity`'s most recent event; `None` if it has none
+    /// or was deleted.
+    pub fn entity_centroid(&self, entity: u64) -> Result<Option<CentroidDigit>, LedgerError> {
+        Ok(self.centroid(entity)?.map(|c| c.digit))
+    }
+
+    /// Full centroid state of `entity`; `None` if it has no events or was
+    /// deleted.
+    pub fn centroid(&self, entity: u64) -> Result<Option<Centroid>, LedgerError> {
+        let cf = self.cf(CENTROIDS_CF)?;
+        self.db
+            .get_cf(cf, entity.to_be_bytes())?
+            .map(|raw| Centroid::from_bytes(&raw))
+            .transpose()
+    }
+
+    /// Every flip of `entity`'s centroid still in the log, oldest first.
+    /// Restarts after a deletion; flips in pruned segments are only
+    /// counted in [`Centroid::flips`].
+    pub fn centroid_history(&self, entity: u64) -> Result<Vec<CentroidTransition>, LedgerError> {
+        let mut state: Option<Centroid> = None;
+        let mut flips = Vec::new();
+        for evt in self.log.iter()? {
+            let evt = evt?;
+            if evt.entity_id != entity {
+                continue;
+            }
+            if evt.tombstone {
+                state = None;
+                continue;
+            }
+            let centroid = state.get_or_insert_with(|| Centroid::before(&evt));
+            flips.extend(centroid.apply(&evt));
+        }
+        Ok(flips)
+    }
+
+    /// Walk `entity`'s events after the latest snapshot, starting from the
+    /// snapshot's digit, and check that each recorded digit is the previous
+    /// one, flipped exactly when the event went via C. The first event after
+    /// a deletion may take any digit.
+    pub fn audit_centroid(&self, entity: u64) -> Result<CentroidAudit, LedgerError> {
+        let snapshot = self.latest_snapshot()?.unwrap_or_default();
+        let mut digit = snapshot.centroids.get(&entity).map(|c| c.digit);
+        let mut audit = CentroidAudit::default();
+        for evt in self.log.iter()? {
+            let evt = evt?;
+            if evt.entity_id != entity || evt.lsn < snapshot.lsn {
+                continue;
+            }
+            audit.events_checked += 1;
+            if evt.tombstone {
+                digit = None;
+                continue;
+            }
+            let expected = match digit {
+                Some(d) if evt.via_c => flip_digit(d),
+                Some(d) => d,
+                None if evt.centroid_digit <= 1 => evt.centroid_digit,
+                // Not a digit at all.
+                None => 0,
+            };
+            if evt.centroid_digit != expected {
+                audit.mismatch = Some(CentroidMismatch {
+                    lsn: evt.lsn,
+                    via_c: evt.via_c,
+                    expected,
+                    found: evt.centroid_digit,
+                });
+                break;
+            }
+            audit.flips += u64::from(evt.via_c);
+            digit = Some(expected);
+        }
+        Ok(audit)
+    }
+
+    /// Stage the centroid each entity in `events` is left with; tombstones
+    /// clear it. Reads the stored state, so the caller must hold the write
+    /// lock.
+    pub(crate) fn stage_centroids(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(CENTROIDS_CF)?;
+        let mut states: HashMap<u64, Option<Centroid>> = HashMap::new();
+        for evt in events {
+            let state = match states.entry(evt.entity_id) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(self.centroid(evt.entity_id)?),
+            };
+            if evt.tombstone {
+                *state = None;
+            } else {
+                state
+                    .get_or_insert_with(|| Centroid::before(evt))
+                    .apply(evt);
+            }
+        }
+        for (entity, state) in states {
+            match state {
+                Some(c) => batch.put_cf(cf, entity.to_be_bytes(), c.to_bytes()),
+                None => batch.delete_cf(cf, entity.to_be_bytes()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace every stored centroid with `centroids`.
+    pub(crate) fn stage_reset_centroids(
+        &self,
+        batch: &mut WriteBatch,
+        centroids: &BTreeMap<u64, Centroid>,
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(CENTROIDS_CF)?;
+        for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+            batch.delete_cf(cf, item?.0);
+        }
+        for (entity, centroid) in centroids {
+            batch.put_cf(cf, entity.to_be_bytes(), centroid.to_bytes());
+        }
+        Ok(())
+    }
+
+    /// Fill the column family from the latest snapshot and the log after
+    /// it; used when upgrading ledgers that predate it. Events the snapshot
+    /// folded in may still be logged if compaction stopped short of
+    /// dropping their segments, so they are skipped.
+    pub(crate) fn backfill_centroids(&self) -> Result<(), LedgerError> {
+        let snapshot = self.latest_snapshot()?.unwrap_or_default();
+        let mut centroids = snapshot.centroids;
+        let first = self.pruned_prefix()?.events;
+        for (lsn, evt) in (first..).zip(self.log.iter()?) {
+            let evt = evt?;
+            if lsn >= snapshot.lsn {
+                fold_centroid(&mut centroids, &evt);
+            }
+        }
+        let mut batch = WriteBatch::default();
+        self.stage_reset_centroids(&mut batch, &centroids)?;
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::centroid::{CentroidCause, CentroidSeed};
+    use crate::{LedgerOptions, ManualClock, SystemClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn batches_continue_from_the_stored_digit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new(0));
+        let ledger =
+            Ledger::with_options(tmp.path(), &LedgerOptions::new().clock(clock.clone())).unwrap();
+
+        // Node 0 → 1 goes via C and flips the digit.
+        let first = ledger.anchor_batch(1, &[(2, 1)]).unwrap();
+        assert!(first[0].via_c);
+        assert_eq!(first[0].centroid_digit, 1);
+        assert_eq!(ledger.entity_centroid(1).unwrap(), Some(1));
+
+        // An even timestamp alone would give digit 0.
+        clock.set(2);
+        let second = ledger.anchor_batch(1, &[(5, 4)]).unwrap();
+        assert_eq!(second[0].centroid_digit, 1);
+
+        // 4 → 5 crosses again.
+        ledger.anchor_batch(1, &[(11, 5)]).unwrap();
+        let centroid = ledger.centroid(1).unwrap().unwrap();
+        assert_eq!((centroid.digit, centroid.flips), (0, 2));
+        assert_eq!(centroid.cause, CentroidCause::ViaC { lsn: 2, prime: 11 });
+        let history = ledger.centroid_history(1).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            (history[0].prime, history[0].from, history[0].to),
+            (2, 0, 1)
+        );
+
+        ledger.compact().unwrap();
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.centroid(1).unwrap(), Some(centroid));
+        ledger.delete_entity(1).unwrap();
+        assert_eq!(ledger.entity_centroid(1).unwrap(), None);
+    }
+
+    #[test]
+    fn audit_reports_the_first_inconsistent_digit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 1), (5, 4)]).unwrap();
+        ledger.anchor_batch(2, &[(2, 1)]).unwrap();
+        ledger.compact().unwrap();
+        ledger.anchor_batch(1, &[(11, 5)]).unwrap();
+        let audit = ledger.audit_centroid(1).unwrap();
+        assert!(audit.is_clean());
+        assert_eq!((audit.events_checked, audit.flips), (1, 1));
+
+        // A digit that should have flipped but didn't.
+        let last = ledger.log.last_event().unwrap().unwrap();
+        let forged = LedgerEvent {
+            prime: 2,
+            lsn: last.lsn + 1,
+            centroid_digit: last.centroid_digit,
+            ..last.clone()
+        };
+        ledger.log.append(&[forged], false).unwrap();
+        assert_eq!(
+            ledger.audit_centroid(1).unwrap().mismatch,
+            Some(CentroidMismatch {
+                lsn: last.lsn + 1,
+                via_c: true,
+                expected: flip_digit(last.centroid_digit),
+                found: last.centroid_digit,
+            })
+        );
+        assert!(ledger.audit_centroid(2).unwrap().is_clean());
+    }
+
+    #[test]
+    fn backfill_skips_events_the_snapshot_folded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert!(ledger.anchor_batch(1, &[(2, 1)]).unwrap()[0].via_c);
+        ledger.log.seal().unwrap();
+        let sealed = ledger.log.sealed_segments().unwrap();
+        let kept = sealed
+            .iter()
+            .map(|p| (p.clone(), std::fs::read(p).unwrap()))
+            .collect::<Vec<_>>();
+        ledger.compact().unwrap();
+
+        // As if compaction died after the snapshot but before the drop.
+        for (path, raw) in kept {
+            std::fs::write(path, raw).unwrap();
+        }
+        ledger.db.delete(b"pruned_prefix").unwrap();
+        ledger.backfill_centroids().unwrap();
+        assert_eq!(ledger.centroid(1).unwrap().unwrap().flips, 1);
+    }
+
+    #[test]
+    fn content_seed_ignores_the_clock() {
+        let digits = |start: u64| {
+            let tmp = tempfile::tempdir().unwrap();
+            let options = LedgerOptions::new()
+                .clock(Arc::new(ManualClock::new(start)))
+                .centroid_seed(CentroidSeed::Content);
+            let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+            (1..=16)
+                .map(|entity| ledger.anchor_batch(entity, &[(2, 2)]).unwrap()[0].centroid_digit)
+                .collect::<Vec<_>>()
+        };
+        let seeded = digits(0);
+        assert_eq!(seeded, digits(1));
+        assert!(seeded.contains(&0) && seeded.contains(&1));
+        assert_eq!(
+            seeded[0],
+            CentroidSeed::Content.digit(&SystemClock, 1, 0, 0)
+        );
+        assert_eq!("content".parse(), Ok(CentroidSeed::Content));
+    }
+}
@@ -0,0 +1,145 @@
+//! Time source for event timestamps and centroid seeds
+//! A new entity's centroid digit comes from the clock too, so tests and
+//! simulations on virtual time inject their own clock instead of reading the
+//! system time, and can pin the digit outright.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+use chrono::Utc;
+
+use crate::centroid::CentroidDigit;
+
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch.
+    fn now_millis(&self) -> u64;
+
+    /// Centroid digit seeded by a batch stamped `ts_ms`; its parity by
+    /// default.
+    fn centroid_digit(&self, ts_ms: u64) -> CentroidDigit {
+        (ts_ms % 2) as u8
+    }
+}
+
+/// Wall-clock time; the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        Utc::now().timestamp_millis() as u64
+    }
+}
+
+/// Stored in [`ManualClock::centroid`] while no digit is pinned.
+const UNPINNED: u8 = u8::MAX;
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct ManualClock {
+    millis: AtomicU64,
+    centroid: AtomicU8,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new(0)
+    }
+}
+
+impl ManualClock {
+    pub fn new(millis: u64) -> Self {
+        ManualClock {
+            millis: AtomicU64::new(millis),
+            centroid: AtomicU8::new(UNPINNED),
+        }
+    }
+
+    /// Seed every new entity with `digit`, whatever the time; `None` goes
+    /// back to timestamp parity.
+    pub fn pin_centroid(&self, digit: Option<CentroidDigit>) {
+        self.centroid
+            .store(digit.map_or(UNPINNED, |d| d & 1), Ordering::SeqCst);
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, millis: u64) {
+        self.millis.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> u64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+
+    fn centroid_digit(&self, ts_ms: u64) -> CentroidDigit {
+        match self.centroid.load(Ordering::SeqCst) {
+            UNPINNED => (ts_ms % 2) as u8,
+            digit => digit,
+        }
+    }
+}
+
+/// Shared clock handle carried by options and the ledger.
+#[derive(Clone)]
+pub(crate) struct SharedClock(pub Arc<dyn Clock>);
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        SharedClock(Arc::new(SystemClock))
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+impl SharedClock {
+    pub fn now_millis(&self) -> u64 {
+        self.0.now_millis()
+    }
+
+    pub fn as_clock(&self) -> &dyn Clock {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ledger, LedgerOptions};
+
+    #[test]
+    fn injected_clock_drives_timestamps_and_centroids() {
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new(1_700_000_000_000));
+        let options = LedgerOptions::new().clock(clock.clone());
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+
+        let even = ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        clock.advance(1);
+        // A fresh entity takes its first digit from the clock.
+        let odd = ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+        assert_eq!(even[0].timestamp, 1_700_000_000_000);
+        assert_eq!(even[0].centroid_digit, 0);
+        assert_eq!(odd[0].timestamp, 1_700_000_000_001);
+        assert_eq!(odd[0].centroid_digit, 1);
+
+        clock.pin_centroid(Some(0));
+        let pinned = ledger.anchor_batch(3, &[(2, 4)]).unwrap();
+        assert_eq!(pinned[0].timestamp % 2, 1);
+        assert_eq!(pinned[0].centroid_digit, 0);
+        clock.pin_centroid(None);
+        assert_eq!(
+            ledger.anchor_batch(4, &[(2, 4)]).unwrap()[0].centroid_digit,
+            1
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Log compaction into state snapshots
+//! A snapshot records every current exponent and each entity's centroid;
+//! the log segments it covers are truncated so replay stays bounded.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rocksdb::{IteratorMode, WriteBatch};
+use serde::{Deserialize, Serialize};
+
+use crate::centroid::Centroid;
+use crate::centroid_state::fold_centroid;
+use crate::keys;
+use crate::scan::FactorEntry;
+use crate::{Ledger, LedgerError};
+
+const SNAPSHOT_DIR: &str = "snapshots";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateSnapshot {
+    /// Number of events folded in; replay resumes at this LSN.
+    pub lsn: u64,
+    /// `event_hash` of the last folded event.
+    pub chain_head: String,
+    pub created_at: u64,
+    pub exponents: Vec<FactorEntry>,
+    /// Centroid per entity; older snapshots hold bare digits.
+    pub centroids: BTreeMap<u64, Centroid>,
+    #[serde(default)]
+    pub versions: BTreeMap<u64, u64>,
+}
+
+impl Ledger {
+    /// Seal the active segment, snapshot current state, then truncate every
+    /// sealed segment. Truncated segments go to the retention archive if one
+    /// is configured.
+    pub fn compact(&self) -> Result<StateSnapshot, LedgerError> {
+        self.ensure_writable()?;
+        let writer = self.write_lock.lock()?;
+        self.log.seal()?;
+        let segments = self.log.sealed_segments()?;
+
+        let mut centroids = self.latest_snapshot()?.unwrap_or_default().centroids;
+        let mut folded = 0u64;
+        for segment in &segments {
+            for evt in self.log.read_segment(segment)? {
+                fold_centroid(&mut centroids, &evt);
+                folded += 1;
+            }
+        }
+        let snapshot = StateSnapshot {
+            lsn: self.pruned_prefix()?.events + folded,
+            chain_head: writer.chain_head.clone(),
+            created_at: self.clock.now_millis(),
+            exponents: self.iter_factors()?.collect::<Result<_, _>>()?,
+            centroids,
+            versions: self.all_versions()?,
+        };
+        self.write_snapshot(&snapshot)?;
+        self.drop_segments(&segments, self.retention_archive())?;
+        Ok(snapshot)
+    }
+
+    /// Most recent snapshot written by [`Ledger::compact`], if any.
+    pub fn latest_snapshot(&self) -> Result<Option<StateSnapshot>, LedgerError> {
+        let Some(path) = snapshot_files(&self.snapshot_dir())?.pop() else {
+            return Ok(None);
+        };
+        let raw = fs::read(&path)?;
+        serde_json::from_slice(&raw)
+            .map(Some)
+            .map_err(|e| LedgerError::Corruption(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Rebuild the factors/postings projections, and the quaternion state
+    /// derived from them, from the latest snapshot plus the log after it.
+    pub fn rebuild_projections(&self) -> Result<(), LedgerError> {
+        self.ensure_writable()?;
+        let _guard = self.write_lock.lock()?;
+        let snapshot = self.latest_snapshot()?.unwrap_or_default();
+        let pruned = self.pruned_prefix()?.events;
+        if pruned > snapshot.lsn {
+            return Err(LedgerError::Corruption(format!(
+                "log pruned through LSN {} but latest snapshot covers only {}",
+                pruned, snapshot.lsn
+            )));
+        }
+
+        let mut batch = WriteBatch::default();
+        for name in ["factors", "postings"] {
+            let cf = self.cf(name)?;
+            for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+                let (key, _) = item?;
+                batch.delete_cf(cf, key);
+            }
+        }
+        let factors_cf = self.cf("factors")?;
+        let postings_cf = self.cf("postings")?;
+        for &(entity, prime, exp) in &snapshot.exponents {
+            let value = keys::encode_exponent(exp);
+            batch.put_cf(factors_cf, keys::factor_key(entity, prime), &value);
+            batch.put_cf(postings_cf, keys::posting_key(prime, entity), &value);
+        }
+        self.stage_reset_versions(&mut batch, &snapshot.versions)?;
+        self.stage_reset_centroids(&mut batch, &snapshot.centroids)?;
+        self.db.write(batch)?;
+
+        // Replay reads the versions just written, so it needs its own batch.
+        let skip = usize::try_from(snapshot.lsn - pruned)?;
+        let events = self.log.read_all()?;
+        let mut batch = WriteBatch::default();
+        self.stage_replay(&mut batch, events.get(skip..).unwrap_or_default())?;
+        self.db.write(batch)?;
+        self.backfill_quat_state()?;
+        self.exponent_cache.clear();
+        Ok(())
+    }
+
+    fn write_snapshot(&self, snapshot: &StateSnapshot) -> Result<(), LedgerError> {
+        let dir = self.snapshot_dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{:020}.json", snapshot.lsn));
+        let tmp = path.with_extension("tmp");
+        let json = serde_json::to_vec(snapshot)?;
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)?;
+        // Older snapshots are superseded.
+        for old in snapshot_files(&dir)? {
+            if old != path {
+                fs::remove_file(&old)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot_dir(&self) -> PathBuf {
+        self.log.base_dir().join(SNAPSHOT_DIR)
+    }
+}
+
+/// Snapshot files in `dir`, oldest first.
+fn snapshot_files(dir: &Path) -> Result<Vec<PathBuf>, LedgerError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_truncates_log_and_rebuild_restores_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+
+        let snapshot = ledger.compact().unwrap();
+        assert_eq!(snapshot.lsn, 3);
+        assert_eq!(snapshot.exponents, vec![(1, 2, 2), (1, 5, 0), (2, 2, 4)]);
+        assert_eq!(snapshot.centroids.len(), 2);
+        assert!(ledger.log.read_all().unwrap().is_empty());
+
+        ledger.anchor_batch(2, &[(2, 6)]).unwrap();
+        assert_eq!(ledger.verify_chain().unwrap(), 1);
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.current_exponent(1, 5).unwrap(), Some(0));
+        assert_eq!(ledger.current_exponent(2, 2).unwrap(), Some(6));
+
+        // Segment numbering keeps counting after truncation.
+        let sealed = ledger.log.seal().unwrap().unwrap();
+        assert_eq!(crate::event_log::segment_number(&sealed), Some(2));
+    }
+}
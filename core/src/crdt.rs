@@ -0,0 +1,294 @@
+//! PN-counter exponents for multi-writer deployments
+//! With a writer ID configured, every exponent change anchored locally is
+//! also counted in the `crdt` column family under (entity, prime, writer):
+//! increases in P, decreases in N. Ledgers exchange [`CrdtState`] and fold
+//! it in with [`Ledger::merge_crdt`], which keeps the pointwise max of every
+//! counter. An exponent is its prime's node plus P − N summed over writers,
+//! so merges commute and repeating one changes nothing.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+use serde::{Deserialize, Serialize};
+
+use crate::keys;
+use crate::msd::Msd;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const CRDT_CF: &str = "crdt";
+
+/// Identifies one of the ledgers merging state; must be unique per ledger.
+pub type WriterId = u32;
+
+/// One writer's counter for one exponent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrdtCounter {
+    pub entity: u64,
+    pub prime: u32,
+    pub writer: WriterId,
+    /// Total of the writer's increases.
+    pub p: u64,
+    /// Total of the writer's decreases.
+    pub n: u64,
+}
+
+/// Every counter a ledger holds, as shipped to the other writers.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrdtState {
+    pub counters: Vec<CrdtCounter>,
+}
+
+fn counter_key(entity: u64, prime: u32, writer: WriterId) -> [u8; keys::KEY_LEN + 4] {
+    let mut key = [0u8; keys::KEY_LEN + 4];
+    key[..keys::KEY_LEN].copy_from_slice(&keys::factor_key(entity, prime));
+    key[keys::KEY_LEN..].copy_from_slice(&writer.to_be_bytes());
+    key
+}
+
+fn encode_counts(p: u64, n: u64) -> [u8; 16] {
+    let mut value = [0u8; 16];
+    value[..8].copy_from_slice(&p.to_be_bytes());
+    value[8..].copy_from_slice(&n.to_be_bytes());
+    value
+}
+
+fn decode_counter(key: &[u8], value: &[u8]) -> Result<CrdtCounter, LedgerError> {
+    let corrupt = || LedgerError::Corruption("malformed crdt counter".to_string());
+    if key.len() != keys::KEY_LEN + 4 || value.len() != 16 {
+        return Err(corrupt());
+    }
+    let (entity, prime) = keys::decode_factor_key(&key[..keys::KEY_LEN])?;
+    let word = |b: &[u8]| b.try_into().map(u64::from_be_bytes).map_err(|_| corrupt());
+    Ok(CrdtCounter {
+        entity,
+        prime,
+        writer: WriterId::from_be_bytes(key[keys::KEY_LEN..].try_into().map_err(|_| corrupt())?),
+        p: word(&value[..8])?,
+        n: word(&value[8..])?,
+    })
+}
+
+/// Signed contribution of a counter to its exponent.
+fn net(p: u64, n: u64) -> i64 {
+    p as i64 - n as i64
+}
+
+impl Ledger {
+    /// Every counter held, for shipping to another writer's
+    /// [`Ledger::merge_crdt`].
+    pub fn crdt_state(&self) -> Result<CrdtState, LedgerError> {
+        let cf = self.cf(CRDT_CF)?;
+        let counters = self
+            .db
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(|item| {
+                let (key, value) = item?;
+                decode_counter(&key, &value)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CrdtState { counters })
+    }
+
+    /// Fold `remote` into this ledger's counters and log one event per
+    /// exponent that changed as a result, returning them. Remote changes
+    /// were validated where they were anchored, so flow rules are not
+    /// applied again. Counters of entities deleted here are ignored;
+    /// deletions themselves are not merged.
+    pub fn merge_crdt(&self, remote: &CrdtState) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.ensure_writable()?;
+        if self.writer_id.is_none() {
+            return Err(LedgerError::InvalidArgument(
+                "merging CRDT state needs a writer ID".to_string(),
+            ));
+        }
+        let mut writer = self.write_lock.lock()?;
+        let cf = self.cf(CRDT_CF)?;
+        let mut batch = WriteBatch::default();
+        let mut merged = HashMap::new();
+        let mut deltas: BTreeMap<(u64, u32), i64> = BTreeMap::new();
+        for c in &remote.counters {
+            self.registry
+                .prime_to_node(c.prime)
+                .ok_or(LedgerError::UnknownPrime(c.prime))?;
+            if self.is_deleted(c.entity)? {
+                continue;
+            }
+            let key = counter_key(c.entity, c.prime, c.writer);
+            let (p, n) = match merged.get(&key) {
+                Some(&counts) => counts,
+                None => self.stored_counts(&key)?,
+            };
+            let (mp, mn) = (p.max(c.p), n.max(c.n));
+            if (mp, mn) != (p, n) {
+                merged.insert(key, (mp, mn));
+                *deltas.entry((c.entity, c.prime)).or_default() += net(mp, mn) - net(p, n);
+            }
+        }
+        for (key, (p, n)) in merged {
+            batch.put_cf(cf, key, encode_counts(p, n));
+        }
+
+        let ts = self.clock.now_millis();
+        let mut events = Vec::new();
+        let mut prev_hash = writer.chain_head.clone();
+        for ((entity, prime), delta) in deltas {
+            if delta == 0 {
+                continue;
+            }
+            let node = self.registry.prime_to_node(prime).map_or(0, i64::from);
+            let before = self.read_exponent(entity, prime)?.map_or(node, i64::from);
+            self.check_exponent(entity, prime, before + delta)?;
+            let delta = i32::try_from(delta)?;
+            let lsn = writer.next_lsn + events.len() as u64;
+            let mut evt = LedgerEvent {
+                entity_id: entity,
+                prime,
+                msd_digits: Msd::from_int(delta),
+                centroid_digit: self.entity_centroid(entity)?.unwrap_or_else(|| {
+                    self.centroid_seed
+                        .digit(self.clock.as_clock(), entity, lsn, ts)
+                }),
+                timestamp: ts,
+                lsn,
+                ..Default::default()
+            };
+            self.seal(&mut evt, &prev_hash)?;
+            prev_hash = evt.event_hash.clone();
+            events.push(evt);
+        }
+        self.stage_projections(&mut batch, &events)?;
+        self.commit(&mut writer, &events, batch)?;
+        Ok(events)
+    }
+
+    /// Count the local changes in `events` towards this ledger's writer.
+    /// Reads the stored counters, so must run before the batch is applied.
+    pub(crate) fn stage_crdt(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let Some(writer) = self.writer_id else {
+            return Ok(());
+        };
+        let mut changes: BTreeMap<(u64, u32), (u64, u64)> = BTreeMap::new();
+        for evt in events.iter().filter(|evt| !evt.tombstone) {
+            let delta = i64::from(evt.msd_digits.to_int());
+            let (p, n) = changes.entry((evt.entity_id, evt.prime)).or_default();
+            *p += delta.max(0) as u64;
+            *n += (-delta).max(0) as u64;
+        }
+        let cf = self.cf(CRDT_CF)?;
+        for ((entity, prime), (dp, dn)) in changes {
+            let key = counter_key(entity, prime, writer);
+            let (p, n) = self.stored_counts(&key)?;
+            batch.put_cf(cf, key, encode_counts(p + dp, n + dn));
+        }
+        Ok(())
+    }
+
+    /// Stage removal of every counter of `entity`.
+    pub(crate) fn stage_delete_crdt(
+        &self,
+        batch: &mut WriteBatch,
+        entity: u64,
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(CRDT_CF)?;
+        let prefix = keys::entity_prefix(entity);
+        let iter = self
+            .db
+            .iterator_cf(cf, IteratorMode::From(&prefix, Direction::Forward));
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            batch.delete_cf(cf, key);
+        }
+        Ok(())
+    }
+
+    /// Credit exponents stored before counters were kept to this ledger's
+    /// writer, so that they take part in merges. Runs only while no
+    /// counters exist.
+    pub(crate) fn seed_crdt(&self) -> Result<(), LedgerError> {
+        let Some(writer) = self.writer_id else {
+            return Ok(());
+        };
+        let cf = self.cf(CRDT_CF)?;
+        if self
+            .db
+            .iterator_cf(cf, IteratorMode::Start)
+            .next()
+            .is_some()
+        {
+            return Ok(());
+        }
+        let mut batch = WriteBatch::default();
+        for item in self
+            .db
+            .iterator_cf(self.cf("factors")?, IteratorMode::Start)
+        {
+            let (key, value) = item?;
+            let (entity, prime) = keys::decode_factor_key(&key)?;
+            let node = self.registry.prime_to_node(prime).map_or(0, i64::from);
+            let offset = i64::from(keys::decode_exponent(&value)?) - node;
+            batch.put_cf(
+                cf,
+                counter_key(entity, prime, writer),
+                encode_counts(offset.max(0) as u64, (-offset).max(0) as u64),
+            );
+        }
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+
+    fn stored_counts(&self, key: &[u8]) -> Result<(u64, u64), LedgerError> {
+        match self.db.get_cf(self.cf(CRDT_CF)?, key)? {
+            Some(value) => decode_counter(key, &value).map(|c| (c.p, c.n)),
+            None => Ok((0, 0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LedgerOptions;
+
+    #[test]
+    fn writers_converge_after_exchanging_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let open = |name: &str, id: WriterId| {
+            Ledger::with_options(tmp.path().join(name), &LedgerOptions::new().writer_id(id))
+                .unwrap()
+        };
+        let (a, b) = (open("a", 1), open("b", 2));
+        a.anchor_batch(1, &[(2, 2)]).unwrap();
+        b.anchor_batch(1, &[(2, 4), (3, 2)]).unwrap();
+
+        let from_a = a.crdt_state().unwrap();
+        let from_b = b.crdt_state().unwrap();
+        assert_eq!(a.merge_crdt(&from_b).unwrap().len(), 2);
+        assert_eq!(b.merge_crdt(&from_a).unwrap().len(), 1);
+        for ledger in [&a, &b] {
+            assert_eq!(
+                ledger.current_exponents(1, &[2, 3]).unwrap(),
+                vec![Some(6), Some(2)]
+            );
+        }
+        assert!(a.merge_crdt(&from_b).unwrap().is_empty());
+
+        // A decrease on one side travels as a larger N.
+        b.anchor_batch(1, &[(2, 4)]).unwrap();
+        a.merge_crdt(&b.crdt_state().unwrap()).unwrap();
+        assert_eq!(a.current_exponents(1, &[2]).unwrap(), vec![Some(4)]);
+        assert_eq!(a.crdt_state().unwrap(), b.crdt_state().unwrap());
+        assert_eq!(a.verify_chain().unwrap(), 4);
+        assert!(matches!(
+            Ledger::new(tmp.path().join("c"))
+                .unwrap()
+                .merge_crdt(&from_a),
+            Err(LedgerError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! Audit trail of denied transitions
+//! With [`crate::LedgerOptions::record_denials`] set, every command rejected
+//! for a flow-rule violation leaves a [`Denial`] in the `denials` column
+//! family, so attempted bypasses can be reviewed after the fact. Denials
+//! are kept apart from the event log and hash chain; deleting an entity
+//! leaves them in place.
+
+use rocksdb::{Direction, IteratorMode};
+use serde::{Deserialize, Serialize};
+
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const DENIALS_CF: &str = "denials";
+
+/// One rejected command.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Denial {
+    /// Position in the audit trail; page with it in [`Ledger::denials`].
+    pub id: u64,
+    pub entity: u64,
+    pub prime: u32,
+    pub src: u8,
+    pub dst: u8,
+    /// Clock time of the rejected batch, in milliseconds.
+    pub timestamp: u64,
+    /// Tag given to [`Ledger::anchor_batch_as`], if any.
+    pub caller: Option<String>,
+}
+
+impl Ledger {
+    /// [`Ledger::anchor_batch`] on behalf of `caller`, whose tag is kept
+    /// with any denial the batch causes.
+    pub fn anchor_batch_as(
+        &self,
+        caller: &str,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.anchor(entity, commands, None, Some(caller))
+            .map(|(events, _)| events)
+    }
+
+    /// Up to `limit` denials recorded after the one with id `cursor`,
+    /// oldest first.
+    pub fn denials(&self, cursor: Option<u64>, limit: usize) -> Result<Vec<Denial>, LedgerError> {
+        let start = match cursor {
+            Some(id) => match id.checked_add(1) {
+                Some(next) => next,
+                None => return Ok(Vec::new()),
+            },
+            None => 0,
+        };
+        let from = start.to_be_bytes();
+        self.db
+            .iterator_cf(
+                self.cf(DENIALS_CF)?,
+                IteratorMode::From(&from, Direction::Forward),
+            )
+            .take(limit)
+            .map(|item| {
+                let (_, value) = item?;
+                serde_json::from_slice(&value)
+                    .map_err(|e| LedgerError::Corruption(format!("malformed denial record: {}", e)))
+            })
+            .collect()
+    }
+
+    /// Store a denial of `prime` on `entity` moving `src` → `dst`, if
+    /// denials are being recorded. Caller holds the write lock, which
+    /// keeps ids unique.
+    pub(crate) fn record_denial(
+        &self,
+        entity: u64,
+        prime: u32,
+        (src, dst): (u8, u8),
+        timestamp: u64,
+        caller: Option<&str>,
+    ) -> Result<(), LedgerError> {
+        if !self.record_denials {
+            return Ok(());
+        }
+        let cf = self.cf(DENIALS_CF)?;
+        let id = match self.db.iterator_cf(cf, IteratorMode::End).next() {
+            Some(item) => {
+                let (key, _) = item?;
+                let last = key[..]
+                    .try_into()
+                    .map(u64::from_be_bytes)
+                    .map_err(|_| LedgerError::Corruption("malformed denial key".to_string()))?;
+                last + 1
+            }
+            None => 0,
+        };
+        let denial = Denial {
+            id,
+            entity,
+            prime,
+            src,
+            dst,
+            timestamp,
+            caller: caller.map(str::to_string),
+        };
+        self.db
+            .put_cf(cf, id.to_be_bytes(), serde_json::to_vec(&denial)?)
+            .map_err(LedgerError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LedgerOptions, ManualClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn flow_violations_are_recorded_with_their_caller() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = LedgerOptions::new()
+            .record_denials(true)
+            .clock(Arc::new(ManualClock::new(7)));
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+
+        assert!(ledger.anchor_batch_as("gateway", 1, &[(3, 4)]).is_err());
+        assert!(ledger.validate_batch(1, &[(3, 4)]).is_err());
+        let outcomes = ledger.anchor_batch_lenient(2, &[(3, 6), (2, 4)]).unwrap();
+        assert!(outcomes[0].is_err() && outcomes[1].is_ok());
+
+        let denials = ledger.denials(None, 10).unwrap();
+        assert_eq!(
+            denials[0],
+            Denial {
+                id: 0,
+                entity: 1,
+                prime: 3,
+                src: 1,
+                dst: 4,
+                timestamp: 7,
+                caller: Some("gateway".to_string()),
+            }
+        );
+        assert_eq!(denials.len(), 2);
+        assert_eq!((denials[1].entity, denials[1].caller.as_deref()), (2, None));
+        assert_eq!(ledger.denials(Some(0), 10).unwrap(), denials[1..]);
+
+        // Off by default.
+        let plain = Ledger::new(tmp.path().join("plain")).unwrap();
+        assert!(plain.anchor_batch(1, &[(3, 4)]).is_err());
+        assert!(plain.denials(None, 10).unwrap().is_empty());
+    }
+}
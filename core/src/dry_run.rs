@@ -0,0 +1,65 @@
+//! Dry-run validation of batches
+//! A batch is staged exactly as `anchor_batch` would stage it and then
+//! dropped, so callers can preflight commands without writing anything.
+
+use crate::{Ledger, LedgerError, LedgerEvent, StagedBatch};
+
+impl Ledger {
+    /// Run `commands` through the flow rules, MSD encoding and centroid
+    /// logic of [`Ledger::anchor_batch`] and return the events it would
+    /// commit right now, LSNs and hashes included, or the error it would
+    /// fail with. Nothing is logged or written, so read-only ledgers can
+    /// validate too.
+    pub fn validate_batch(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let _span = span!("validate_batch", entity, commands = commands.len());
+        self.ensure_live(entity)?;
+        let writer = self.write_lock.lock()?;
+        let mut staged = StagedBatch::new(self.clock.now_millis(), commands.len());
+        staged.audit = false;
+        self.prefetch(&mut staged, entity, commands)?;
+        for &(prime, target_node) in commands {
+            self.stage_command(&mut staged, &writer, entity, prime, target_node)?;
+        }
+        Ok(staged.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LedgerOptions, ManualClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn validated_events_match_the_commit_and_nothing_is_written() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = LedgerOptions::new().clock(Arc::new(ManualClock::new(7)));
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+
+        let commands = [(2, 4), (3, 2), (5, 2)];
+        let preview = ledger.validate_batch(1, &commands).unwrap();
+        assert_eq!(preview.len(), 2);
+        assert_eq!(ledger.next_lsn().unwrap(), 1);
+        assert_eq!(ledger.current_exponents(1, &[2]).unwrap(), vec![Some(2)]);
+        let committed = ledger.anchor_batch(1, &commands).unwrap();
+        let hashes = |events: &[LedgerEvent]| {
+            events
+                .iter()
+                .map(|e| (e.lsn, e.event_hash.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hashes(&preview), hashes(&committed));
+
+        assert!(matches!(
+            ledger.validate_batch(2, &[(3, 4)]),
+            Err(LedgerError::FlowRuleViolation { src: 1, dst: 4 })
+        ));
+        assert!(ledger.validate_batch(2, &[(4, 0)]).is_err());
+        assert_eq!(ledger.next_lsn().unwrap(), 3);
+    }
+}
@@ -0,0 +1,121 @@
+//! Dual-quaternion packing for 8-prime star
+//! Ψ₁ (exp₀…exp₃) is the real part and Ψ₂ (exp₄…exp₇) rides as the dual
+//! part, both unnormalized, so one unit dual quaternion applies a screw
+//! motion (rotation plus translation) to the whole exponent vector.
+
+use nalgebra::{
+    DualQuaternion, Quaternion, Translation3, UnitDualQuaternion, UnitQuaternion, Vector3,
+};
+
+use crate::qp_encode::{unit_and_norm, QpQuatOf, QuatScalar};
+
+/// `(real, dual)`, each as (w, i, j, k).
+pub type DualQuatParts<T = f32> = ([T; 4], [T; 4]);
+
+/// Eight prime exponents as a dual quaternion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DualQuatOf<T: QuatScalar>(pub DualQuaternion<T>);
+
+pub type DualQuat = DualQuatOf<f32>;
+pub type DualQuat64 = DualQuatOf<f64>;
+
+impl<T: QuatScalar> DualQuatOf<T> {
+    pub fn pack(exponents: &[i32; 8]) -> Self {
+        let e = |i: usize| T::from_exponent(exponents[i]);
+        DualQuatOf(DualQuaternion::from_real_and_dual(
+            Quaternion::new(e(0), e(1), e(2), e(3)),
+            Quaternion::new(e(4), e(5), e(6), e(7)),
+        ))
+    }
+
+    /// Nearest exponents, real part first.
+    pub fn unpack(&self) -> [i32; 8] {
+        let (real, dual) = self.to_parts();
+        let [a, b, c, d] = real.map(T::to_exponent);
+        let [e, f, g, h] = dual.map(T::to_exponent);
+        [a, b, c, d, e, f, g, h]
+    }
+
+    pub fn from_parts((real, dual): DualQuatParts<T>) -> Self {
+        let quat = |[w, i, j, k]: [T; 4]| Quaternion::new(w, i, j, k);
+        DualQuatOf(DualQuaternion::from_real_and_dual(quat(real), quat(dual)))
+    }
+
+    pub fn to_parts(&self) -> DualQuatParts<T> {
+        let parts = |q: &Quaternion<T>| [q.w, q.i, q.j, q.k];
+        (parts(&self.0.real), parts(&self.0.dual))
+    }
+
+    /// Rotate by `rotation`, then translate by `translation`.
+    pub fn motion(rotation: UnitQuaternion<T>, translation: Vector3<T>) -> UnitDualQuaternion<T> {
+        UnitDualQuaternion::from_parts(Translation3::from(translation), rotation)
+    }
+
+    /// Apply `motion` as `m * self * m̄`, with `m̄` conjugated as both a
+    /// quaternion and a dual number. A state of `(1, p)` moves like the
+    /// point `p`; a pure rotation acts as [`QpQuatOf::rotate`].
+    pub fn transform(&mut self, motion: &UnitDualQuaternion<T>) {
+        let m = motion.dual_quaternion();
+        let m_bar = DualQuaternion::from_real_and_dual(m.real.conjugate(), -m.dual.conjugate());
+        self.0 = *m * self.0 * m_bar;
+    }
+}
+
+impl<T: QuatScalar> From<QpQuatOf<T>> for DualQuatOf<T> {
+    fn from(qp: QpQuatOf<T>) -> Self {
+        DualQuatOf(DualQuaternion::from_real_and_dual(
+            qp.psi1 * qp.psi1_norm,
+            qp.psi2 * qp.psi2_norm,
+        ))
+    }
+}
+
+impl<T: QuatScalar> From<DualQuatOf<T>> for QpQuatOf<T> {
+    fn from(dq: DualQuatOf<T>) -> Self {
+        let (psi1, psi1_norm) = unit_and_norm(dq.0.real);
+        let (psi2, psi2_norm) = unit_and_norm(dq.0.dual);
+        QpQuatOf {
+            psi1,
+            psi2,
+            psi1_norm,
+            psi2_norm,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+    use crate::qp_encode::{QpQuat, QpQuat64};
+
+    #[test]
+    fn screw_motion_moves_points_and_matches_rotation() {
+        let exponents = [3, -1, 4, 1, -5, 9, 2, -6];
+        let dq = DualQuat64::pack(&exponents);
+        assert_eq!(dq.unpack(), exponents);
+        let qp = QpQuat64::from(dq);
+        assert_eq!(qp.unpack(), exponents);
+        assert_eq!(DualQuat64::from(qp).unpack(), exponents);
+
+        let rotation = UnitQuaternion::from_euler_angles(0.3, -1.2, 0.7);
+        let translation = Vector3::new(2.0, -3.0, 0.5);
+        let motion = DualQuat64::motion(rotation, translation);
+        let p = Point3::new(1.0, 2.0, -4.0);
+        let mut point = DualQuat64::from_parts(([1.0, 0.0, 0.0, 0.0], [0.0, p.x, p.y, p.z]));
+        point.transform(&motion);
+        let (real, [_, x, y, z]) = point.to_parts();
+        let expected = motion.transform_point(&p);
+        assert!((real[0] - 1.0).abs() < 1e-12);
+        assert!((Point3::new(x, y, z) - expected).norm() < 1e-12);
+
+        let mut rotated = DualQuat::pack(&exponents);
+        rotated.transform(&DualQuat::motion(rotation.cast(), Vector3::zeros()));
+        let mut qp = QpQuat::pack(&exponents);
+        qp.rotate(rotation.cast::<f32>().into_inner());
+        let (a, b) = (QpQuat::from(rotated), qp);
+        assert!((a.psi1 - b.psi1).norm() < 1e-5 && (a.psi2 - b.psi2).norm() < 1e-5);
+        assert_eq!(rotated.unpack(), qp.unpack());
+    }
+}
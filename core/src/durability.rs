@@ -0,0 +1,105 @@
+//! When commits are fsynced
+//! The event log and the RocksDB WAL follow one policy. Without a sync a
+//! process crash loses nothing, but a power loss can drop the newest
+//! commits from both.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rocksdb::WriteOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Leave flushing to the OS.
+    #[default]
+    Buffered,
+    /// fsync on every commit.
+    EveryBatch,
+    /// fsync on every `n`th commit, which also covers the ones before it.
+    EveryN(u32),
+}
+
+impl FromStr for SyncPolicy {
+    type Err = String;
+
+    /// `buffered`, `batch` or `every-<n>`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "buffered" => Ok(SyncPolicy::Buffered),
+            "batch" => Ok(SyncPolicy::EveryBatch),
+            _ => s
+                .strip_prefix("every-")
+                .and_then(|n| n.parse().ok())
+                .filter(|&n| n > 0)
+                .map(SyncPolicy::EveryN)
+                .ok_or_else(|| format!("unknown sync policy {:?}", s)),
+        }
+    }
+}
+
+/// Applies a [`SyncPolicy`], counting log appends and DB writes apart since
+/// the pipelined writer does them on different threads.
+#[derive(Debug, Default)]
+pub(crate) struct SyncState {
+    policy: SyncPolicy,
+    log_commits: AtomicU64,
+    db_commits: AtomicU64,
+}
+
+impl SyncState {
+    pub(crate) fn new(policy: SyncPolicy) -> Self {
+        SyncState {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Whether this log append must be fsynced.
+    pub(crate) fn sync_log(&self) -> bool {
+        self.due(&self.log_commits)
+    }
+
+    /// Write options for this commit's WriteBatch.
+    pub(crate) fn db_write_options(&self) -> WriteOptions {
+        let mut opts = WriteOptions::default();
+        opts.set_sync(self.due(&self.db_commits));
+        opts
+    }
+
+    fn due(&self, commits: &AtomicU64) -> bool {
+        match self.policy {
+            SyncPolicy::Buffered => false,
+            SyncPolicy::EveryBatch => true,
+            SyncPolicy::EveryN(n) => {
+                (commits.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(u64::from(n.max(1)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ledger, LedgerOptions};
+
+    #[test]
+    fn every_n_syncs_each_nth_commit() {
+        assert_eq!("every-3".parse(), Ok(SyncPolicy::EveryN(3)));
+        assert_eq!("Batch".parse(), Ok(SyncPolicy::EveryBatch));
+        assert!("every-0".parse::<SyncPolicy>().is_err());
+
+        let state = SyncState::new(SyncPolicy::EveryN(3));
+        let pattern = (0..6).map(|_| state.sync_log()).collect::<Vec<_>>();
+        assert_eq!(pattern, [false, false, true, false, false, true]);
+        assert!(!SyncState::new(SyncPolicy::Buffered).sync_log());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let options = LedgerOptions::new().sync(SyncPolicy::EveryBatch);
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        drop(ledger);
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        assert_eq!(ledger.verify_chain().unwrap(), 1);
+    }
+}
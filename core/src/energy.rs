@@ -0,0 +1,340 @@
+//! Energy meters and their calibration
+//! A meter is a monotonic counter; backends that know what a count is worth
+//! in joules say so, and [`calibrate`] borrows that from a reference meter
+//! for the ones that don't, so cycle counts from ARM and x86 hosts become
+//! comparable joules per operation.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub trait EnergyMeter {
+    /// Short label for reports, e.g. `"rapl"`.
+    fn name(&self) -> &'static str;
+
+    /// Current counter value; only differences between reads mean anything.
+    fn read(&self) -> io::Result<u64>;
+
+    /// Joules per count, when the backend measures energy directly or
+    /// models it.
+    fn joules_per_count(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// CPU cycle counter, from the best [`CycleSource`] this host allows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CycleCounter;
+
+/// Where [`CycleCounter`] reads from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleSource {
+    /// aarch64 PMU cycle counter; only where the kernel lets EL0 read it.
+    Pmccntr,
+    /// aarch64 virtual timer count, readable from user space everywhere
+    /// but ticking at the timer frequency rather than per cycle.
+    Cntvct,
+    /// x86_64 time-stamp counter.
+    Rdtsc,
+    /// [`WallClock`] nanoseconds.
+    Clock,
+}
+
+impl CycleSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            CycleSource::Pmccntr => "pmccntr",
+            CycleSource::Cntvct => "cntvct",
+            CycleSource::Rdtsc => "rdtsc",
+            CycleSource::Clock => "clock",
+        }
+    }
+}
+
+impl CycleCounter {
+    /// Chosen on first use. Reading PMCCNTR_EL0 without user access traps
+    /// with SIGILL, so it is tried in a forked child first and the counter
+    /// falls back to CNTVCT_EL0 if the child dies, the count stands still,
+    /// or SIGCHLD is ignored and the child cannot be waited for.
+    pub fn source() -> CycleSource {
+        static SOURCE: OnceLock<CycleSource> = OnceLock::new();
+        *SOURCE.get_or_init(detect_source)
+    }
+
+    pub fn now() -> u64 {
+        match Self::source() {
+            #[cfg(target_arch = "aarch64")]
+            CycleSource::Pmccntr => aarch64::pmccntr(),
+            #[cfg(target_arch = "aarch64")]
+            CycleSource::Cntvct => aarch64::cntvct(),
+            #[cfg(target_arch = "x86_64")]
+            CycleSource::Rdtsc => unsafe { std::arch::x86_64::_rdtsc() },
+            _ => WallClock.read().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect_source() -> CycleSource {
+    CycleSource::Rdtsc
+}
+
+#[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+fn detect_source() -> CycleSource {
+    if aarch64::pmccntr_readable() {
+        CycleSource::Pmccntr
+    } else {
+        CycleSource::Cntvct
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", not(target_os = "linux")))]
+fn detect_source() -> CycleSource {
+    CycleSource::Cntvct
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn detect_source() -> CycleSource {
+    CycleSource::Clock
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64 {
+    pub fn pmccntr() -> u64 {
+        let val: u64;
+        unsafe {
+            core::arch::asm!("mrs {0}, pmccntr_el0", out(reg) val);
+        }
+        val
+    }
+
+    pub fn cntvct() -> u64 {
+        let val: u64;
+        unsafe {
+            core::arch::asm!("isb", "mrs {0}, cntvct_el0", out(reg) val);
+        }
+        val
+    }
+
+    /// Whether a child can read PMCCNTR_EL0 twice and see it advance. The
+    /// child drops its core limit first so a SIGILL leaves no core file.
+    /// If the process ignores SIGCHLD the child is reaped before
+    /// `waitpid` sees it, which reads as a failure and picks Cntvct.
+    #[cfg(target_os = "linux")]
+    pub fn pmccntr_readable() -> bool {
+        unsafe {
+            match libc::fork() {
+                -1 => false,
+                0 => {
+                    let no_core = libc::rlimit {
+                        rlim_cur: 0,
+                        rlim_max: 0,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_CORE, &no_core) != 0 {
+                        libc::_exit(1);
+                    }
+                    let start = pmccntr();
+                    for _ in 0..1_000 {
+                        core::arch::asm!("nop");
+                    }
+                    libc::_exit(if pmccntr() > start { 0 } else { 1 })
+                }
+                pid => {
+                    let mut status = 0;
+                    libc::waitpid(pid, &mut status, 0) == pid
+                        && libc::WIFEXITED(status)
+                        && libc::WEXITSTATUS(status) == 0
+                }
+            }
+        }
+    }
+}
+
+impl EnergyMeter for CycleCounter {
+    fn name(&self) -> &'static str {
+        "cycles"
+    }
+
+    fn read(&self) -> io::Result<u64> {
+        Ok(CycleCounter::now())
+    }
+}
+
+/// Nanoseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WallClock;
+
+impl EnergyMeter for WallClock {
+    fn name(&self) -> &'static str {
+        "wall_clock"
+    }
+
+    fn read(&self) -> io::Result<u64> {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(io::Error::other)?;
+        Ok(elapsed.as_nanos() as u64)
+    }
+}
+
+/// Intel/AMD RAPL package energy in microjoules, from Linux powercap.
+#[derive(Debug, Clone)]
+pub struct Rapl {
+    path: PathBuf,
+}
+
+impl Rapl {
+    pub const DEFAULT_PATH: &'static str = "/sys/class/powercap/intel-rapl:0/energy_uj";
+
+    /// The first package domain; fails where RAPL is missing or unreadable.
+    pub fn open() -> io::Result<Self> {
+        Self::at(Self::DEFAULT_PATH)
+    }
+
+    pub fn at(path: impl AsRef<Path>) -> io::Result<Self> {
+        let rapl = Rapl {
+            path: path.as_ref().to_path_buf(),
+        };
+        rapl.read()?;
+        Ok(rapl)
+    }
+}
+
+impl EnergyMeter for Rapl {
+    fn name(&self) -> &'static str {
+        "rapl"
+    }
+
+    fn read(&self) -> io::Result<u64> {
+        fs::read_to_string(&self.path)?
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn joules_per_count(&self) -> Option<f64> {
+        Some(1e-6)
+    }
+}
+
+/// Assumes a constant power draw: counts elapsed nanoseconds and prices
+/// each at `watts`.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantModel {
+    pub watts: f64,
+    start: Instant,
+}
+
+impl ConstantModel {
+    pub fn new(watts: f64) -> Self {
+        ConstantModel {
+            watts,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl EnergyMeter for ConstantModel {
+    fn name(&self) -> &'static str {
+        "constant_model"
+    }
+
+    fn read(&self) -> io::Result<u64> {
+        Ok(self.start.elapsed().as_nanos() as u64)
+    }
+
+    fn joules_per_count(&self) -> Option<f64> {
+        Some(self.watts * 1e-9)
+    }
+}
+
+/// What one operation costs on the calibrated meter.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Calibration {
+    pub meter: &'static str,
+    pub reference: &'static str,
+    pub iterations: u64,
+    pub counts_per_op: f64,
+    pub joules_per_count: f64,
+    pub joules_per_op: f64,
+}
+
+/// Run `op` `iterations` times, reading `meter` and `reference` around the
+/// loop, and price `meter`'s counts with the energy `reference` saw.
+/// Fails if `reference` has no [`EnergyMeter::joules_per_count`] or
+/// `meter` did not advance.
+pub fn calibrate(
+    meter: &dyn EnergyMeter,
+    reference: &dyn EnergyMeter,
+    iterations: u64,
+    mut op: impl FnMut(),
+) -> io::Result<Calibration> {
+    let ref_joules = reference.joules_per_count().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} cannot serve as an energy reference", reference.name()),
+        )
+    })?;
+    let (ref_start, start) = (reference.read()?, meter.read()?);
+    for _ in 0..iterations {
+        op();
+    }
+    let (end, ref_end) = (meter.read()?, reference.read()?);
+    let counts = end.wrapping_sub(start) as f64;
+    if counts == 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} did not advance", meter.name()),
+        ));
+    }
+    let joules = ref_end.wrapping_sub(ref_start) as f64 * ref_joules;
+    let ops = iterations.max(1) as f64;
+    Ok(Calibration {
+        meter: meter.name(),
+        reference: reference.name(),
+        iterations,
+        counts_per_op: counts / ops,
+        joules_per_count: joules / counts,
+        joules_per_op: joules / ops,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_counter_picks_a_readable_source() {
+        let source = CycleCounter::source();
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(source, CycleSource::Rdtsc);
+        assert_eq!(CycleCounter::source(), source);
+        let start = CycleCounter::now();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert!(CycleCounter::now() > start, "{} stood still", source.name());
+    }
+
+    #[test]
+    fn calibration_prices_counts_against_a_reference() {
+        let reference = ConstantModel::new(10.0);
+        let work = || std::hint::black_box((0..1_000u64).sum::<u64>());
+        let cal = calibrate(&WallClock, &reference, 2_000, || {
+            work();
+        })
+        .unwrap();
+        assert_eq!((cal.meter, cal.reference), ("wall_clock", "constant_model"));
+        // Both count nanoseconds, so each is worth about 10 nJ.
+        assert!((cal.joules_per_count / 1e-8 - 1.0).abs() < 0.5);
+        assert!(cal.joules_per_op > 0.0 && cal.counts_per_op > 0.0);
+        assert!(calibrate(&CycleCounter, &WallClock, 1, || {}).is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("energy_uj");
+        fs::write(&path, "12345\n").unwrap();
+        let rapl = Rapl::at(&path).unwrap();
+        assert_eq!(rapl.read().unwrap(), 12345);
+        assert!(Rapl::at(tmp.path().join("missing")).is_err());
+    }
+}
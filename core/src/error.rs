@@ -0,0 +1,228 @@
+//! Typed errors for every fallible ledger operation
+//! Python callers see each kind as its own exception class (see `lib.rs`).
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::PoisonError;
+
+use crate::registry::NODES;
+use crate::{AccessMode, MsdError};
+
+#[derive(Debug)]
+pub enum LedgerError {
+    Io(std::io::Error),
+    Rocks(rocksdb::Error),
+    Json(serde_json::Error),
+    /// `anchor_batch` refused a transition the flow rule forbids.
+    FlowRuleViolation {
+        src: u8,
+        dst: u8,
+    },
+    UnknownPrime(u32),
+    InvalidNode(u8),
+    /// An exponent fell outside the configured bounds; see `bounds`.
+    ExponentOutOfBounds {
+        entity: u64,
+        prime: u32,
+        exponent: i64,
+        floor: i32,
+        ceiling: i32,
+    },
+    VersionConflict {
+        entity: u64,
+        expected: u64,
+        found: u64,
+    },
+    /// The entity was deleted; see `Ledger::delete_entity`.
+    EntityGone(u64),
+    /// A write was attempted on a read-only or secondary ledger.
+    ReadOnly(AccessMode),
+    /// Another process (or handle) holds the ledger; `pid` is read from the
+    /// lock file and may be missing if the owner has not written it yet.
+    AlreadyLocked {
+        path: PathBuf,
+        pid: Option<u32>,
+    },
+    /// Stored keys, values or files that don't decode.
+    Corruption(String),
+    /// A hash-chain, Merkle or signature check failed.
+    Verification(String),
+    InvalidArgument(String),
+    /// The ledger was created with a different prime registry than the one
+    /// it is being opened with; see `registry`.
+    RegistryMismatch {
+        stored: [u32; NODES],
+        requested: [u32; NODES],
+    },
+    /// Poisoned locks, lost background tasks and similar.
+    Internal(String),
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::Io(e) => write!(f, "I/O error: {}", e),
+            LedgerError::Rocks(e) => write!(f, "RocksDB error: {}", e),
+            LedgerError::Json(e) => write!(f, "JSON error: {}", e),
+            LedgerError::FlowRuleViolation { src, dst } => {
+                write!(f, "Transition {}→{} forbidden", src, dst)
+            }
+            LedgerError::UnknownPrime(p) => write!(f, "Prime {} not in the registry", p),
+            LedgerError::InvalidNode(n) => write!(f, "Invalid node {}", n),
+            LedgerError::ExponentOutOfBounds {
+                entity,
+                prime,
+                exponent,
+                floor,
+                ceiling,
+            } => write!(
+                f,
+                "exponent {} of prime {} on entity {} is outside [{}, {}]",
+                exponent, prime, entity, floor, ceiling
+            ),
+            LedgerError::VersionConflict {
+                entity,
+                expected,
+                found,
+            } => write!(
+                f,
+                "version conflict on entity {}: expected {}, found {}",
+                entity, expected, found
+            ),
+            LedgerError::EntityGone(entity) => write!(f, "entity {} is gone", entity),
+            LedgerError::ReadOnly(mode) => {
+                write!(f, "ledger is opened {:?}; writes are not allowed", mode)
+            }
+            LedgerError::AlreadyLocked {
+                path,
+                pid: Some(pid),
+            } => write!(f, "ledger {} is locked by process {}", path.display(), pid),
+            LedgerError::AlreadyLocked { path, pid: None } => {
+                write!(f, "ledger {} is locked by another process", path.display())
+            }
+            LedgerError::RegistryMismatch { stored, requested } => write!(
+                f,
+                "ledger was created with primes {:?}, not {:?}",
+                stored, requested
+            ),
+            LedgerError::Corruption(msg)
+            | LedgerError::Verification(msg)
+            | LedgerError::InvalidArgument(msg)
+            | LedgerError::Internal(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LedgerError::Io(e) => Some(e),
+            LedgerError::Rocks(e) => Some(e),
+            LedgerError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LedgerError {
+    fn from(e: std::io::Error) -> Self {
+        LedgerError::Io(e)
+    }
+}
+
+impl From<rocksdb::Error> for LedgerError {
+    fn from(e: rocksdb::Error) -> Self {
+        LedgerError::Rocks(e)
+    }
+}
+
+impl From<serde_json::Error> for LedgerError {
+    fn from(e: serde_json::Error) -> Self {
+        LedgerError::Json(e)
+    }
+}
+
+impl From<MsdError> for LedgerError {
+    fn from(e: MsdError) -> Self {
+        LedgerError::Corruption(e.to_string())
+    }
+}
+
+impl From<std::num::TryFromIntError> for LedgerError {
+    fn from(e: std::num::TryFromIntError) -> Self {
+        LedgerError::Internal(e.to_string())
+    }
+}
+
+impl<T> From<PoisonError<T>> for LedgerError {
+    fn from(e: PoisonError<T>) -> Self {
+        LedgerError::Internal(e.to_string())
+    }
+}
+
+/// Python exception classes, one per error kind that callers branch on.
+pub(crate) mod py {
+    use pyo3::exceptions::{PyIOError, PyRuntimeError, PyValueError};
+    use pyo3::prelude::*;
+
+    pyo3::create_exception!(core, LedgerError, PyRuntimeError);
+    pyo3::create_exception!(core, FlowRuleViolationError, LedgerError);
+    pyo3::create_exception!(core, UnknownPrimeError, LedgerError);
+    pyo3::create_exception!(core, ExponentOutOfBoundsError, LedgerError);
+    pyo3::create_exception!(core, VersionConflictError, LedgerError);
+    pyo3::create_exception!(core, EntityGoneError, LedgerError);
+    pyo3::create_exception!(core, ReadOnlyError, LedgerError);
+    pyo3::create_exception!(core, AlreadyLockedError, LedgerError);
+    pyo3::create_exception!(core, CorruptionError, LedgerError);
+    pyo3::create_exception!(core, VerificationError, LedgerError);
+    pyo3::create_exception!(core, RegistryMismatchError, LedgerError);
+
+    impl From<super::LedgerError> for PyErr {
+        fn from(e: super::LedgerError) -> PyErr {
+            use super::LedgerError as E;
+            let msg = e.to_string();
+            match e {
+                E::Io(_) => PyIOError::new_err(msg),
+                E::FlowRuleViolation { .. } => FlowRuleViolationError::new_err(msg),
+                E::UnknownPrime(_) => UnknownPrimeError::new_err(msg),
+                E::ExponentOutOfBounds { .. } => ExponentOutOfBoundsError::new_err(msg),
+                E::VersionConflict { .. } => VersionConflictError::new_err(msg),
+                E::EntityGone(_) => EntityGoneError::new_err(msg),
+                E::ReadOnly(_) => ReadOnlyError::new_err(msg),
+                E::AlreadyLocked { .. } => AlreadyLockedError::new_err(msg),
+                E::Corruption(_) => CorruptionError::new_err(msg),
+                E::Verification(_) => VerificationError::new_err(msg),
+                E::RegistryMismatch { .. } => RegistryMismatchError::new_err(msg),
+                E::InvalidNode(_) | E::InvalidArgument(_) => PyValueError::new_err(msg),
+                E::Rocks(_) | E::Json(_) | E::Internal(_) => LedgerError::new_err(msg),
+            }
+        }
+    }
+
+    pub fn register(py: Python, m: &PyModule) -> PyResult<()> {
+        m.add("LedgerError", py.get_type::<LedgerError>())?;
+        m.add(
+            "FlowRuleViolationError",
+            py.get_type::<FlowRuleViolationError>(),
+        )?;
+        m.add("UnknownPrimeError", py.get_type::<UnknownPrimeError>())?;
+        m.add(
+            "ExponentOutOfBoundsError",
+            py.get_type::<ExponentOutOfBoundsError>(),
+        )?;
+        m.add(
+            "VersionConflictError",
+            py.get_type::<VersionConflictError>(),
+        )?;
+        m.add("EntityGoneError", py.get_type::<EntityGoneError>())?;
+        m.add("ReadOnlyError", py.get_type::<ReadOnlyError>())?;
+        m.add("AlreadyLockedError", py.get_type::<AlreadyLockedError>())?;
+        m.add("CorruptionError", py.get_type::<CorruptionError>())?;
+        m.add("VerificationError", py.get_type::<VerificationError>())?;
+        m.add(
+            "RegistryMismatchError",
+            py.get_type::<RegistryMismatchError>(),
+        )?;
+        Ok(())
+    }
+}
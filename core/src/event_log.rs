@@ -0,0 +1,464 @@
+//! Append-only JSON-lines event log, split into segments
+//! Active segment: `<base>/event.log`; sealed segments: `<base>/segments/NNNNNN.log`
+//! Lines hold events as their JSON, except that MSD digits are nibble-packed;
+//! see [`LogRecord`] and `schema` for older forms.
+
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::centroid::CentroidDigit;
+use crate::encryption::{self, LogCipher};
+use crate::lsn;
+use crate::msd::Msd;
+use crate::schema::{self, EVENT_SCHEMA_VERSION};
+use crate::{LedgerError, LedgerEvent};
+
+/// Active segment is sealed once it grows past this many bytes.
+pub const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// On-disk form of a [`LedgerEvent`] at [`EVENT_SCHEMA_VERSION`]: its JSON
+/// with `msd_digits` replaced by `msd`, the hex of [`Msd::to_bytes`],
+/// and the version in `v`. Event hashes are taken over the unpacked JSON,
+/// so they do not depend on this form.
+#[derive(Serialize, Deserialize)]
+struct LogRecord<'a> {
+    #[serde(default)]
+    v: u32,
+    entity_id: u64,
+    prime: u32,
+    msd: Cow<'a, str>,
+    via_c: bool,
+    centroid_digit: CentroidDigit,
+    timestamp: u64,
+    #[serde(default)]
+    prev_hash: Cow<'a, str>,
+    #[serde(default)]
+    event_hash: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    signature: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    tombstone: bool,
+    #[serde(default, skip_serializing_if = "lsn::is_zero")]
+    lsn: u64,
+}
+
+impl<'a> LogRecord<'a> {
+    fn new(evt: &'a LedgerEvent) -> Self {
+        LogRecord {
+            v: EVENT_SCHEMA_VERSION,
+            entity_id: evt.entity_id,
+            prime: evt.prime,
+            msd: Cow::Owned(hex::encode(evt.msd_digits.to_bytes())),
+            via_c: evt.via_c,
+            centroid_digit: evt.centroid_digit,
+            timestamp: evt.timestamp,
+            prev_hash: Cow::Borrowed(&evt.prev_hash),
+            event_hash: Cow::Borrowed(&evt.event_hash),
+            signature: Cow::Borrowed(&evt.signature),
+            tombstone: evt.tombstone,
+            lsn: evt.lsn,
+        }
+    }
+
+    /// Parse a record of any schema version, up-converting older ones.
+    fn parse(json: &str) -> Result<Self, String> {
+        if let Ok(record) = serde_json::from_str::<LogRecord>(json) {
+            if record.v == EVENT_SCHEMA_VERSION {
+                return Ok(record);
+            }
+        }
+        let mut record = schema::parse_record(json)?;
+        let version = schema::record_version(&record);
+        schema::convert(&mut record, version, EVENT_SCHEMA_VERSION)?;
+        serde_json::from_value(record.into()).map_err(|e| e.to_string())
+    }
+
+    fn into_event(self) -> Result<LedgerEvent, String> {
+        let msd_digits = hex::decode(&*self.msd)
+            .map_err(|_| format!("malformed packed MSD digits {:?}", self.msd))
+            .and_then(|raw| Msd::from_bytes(&raw).map_err(|e| e.to_string()))?;
+        // Every reader of the log takes deltas as `i32`s.
+        if msd_digits.to_i32().is_none() {
+            return Err(format!("MSD delta {} is out of i32 range", msd_digits));
+        }
+        Ok(LedgerEvent {
+            entity_id: self.entity_id,
+            prime: self.prime,
+            msd_digits,
+            via_c: self.via_c,
+            centroid_digit: self.centroid_digit,
+            timestamp: self.timestamp,
+            prev_hash: self.prev_hash.into_owned(),
+            event_hash: self.event_hash.into_owned(),
+            signature: self.signature.into_owned(),
+            tombstone: self.tombstone,
+            lsn: self.lsn,
+        })
+    }
+}
+
+pub struct EventLog {
+    base_dir: PathBuf,
+    active: PathBuf,
+    segments_dir: PathBuf,
+    segment_bytes: u64,
+    /// Lowest number the next sealed segment may take; keeps numbering
+    /// monotonic after old segments have been pruned.
+    next_floor: AtomicU64,
+    /// Set when the log is encrypted at rest; see `encryption`.
+    cipher: Option<LogCipher>,
+}
+
+impl EventLog {
+    pub fn open<P: AsRef<Path>>(
+        base_path: P,
+        cipher: Option<LogCipher>,
+    ) -> Result<Self, LedgerError> {
+        let base_path = base_path.as_ref();
+        let segments_dir = base_path.join("segments");
+        fs::create_dir_all(&segments_dir)?;
+
+        let active = base_path.join("event.log");
+        OpenOptions::new().create(true).append(true).open(&active)?;
+
+        Ok(EventLog {
+            base_dir: base_path.to_path_buf(),
+            active,
+            segments_dir,
+            segment_bytes: DEFAULT_SEGMENT_BYTES,
+            next_floor: AtomicU64::new(1),
+            cipher,
+        })
+    }
+
+    /// Open a log that must already exist, without creating anything;
+    /// used by read-only opens.
+    pub fn open_existing<P: AsRef<Path>>(
+        base_path: P,
+        cipher: Option<LogCipher>,
+    ) -> Result<Self, LedgerError> {
+        let base_path = base_path.as_ref();
+        let active = base_path.join("event.log");
+        if !active.exists() {
+            return Err(LedgerError::InvalidArgument(format!(
+                "no event log at {}",
+                active.display()
+            )));
+        }
+        Ok(EventLog {
+            base_dir: base_path.to_path_buf(),
+            active,
+            segments_dir: base_path.join("segments"),
+            segment_bytes: DEFAULT_SEGMENT_BYTES,
+            next_floor: AtomicU64::new(1),
+            cipher,
+        })
+    }
+
+    /// Append `events` to the active segment, one [`LogRecord`] (or
+    /// encrypted envelope) per line, and return the bytes written; `sync`
+    /// fsyncs the segment afterwards.
+    pub fn append(&self, events: &[LedgerEvent], sync: bool) -> Result<u64, LedgerError> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+        let mut buf = String::new();
+        for evt in events {
+            let json = serde_json::to_string(&LogRecord::new(evt))?;
+            match &self.cipher {
+                Some(cipher) => buf.push_str(&cipher.seal(&json)?),
+                None => buf.push_str(&json),
+            }
+            buf.push('\n');
+        }
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.active)?;
+        log.write_all(buf.as_bytes())?;
+        if sync {
+            log.sync_data()?;
+        }
+        Ok(buf.len() as u64)
+    }
+
+    /// Seal the active segment if it has outgrown the segment size.
+    pub fn maybe_rotate(&self) -> Result<Option<PathBuf>, LedgerError> {
+        if self.active_len()? >= self.segment_bytes {
+            self.seal()
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Move the active segment into `segments/` and start a fresh one.
+    /// Returns `None` when the active segment is empty.
+    pub fn seal(&self) -> Result<Option<PathBuf>, LedgerError> {
+        if self.active_len()? == 0 {
+            return Ok(None);
+        }
+        let next = self
+            .sealed_segments()?
+            .last()
+            .and_then(|p| segment_number(p))
+            .map_or(1, |n| n + 1)
+            .max(self.next_floor.load(Ordering::SeqCst));
+        let sealed = self.segments_dir.join(segment_file_name(next));
+        fs::rename(&self.active, &sealed)?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.active)?;
+        Ok(Some(sealed))
+    }
+
+    /// Every event in log order: sealed segments first, then the active one.
+    pub fn read_all(&self) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let mut events = Vec::new();
+        for segment in self.sealed_segments()? {
+            events.extend(self.read_segment(&segment)?);
+        }
+        events.extend(self.read_segment(&self.active)?);
+        Ok(events)
+    }
+
+    /// Every event in log order, parsed lazily line by line. All segment
+    /// files are opened up front, so rotation or pruning while iterating
+    /// does not cause events to be skipped.
+    pub fn iter(&self) -> Result<EventIter, LedgerError> {
+        let files = self
+            .all_segments()?
+            .into_iter()
+            .map(|path| Ok((File::open(&path)?, path)))
+            .collect::<Result<Vec<_>, LedgerError>>()?;
+        Ok(EventIter {
+            pending: files.into_iter(),
+            current: None,
+            cipher: self.cipher.clone(),
+        })
+    }
+
+    /// Most recently logged event, if any.
+    pub fn last_event(&self) -> Result<Option<LedgerEvent>, LedgerError> {
+        if let Some(evt) = self.read_segment(&self.active)?.pop() {
+            return Ok(Some(evt));
+        }
+        match self.sealed_segments()?.last() {
+            Some(segment) => Ok(self.read_segment(segment)?.pop()),
+            None => Ok(None),
+        }
+    }
+
+    /// Parse every event in a segment file written by this log.
+    pub fn read_segment(&self, path: &Path) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.decode_segment(path, &fs::read(path)?)
+    }
+
+    /// Parse the raw bytes of a segment, e.g. one shipped from another
+    /// ledger. `path` only labels errors.
+    pub fn decode_segment(&self, path: &Path, raw: &[u8]) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let text = std::str::from_utf8(raw)
+            .map_err(|e| LedgerError::Corruption(format!("{}: {}", path.display(), e)))?;
+        let mut events = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            if let Some(evt) = parse_line(path, n, line, self.cipher.as_ref())? {
+                events.push(evt);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Never reuse segment numbers up to and including `n`.
+    pub fn reserve_segments_through(&self, n: u64) {
+        self.next_floor.fetch_max(n + 1, Ordering::SeqCst);
+    }
+
+    /// Ledger directory holding the log.
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Rewrite the segment at `path` line by line. `convert` gets each
+    /// line's plaintext and returns its replacement, or `None` to keep it;
+    /// the file is replaced atomically, and only if something changed.
+    /// Returns the number of lines replaced.
+    pub fn rewrite_segment(
+        &self,
+        path: &Path,
+        mut convert: impl FnMut(&str) -> Result<Option<String>, String>,
+    ) -> Result<u64, LedgerError> {
+        let text = fs::read_to_string(path)?;
+        let mut out = String::with_capacity(text.len());
+        let mut rewritten = 0;
+        for (n, line) in text.lines().enumerate() {
+            let corrupt =
+                |e: String| LedgerError::Corruption(format!("{}:{}: {}", path.display(), n + 1, e));
+            let replacement = if line.trim().is_empty() {
+                None
+            } else {
+                let json = encryption::open_line(line, self.cipher.as_ref()).map_err(corrupt)?;
+                convert(&json).map_err(corrupt)?
+            };
+            match replacement {
+                Some(json) => {
+                    match &self.cipher {
+                        Some(cipher) => out.push_str(&cipher.seal(&json)?),
+                        None => out.push_str(&json),
+                    }
+                    rewritten += 1;
+                }
+                None => out.push_str(line),
+            }
+            out.push('\n');
+        }
+        if rewritten > 0 {
+            let tmp = path.with_extension("rewrite");
+            let mut file = File::create(&tmp)?;
+            file.write_all(out.as_bytes())?;
+            file.sync_data()?;
+            fs::rename(&tmp, path)?;
+        }
+        Ok(rewritten)
+    }
+
+    /// Sealed segments then the active one.
+    pub fn all_segments(&self) -> Result<Vec<PathBuf>, LedgerError> {
+        let mut paths = self.sealed_segments()?;
+        paths.push(self.active.clone());
+        Ok(paths)
+    }
+
+    /// Sealed segments, oldest first.
+    pub fn sealed_segments(&self) -> Result<Vec<PathBuf>, LedgerError> {
+        list_segments(&self.segments_dir)
+    }
+
+    fn active_len(&self) -> Result<u64, LedgerError> {
+        fs::metadata(&self.active)
+            .map(|m| m.len())
+            .map_err(LedgerError::from)
+    }
+}
+
+pub fn segment_file_name(n: u64) -> String {
+    format!("{:06}.log", n)
+}
+
+pub fn segment_number(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+fn parse_line(
+    path: &Path,
+    n: usize,
+    line: &str,
+    cipher: Option<&LogCipher>,
+) -> Result<Option<LedgerEvent>, LedgerError> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let corrupt =
+        |e: String| LedgerError::Corruption(format!("{}:{}: {}", path.display(), n + 1, e));
+    let json = encryption::open_line(line, cipher).map_err(corrupt)?;
+    LogRecord::parse(&json)
+        .and_then(LogRecord::into_event)
+        .map(Some)
+        .map_err(corrupt)
+}
+
+/// Streaming reader over the log; see [`EventLog::iter`].
+pub struct EventIter {
+    pending: std::vec::IntoIter<(File, PathBuf)>,
+    current: Option<(PathBuf, std::iter::Enumerate<Lines<BufReader<File>>>)>,
+    cipher: Option<LogCipher>,
+}
+
+impl Iterator for EventIter {
+    type Item = Result<LedgerEvent, LedgerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((path, lines)) = &mut self.current else {
+                let (file, path) = self.pending.next()?;
+                self.current = Some((path, BufReader::new(file).lines().enumerate()));
+                continue;
+            };
+            match lines.next() {
+                None => self.current = None,
+                Some((_, Err(e))) => return Some(Err(e.into())),
+                Some((n, Ok(line))) => match parse_line(path, n, &line, self.cipher.as_ref()) {
+                    Ok(None) => {}
+                    Ok(Some(evt)) => return Some(Ok(evt)),
+                    Err(e) => return Some(Err(e)),
+                },
+            }
+        }
+    }
+}
+
+/// Segment files in `dir`, ordered by segment number.
+pub fn list_segments(dir: &Path) -> Result<Vec<PathBuf>, LedgerError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut segments = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log") && segment_number(p).is_some())
+        .collect::<Vec<_>>();
+    segments.sort_by_key(|p| segment_number(p));
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ledger;
+
+    #[test]
+    fn digits_are_packed_and_legacy_lines_still_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        let events = ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        let log = fs::read_to_string(tmp.path().join("event.log")).unwrap();
+        assert!(log.lines().next().unwrap().contains("\"msd\":\"0102\""));
+        assert!(!log.contains("msd_digits"));
+        assert_eq!(
+            ledger.log.read_all().unwrap()[1].msd_digits.as_slice(),
+            [-2]
+        );
+
+        let mut legacy = events[0].clone();
+        legacy.lsn = 2;
+        let line = serde_json::to_string(&legacy).unwrap();
+        assert!(line.contains("\"msd_digits\":[2]"));
+        fs::write(tmp.path().join("event.log"), format!("{}\n{}\n", log, line)).unwrap();
+        let read = ledger.log.read_all().unwrap();
+        assert_eq!(read[2].msd_digits.as_slice(), [2]);
+        assert_eq!(read[2].event_hash, legacy.event_hash);
+    }
+
+    #[test]
+    fn oversized_deltas_are_corruption() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        let log = fs::read_to_string(tmp.path().join("event.log")).unwrap();
+        // 17 digits, 4^16 > i32::MAX.
+        let mut huge = vec![0; 16];
+        huge.push(1);
+        let huge = hex::encode(Msd::from_digits(huge).to_bytes());
+        let line = log.replacen("\"msd\":\"0102\"", &format!("\"msd\":\"{}\"", huge), 1);
+        fs::write(tmp.path().join("event.log"), line).unwrap();
+        assert!(matches!(
+            ledger.log.read_all(),
+            Err(LedgerError::Corruption(msg)) if msg.contains("out of i32 range")
+        ));
+        assert!(matches!(ledger.fsck(), Err(LedgerError::Corruption(_))));
+        assert!(ledger.rebuild_projections().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! In-process LRU of current exponents for the write path
+//! Only code holding the write lock reads or fills the cache, and every
+//! commit refreshes the pairs it touched, so it never serves stale values.
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use lru::LruCache;
+
+/// Entries kept when `LedgerOptions::exponent_cache_size` is not set.
+pub const DEFAULT_EXPONENT_CACHE_SIZE: usize = 65_536;
+
+/// (entity, prime) → exponent; `None` caches a confirmed absence, which
+/// saves a get just the same.
+type Lru = LruCache<(u64, u32), Option<i32>>;
+
+pub(crate) struct ExponentCache {
+    inner: Option<Mutex<Lru>>,
+}
+
+impl ExponentCache {
+    /// A cache holding up to `size` pairs; 0 disables caching.
+    pub(crate) fn new(size: usize) -> Self {
+        ExponentCache {
+            inner: NonZeroUsize::new(size).map(|n| Mutex::new(LruCache::new(n))),
+        }
+    }
+
+    pub(crate) fn disabled() -> Self {
+        Self::new(0)
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// `Some(value)` on a hit.
+    pub(crate) fn get(&self, entity: u64, prime: u32) -> Option<Option<i32>> {
+        let inner = self.inner.as_ref()?;
+        let mut cache = inner.lock().unwrap_or_else(|e| e.into_inner());
+        cache.get(&(entity, prime)).copied()
+    }
+
+    pub(crate) fn put(&self, entity: u64, prime: u32, value: Option<i32>) {
+        if let Some(inner) = &self.inner {
+            let mut cache = inner.lock().unwrap_or_else(|e| e.into_inner());
+            cache.put((entity, prime), value);
+        }
+    }
+
+    /// Record values a commit has just written.
+    pub(crate) fn put_all(&self, values: &HashMap<(u64, u32), i32>) {
+        if let Some(inner) = &self.inner {
+            let mut cache = inner.lock().unwrap_or_else(|e| e.into_inner());
+            for (&key, &value) in values {
+                cache.put(key, Some(value));
+            }
+        }
+    }
+
+    pub(crate) fn invalidate(&self, entity: u64, prime: u32) {
+        if let Some(inner) = &self.inner {
+            let mut cache = inner.lock().unwrap_or_else(|e| e.into_inner());
+            cache.pop(&(entity, prime));
+        }
+    }
+
+    /// Drop everything; for writes that bypass the anchor path.
+    pub(crate) fn clear(&self) {
+        if let Some(inner) = &self.inner {
+            inner.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ledger, LedgerOptions};
+
+    #[test]
+    fn cache_stays_coherent_across_write_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = LedgerOptions::new().exponent_cache_size(16);
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        ledger.anchor_batch(1, &[(2, 4)]).unwrap();
+        assert_eq!(ledger.exponent_cache.get(1, 2), Some(Some(4)));
+
+        ledger.bulk_load(vec![(1, 2, 6)]).unwrap();
+        assert_eq!(ledger.exponent_cache.get(1, 2), None);
+        // 6 → 2 is a delta of -4, computed from the loaded value.
+        let events = ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        assert_eq!(events[0].msd_digits.to_int(), -4);
+
+        let m = ledger.metrics().unwrap();
+        assert!(m.exponent_cache_hits >= 1);
+        assert!(m.exponent_cache_misses >= 2);
+        assert!(m.exponent_cache_hit_rate > 0.0);
+    }
+}
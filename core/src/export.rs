@@ -0,0 +1,491 @@
+//! Streaming export of current factors, entity metadata or the event history
+//! CSV and JSON Lines are always available; Parquet needs the `parquet` feature.
+
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::{Ledger, LedgerError, LedgerEvent, LedgerSnapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = LedgerError;
+
+    fn from_str(s: &str) -> Result<Self, LedgerError> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ExportFormat::Jsonl),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(LedgerError::InvalidArgument(format!(
+                "unknown export format {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+/// What to export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportData {
+    /// Current exponents: one row per (entity, prime).
+    Factors,
+    /// Every logged event, oldest first.
+    Events,
+    /// Entity metadata; see [`Ledger::set_metadata`].
+    Metadata,
+}
+
+impl FromStr for ExportData {
+    type Err = LedgerError;
+
+    fn from_str(s: &str) -> Result<Self, LedgerError> {
+        match s.to_ascii_lowercase().as_str() {
+            "factors" => Ok(ExportData::Factors),
+            "events" => Ok(ExportData::Events),
+            "metadata" => Ok(ExportData::Metadata),
+            _ => Err(LedgerError::InvalidArgument(format!(
+                "unknown export data {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnType {
+    UInt64,
+    UInt32,
+    Int32,
+    Bool,
+    Utf8,
+}
+
+enum Value<'a> {
+    UInt(u64),
+    Int(i32),
+    Bool(bool),
+    Str(&'a str),
+}
+
+/// A flat export row; every format writes the same columns.
+trait Row: Serialize {
+    const COLUMNS: &'static [(&'static str, ColumnType)];
+
+    fn values(&self) -> Vec<Value<'_>>;
+}
+
+#[derive(Serialize)]
+struct FactorRow {
+    entity_id: u64,
+    prime: u32,
+    exponent: i32,
+}
+
+impl Row for FactorRow {
+    const COLUMNS: &'static [(&'static str, ColumnType)] = &[
+        ("entity_id", ColumnType::UInt64),
+        ("prime", ColumnType::UInt32),
+        ("exponent", ColumnType::Int32),
+    ];
+
+    fn values(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::UInt(self.entity_id),
+            Value::UInt(self.prime.into()),
+            Value::Int(self.exponent),
+        ]
+    }
+}
+
+/// An event with its MSD digits decoded to the exponent delta.
+#[derive(Serialize)]
+struct EventRow {
+    entity_id: u64,
+    prime: u32,
+    delta: i32,
+    via_c: bool,
+    centroid_digit: u32,
+    timestamp: u64,
+    prev_hash: String,
+    event_hash: String,
+    signature: String,
+    tombstone: bool,
+    kind: &'static str,
+}
+
+impl From<LedgerEvent> for EventRow {
+    fn from(evt: LedgerEvent) -> Self {
+        EventRow {
+            entity_id: evt.entity_id,
+            prime: evt.prime,
+            delta: evt.msd_digits.to_int(),
+            via_c: evt.via_c,
+            centroid_digit: evt.centroid_digit.into(),
+            timestamp: evt.timestamp,
+            prev_hash: evt.prev_hash,
+            event_hash: evt.event_hash,
+            signature: evt.signature,
+            tombstone: evt.tombstone,
+            kind: evt.kind().as_str(),
+        }
+    }
+}
+
+impl Row for EventRow {
+    const COLUMNS: &'static [(&'static str, ColumnType)] = &[
+        ("entity_id", ColumnType::UInt64),
+        ("prime", ColumnType::UInt32),
+        ("delta", ColumnType::Int32),
+        ("via_c", ColumnType::Bool),
+        ("centroid_digit", ColumnType::UInt32),
+        ("timestamp", ColumnType::UInt64),
+        ("prev_hash", ColumnType::Utf8),
+        ("event_hash", ColumnType::Utf8),
+        ("signature", ColumnType::Utf8),
+        ("tombstone", ColumnType::Bool),
+        ("kind", ColumnType::Utf8),
+    ];
+
+    fn values(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::UInt(self.entity_id),
+            Value::UInt(self.prime.into()),
+            Value::Int(self.delta),
+            Value::Bool(self.via_c),
+            Value::UInt(self.centroid_digit.into()),
+            Value::UInt(self.timestamp),
+            Value::Str(&self.prev_hash),
+            Value::Str(&self.event_hash),
+            Value::Str(&self.signature),
+            Value::Bool(self.tombstone),
+            Value::Str(self.kind),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct MetadataRow {
+    entity_id: u64,
+    metadata: serde_json::Value,
+    /// `metadata` as JSON text, for the flat formats.
+    #[serde(skip)]
+    text: String,
+}
+
+impl Row for MetadataRow {
+    const COLUMNS: &'static [(&'static str, ColumnType)] = &[
+        ("entity_id", ColumnType::UInt64),
+        ("metadata", ColumnType::Utf8),
+    ];
+
+    fn values(&self) -> Vec<Value<'_>> {
+        vec![Value::UInt(self.entity_id), Value::Str(&self.text)]
+    }
+}
+
+impl Ledger {
+    /// Stream `data` to `writer` as `format`; returns the number of rows.
+    /// Reads go through one [`Ledger::snapshot`], so commits made while
+    /// exporting are left out; rows are streamed, never loaded as a whole.
+    pub fn export<W: Write + Send>(
+        &self,
+        data: ExportData,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<u64, LedgerError> {
+        self.snapshot()?.export(data, format, writer)
+    }
+}
+
+impl LedgerSnapshot<'_> {
+    /// [`Ledger::export`] of this snapshot.
+    pub fn export<W: Write + Send>(
+        &self,
+        data: ExportData,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<u64, LedgerError> {
+        match data {
+            ExportData::Factors => {
+                let rows = self.iter_factors()?.map(|entry| {
+                    entry.map(|(entity_id, prime, exponent)| FactorRow {
+                        entity_id,
+                        prime,
+                        exponent,
+                    })
+                });
+                write_rows(rows, format, writer)
+            }
+            ExportData::Events => {
+                let rows = self.events()?.map(|evt| evt.map(EventRow::from));
+                write_rows(rows, format, writer)
+            }
+            ExportData::Metadata => {
+                let rows = self.iter_metadata()?.map(|entry| {
+                    entry.map(|(entity_id, metadata)| MetadataRow {
+                        entity_id,
+                        text: metadata.to_string(),
+                        metadata,
+                    })
+                });
+                write_rows(rows, format, writer)
+            }
+        }
+    }
+}
+
+fn write_rows<R: Row, W: Write + Send>(
+    rows: impl Iterator<Item = Result<R, LedgerError>>,
+    format: ExportFormat,
+    writer: W,
+) -> Result<u64, LedgerError> {
+    match format {
+        ExportFormat::Csv => write_csv(rows, writer),
+        ExportFormat::Jsonl => write_jsonl(rows, writer),
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => parquet_sink::write_parquet(rows, writer),
+    }
+}
+
+fn write_csv<R: Row, W: Write>(
+    rows: impl Iterator<Item = Result<R, LedgerError>>,
+    writer: W,
+) -> Result<u64, LedgerError> {
+    let mut out = BufWriter::new(writer);
+    let header: Vec<&str> = R::COLUMNS.iter().map(|(name, _)| *name).collect();
+    writeln!(out, "{}", header.join(","))?;
+    let mut count = 0;
+    for row in rows {
+        let row = row?;
+        let mut line = String::new();
+        for (i, value) in row.values().into_iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            match value {
+                Value::UInt(v) => line.push_str(&v.to_string()),
+                Value::Int(v) => line.push_str(&v.to_string()),
+                Value::Bool(v) => line.push_str(if v { "true" } else { "false" }),
+                Value::Str(v) => push_csv_field(&mut line, v),
+            }
+        }
+        writeln!(out, "{}", line)?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Append `v`, quoted per RFC 4180 if it contains a delimiter.
+fn push_csv_field(line: &mut String, v: &str) {
+    if v.contains([',', '"', '\n', '\r']) {
+        line.push('"');
+        line.push_str(&v.replace('"', "\"\""));
+        line.push('"');
+    } else {
+        line.push_str(v);
+    }
+}
+
+fn write_jsonl<R: Row, W: Write>(
+    rows: impl Iterator<Item = Result<R, LedgerError>>,
+    writer: W,
+) -> Result<u64, LedgerError> {
+    let mut out = BufWriter::new(writer);
+    let mut count = 0;
+    for row in rows {
+        serde_json::to_writer(&mut out, &row?)?;
+        out.write_all(b"\n")?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use parquet::column::writer::ColumnWriter;
+    use parquet::data_type::ByteArray;
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    use super::{ColumnType, Row, Value};
+    use crate::LedgerError;
+
+    /// Rows buffered per row group.
+    const ROW_GROUP: usize = 64 * 1024;
+
+    impl From<ParquetError> for LedgerError {
+        fn from(e: ParquetError) -> Self {
+            LedgerError::Io(std::io::Error::other(e))
+        }
+    }
+
+    enum Column {
+        Int64(Vec<i64>),
+        Int32(Vec<i32>),
+        Bool(Vec<bool>),
+        Bytes(Vec<ByteArray>),
+    }
+
+    impl Column {
+        fn new(ty: ColumnType) -> Self {
+            match ty {
+                ColumnType::UInt64 => Column::Int64(Vec::new()),
+                ColumnType::UInt32 | ColumnType::Int32 => Column::Int32(Vec::new()),
+                ColumnType::Bool => Column::Bool(Vec::new()),
+                ColumnType::Utf8 => Column::Bytes(Vec::new()),
+            }
+        }
+
+        fn push(&mut self, value: Value<'_>) {
+            // Unsigned values are stored bit-for-bit; the schema marks them
+            // unsigned so readers decode them correctly.
+            match (self, value) {
+                (Column::Int64(c), Value::UInt(v)) => c.push(v as i64),
+                (Column::Int32(c), Value::UInt(v)) => c.push(v as u32 as i32),
+                (Column::Int32(c), Value::Int(v)) => c.push(v),
+                (Column::Bool(c), Value::Bool(v)) => c.push(v),
+                (Column::Bytes(c), Value::Str(v)) => c.push(ByteArray::from(v)),
+                _ => unreachable!("row values follow Row::COLUMNS"),
+            }
+        }
+    }
+
+    fn schema(columns: &[(&str, ColumnType)]) -> String {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|(name, ty)| {
+                let ty = match ty {
+                    ColumnType::UInt64 => "INT64 (INTEGER(64,false))",
+                    ColumnType::UInt32 => "INT32 (INTEGER(32,false))",
+                    ColumnType::Int32 => "INT32",
+                    ColumnType::Bool => "BOOLEAN",
+                    ColumnType::Utf8 => "BYTE_ARRAY (UTF8)",
+                };
+                format!("REQUIRED {} {};", ty, name)
+            })
+            .collect();
+        format!("message ledger_export {{ {} }}", fields.join(" "))
+    }
+
+    pub(super) fn write_parquet<R: Row, W: Write + Send>(
+        rows: impl Iterator<Item = Result<R, LedgerError>>,
+        writer: W,
+    ) -> Result<u64, LedgerError> {
+        let schema = Arc::new(parse_message_type(&schema(R::COLUMNS))?);
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut file = SerializedFileWriter::new(writer, schema, props)?;
+        let fresh =
+            || -> Vec<Column> { R::COLUMNS.iter().map(|(_, ty)| Column::new(*ty)).collect() };
+        let mut columns = fresh();
+        let mut buffered = 0;
+        let mut count = 0;
+        for row in rows {
+            for (column, value) in columns.iter_mut().zip(row?.values()) {
+                column.push(value);
+            }
+            buffered += 1;
+            count += 1;
+            if buffered == ROW_GROUP {
+                write_group(&mut file, std::mem::replace(&mut columns, fresh()))?;
+                buffered = 0;
+            }
+        }
+        if buffered > 0 {
+            write_group(&mut file, columns)?;
+        }
+        file.close()?;
+        Ok(count)
+    }
+
+    fn write_group<W: Write + Send>(
+        file: &mut SerializedFileWriter<W>,
+        columns: Vec<Column>,
+    ) -> Result<(), LedgerError> {
+        let mut group = file.next_row_group()?;
+        for column in columns {
+            let Some(mut writer) = group.next_column()? else {
+                break;
+            };
+            match (writer.untyped(), column) {
+                (ColumnWriter::Int64ColumnWriter(w), Column::Int64(v)) => {
+                    w.write_batch(&v, None, None)?;
+                }
+                (ColumnWriter::Int32ColumnWriter(w), Column::Int32(v)) => {
+                    w.write_batch(&v, None, None)?;
+                }
+                (ColumnWriter::BoolColumnWriter(w), Column::Bool(v)) => {
+                    w.write_batch(&v, None, None)?;
+                }
+                (ColumnWriter::ByteArrayColumnWriter(w), Column::Bytes(v)) => {
+                    w.write_batch(&v, None, None)?;
+                }
+                _ => unreachable!("column buffers follow the schema"),
+            }
+            writer.close()?;
+        }
+        group.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factors_and_events_export_as_csv_and_jsonl() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(7, &[(2, 4)]).unwrap();
+
+        let mut csv = Vec::new();
+        let rows = ledger
+            .export(ExportData::Factors, ExportFormat::Csv, &mut csv)
+            .unwrap();
+        assert_eq!(rows, 3);
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "entity_id,prime,exponent");
+        assert_eq!(lines[1], "1,2,2");
+
+        let mut jsonl = Vec::new();
+        let rows = ledger
+            .export(ExportData::Events, ExportFormat::Jsonl, &mut jsonl)
+            .unwrap();
+        assert_eq!(rows, 3);
+        let last: serde_json::Value =
+            serde_json::from_str(String::from_utf8(jsonl).unwrap().lines().last().unwrap())
+                .unwrap();
+        assert_eq!(last["entity_id"], 7);
+        assert_eq!(last["delta"], 4);
+
+        ledger
+            .set_metadata(7, &serde_json::json!({"name": "a,b"}))
+            .unwrap();
+        let mut csv = Vec::new();
+        ledger
+            .export(ExportData::Metadata, ExportFormat::Csv, &mut csv)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "entity_id,metadata\n7,\"{\"\"name\"\":\"\"a,b\"\"}\"\n"
+        );
+    }
+}
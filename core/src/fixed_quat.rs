@@ -0,0 +1,156 @@
+//! Fixed-point quaternion packing for 8-prime star
+//! Same layout as [`QpQuatOf`], but every component is an `i64` with `F`
+//! fractional bits and all arithmetic is integer with round-half-away
+//! rounding, so pack, rotate and unpack give identical bits on every
+//! architecture. Unit components and norms share the format; the norm of
+//! eight `i32` exponents needs 33 integer bits, so `F` is at most 30.
+
+use nalgebra::Quaternion;
+
+use crate::qp_encode::QpQuatOf;
+
+/// Components as (w, i, j, k), each scaled by `2^F`.
+pub type FixedQuaternion = [i64; 4];
+
+/// Paired unit quaternions and norms in fixed point with `F` fraction bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QpQuatFixed<const F: u32> {
+    pub psi1: FixedQuaternion,
+    pub psi2: FixedQuaternion,
+    pub psi1_norm: i64,
+    pub psi2_norm: i64,
+}
+
+/// Q16.16-style components: exponents round-trip while each chunk's norm
+/// stays below about 2¹⁵.
+pub type QpQuatQ16 = QpQuatFixed<16>;
+
+/// `n / d` rounded half away from zero; `d > 0`.
+fn div_round(n: i128, d: i128) -> i128 {
+    let half = d / 2;
+    if n >= 0 {
+        (n + half) / d
+    } else {
+        (n - half) / d
+    }
+}
+
+impl<const F: u32> QpQuatFixed<F> {
+    const FRAC_OK: () = assert!(F >= 1 && F <= 30, "F must be in 1..=30");
+    pub const ONE: i64 = 1 << F;
+
+    /// `q`, with `shift` fraction bits, scaled to unit length, and its norm;
+    /// both at `F` bits. The identity for zero.
+    fn unit_and_norm(q: [i128; 4], shift: u32) -> (FixedQuaternion, i64) {
+        let sq: u128 = q.iter().map(|&c| (c * c) as u128).sum();
+        let norm = (sq << (2 * F)).isqrt() >> shift;
+        if norm == 0 {
+            return ([Self::ONE, 0, 0, 0], 0);
+        }
+        let unit = q.map(|c| div_round(c << (2 * F), (norm as i128) << shift) as i64);
+        (unit, norm as i64)
+    }
+
+    pub fn pack(exponents: &[i32; 8]) -> Self {
+        let () = Self::FRAC_OK;
+        let chunk = |c: &[i32]| [0, 1, 2, 3].map(|n| i128::from(c[n]));
+        let (psi1, psi1_norm) = Self::unit_and_norm(chunk(&exponents[0..4]), 0);
+        let (psi2, psi2_norm) = Self::unit_and_norm(chunk(&exponents[4..8]), 0);
+        QpQuatFixed {
+            psi1,
+            psi2,
+            psi1_norm,
+            psi2_norm,
+        }
+    }
+
+    pub fn unpack(&self) -> [i32; 8] {
+        let scale = |psi: FixedQuaternion, norm: i64| {
+            psi.map(|c| {
+                let e = div_round(i128::from(c) * i128::from(norm), 1 << (2 * F));
+                e.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+            })
+        };
+        let [a, b, c, d] = scale(self.psi1, self.psi1_norm);
+        let [e, f, g, h] = scale(self.psi2, self.psi2_norm);
+        [a, b, c, d, e, f, g, h]
+    }
+
+    /// Hamilton product at `F` fraction bits.
+    pub fn mul(a: FixedQuaternion, b: FixedQuaternion) -> FixedQuaternion {
+        let [aw, ai, aj, ak] = a.map(i128::from);
+        let [bw, bi, bj, bk] = b.map(i128::from);
+        [
+            aw * bw - ai * bi - aj * bj - ak * bk,
+            aw * bi + ai * bw + aj * bk - ak * bj,
+            aw * bj - ai * bk + aj * bw + ak * bi,
+            aw * bk + ai * bj - aj * bi + ak * bw,
+        ]
+        .map(|c| div_round(c, 1 << F) as i64)
+    }
+
+    pub fn conjugate([w, i, j, k]: FixedQuaternion) -> FixedQuaternion {
+        [w, -i, -j, -k]
+    }
+
+    /// `q` rounded to `F` fraction bits.
+    pub fn quantize(q: Quaternion<f64>) -> FixedQuaternion {
+        [q.w, q.i, q.j, q.k].map(|c| (c * Self::ONE as f64).round() as i64)
+    }
+
+    /// Rotate both quaternions by `q` (normalized first) using conjugation,
+    /// as [`QpQuatOf::rotate`] does in floating point. Components of `q`
+    /// should stay within ±2³¹ so their squares sum without overflow.
+    pub fn rotate(&mut self, q: FixedQuaternion) {
+        let (rot, _) = Self::unit_and_norm(q.map(i128::from), F);
+        let conj = Self::conjugate(rot);
+        self.psi1 = Self::mul(Self::mul(rot, self.psi1), conj);
+        self.psi2 = Self::mul(Self::mul(rot, self.psi2), conj);
+    }
+
+    pub fn to_float(&self) -> QpQuatOf<f64> {
+        let scale = Self::ONE as f64;
+        let quat = |[w, i, j, k]: FixedQuaternion| {
+            Quaternion::new(w as f64, i as f64, j as f64, k as f64) / scale
+        };
+        QpQuatOf {
+            psi1: quat(self.psi1),
+            psi2: quat(self.psi2),
+            psi1_norm: self.psi1_norm as f64 / scale,
+            psi2_norm: self.psi2_norm as f64 / scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qp_encode::QpQuat64;
+
+    #[test]
+    fn fixed_point_tracks_float_and_rotates_exactly() {
+        let exponents = [2, 1, -3, 4, -1, 2, -5, 6];
+        let fixed = QpQuatQ16::pack(&exponents);
+        assert_eq!(fixed.unpack(), exponents);
+        assert_eq!(QpQuatQ16::pack(&[0; 8]).unpack(), [0; 8]);
+        let float = QpQuat64::pack(&exponents);
+        let approx = fixed.to_float();
+        assert!((approx.psi1 - float.psi1).norm() < 1e-4);
+        assert!((approx.psi2_norm - float.psi2_norm).abs() < 1e-4);
+
+        // Half a turn about i is exact: (w, i, j, k) → (w, i, −j, −k).
+        let mut turned = fixed;
+        turned.rotate([0, 5, 0, 0]);
+        let [w, i, j, k] = fixed.psi1;
+        assert_eq!(turned.psi1, [w, i, -j, -k]);
+        assert_eq!(turned.unpack(), [2, 1, 3, -4, -1, 2, 5, -6]);
+
+        let q = QpQuatQ16::quantize(Quaternion::new(1.0, 0.5, -0.25, 0.75));
+        let mut rotated = fixed;
+        rotated.rotate(q);
+        let mut reference = float;
+        reference.rotate(Quaternion::new(1.0, 0.5, -0.25, 0.75));
+        assert!((rotated.to_float().psi1 - reference.psi1).norm() < 1e-4);
+        assert_eq!(rotated.unpack(), reference.unpack());
+    }
+}
@@ -0,0 +1,186 @@
+//! Consistency check of the factors/postings projections against the log
+//! The latest snapshot plus the log after it is folded into an in-memory
+//! state, the state `rebuild_projections` would produce, and compared key
+//! by key with both column families.
+
+use std::collections::BTreeMap;
+
+use rocksdb::IteratorMode;
+use serde::Serialize;
+
+use crate::keys;
+use crate::{Ledger, LedgerError};
+
+/// One key whose stored exponent differs from what the log implies.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// `factors` or `postings`.
+    pub cf: &'static str,
+    pub entity: u64,
+    pub prime: u32,
+    /// `None` if the key should not exist.
+    pub expected: Option<i32>,
+    /// `None` if the key is missing.
+    pub found: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsckReport {
+    /// Log events folded on top of the snapshot.
+    pub events_replayed: u64,
+    /// Exponents the log implies.
+    pub keys_checked: u64,
+    pub divergences: Vec<Divergence>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl Ledger {
+    /// Replay snapshot plus log and report every factors/postings key that
+    /// disagrees with it. Writers are paused for the duration. Exponents
+    /// from [`Ledger::bulk_load`] show up as divergent until the next
+    /// [`Ledger::compact`]; after a failed check,
+    /// [`Ledger::rebuild_projections`] repairs the projections.
+    pub fn fsck(&self) -> Result<FsckReport, LedgerError> {
+        let _guard = self.write_lock.lock()?;
+        let snapshot = self.latest_snapshot()?.unwrap_or_default();
+        let pruned = self.pruned_prefix()?.events;
+        if pruned > snapshot.lsn {
+            return Err(LedgerError::Corruption(format!(
+                "log pruned through LSN {} but latest snapshot covers only {}",
+                pruned, snapshot.lsn
+            )));
+        }
+
+        let mut expected = snapshot
+            .exponents
+            .iter()
+            .map(|&(entity, prime, exp)| ((entity, prime), exp))
+            .collect::<BTreeMap<_, _>>();
+        let skip = usize::try_from(snapshot.lsn - pruned)?;
+        let mut events_replayed = 0;
+        for evt in self.log.iter()?.skip(skip) {
+            let evt = evt?;
+            if evt.tombstone {
+                expected.retain(|&(entity, _), _| entity != evt.entity_id);
+            } else {
+                // A missing exponent starts from the prime's node, as in `merge`.
+                let base = self.registry.prime_to_node(evt.prime).map_or(0, i32::from);
+                let delta = evt.msd_digits.to_int();
+                *expected.entry((evt.entity_id, evt.prime)).or_insert(base) += delta;
+            }
+            events_replayed += 1;
+        }
+
+        let mut divergences = Vec::new();
+        let factors = self
+            .db
+            .iterator_cf(self.cf("factors")?, IteratorMode::Start)
+            .map(|item| {
+                let (key, value) = item?;
+                let (entity, prime) = keys::decode_factor_key(&key)?;
+                Ok(((entity, prime), keys::decode_exponent(&value)?))
+            })
+            .collect::<Result<BTreeMap<_, _>, LedgerError>>()?;
+        diff("factors", &expected, &factors, &mut divergences);
+        let postings = self
+            .db
+            .iterator_cf(self.cf("postings")?, IteratorMode::Start)
+            .map(|item| {
+                let (key, value) = item?;
+                let (prime, entity) = keys::decode_posting_key(&key)?;
+                Ok(((entity, prime), keys::decode_exponent(&value)?))
+            })
+            .collect::<Result<BTreeMap<_, _>, LedgerError>>()?;
+        diff("postings", &expected, &postings, &mut divergences);
+
+        Ok(FsckReport {
+            events_replayed,
+            keys_checked: expected.len() as u64,
+            divergences,
+        })
+    }
+}
+
+fn diff(
+    cf: &'static str,
+    expected: &BTreeMap<(u64, u32), i32>,
+    found: &BTreeMap<(u64, u32), i32>,
+    out: &mut Vec<Divergence>,
+) {
+    let mut keys = expected.keys().chain(found.keys()).collect::<Vec<_>>();
+    keys.sort_unstable();
+    keys.dedup();
+    for &(entity, prime) in keys {
+        let (want, got) = (
+            expected.get(&(entity, prime)).copied(),
+            found.get(&(entity, prime)).copied(),
+        );
+        if want != got {
+            out.push(Divergence {
+                cf,
+                entity,
+                prime,
+                expected: want,
+                found: got,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fsck_reports_keys_that_diverge_from_the_log() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+        ledger.compact().unwrap();
+        ledger.anchor_batch(3, &[(3, 2)]).unwrap();
+        ledger.delete_entity(1).unwrap();
+        let report = ledger.fsck().unwrap();
+        assert!(report.is_clean());
+        assert_eq!((report.events_replayed, report.keys_checked), (2, 2));
+
+        let factors = ledger.cf("factors").unwrap();
+        ledger
+            .db
+            .put_cf(factors, keys::factor_key(2, 2), keys::encode_exponent(7))
+            .unwrap();
+        let postings = ledger.cf("postings").unwrap();
+        ledger
+            .db
+            .delete_cf(postings, keys::posting_key(3, 3))
+            .unwrap();
+        let report = ledger.fsck().unwrap();
+        assert_eq!(
+            report.divergences,
+            vec![
+                Divergence {
+                    cf: "factors",
+                    entity: 2,
+                    prime: 2,
+                    expected: Some(4),
+                    found: Some(7),
+                },
+                Divergence {
+                    cf: "postings",
+                    entity: 3,
+                    prime: 3,
+                    expected: Some(2),
+                    found: None,
+                },
+            ]
+        );
+
+        ledger.rebuild_projections().unwrap();
+        assert!(ledger.fsck().unwrap().is_clean());
+    }
+}
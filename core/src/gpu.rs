@@ -0,0 +1,216 @@
+//! Compute-shader rotation of packed quaternion state (`gpu` feature)
+//! One dispatch conjugates every Ψ₁ and Ψ₂ in a slice by a shared
+//! rotation, as [`crate::QpQuatOf::rotate`] does one pair at a time. Results agree
+//! with the CPU to within float rounding, not bit for bit.
+
+use std::fmt;
+
+use nalgebra::Quaternion;
+use wgpu::util::DeviceExt;
+
+use crate::qp_encode::{unit_and_norm, QpQuat};
+
+const WORKGROUP: u32 = 64;
+
+const SHADER: &str = r#"
+@group(0) @binding(0) var<uniform> rot: vec4<f32>;
+@group(0) @binding(1) var<storage, read_write> quats: array<vec4<f32>>;
+
+// Hamilton product of (w, i, j, k) quaternions.
+fn mul(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(
+        a.x * b.x - a.y * b.y - a.z * b.z - a.w * b.w,
+        a.x * b.y + a.y * b.x + a.z * b.w - a.w * b.z,
+        a.x * b.z - a.y * b.w + a.z * b.x + a.w * b.y,
+        a.x * b.w + a.y * b.z - a.z * b.y + a.w * b.x,
+    );
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= arrayLength(&quats)) {
+        return;
+    }
+    let conj = vec4<f32>(rot.x, -rot.yzw);
+    quats[id.x] = mul(mul(rot, quats[id.x]), conj);
+}
+"#;
+
+/// No usable adapter, or the device failed a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuError(pub String);
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gpu: {}", self.0)
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+/// A device with the rotation pipeline compiled; build once and reuse.
+pub struct GpuRotator {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    /// Pairs per dispatch, within the device's buffer and workgroup limits.
+    chunk: usize,
+}
+
+impl GpuRotator {
+    /// The default high-performance adapter.
+    pub fn new() -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or_else(|| GpuError("no adapter available".into()))?;
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("qpquat-rotate"),
+                required_limits: limits.clone(),
+                ..Default::default()
+            },
+            None,
+        ))
+        .map_err(|e| GpuError(e.to_string()))?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("qpquat-rotate"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("qpquat-rotate"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        // A pair is two vec4<f32>, 32 bytes, and two invocations.
+        let by_size = (limits.max_storage_buffer_binding_size as usize)
+            .min(usize::try_from(limits.max_buffer_size).unwrap_or(usize::MAX))
+            / 32;
+        let by_dispatch =
+            limits.max_compute_workgroups_per_dimension as usize * WORKGROUP as usize / 2;
+        Ok(GpuRotator {
+            device,
+            queue,
+            pipeline,
+            chunk: by_size.min(by_dispatch).max(1),
+        })
+    }
+
+    /// [`crate::QpQuatOf::rotate`] every pair in `quats` by `q`.
+    pub fn rotate(&self, quats: &mut [QpQuat], q: Quaternion<f32>) -> Result<(), GpuError> {
+        let (rot, _) = unit_and_norm(q);
+        let uniform = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("rotation"),
+                contents: bytemuck::cast_slice(&[rot.w, rot.i, rot.j, rot.k]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        for chunk in quats.chunks_mut(self.chunk) {
+            self.rotate_chunk(&uniform, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn rotate_chunk(&self, uniform: &wgpu::Buffer, chunk: &mut [QpQuat]) -> Result<(), GpuError> {
+        let mut data = Vec::with_capacity(chunk.len() * 8);
+        for qp in chunk.iter() {
+            for q in [&qp.psi1, &qp.psi2] {
+                data.extend_from_slice(&[q.w, q.i, q.j, q.k]);
+            }
+        }
+        let bytes: &[u8] = bytemuck::cast_slice(&data);
+        let storage = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("quaternions"),
+                contents: bytes,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: bytes.len() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: storage.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let invocations = (chunk.len() * 2) as u32;
+            pass.dispatch_workgroups(invocations.div_ceil(WORKGROUP), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&storage, 0, &readback, 0, bytes.len() as u64);
+        self.queue.submit([encoder.finish()]);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            let _ = tx.send(res);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|e| GpuError(e.to_string()))?
+            .map_err(|e| GpuError(e.to_string()))?;
+        {
+            let mapped = slice.get_mapped_range();
+            let out: &[[f32; 4]] = bytemuck::cast_slice(&mapped);
+            for (qp, pair) in chunk.iter_mut().zip(out.chunks_exact(2)) {
+                let quat = |[w, i, j, k]: [f32; 4]| Quaternion::new(w, i, j, k);
+                qp.psi1 = quat(pair[0]);
+                qp.psi2 = quat(pair[1]);
+            }
+        }
+        readback.unmap();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_rotation_matches_cpu() {
+        let rotator = match GpuRotator::new() {
+            Ok(rotator) => rotator,
+            // Nothing to check on hosts without an adapter.
+            Err(_) => return,
+        };
+        let states: Vec<[i32; 8]> = (0..1000)
+            .map(|n: i32| std::array::from_fn(|c| (n * 7 - 3 * c as i32) % 41))
+            .collect();
+        let mut gpu = QpQuat::pack_batch(&states);
+        let rot = Quaternion::new(1.0, 0.5, -0.25, 0.75);
+        rotator.rotate(&mut gpu, rot).unwrap();
+        for (state, qp) in states.iter().zip(&gpu) {
+            let mut cpu = QpQuat::pack(state);
+            cpu.rotate(rot);
+            assert!((cpu.psi1 - qp.psi1).norm() < 1e-5);
+            assert!((cpu.psi2 - qp.psi2).norm() < 1e-5);
+            assert_eq!(cpu.psi1_norm, qp.psi1_norm);
+        }
+    }
+}
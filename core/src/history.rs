@@ -0,0 +1,215 @@
+//! Time-travel queries over an index of every exponent change
+//! Each committed event records its change in the `history` column family
+//! under (entity, prime, LSN), so an as-of lookup is a single prefix scan.
+
+use std::collections::HashMap;
+
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+
+use crate::keys;
+use crate::migrate::CHUNK;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const HISTORY_CF: &str = "history";
+
+/// Change kinds, stored after the timestamp in each history value.
+const DELTA: u8 = 0;
+const SET: u8 = 1;
+const GONE: u8 = 2;
+
+/// Point in the ledger's past to evaluate a query at; both are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    Lsn(u64),
+    /// Milliseconds since the epoch, compared with event timestamps.
+    Timestamp(u64),
+}
+
+fn encode_change(ts: u64, kind: u8, exp: i32) -> Vec<u8> {
+    let mut out = ts.to_be_bytes().to_vec();
+    out.push(kind);
+    if kind != GONE {
+        out.extend(keys::encode_exponent(exp));
+    }
+    out
+}
+
+fn decode_change(raw: &[u8]) -> Result<(u64, u8, i32), LedgerError> {
+    let corrupt = || LedgerError::Corruption("malformed history value".to_string());
+    if raw.len() < 9 {
+        return Err(corrupt());
+    }
+    let ts = u64::from_be_bytes(raw[..8].try_into().map_err(|_| corrupt())?);
+    match raw[8] {
+        GONE => Ok((ts, GONE, 0)),
+        kind @ (DELTA | SET) => Ok((ts, kind, keys::decode_exponent(&raw[9..])?)),
+        _ => Err(corrupt()),
+    }
+}
+
+impl Ledger {
+    /// Exponent of `prime` on `entity` as it stood at `as_of`; `None` if it
+    /// had no value yet. Fails with `EntityGone` if the entity had already
+    /// been deleted. Values written by `bulk_load` are not part of the
+    /// history.
+    pub fn get_exponent_at(
+        &self,
+        entity: u64,
+        prime: u32,
+        as_of: AsOf,
+    ) -> Result<Option<i32>, LedgerError> {
+        let node = self
+            .registry
+            .prime_to_node(prime)
+            .ok_or(LedgerError::UnknownPrime(prime))?;
+        let cf = self.cf(HISTORY_CF)?;
+        let prefix = keys::factor_key(entity, prime);
+        let mut exp = None;
+        let mut gone = false;
+        let iter = self
+            .db
+            .iterator_cf(cf, IteratorMode::From(&prefix, Direction::Forward));
+        for item in iter {
+            let (key, value) = item?;
+            let Some(lsn) = key.strip_prefix(&prefix[..]) else {
+                break;
+            };
+            let lsn = lsn
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| LedgerError::Corruption("malformed history key".to_string()))?;
+            let (ts, kind, v) = decode_change(&value)?;
+            let after = match as_of {
+                AsOf::Lsn(at) => lsn > at,
+                AsOf::Timestamp(at) => ts > at,
+            };
+            if after {
+                break;
+            }
+            match kind {
+                DELTA => exp = Some(exp.unwrap_or(node as i32) + v),
+                SET => exp = Some(v),
+                _ => gone = true,
+            }
+        }
+        if gone {
+            return Err(LedgerError::EntityGone(entity));
+        }
+        Ok(exp)
+    }
+
+    /// Index the changes made by `events`. Tombstones mark every prime the
+    /// entity holds, so must be staged before the deletion is applied.
+    pub(crate) fn stage_history(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(HISTORY_CF)?;
+        for evt in events {
+            if evt.tombstone {
+                for entry in self.iter_entity_raw(evt.entity_id)? {
+                    let key = keys::history_key(evt.entity_id, entry?.1, evt.lsn);
+                    batch.put_cf(cf, key, encode_change(evt.timestamp, GONE, 0));
+                }
+                continue;
+            }
+            let delta = evt.msd_digits.to_int();
+            let key = keys::history_key(evt.entity_id, evt.prime, evt.lsn);
+            batch.put_cf(cf, key, encode_change(evt.timestamp, DELTA, delta));
+        }
+        Ok(())
+    }
+
+    /// Build the index from the latest snapshot and the retained log; used
+    /// when upgrading ledgers that predate it. History folded into a
+    /// snapshot is recorded as one absolute value at the snapshot's LSN.
+    pub(crate) fn backfill_history(&self) -> Result<(), LedgerError> {
+        let cf = self.cf(HISTORY_CF)?;
+        let snapshot = self.latest_snapshot()?.unwrap_or_default();
+        let mut primes: HashMap<u64, Vec<u32>> = HashMap::new();
+        let mut batch = WriteBatch::default();
+        if let Some(at) = snapshot.lsn.checked_sub(1) {
+            for &(entity, prime, exp) in &snapshot.exponents {
+                let key = keys::history_key(entity, prime, at);
+                batch.put_cf(cf, key, encode_change(snapshot.created_at, SET, exp));
+                primes.entry(entity).or_default().push(prime);
+            }
+        }
+
+        let first = self.pruned_prefix()?.events;
+        for (lsn, evt) in (first..).zip(self.log.iter()?) {
+            let evt = evt?;
+            if lsn >= snapshot.lsn {
+                if evt.tombstone {
+                    for prime in primes.remove(&evt.entity_id).unwrap_or_default() {
+                        let key = keys::history_key(evt.entity_id, prime, lsn);
+                        batch.put_cf(cf, key, encode_change(evt.timestamp, GONE, 0));
+                    }
+                } else {
+                    let delta = evt.msd_digits.to_int();
+                    let key = keys::history_key(evt.entity_id, evt.prime, lsn);
+                    batch.put_cf(cf, key, encode_change(evt.timestamp, DELTA, delta));
+                    primes.entry(evt.entity_id).or_default().push(evt.prime);
+                }
+            }
+            if batch.len() >= CHUNK {
+                self.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LedgerOptions, ManualClock};
+    use std::sync::Arc;
+
+    #[test]
+    fn exponents_can_be_read_as_of_an_lsn_or_timestamp() {
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new(1_000));
+        let ledger =
+            Ledger::with_options(tmp.path(), &LedgerOptions::new().clock(clock.clone())).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        clock.set(2_000);
+        ledger.anchor_batch(1, &[(2, 4)]).unwrap();
+        ledger.compact().unwrap();
+        clock.set(3_000);
+        ledger.anchor_batch(1, &[(2, 6)]).unwrap();
+
+        assert_eq!(ledger.get_exponent_at(1, 2, AsOf::Lsn(0)).unwrap(), Some(2));
+        assert_eq!(ledger.get_exponent_at(1, 2, AsOf::Lsn(1)).unwrap(), Some(4));
+        assert_eq!(
+            ledger.get_exponent_at(1, 2, AsOf::Timestamp(999)).unwrap(),
+            None
+        );
+        assert_eq!(
+            ledger
+                .get_exponent_at(1, 2, AsOf::Timestamp(2_500))
+                .unwrap(),
+            Some(4)
+        );
+        assert_eq!(
+            ledger
+                .get_exponent_at(1, 2, AsOf::Timestamp(3_000))
+                .unwrap(),
+            Some(6)
+        );
+
+        clock.set(4_000);
+        ledger.delete_entity(1).unwrap();
+        assert_eq!(
+            ledger
+                .get_exponent_at(1, 2, AsOf::Timestamp(3_500))
+                .unwrap(),
+            Some(6)
+        );
+        assert!(matches!(
+            ledger.get_exponent_at(1, 2, AsOf::Lsn(3)),
+            Err(LedgerError::EntityGone(1))
+        ));
+    }
+}
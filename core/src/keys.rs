@@ -0,0 +1,137 @@
+//! Key/value layout of the factors and postings column families
+//! factors:  entity (u64 BE) ‖ prime (u32 BE) → exponent
+//! postings: prime (u32 BE) ‖ entity (u64 BE) → exponent
+//! history:  entity (u64 BE) ‖ prime (u32 BE) ‖ lsn (u64 BE) → change
+//! Big-endian fixed-width keys sort numerically, so prefix seeks work.
+//! Values are a version byte followed by the zigzag varint exponent.
+
+use crate::LedgerError;
+
+pub const KEY_LEN: usize = 12;
+
+pub fn factor_key(entity: u64, prime: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    key[..8].copy_from_slice(&entity.to_be_bytes());
+    key[8..].copy_from_slice(&prime.to_be_bytes());
+    key
+}
+
+pub fn posting_key(prime: u32, entity: u64) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    key[..4].copy_from_slice(&prime.to_be_bytes());
+    key[4..].copy_from_slice(&entity.to_be_bytes());
+    key
+}
+
+pub const HISTORY_KEY_LEN: usize = KEY_LEN + 8;
+
+pub fn history_key(entity: u64, prime: u32, lsn: u64) -> [u8; HISTORY_KEY_LEN] {
+    let mut key = [0u8; HISTORY_KEY_LEN];
+    key[..KEY_LEN].copy_from_slice(&factor_key(entity, prime));
+    key[KEY_LEN..].copy_from_slice(&lsn.to_be_bytes());
+    key
+}
+
+/// Prefix shared by every factors key of `entity`.
+pub fn entity_prefix(entity: u64) -> [u8; 8] {
+    entity.to_be_bytes()
+}
+
+pub fn decode_factor_key(key: &[u8]) -> Result<(u64, u32), LedgerError> {
+    let key: &[u8; KEY_LEN] = key.try_into().map_err(|_| {
+        LedgerError::Corruption(format!("malformed factors key ({} bytes)", key.len()))
+    })?;
+    let (entity, prime) = key.split_at(8);
+    Ok((
+        u64::from_be_bytes(entity.try_into().unwrap()),
+        u32::from_be_bytes(prime.try_into().unwrap()),
+    ))
+}
+
+pub fn decode_posting_key(key: &[u8]) -> Result<(u32, u64), LedgerError> {
+    let key: &[u8; KEY_LEN] = key.try_into().map_err(|_| {
+        LedgerError::Corruption(format!("malformed postings key ({} bytes)", key.len()))
+    })?;
+    let (prime, entity) = key.split_at(4);
+    Ok((
+        u32::from_be_bytes(prime.try_into().unwrap()),
+        u64::from_be_bytes(entity.try_into().unwrap()),
+    ))
+}
+
+/// Parse a pre-binary `"<a>:<b>"` key; used only by the format migration.
+pub fn decode_legacy_key(key: &[u8]) -> Option<(u64, u64)> {
+    let text = std::str::from_utf8(key).ok()?;
+    let (a, b) = text.split_once(':')?;
+    Some((a.parse().ok()?, b.parse().ok()?))
+}
+
+pub const VALUE_VERSION: u8 = 1;
+
+pub fn encode_exponent(exp: i32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(6);
+    out.push(VALUE_VERSION);
+    let mut z = ((exp << 1) ^ (exp >> 31)) as u32;
+    while z >= 0x80 {
+        out.push((z as u8) | 0x80);
+        z >>= 7;
+    }
+    out.push(z as u8);
+    out
+}
+
+pub fn decode_exponent(raw: &[u8]) -> Result<i32, LedgerError> {
+    match raw.split_first() {
+        Some((&VALUE_VERSION, varint)) => {
+            let mut z: u32 = 0;
+            for (i, &b) in varint.iter().enumerate() {
+                if i >= 5 {
+                    break;
+                }
+                z |= ((b & 0x7f) as u32) << (7 * i);
+                if b & 0x80 == 0 {
+                    return Ok(((z >> 1) as i32) ^ -((z & 1) as i32));
+                }
+            }
+            Err(LedgerError::Corruption(
+                "truncated exponent varint".to_string(),
+            ))
+        }
+        Some((v, _)) => Err(LedgerError::Corruption(format!(
+            "unknown exponent encoding version {}",
+            v
+        ))),
+        None => Err(LedgerError::Corruption("empty exponent value".to_string())),
+    }
+}
+
+/// Parse a pre-varint decimal value; used only by the format migration.
+pub fn decode_legacy_exponent(raw: &[u8]) -> Option<i32> {
+    std::str::from_utf8(raw).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_keys_sort_numerically() {
+        assert!(factor_key(2, 19) < factor_key(10, 2));
+        assert!(factor_key(10, 3) < factor_key(10, 17));
+        assert_eq!(
+            decode_factor_key(&factor_key(u64::MAX, 19)).unwrap(),
+            (u64::MAX, 19)
+        );
+        assert!(factor_key(12, 2).starts_with(&entity_prefix(12)));
+        assert!(!factor_key(12, 2).starts_with(&entity_prefix(1)));
+    }
+
+    #[test]
+    fn exponent_varint_round_trips() {
+        for exp in [0, 1, -1, 63, -64, 64, 300, -300, i32::MAX, i32::MIN] {
+            assert_eq!(decode_exponent(&encode_exponent(exp)).unwrap(), exp);
+        }
+        assert_eq!(encode_exponent(-1).len(), 2);
+        assert!(decode_exponent(b"42").is_err());
+    }
+}
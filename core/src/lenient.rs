@@ -0,0 +1,103 @@
+//! Lenient batches: rejected commands are reported per command instead of
+//! failing the whole batch, so bulk loaders can quarantine just the rejects.
+
+use std::fmt;
+
+use crate::{Ledger, LedgerError, LedgerEvent, StagedBatch};
+
+/// A command that `anchor_batch_lenient` rejected; nothing was written for it.
+#[derive(Debug)]
+pub struct CommandError {
+    /// Position of the command in the submitted batch.
+    pub index: usize,
+    pub prime: u32,
+    pub target_node: u8,
+    pub error: LedgerError,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "command {} ({} → node {}): {}",
+            self.index, self.prime, self.target_node, self.error
+        )
+    }
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl Ledger {
+    /// Like [`Ledger::anchor_batch`], but a command that fails validation is
+    /// returned as an `Err` in its place while the rest of the batch commits.
+    /// No-op commands produce no entry, as in `anchor_batch`; use
+    /// [`CommandError::index`] to map rejects back to the input.
+    ///
+    /// The outer error covers failures of the batch as a whole (read-only
+    /// ledger, deleted entity, storage errors); then nothing is written.
+    pub fn anchor_batch_lenient(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<Vec<Result<LedgerEvent, CommandError>>, LedgerError> {
+        let _span = span!("anchor_batch_lenient", entity, commands = commands.len());
+        self.ensure_writable()?;
+        let mut writer = self.write_lock.lock()?;
+        self.ensure_live(entity)?;
+        let mut staged = StagedBatch::new(self.clock.now_millis(), commands.len());
+        self.prefetch(&mut staged, entity, commands)?;
+        // `None` marks a staged event, in order; no-ops are left out.
+        let mut outcomes = Vec::with_capacity(commands.len());
+        for (index, &(prime, target_node)) in commands.iter().enumerate() {
+            match self.stage_command(&mut staged, &writer, entity, prime, target_node) {
+                Ok(true) => outcomes.push(None),
+                Ok(false) => {}
+                Err(error @ (LedgerError::Io(_) | LedgerError::Rocks(_))) => return Err(error),
+                Err(error) => outcomes.push(Some(CommandError {
+                    index,
+                    prime,
+                    target_node,
+                    error,
+                })),
+            }
+        }
+        let mut events = self.commit_staged(&mut writer, staged)?.0.into_iter();
+        Ok(outcomes
+            .into_iter()
+            .map(|outcome| match outcome {
+                Some(reject) => Err(reject),
+                None => Ok(events
+                    .next()
+                    .expect("one staged event per accepted command")),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_are_reported_and_the_rest_commits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        let results = ledger
+            .anchor_batch_lenient(1, &[(2, 2), (4, 1), (5, 2), (2, 4)])
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().prime, 2);
+        let reject = results[1].as_ref().unwrap_err();
+        assert_eq!(reject.index, 1);
+        assert!(matches!(reject.error, LedgerError::UnknownPrime(4)));
+        assert_eq!(results[2].as_ref().unwrap().prime, 2);
+
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(4));
+        assert_eq!(ledger.entity_version(1).unwrap(), 2);
+        assert_eq!(ledger.verify_chain().unwrap(), 2);
+    }
+}
@@ -1,22 +1,148 @@
 #![allow(non_local_definitions)]
 
+#[macro_use]
+mod trace;
+
+mod archive;
+#[cfg(feature = "async")]
+mod async_ledger;
+mod backup;
+mod bounds;
+mod bulk_load;
 mod centroid;
+mod centroid_state;
+mod chain;
+mod clock;
+mod compaction;
+mod crdt;
+mod denials;
+mod dry_run;
+mod dual_quat;
+mod durability;
+mod encryption;
+mod energy;
+mod error;
+mod event_log;
+mod exponent_cache;
+mod export;
+mod fixed_quat;
+mod fsck;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod history;
+mod keys;
+mod lenient;
+mod lock;
+mod lsn;
+mod merge;
+mod merkle;
+mod metadata;
+mod metrics;
+mod migrate;
 mod msd;
+mod octonion;
+mod options;
+mod outbox;
+mod pipeline;
 mod python;
 mod qp_encode;
+mod quat_state;
+mod readonly;
 mod registry;
+mod replication;
+mod retention;
+mod rotation_log;
+mod scan;
+mod schema;
+mod sharded;
+mod signing;
+mod snapshot;
+mod stats;
+mod stream;
+mod subscription;
+mod summary;
+mod tombstone;
+mod transfer;
+mod ufd;
+mod versioning;
 
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+#[cfg(feature = "cloud")]
+pub use archive::CloudStore;
+use archive::SharedStore;
+pub use archive::{Archive, ArchiveManifest, ArchivedSegment, DirStore, ObjectStore};
+#[cfg(feature = "async")]
+pub use async_ledger::AsyncLedger;
+pub use backup::{BackupManifest, SegmentEntry};
+use bounds::BoundsConfig;
+pub use bounds::{BoundsPolicy, ExponentBounds};
 use centroid::CentroidDigit;
-use chrono::Utc;
+pub use centroid::{Centroid, CentroidCause, CentroidSeed, CentroidTransition};
+pub use centroid_state::{CentroidAudit, CentroidMismatch};
+use clock::SharedClock;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use compaction::StateSnapshot;
+pub use crdt::{CrdtCounter, CrdtState, WriterId};
+pub use denials::Denial;
+pub use dual_quat::{DualQuat, DualQuat64, DualQuatOf, DualQuatParts};
+pub use durability::SyncPolicy;
+use durability::SyncState;
+pub use encryption::{EnvKey, FileKey, KeyProvider};
+pub use energy::{
+    calibrate, Calibration, ConstantModel, CycleCounter, CycleSource, EnergyMeter, Rapl, WallClock,
+};
+pub use error::LedgerError;
+use event_log::EventLog;
+use exponent_cache::ExponentCache;
+pub use exponent_cache::DEFAULT_EXPONENT_CACHE_SIZE;
+pub use export::{ExportData, ExportFormat};
+pub use fixed_quat::{FixedQuaternion, QpQuatFixed, QpQuatQ16};
 use flow_rule::Node;
-use msd::Msd;
+pub use fsck::{Divergence, FsckReport};
+#[cfg(feature = "gpu")]
+pub use gpu::{GpuError, GpuRotator};
+pub use history::AsOf;
+pub use lenient::CommandError;
+use lock::LockFile;
+pub use merkle::{verify_proof, InclusionProof};
+use metrics::Metrics;
+pub use metrics::{MetricsSnapshot, RocksDbStats};
+pub use msd::{Digit, Msd, MsdBuilder, MsdError, SignedDigit, SignedDigitBuilder};
+pub use octonion::{Octonion, Octonion64, OctonionOf};
+pub use options::{CfCompression, Compression, LedgerOptions};
+#[cfg(feature = "kafka")]
+pub use outbox::KafkaPublisher;
+#[cfg(feature = "nats")]
+pub use outbox::NatsPublisher;
+use outbox::Outbox;
+pub use outbox::{decode_event, encode_event, EventPublisher};
+pub use pipeline::{PendingBatch, PipelinedWriter, DEFAULT_PIPELINE_DEPTH};
 use pyo3::prelude::*;
-use rocksdb::{ColumnFamilyDescriptor, Options, WriteBatch};
+pub use qp_encode::{
+    bench, AnyQpQuat, BenchReport, OpCost, PackError, QpDelta, QpDelta64, QpDeltaOf, QpQuat,
+    QpQuat64, QpQuatOf, QpQuatParts, QuatScalar, QPQUAT_FORMAT_VERSION,
+};
+pub use readonly::AccessMode;
+pub use registry::{Registry, RegistryError, TierNode, Tiers, S0_PRIMES};
+pub use replication::{DirSource, Follower, ReplicationStatus, SegmentSource};
+pub use retention::{PrunedPrefix, RetentionPolicy};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, MergeOperands, WriteBatch};
+pub use rotation_log::RotationLog;
+pub use scan::FactorEntry;
+pub use schema::EVENT_SCHEMA_VERSION;
 use serde::{Deserialize, Serialize};
+pub use sharded::ShardedLedger;
+pub use signing::verify_event_signature;
+pub use snapshot::LedgerSnapshot;
+pub use stats::LedgerStats;
+pub use stream::{CommandFormat, StreamProgress, DEFAULT_STREAM_BATCH};
+use subscription::Subscribers;
+pub use summary::BatchSummary;
+pub use ufd::{Factor, Factorization, GaussianPrime, NodeMap};
 
 fn node_from_u8(n: u8) -> Option<Node> {
     match n {
@@ -33,73 +159,613 @@ fn node_from_u8(n: u8) -> Option<Node> {
 }
 
 #[pyclass]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct LedgerEvent {
     #[pyo3(get)]
     pub entity_id: u64,
     #[pyo3(get)]
     pub prime: u32,
     #[pyo3(get)]
-    pub msd_digits: Vec<i8>,
+    pub msd_digits: Msd,
     #[pyo3(get)]
     pub via_c: bool,
     #[pyo3(get)]
     pub centroid_digit: CentroidDigit,
     #[pyo3(get)]
     pub timestamp: u64,
+    /// Hash of the preceding event (hex); see `chain`.
+    #[pyo3(get)]
+    #[serde(default)]
+    pub prev_hash: String,
+    #[pyo3(get)]
+    #[serde(default)]
+    pub event_hash: String,
+    /// Ed25519 signature over `event_hash` (hex); empty when unsigned.
+    #[pyo3(get)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+    /// Marks the entity as deleted; see `tombstone`.
+    #[pyo3(get)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tombstone: bool,
+    /// Global log sequence number; see `lsn`.
+    #[pyo3(get)]
+    #[serde(default, skip_serializing_if = "lsn::is_zero")]
+    pub lsn: u64,
+}
+
+/// What an event did, so consumers can pick out centroid crossings or
+/// deletions without re-deriving them from the flow rules.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// An exponent change that stayed off the centroid.
+    Anchor,
+    /// An exponent change routed via C, which flipped the entity's
+    /// centroid digit.
+    CentroidFlip,
+    /// The entity was deleted; it carries no exponent change.
+    Tombstone,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Anchor => "anchor",
+            EventKind::CentroidFlip => "centroid_flip",
+            EventKind::Tombstone => "tombstone",
+        }
+    }
+}
+
+impl LedgerEvent {
+    pub fn kind(&self) -> EventKind {
+        if self.tombstone {
+            EventKind::Tombstone
+        } else if self.via_c {
+            EventKind::CentroidFlip
+        } else {
+            EventKind::Anchor
+        }
+    }
+}
+
+/// State owned by whoever holds the write lock.
+struct WriterState {
+    chain_head: String,
+    next_lsn: u64,
 }
 
 #[pyclass]
 pub struct Ledger {
-    db: rocksdb::DB,
-    log_path: PathBuf,
+    db: Arc<rocksdb::DB>,
+    log: EventLog,
+    signer: Option<ed25519_dalek::SigningKey>,
+    subscribers: Subscribers,
+    /// Serialises batches so log order matches commit order.
+    write_lock: Mutex<WriterState>,
+    mode: AccessMode,
+    retention: RetentionPolicy,
+    metrics: Arc<Metrics>,
+    exponent_cache: ExponentCache,
+    clock: SharedClock,
+    sync: SyncState,
+    /// Set in multi-writer deployments; see `crdt`.
+    writer_id: Option<WriterId>,
+    /// Keep flow-rule rejections; see `denials`.
+    record_denials: bool,
+    bounds: BoundsConfig,
+    /// Prime alphabet; see `registry`.
+    registry: Registry,
+    centroid_seed: CentroidSeed,
+    /// Where sealed segments are uploaded; see `archive`.
+    archive: Option<SharedStore>,
+    /// Publisher thread, when an event bus is configured; see `outbox`.
+    outbox: Option<Outbox>,
+    /// Declared last so the DB is closed before the lock is released.
+    _lock: Option<LockFile>,
+}
+
+/// Events and writes of an `anchor_batch` call, built up command by command.
+struct StagedBatch {
+    ts: u64,
+    /// Centroid digit each entity's next event starts from.
+    centroids: HashMap<u64, CentroidDigit>,
+    events: Vec<LedgerEvent>,
+    batch: WriteBatch,
+    /// Exponents already touched by this batch; later commands must see them.
+    pending: HashMap<(u64, u32), i32>,
+    /// Stored exponents read up front by [`Ledger::prefetch`].
+    prefetched: HashMap<(u64, u32), Option<i32>>,
+    /// Whether rejected commands are recorded as denials; off for dry runs.
+    audit: bool,
+    /// Tag recorded with any denial.
+    caller: Option<String>,
+}
+
+impl StagedBatch {
+    fn new(ts: u64, capacity: usize) -> Self {
+        StagedBatch {
+            ts,
+            centroids: HashMap::new(),
+            events: Vec::with_capacity(capacity),
+            batch: WriteBatch::default(),
+            pending: HashMap::new(),
+            prefetched: HashMap::new(),
+            audit: true,
+            caller: None,
+        }
+    }
 }
 
 #[pymethods]
 impl Ledger {
     #[new]
-    fn py_new(path: String) -> PyResult<Self> {
-        Ledger::new(path).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e))
+    #[pyo3(signature = (
+        path,
+        block_cache_mb=None,
+        compression=None,
+        bloom_bits=None,
+        exponent_cache_size=None,
+        key_file=None,
+        cf_compression=None,
+        sync=None,
+        writer_id=None,
+        record_denials=false,
+        exponent_bounds=None,
+        primes=None,
+        centroid_seed=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        path: String,
+        block_cache_mb: Option<usize>,
+        compression: Option<&str>,
+        bloom_bits: Option<u32>,
+        exponent_cache_size: Option<usize>,
+        key_file: Option<String>,
+        cf_compression: Option<HashMap<String, String>>,
+        sync: Option<&str>,
+        writer_id: Option<WriterId>,
+        record_denials: bool,
+        exponent_bounds: Option<(i32, i32, &str)>,
+        primes: Option<Vec<u32>>,
+        centroid_seed: Option<&str>,
+    ) -> PyResult<Self> {
+        let mut options = LedgerOptions::new();
+        if let Some(mb) = block_cache_mb {
+            options = options.block_cache_mb(mb);
+        }
+        if let Some(name) = compression {
+            let c = name
+                .parse()
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+            options = options.compression(c);
+        }
+        if let Some(bits) = bloom_bits {
+            options = options.bloom_bits(bits);
+        }
+        if let Some(size) = exponent_cache_size {
+            options = options.exponent_cache_size(size);
+        }
+        for (cf, name) in cf_compression.unwrap_or_default() {
+            let c: Compression = name
+                .parse()
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+            options = options.cf_compression(&cf, c);
+        }
+        if let Some(key_file) = key_file {
+            options = options.encryption(Arc::new(FileKey::new(key_file)));
+        }
+        if let Some(name) = sync {
+            let policy = name
+                .parse()
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+            options = options.sync(policy);
+        }
+        if let Some(id) = writer_id {
+            options = options.writer_id(id);
+        }
+        options = options.record_denials(record_denials);
+        if let Some((floor, ceiling, policy)) = exponent_bounds {
+            let policy = policy
+                .parse()
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+            options = options.exponent_bounds(ExponentBounds::new(floor, ceiling, policy));
+        }
+        if let Some(primes) = primes {
+            options = options.registry(Registry::new(&primes).map_err(LedgerError::from)?);
+        }
+        if let Some(name) = centroid_seed {
+            let seed = name
+                .parse()
+                .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+            options = options.centroid_seed(seed);
+        }
+        Ledger::with_options(path, &options).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "anchor_batch", signature = (entity, commands, expected_version=None, caller=None))]
+    fn anchor_batch_py(
+        &self,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+        expected_version: Option<u64>,
+        caller: Option<&str>,
+    ) -> PyResult<Vec<LedgerEvent>> {
+        Ledger::anchor(self, entity, &commands, expected_version, caller)
+            .map(|(events, _)| events)
+            .map_err(PyErr::from)
+    }
+
+    /// `anchor_batch` plus its `BatchSummary` as a JSON document.
+    #[pyo3(name = "anchor_batch_with_summary")]
+    fn anchor_batch_with_summary_py(
+        &self,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+    ) -> PyResult<(Vec<LedgerEvent>, String)> {
+        let (events, summary) = Ledger::anchor_batch_with_summary(self, entity, &commands)?;
+        let summary = serde_json::to_string(&summary).map_err(LedgerError::from)?;
+        Ok((events, summary))
+    }
+
+    #[pyo3(name = "validate_batch")]
+    fn validate_batch_py(
+        &self,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+    ) -> PyResult<Vec<LedgerEvent>> {
+        Ledger::validate_batch(self, entity, &commands).map_err(PyErr::from)
+    }
+
+    /// Returns one item per non-no-op command: the committed `LedgerEvent`,
+    /// or the exception instance describing why the command was rejected.
+    #[pyo3(name = "anchor_batch_lenient")]
+    fn anchor_batch_lenient_py(
+        &self,
+        py: Python,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+    ) -> PyResult<Vec<PyObject>> {
+        let results = Ledger::anchor_batch_lenient(self, entity, &commands)?;
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                Ok(evt) => evt.into_py(py),
+                Err(reject) => PyErr::from(reject.error).into_value(py).into_py(py),
+            })
+            .collect())
+    }
+
+    /// Anchor the commands in the file at `path` ("ndjson" or "csv"),
+    /// committing every `batch_size` commands. `progress`, if given, is
+    /// called with `(lines, commands, events)` after each commit. Returns
+    /// the number of events written.
+    #[pyo3(name = "anchor_stream", signature = (path, format, batch_size=DEFAULT_STREAM_BATCH, progress=None))]
+    fn anchor_stream_py(
+        &self,
+        py: Python,
+        path: String,
+        format: &str,
+        batch_size: usize,
+        progress: Option<PyObject>,
+    ) -> PyResult<u64> {
+        let file = std::fs::File::open(path).map_err(LedgerError::from)?;
+        let mut callback_error = None;
+        let done = Ledger::anchor_stream(
+            self,
+            std::io::BufReader::new(file),
+            format.parse()?,
+            batch_size,
+            |p| {
+                if let (Some(progress), None) = (&progress, &callback_error) {
+                    if let Err(e) = progress.call1(py, (p.lines, p.commands, p.events)) {
+                        callback_error = Some(e);
+                    }
+                }
+            },
+        );
+        match callback_error {
+            Some(e) => Err(e),
+            None => Ok(done?.events),
+        }
+    }
+
+    /// Write `data` ("factors" or "events") to the file at `path` as
+    /// `format` ("csv", "jsonl" or, with the `parquet` feature, "parquet").
+    #[pyo3(name = "export")]
+    fn export_py(&self, data: &str, format: &str, path: String) -> PyResult<u64> {
+        let file = std::fs::File::create(path).map_err(LedgerError::from)?;
+        Ledger::export(self, data.parse()?, format.parse()?, file).map_err(PyErr::from)
+    }
+
+    /// `entries` is a list of `(entity, prime, exponent)` tuples.
+    #[pyo3(name = "bulk_load")]
+    fn bulk_load_py(&self, entries: Vec<(u64, u32, i32)>) -> PyResult<u64> {
+        Ledger::bulk_load(self, entries).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "transfer")]
+    fn transfer_py(
+        &self,
+        from_entity: u64,
+        to_entity: u64,
+        prime: u32,
+        amount: i32,
+    ) -> PyResult<Vec<LedgerEvent>> {
+        Ledger::transfer(self, from_entity, to_entity, prime, amount).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "events_since")]
+    fn events_since_py(&self, lsn: u64) -> PyResult<Vec<LedgerEvent>> {
+        Ledger::events_since(self, lsn)?
+            .collect::<Result<_, _>>()
+            .map_err(PyErr::from)
+    }
+
+    /// Pass exactly one of `lsn` or `timestamp` (milliseconds).
+    #[pyo3(name = "get_exponent_at", signature = (entity, prime, lsn=None, timestamp=None))]
+    fn get_exponent_at_py(
+        &self,
+        entity: u64,
+        prime: u32,
+        lsn: Option<u64>,
+        timestamp: Option<u64>,
+    ) -> PyResult<Option<i32>> {
+        let as_of = match (lsn, timestamp) {
+            (Some(lsn), None) => AsOf::Lsn(lsn),
+            (None, Some(ts)) => AsOf::Timestamp(ts),
+            _ => {
+                return Err(LedgerError::InvalidArgument(
+                    "pass exactly one of lsn or timestamp".to_string(),
+                )
+                .into())
+            }
+        };
+        Ledger::get_exponent_at(self, entity, prime, as_of).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "list_entities", signature = (cursor=None, limit=100))]
+    fn list_entities_py(&self, cursor: Option<u64>, limit: usize) -> PyResult<Vec<u64>> {
+        Ledger::list_entities(self, cursor, limit).map_err(PyErr::from)
+    }
+
+    /// `metadata` is any JSON-serialisable object (dict, list, ...).
+    #[pyo3(name = "set_metadata")]
+    fn set_metadata_py(&self, py: Python, entity: u64, metadata: PyObject) -> PyResult<()> {
+        let text: String = py
+            .import("json")?
+            .call_method1("dumps", (metadata,))?
+            .extract()?;
+        let value = serde_json::from_str(&text).map_err(LedgerError::from)?;
+        Ledger::set_metadata(self, entity, &value).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "get_metadata")]
+    fn get_metadata_py(&self, py: Python, entity: u64) -> PyResult<Option<PyObject>> {
+        match Ledger::get_metadata(self, entity)? {
+            Some(value) => {
+                let text = value.to_string();
+                let obj = py.import("json")?.call_method1("loads", (text,))?;
+                Ok(Some(obj.into()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[pyo3(name = "current_exponents")]
+    fn current_exponents_py(&self, entity: u64, primes: Vec<u32>) -> PyResult<Vec<Option<i32>>> {
+        Ledger::current_exponents(self, entity, &primes).map_err(PyErr::from)
+    }
+
+    /// `(psi1, psi2, psi1_norm, psi2_norm)` as returned by
+    /// `py_pack_quaternion`, or `None`.
+    #[pyo3(name = "quaternion_state")]
+    fn quaternion_state_py(&self, entity: u64) -> PyResult<Option<QpQuatParts>> {
+        let state = Ledger::quaternion_state(self, entity)?;
+        Ok(state.map(|q| q.to_parts()))
+    }
+
+    #[pyo3(name = "pack_entity")]
+    fn pack_entity_py(&self, entity: u64) -> PyResult<QpQuatParts> {
+        Ok(Ledger::pack_entity(self, entity)?.to_parts())
+    }
+
+    /// Takes the parts `pack_entity` returns.
+    #[pyo3(name = "apply_quat_state")]
+    fn apply_quat_state_py(&self, entity: u64, state: QpQuatParts) -> PyResult<Vec<LedgerEvent>> {
+        Ledger::apply_quat_state(self, entity, &QpQuat::from_parts(state)).map_err(PyErr::from)
+    }
+
+    /// `q` as (w, i, j, k).
+    #[pyo3(name = "rotate_legal")]
+    fn rotate_legal_py(&self, entity: u64, q: [f32; 4]) -> PyResult<Vec<LedgerEvent>> {
+        let [w, i, j, k] = q;
+        Ledger::rotate_legal(self, entity, nalgebra::Quaternion::new(w, i, j, k))
+            .map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "entity_version")]
+    fn entity_version_py(&self, entity: u64) -> PyResult<u64> {
+        Ledger::entity_version(self, entity).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "create_checkpoint")]
+    fn create_checkpoint_py(&self, dir: String) -> PyResult<()> {
+        Ledger::create_checkpoint(self, dir)
+            .map(|_| ())
+            .map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "verify_chain")]
+    fn verify_chain_py(&self) -> PyResult<u64> {
+        Ledger::verify_chain(self).map_err(PyErr::from)
+    }
+
+    /// Sign subsequent events with a 32-byte Ed25519 secret key.
+    #[pyo3(name = "set_signing_key")]
+    fn set_signing_key_py(&mut self, secret: Vec<u8>) -> PyResult<()> {
+        let key = signing::signing_key_from_bytes(&secret).map_err(PyErr::from)?;
+        self.set_signing_key(key);
+        Ok(())
+    }
+
+    #[pyo3(name = "merkle_root")]
+    fn merkle_root_py(&self) -> PyResult<String> {
+        Ledger::merkle_root(self).map_err(PyErr::from)
+    }
+
+    /// Snapshot current state and truncate the sealed log; returns the
+    /// snapshot's LSN.
+    #[pyo3(name = "compact")]
+    fn compact_py(&self) -> PyResult<u64> {
+        Ledger::compact(self).map(|s| s.lsn).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "delete_entity")]
+    fn delete_entity_py(&self, entity: u64) -> PyResult<LedgerEvent> {
+        Ledger::delete_entity(self, entity).map_err(PyErr::from)
     }
 
-    #[pyo3(name = "anchor_batch")]
-    fn anchor_batch_py(&self, entity: u64, commands: Vec<(u32, u8)>) -> PyResult<Vec<LedgerEvent>> {
-        Ledger::anchor_batch(self, entity, &commands)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e))
+    /// `Ledger.crdt_state()` as a JSON string, for another writer's
+    /// `merge_crdt`.
+    #[pyo3(name = "crdt_state")]
+    fn crdt_state_py(&self) -> PyResult<String> {
+        Ledger::crdt_state(self)
+            .and_then(|s| serde_json::to_string(&s).map_err(LedgerError::from))
+            .map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "merge_crdt")]
+    fn merge_crdt_py(&self, state: &str) -> PyResult<Vec<LedgerEvent>> {
+        let state = serde_json::from_str(state).map_err(LedgerError::from)?;
+        Ledger::merge_crdt(self, &state).map_err(PyErr::from)
+    }
+
+    /// `Ledger.denials()` as a JSON array; pass the last id seen as
+    /// `cursor` to page on.
+    #[pyo3(name = "denials", signature = (cursor=None, limit=100))]
+    fn denials_py(&self, cursor: Option<u64>, limit: usize) -> PyResult<String> {
+        Ledger::denials(self, cursor, limit)
+            .and_then(|d| serde_json::to_string(&d).map_err(LedgerError::from))
+            .map_err(PyErr::from)
+    }
+
+    /// `(node, prime)` pairs of the ledger's registry, in node order.
+    #[pyo3(name = "registry")]
+    fn registry_py(&self) -> Vec<(u8, u32)> {
+        self.registry.iter().collect()
+    }
+
+    /// `Ledger.stats()` as a JSON string.
+    #[pyo3(name = "stats")]
+    fn stats_py(&self) -> PyResult<String> {
+        Ledger::stats(self)
+            .and_then(|s| serde_json::to_string(&s).map_err(LedgerError::from))
+            .map_err(PyErr::from)
+    }
+
+    /// Metrics snapshot as a JSON document.
+    #[pyo3(name = "metrics")]
+    fn metrics_py(&self) -> PyResult<String> {
+        Ledger::metrics(self)
+            .and_then(|m| serde_json::to_string(&m).map_err(LedgerError::from))
+            .map_err(PyErr::from)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "open_read_only")]
+    fn open_read_only_py(path: String) -> PyResult<Self> {
+        Ledger::open_read_only(path).map_err(PyErr::from)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "open_as_secondary")]
+    fn open_as_secondary_py(path: String, secondary_path: String) -> PyResult<Self> {
+        Ledger::open_as_secondary(path, secondary_path).map_err(PyErr::from)
+    }
+
+    #[pyo3(name = "catch_up")]
+    fn catch_up_py(&self) -> PyResult<()> {
+        Ledger::catch_up(self).map_err(PyErr::from)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "restore")]
+    fn restore_py(backup_dir: String, target_dir: String) -> PyResult<Self> {
+        Ledger::restore(backup_dir, target_dir).map_err(PyErr::from)
     }
 }
 
 impl Ledger {
-    pub fn new<P: AsRef<Path>>(base_path: P) -> Result<Self, String> {
+    pub fn new<P: AsRef<Path>>(base_path: P) -> Result<Self, LedgerError> {
+        Ledger::with_options(base_path, &LedgerOptions::default())
+    }
+
+    pub fn with_options<P: AsRef<Path>>(
+        base_path: P,
+        options: &LedgerOptions,
+    ) -> Result<Self, LedgerError> {
         let base_path = base_path.as_ref();
-        std::fs::create_dir_all(base_path).map_err(|e| e.to_string())?;
+        options.bounds.check()?;
+        std::fs::create_dir_all(base_path)?;
+        let lock = LockFile::acquire(base_path, options.lock_wait)?;
 
         let db_path = base_path.join("db");
-        std::fs::create_dir_all(&db_path).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&db_path)?;
 
-        let mut opts = Options::default();
+        let mut opts = options.db_options();
         opts.create_if_missing(true);
         opts.create_missing_column_families(true);
 
-        let cf_descriptors = ["default", "factors", "postings"]
-            .iter()
-            .map(|name| ColumnFamilyDescriptor::new(*name, Options::default()))
-            .collect::<Vec<_>>();
+        let (registry, unrecorded) = registry::resolve(&db_path, options.registry)?;
+        let db =
+            rocksdb::DB::open_cf_descriptors(&opts, &db_path, cf_descriptors(options, registry)?)?;
 
-        let db = rocksdb::DB::open_cf_descriptors(&opts, &db_path, cf_descriptors)
-            .map_err(|e| e.to_string())?;
+        let db = Arc::new(db);
+        let log = EventLog::open(base_path, options.log_cipher()?)?;
+        let chain_head = chain::chain_head(log.last_event()?.as_ref());
 
-        let log_path = base_path.join("event.log");
-        if let Some(parent) = log_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        let mut ledger = Ledger {
+            archive: options.archive.clone(),
+            outbox: options
+                .publisher
+                .clone()
+                .map(|publisher| Outbox::start(db.clone(), publisher)),
+            db,
+            log,
+            signer: None,
+            subscribers: Subscribers::default(),
+            write_lock: Mutex::new(WriterState {
+                chain_head,
+                next_lsn: 0,
+            }),
+            mode: AccessMode::Primary,
+            retention: options.retention.clone(),
+            metrics: Arc::default(),
+            exponent_cache: ExponentCache::new(options.exponent_cache_size_or_default()),
+            clock: options.clock.clone(),
+            sync: SyncState::new(options.sync),
+            writer_id: options.writer_id,
+            record_denials: options.record_denials,
+            bounds: options.bounds.clone(),
+            registry,
+            centroid_seed: options.centroid_seed,
+            _lock: Some(lock),
+        };
+        if unrecorded {
+            ledger.record_registry()?;
         }
-        OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_path)
-            .map_err(|e| e.to_string())?;
-
-        Ok(Ledger { db, log_path })
+        ledger.migrate_format()?;
+        ledger.seed_crdt()?;
+        let next_lsn = ledger.load_next_lsn()?;
+        ledger.catch_up_merkle(next_lsn)?;
+        ledger.write_lock.get_mut()?.next_lsn = next_lsn;
+        ledger
+            .log
+            .reserve_segments_through(ledger.pruned_prefix()?.last_segment);
+        Ok(ledger)
     }
 
     /// high-throughput entry: 10 k ops / call
@@ -107,104 +773,446 @@ impl Ledger {
         &self,
         entity: u64,
         commands: &[(u32, u8)],
-    ) -> Result<Vec<LedgerEvent>, String> {
-        let ts = Utc::now().timestamp_millis() as u64;
-        let mut base_centroid = centroid::centroid_now(ts);
-        let mut events = Vec::with_capacity(commands.len());
-        let mut batch = WriteBatch::default();
-
-        let factors_cf = self
-            .db
-            .cf_handle("factors")
-            .ok_or_else(|| "missing column family: factors".to_string())?;
-        let postings_cf = self
-            .db
-            .cf_handle("postings")
-            .ok_or_else(|| "missing column family: postings".to_string())?;
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.anchor(entity, commands, None, None)
+            .map(|(events, _)| events)
+    }
 
+    fn anchor(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+        expected_version: Option<u64>,
+        caller: Option<&str>,
+    ) -> Result<(Vec<LedgerEvent>, BatchSummary), LedgerError> {
+        let _span = span!("anchor_batch", entity, commands = commands.len());
+        let started = Instant::now();
+        self.ensure_writable()?;
+        let mut writer = self.write_lock.lock()?;
+        self.ensure_live(entity)?;
+        self.check_version(entity, expected_version)?;
+        let mut staged = StagedBatch::new(self.clock.now_millis(), commands.len());
+        staged.caller = caller.map(str::to_string);
+        self.prefetch(&mut staged, entity, commands)?;
         for &(prime, target_node) in commands {
-            let src_node = registry::prime_to_node(prime)
-                .ok_or_else(|| format!("Prime {} not in S0", prime))?;
-            let dst_node = target_node;
-
-            let current = self
-                .current_exponent(entity, prime)?
-                .unwrap_or(src_node as i32);
-            let delta_i32 = (dst_node as i32) - current;
-            if delta_i32 == 0 {
-                continue; // no-op
+            self.stage_command(&mut staged, &writer, entity, prime, target_node)?;
+        }
+        let (events, log_bytes) = self.commit_staged(&mut writer, staged)?;
+        let summary = BatchSummary::new(commands.len(), &events, log_bytes, started.elapsed());
+        Ok((events, summary))
+    }
+
+    /// Validate one command against everything staged so far and, unless
+    /// it is a no-op, stage its event and merges. Returns whether an event
+    /// was staged; on error `staged` is left untouched.
+    fn stage_command(
+        &self,
+        staged: &mut StagedBatch,
+        writer: &WriterState,
+        entity: u64,
+        prime: u32,
+        target_node: u8,
+    ) -> Result<bool, LedgerError> {
+        let _validate = span!("validate", entity, prime, target_node);
+        let src_node = self
+            .registry
+            .prime_to_node(prime)
+            .ok_or(LedgerError::UnknownPrime(prime))?;
+        let bounded = self.bound_exponent(entity, prime, i64::from(target_node))?;
+        let dst_node = u8::try_from(bounded).map_err(|_| {
+            LedgerError::InvalidArgument(format!(
+                "exponent bounds move node {} to {}, which is not a node",
+                target_node, bounded
+            ))
+        })?;
+
+        let current = match staged.pending.get(&(entity, prime)) {
+            Some(&v) => v,
+            None => match staged.prefetched.get(&(entity, prime)) {
+                Some(&stored) => stored,
+                None => self.current_exponent(entity, prime)?,
             }
+            .unwrap_or(src_node as i32),
+        };
+        let delta_i32 = (dst_node as i32) - current;
+        if delta_i32 == 0 {
+            return Ok(false); // no-op
+        }
+
+        let msd_digits = Msd::from_int(delta_i32);
 
-            let msd = Msd::from_int(delta_i32);
-            let msd_digits = msd.as_vector().data().to_vec();
+        let via_c = (src_node % 2 == 0 && dst_node % 2 == 1)
+            && !matches!(
+                (src_node, dst_node),
+                (1, 2) | (5, 6) | (3, 0) | (7, 4) | (1, 0)
+            );
+        let src_node_enum = node_from_u8(src_node).ok_or(LedgerError::InvalidNode(src_node))?;
+        let dst_node_enum = node_from_u8(dst_node).ok_or(LedgerError::InvalidNode(dst_node))?;
 
-            let via_c = (src_node % 2 == 0 && dst_node % 2 == 1)
-                && !matches!(
+        let allowed = flow_rule::transition_allowed(src_node_enum, dst_node_enum);
+        if !allowed && !via_c {
+            self.metrics.record_denied();
+            if staged.audit {
+                self.record_denial(
+                    entity,
+                    prime,
                     (src_node, dst_node),
-                    (1, 2) | (5, 6) | (3, 0) | (7, 4) | (1, 0)
-                );
-            let src_node_enum = node_from_u8(src_node)
-                .ok_or_else(|| format!("Invalid source node {}", src_node))?;
-            let dst_node_enum = node_from_u8(dst_node)
-                .ok_or_else(|| format!("Invalid target node {}", dst_node))?;
-
-            let allowed = flow_rule::transition_allowed(src_node_enum, dst_node_enum);
-            if !allowed && !via_c {
-                return Err(format!("Transition {}→{} forbidden", src_node, dst_node));
+                    staged.ts,
+                    staged.caller.as_deref(),
+                )?;
+            }
+            return Err(LedgerError::FlowRuleViolation {
+                src: src_node,
+                dst: dst_node,
+            });
+        }
+
+        let lsn = writer.next_lsn + staged.events.len() as u64;
+        let base_centroid = match staged.centroids.get(&entity) {
+            Some(&d) => d,
+            None => self.entity_centroid(entity)?.unwrap_or_else(|| {
+                self.centroid_seed
+                    .digit(self.clock.as_clock(), entity, lsn, staged.ts)
+            }),
+        };
+        let centroid_digit = if via_c {
+            centroid::flip_digit(base_centroid)
+        } else {
+            base_centroid
+        };
+        let mut evt = LedgerEvent {
+            entity_id: entity,
+            prime,
+            msd_digits,
+            via_c,
+            centroid_digit,
+            timestamp: staged.ts,
+            lsn,
+            ..Default::default()
+        };
+        let prev_hash = staged
+            .events
+            .last()
+            .map_or(writer.chain_head.as_str(), |e| e.event_hash.as_str());
+        self.seal(&mut evt, prev_hash)?;
+
+        staged.centroids.insert(entity, centroid_digit);
+        staged.pending.insert((entity, prime), current + delta_i32);
+        let delta = keys::encode_exponent(delta_i32);
+        staged
+            .batch
+            .merge_cf(self.cf("factors")?, keys::factor_key(entity, prime), &delta);
+        staged.batch.merge_cf(
+            self.cf("postings")?,
+            keys::posting_key(prime, entity),
+            &delta,
+        );
+        staged.events.push(evt);
+        Ok(true)
+    }
+
+    /// Commit a staged batch and cache the exponents it wrote. Returns the
+    /// events with the bytes they took in the log.
+    fn commit_staged(
+        &self,
+        writer: &mut WriterState,
+        mut staged: StagedBatch,
+    ) -> Result<(Vec<LedgerEvent>, u64), LedgerError> {
+        self.stage_crdt(&mut staged.batch, &staged.events)?;
+        let log_bytes = self.commit(writer, &staged.events, staged.batch)?;
+        self.exponent_cache.put_all(&staged.pending);
+        Ok((staged.events, log_bytes))
+    }
+
+    /// Log, apply and publish sealed events. Caller holds the write lock.
+    /// Returns the bytes appended to the log.
+    fn commit(
+        &self,
+        writer: &mut WriterState,
+        events: &[LedgerEvent],
+        mut batch: WriteBatch,
+    ) -> Result<u64, LedgerError> {
+        let next_lsn = writer.next_lsn + events.len() as u64;
+        self.stage_commit(&mut batch, events, next_lsn)?;
+        let started = Instant::now();
+        let log_bytes = self.append_events(events)?;
+        self.apply_events(events, batch)?;
+        self.record_commit(events, started);
+        if let Some(last) = events.last() {
+            writer.chain_head = last.event_hash.clone();
+        }
+        writer.next_lsn = next_lsn;
+        self.subscribers.publish(events);
+        self.rotate_log()?;
+        Ok(log_bytes)
+    }
+
+    /// Stage the derived state of `events`: [`Ledger::stage_derived`], then
+    /// Merkle nodes, outbox entries and the LSN counter.
+    fn stage_commit(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+        next_lsn: u64,
+    ) -> Result<(), LedgerError> {
+        self.stage_derived(batch, events)?;
+        self.stage_merkle(batch, events)?;
+        self.stage_outbox(batch, events);
+        self.stage_next_lsn(batch, next_lsn);
+        Ok(())
+    }
+
+    /// Stage statistics, versions, centroids, quaternion state and history.
+    fn stage_derived(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        // Reads the versions as they stand before this batch.
+        self.stage_stats(batch, events)?;
+        self.stage_versions(batch, events)?;
+        self.stage_centroids(batch, events)?;
+        self.stage_quat_state(batch, events)?;
+        self.stage_history(batch, events)
+    }
+
+    fn append_events(&self, events: &[LedgerEvent]) -> Result<u64, LedgerError> {
+        let _span = span!("log_append", events = events.len());
+        self.log.append(events, self.sync.sync_log())
+    }
+
+    /// Write `batch`, drop cached exponents `events` touched and wake the
+    /// outbox publisher.
+    fn apply_events(&self, events: &[LedgerEvent], batch: WriteBatch) -> Result<(), LedgerError> {
+        {
+            let _span = span!("db_commit", ops = batch.len());
+            self.db.write_opt(batch, &self.sync.db_write_options())?;
+        }
+        if let Some(outbox) = &self.outbox {
+            outbox.notify();
+        }
+        for evt in events {
+            if evt.tombstone {
+                self.exponent_cache.clear();
+            } else {
+                self.exponent_cache.invalidate(evt.entity_id, evt.prime);
             }
+        }
+        Ok(())
+    }
+
+    fn record_commit(&self, events: &[LedgerEvent], started: Instant) {
+        let via_c = events.iter().filter(|e| e.via_c).count() as u64;
+        self.metrics
+            .record_commit(events.len() as u64, via_c, started.elapsed());
+    }
+
+    /// Seal the active segment once it is full, archive it and apply
+    /// retention.
+    fn rotate_log(&self) -> Result<(), LedgerError> {
+        if self.log.maybe_rotate()?.is_some() {
+            // A failed upload is retried at the next rotation; retention
+            // keeps unarchived segments until then.
+            let _ = self.archive_segments();
+            self.prune_segments()?;
+        }
+        Ok(())
+    }
+
+    /// Chain (and sign, if configured) `evt` after `prev_hash`.
+    fn seal(&self, evt: &mut LedgerEvent, prev_hash: &str) -> Result<(), LedgerError> {
+        chain::seal_event(evt, prev_hash)?;
+        if let Some(key) = &self.signer {
+            signing::sign_event(evt, key)?;
+        }
+        Ok(())
+    }
+
+    /// Apply events that are in the log but not the DB, staging what a live
+    /// commit would apart from the log-derived Merkle nodes, which opening
+    /// already caught up.
+    pub(crate) fn replay_events(&self, events: &[LedgerEvent]) -> Result<(), LedgerError> {
+        self.ensure_writable()?;
+        let writer = self.write_lock.lock()?;
+        let mut batch = WriteBatch::default();
+        self.stage_projections(&mut batch, events)?;
+        self.stage_crdt(&mut batch, events)?;
+        self.stage_derived(&mut batch, events)?;
+        self.stage_next_lsn(&mut batch, writer.next_lsn);
+        self.db.write(batch)?;
+        self.exponent_cache.clear();
+        Ok(())
+    }
 
-            if via_c {
-                base_centroid = centroid::flip_digit(base_centroid);
+    /// Stage event deltas as merges. Pure merges: no reads are needed to
+    /// replay deltas.
+    pub(crate) fn stage_replay(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        self.stage_versions(batch, events)?;
+        self.stage_centroids(batch, events)?;
+        self.stage_projections(batch, events)
+    }
+
+    /// Stage the factors/postings side of `events`: exponent deltas as
+    /// merges, tombstones as deletes.
+    fn stage_projections(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let factors_cf = self.cf("factors")?;
+        let postings_cf = self.cf("postings")?;
+        let mut touched: HashMap<u64, Vec<u32>> = HashMap::new();
+        for evt in events {
+            if evt.tombstone {
+                let staged = touched.remove(&evt.entity_id).unwrap_or_default();
+                self.stage_tombstone(batch, evt.entity_id, &staged)?;
+                continue;
             }
+            touched.entry(evt.entity_id).or_default().push(evt.prime);
+            let delta = keys::encode_exponent(evt.msd_digits.to_int());
+            batch.merge_cf(
+                factors_cf,
+                keys::factor_key(evt.entity_id, evt.prime),
+                &delta,
+            );
+            batch.merge_cf(
+                postings_cf,
+                keys::posting_key(evt.prime, evt.entity_id),
+                &delta,
+            );
+        }
+        Ok(())
+    }
 
-            let evt = LedgerEvent {
-                entity_id: entity,
-                prime,
-                msd_digits: msd_digits.clone(),
-                via_c,
-                centroid_digit: base_centroid,
-                timestamp: ts,
-            };
-
-            let mut log = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&self.log_path)
-                .map_err(|e| e.to_string())?;
-            writeln!(
-                log,
-                "{}",
-                serde_json::to_string(&evt).map_err(|e| e.to_string())?
-            )
-            .map_err(|e| e.to_string())?;
-
-            let new_exp = current + delta_i32;
-            let f_key = format!("{}:{}", entity, prime);
-            batch.put_cf(factors_cf, &f_key, new_exp.to_string().as_bytes());
-            let p_key = format!("{}:{}", prime, entity);
-            batch.put_cf(postings_cf, &p_key, new_exp.to_string().as_bytes());
-
-            events.push(evt);
-        }
-
-        self.db.write(batch).map_err(|e| e.to_string())?;
-        Ok(events)
-    }
-
-    fn current_exponent(&self, entity: u64, prime: u32) -> Result<Option<i32>, String> {
-        let key = format!("{}:{}", entity, prime);
-        let cf = self
-            .db
-            .cf_handle("factors")
-            .ok_or_else(|| "missing column family: factors".to_string())?;
-        match self.db.get_cf(cf, &key).map_err(|e| e.to_string())? {
-            Some(v) => {
-                let text = std::str::from_utf8(&v).map_err(|e| e.to_string())?;
-                text.parse::<i32>().map(Some).map_err(|e| e.to_string())
+    /// Current exponent, through the exponent cache. Write path only:
+    /// callers must hold the write lock.
+    fn current_exponent(&self, entity: u64, prime: u32) -> Result<Option<i32>, LedgerError> {
+        if let Some(cached) = self.exponent_cache.get(entity, prime) {
+            self.metrics.record_cache(true);
+            return Ok(cached);
+        }
+        let value = self.read_exponent(entity, prime)?;
+        if self.exponent_cache.is_enabled() {
+            self.metrics.record_cache(false);
+            self.exponent_cache.put(entity, prime, value);
+        }
+        Ok(value)
+    }
+
+    /// Read the stored exponents `commands` will need in one `multi_get`,
+    /// skipping pairs the exponent cache already has.
+    fn prefetch(
+        &self,
+        staged: &mut StagedBatch,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<(), LedgerError> {
+        let _span = span!("prefetch", entity, commands = commands.len());
+        let mut missing = Vec::new();
+        for &(prime, _) in commands {
+            if staged.prefetched.contains_key(&(entity, prime)) || missing.contains(&prime) {
+                continue;
             }
+            match self.exponent_cache.get(entity, prime) {
+                Some(cached) => {
+                    self.metrics.record_cache(true);
+                    staged.prefetched.insert((entity, prime), cached);
+                }
+                None => missing.push(prime),
+            }
+        }
+        let values = self.current_exponents(entity, &missing)?;
+        for (prime, value) in missing.into_iter().zip(values) {
+            if self.exponent_cache.is_enabled() {
+                self.metrics.record_cache(false);
+                self.exponent_cache.put(entity, prime, value);
+            }
+            staged.prefetched.insert((entity, prime), value);
+        }
+        Ok(())
+    }
+
+    /// Stored exponents of `primes` on `entity`, in order, read with one
+    /// RocksDB `multi_get`.
+    pub fn current_exponents(
+        &self,
+        entity: u64,
+        primes: &[u32],
+    ) -> Result<Vec<Option<i32>>, LedgerError> {
+        let cf = self.cf("factors")?;
+        self.db
+            .multi_get_cf(primes.iter().map(|&p| (cf, keys::factor_key(entity, p))))
+            .into_iter()
+            .map(|value| match value? {
+                Some(raw) => keys::decode_exponent(&raw).map(Some),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    fn read_exponent(&self, entity: u64, prime: u32) -> Result<Option<i32>, LedgerError> {
+        let cf = self.cf("factors")?;
+        match self.db.get_cf(cf, keys::factor_key(entity, prime))? {
+            Some(v) => keys::decode_exponent(&v).map(Some),
             None => Ok(None),
         }
     }
+
+    fn cf(&self, name: &str) -> Result<&ColumnFamily, LedgerError> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| LedgerError::Corruption(format!("missing column family: {}", name)))
+    }
+}
+
+/// Column families and their options; shared by every open mode.
+pub(crate) fn cf_descriptors(
+    options: &LedgerOptions,
+    registry: Registry,
+) -> Result<Vec<ColumnFamilyDescriptor>, LedgerError> {
+    let names = [
+        "default",
+        "factors",
+        "postings",
+        centroid_state::CENTROIDS_CF,
+        crdt::CRDT_CF,
+        denials::DENIALS_CF,
+        history::HISTORY_CF,
+        merkle::MERKLE_CF,
+        metadata::METADATA_CF,
+        quat_state::QUAT_STATE_CF,
+        registry::META_CF,
+    ];
+    options.check_cf_names(&names)?;
+    let cache = options.block_cache();
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let mut opts = options.cf_options(name, cache.as_ref());
+            match name {
+                "factors" => opts.set_merge_operator(
+                    merge::MERGE_OPERATOR,
+                    move |key: &[u8], existing: Option<&[u8]>, ops: &MergeOperands| {
+                        merge::factors_full_merge(&registry, key, existing, ops)
+                    },
+                    merge::partial_merge,
+                ),
+                "postings" => opts.set_merge_operator(
+                    merge::MERGE_OPERATOR,
+                    move |key: &[u8], existing: Option<&[u8]>, ops: &MergeOperands| {
+                        merge::postings_full_merge(&registry, key, existing, ops)
+                    },
+                    merge::partial_merge,
+                ),
+                _ => {}
+            }
+            ColumnFamilyDescriptor::new(name, opts)
+        })
+        .collect())
 }
 
 #[pyfunction]
@@ -214,18 +1222,31 @@ fn py_anchor_batch(
     entity: u64,
     commands: Vec<(u32, u8)>,
 ) -> PyResult<Vec<LedgerEvent>> {
-    Ledger::anchor_batch(ledger, entity, &commands)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e))
+    Ledger::anchor_batch(ledger, entity, &commands).map_err(PyErr::from)
+}
+
+#[pyfunction]
+fn py_verify_event_signature(event: &LedgerEvent, public_key: Vec<u8>) -> PyResult<bool> {
+    let key = signing::verifying_key_from_bytes(&public_key).map_err(PyErr::from)?;
+    Ok(verify_event_signature(event, &key).is_ok())
 }
 
 #[pymodule]
 fn core(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Ledger>()?;
     m.add_class::<LedgerEvent>()?;
+    m.add_class::<python::PyMsd>()?;
+    error::py::register(_py, m)?;
     m.add_function(wrap_pyfunction!(py_anchor_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(py_verify_event_signature, m)?)?;
     m.add_function(wrap_pyfunction!(python::py_pack_quaternion, m)?)?;
     m.add_function(wrap_pyfunction!(python::py_unpack_quaternion, m)?)?;
     m.add_function(wrap_pyfunction!(python::py_rotate_quaternion, m)?)?;
+    m.add_function(wrap_pyfunction!(python::py_pack_quaternion_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(python::py_unpack_quaternion_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(python::py_rotate_quaternion_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(python::py_screw_quaternion, m)?)?;
     m.add_function(wrap_pyfunction!(python::py_energy_proxy, m)?)?;
+    m.add_function(wrap_pyfunction!(python::py_bench_packing, m)?)?;
     Ok(())
 }
@@ -0,0 +1,89 @@
+//! Advisory lock file guarding a ledger directory across processes
+//! `<base>/ledger.lock` is flocked by the writer and records its PID.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::LedgerError;
+
+pub const LOCK_FILE: &str = "ledger.lock";
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held for as long as the ledger is open; dropping it releases the lock.
+#[derive(Debug)]
+pub struct LockFile {
+    _file: File,
+}
+
+impl LockFile {
+    /// Lock `base_path`, polling for up to `wait` if it is held elsewhere.
+    pub fn acquire(base_path: &Path, wait: Option<Duration>) -> Result<Self, LedgerError> {
+        let path = base_path.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let deadline = wait.map(|w| Instant::now() + w);
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(std::fs::TryLockError::WouldBlock) => {
+                    if deadline.is_some_and(|d| Instant::now() < d) {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    return Err(LedgerError::AlreadyLocked {
+                        path: base_path.to_path_buf(),
+                        pid: read_pid(&mut file),
+                    });
+                }
+                Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", std::process::id())?;
+        Ok(LockFile { _file: file })
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut raw = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut raw).ok()?;
+    raw.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ledger, LedgerOptions};
+
+    #[test]
+    fn second_writer_gets_already_locked_with_pid() {
+        let tmp = tempfile::tempdir().unwrap();
+        let first = Ledger::new(tmp.path()).unwrap();
+        match Ledger::with_options(tmp.path(), &LedgerOptions::default()) {
+            Err(LedgerError::AlreadyLocked { pid, .. }) => {
+                assert_eq!(pid, Some(std::process::id()))
+            }
+            other => panic!("expected AlreadyLocked, got {:?}", other.err()),
+        }
+
+        let waiter = {
+            let path = tmp.path().to_path_buf();
+            thread::spawn(move || {
+                let options = LedgerOptions::new().wait_for_lock(Duration::from_secs(10));
+                Ledger::with_options(path, &options).map(|_| ())
+            })
+        };
+        thread::sleep(Duration::from_millis(200));
+        drop(first);
+        waiter.join().unwrap().unwrap();
+    }
+}
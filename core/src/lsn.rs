@@ -0,0 +1,93 @@
+//! Global log sequence numbers
+//! An event's LSN is its position in the whole log, counting events pruned
+//! by retention; the next LSN to assign is kept under `next_lsn` in the
+//! default column family.
+
+use rocksdb::WriteBatch;
+
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const NEXT_LSN_KEY: &[u8] = b"next_lsn";
+
+pub(crate) fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl Ledger {
+    /// Events with LSN `lsn` or later, oldest first, read lazily.
+    /// Events logged before LSNs were assigned carry `lsn == 0`.
+    pub fn events_since(
+        &self,
+        lsn: u64,
+    ) -> Result<impl Iterator<Item = Result<LedgerEvent, LedgerError>>, LedgerError> {
+        let first = self.pruned_prefix()?.events;
+        if lsn < first {
+            return Err(LedgerError::InvalidArgument(format!(
+                "LSN {} has been pruned by retention; the log starts at {}",
+                lsn, first
+            )));
+        }
+        let skip = usize::try_from(lsn - first)?;
+        Ok(self.log.iter()?.skip(skip))
+    }
+
+    /// LSN the next committed event will get.
+    pub fn next_lsn(&self) -> Result<u64, LedgerError> {
+        Ok(self.write_lock.lock()?.next_lsn)
+    }
+
+    /// Work out the next LSN when opening. The stored counter can lag the
+    /// log if the process died between the log append and the DB write.
+    pub(crate) fn load_next_lsn(&self) -> Result<u64, LedgerError> {
+        let stored = match self.db.get(NEXT_LSN_KEY)? {
+            Some(raw) => raw
+                .as_slice()
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| LedgerError::Corruption("corrupt next_lsn".to_string()))?,
+            // Ledgers from before LSNs: count what the log has seen.
+            None => {
+                let mut count = self.pruned_prefix()?.events;
+                for evt in self.log.iter()? {
+                    evt?;
+                    count += 1;
+                }
+                return Ok(count);
+            }
+        };
+        let logged = self.log.last_event()?.map_or(0, |evt| evt.lsn + 1);
+        Ok(stored.max(logged))
+    }
+
+    pub(crate) fn stage_next_lsn(&self, batch: &mut WriteBatch, next_lsn: u64) {
+        batch.put(NEXT_LSN_KEY, next_lsn.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lsns_are_global_and_resume_after_reopen() {
+        let tmp = tempfile::tempdir().unwrap();
+        {
+            let ledger = Ledger::new(tmp.path()).unwrap();
+            let events = ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+            assert_eq!(events[0].lsn, 0);
+            assert_eq!(events[1].lsn, 1);
+        }
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.next_lsn().unwrap(), 2);
+        let events = ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+        assert_eq!(events[0].lsn, 2);
+        assert_eq!(ledger.verify_chain().unwrap(), 3);
+
+        let tail: Vec<_> = ledger
+            .events_since(1)
+            .unwrap()
+            .map(|evt| evt.unwrap().lsn)
+            .collect();
+        assert_eq!(tail, vec![1, 2]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Merge operator for exponent deltas on the factors/postings CFs
+//! Writers merge signed deltas instead of read-modify-write puts; a missing
+//! base value starts from the prime's node, matching `anchor_batch`.
+
+use rocksdb::MergeOperands;
+
+use crate::keys;
+use crate::registry::Registry;
+
+pub const MERGE_OPERATOR: &str = "exponent_delta";
+
+pub fn factors_full_merge(
+    registry: &Registry,
+    key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let prime = u32::from_be_bytes(key.get(8..12)?.try_into().ok()?);
+    full_merge(registry, prime, existing, operands)
+}
+
+pub fn postings_full_merge(
+    registry: &Registry,
+    key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let prime = u32::from_be_bytes(key.get(0..4)?.try_into().ok()?);
+    full_merge(registry, prime, existing, operands)
+}
+
+/// Collapse stacked deltas into one; never sees the base value.
+pub fn partial_merge(
+    _key: &[u8],
+    _existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    sum_deltas(0, operands).map(keys::encode_exponent)
+}
+
+fn full_merge(
+    registry: &Registry,
+    prime: u32,
+    existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let base = match existing {
+        Some(raw) => keys::decode_exponent(raw).ok()?,
+        None => registry.prime_to_node(prime).map_or(0, i32::from),
+    };
+    sum_deltas(base, operands).map(keys::encode_exponent)
+}
+
+fn sum_deltas(base: i32, operands: &MergeOperands) -> Option<i32> {
+    operands.iter().try_fold(base, |acc, op| {
+        acc.checked_add(keys::decode_exponent(op).ok()?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{keys, Ledger};
+
+    #[test]
+    fn repeated_prime_in_batch_merges_deltas() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        let events = ledger.anchor_batch(1, &[(2, 2), (2, 4)]).unwrap();
+        let deltas = events
+            .iter()
+            .map(|e| e.msd_digits.to_int())
+            .collect::<Vec<_>>();
+        assert_eq!(deltas, vec![2, 2]);
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(4));
+
+        let postings = ledger.cf("postings").unwrap();
+        let raw = ledger
+            .db
+            .get_cf(postings, keys::posting_key(2, 1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(keys::decode_exponent(&raw).unwrap(), 4);
+    }
+}
@@ -0,0 +1,457 @@
+//! Merkle tree over the event stream (RFC 6962 hashing)
+//! Leaves are events in log order, indexed by LSN from the tree's first
+//! leaf. Each commit stores the leaf and every perfect subtree it completes
+//! in the `merkle` column family, so roots and proofs read O(log² n) nodes
+//! rather than the log. Retention drops only nodes no proof can need.
+
+use std::collections::HashMap;
+
+use rocksdb::WriteBatch;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::migrate::CHUNK;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const MERKLE_CF: &str = "merkle";
+/// First leaf's LSN and leaf count, both BE, in the default column family.
+const TREE_KEY: &[u8] = b"merkle/tree";
+
+pub type Hash = [u8; 32];
+
+/// Audit path proving one event is part of a tree of `tree_size` leaves.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub lsn: u64,
+    /// LSN of the tree's first leaf; non-zero only for ledgers pruned
+    /// before they kept a tree.
+    #[serde(default)]
+    pub first_lsn: u64,
+    pub tree_size: u64,
+    /// Sibling hashes (hex), leaf level first.
+    pub path: Vec<String>,
+}
+
+pub fn leaf_hash(evt: &LedgerEvent) -> Result<Hash, LedgerError> {
+    let bytes = serde_json::to_vec(evt)?;
+    let mut h = Sha256::new();
+    h.update([0x00]);
+    h.update(&bytes);
+    Ok(h.finalize().into())
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut h = Sha256::new();
+    h.update([0x01]);
+    h.update(left);
+    h.update(right);
+    h.finalize().into()
+}
+
+/// Largest power of two strictly below `n` (n > 1).
+fn split_point(n: u64) -> u64 {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+/// The perfect subtree of `2^level` leaves starting at leaf
+/// `index << level`.
+fn node_key(level: u32, index: u64) -> [u8; 9] {
+    let mut key = [0u8; 9];
+    key[0] = level as u8;
+    key[1..].copy_from_slice(&index.to_be_bytes());
+    key
+}
+
+/// Leaves the tree covers: `size` of them from LSN `first`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Extent {
+    first: u64,
+    size: u64,
+}
+
+impl Extent {
+    fn to_bytes(self) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        out[..8].copy_from_slice(&self.first.to_be_bytes());
+        out[8..].copy_from_slice(&self.size.to_be_bytes());
+        out
+    }
+
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        let raw: &[u8; 16] = raw.try_into().ok()?;
+        let (first, size) = raw.split_at(8);
+        Some(Extent {
+            first: u64::from_be_bytes(first.try_into().ok()?),
+            size: u64::from_be_bytes(size.try_into().ok()?),
+        })
+    }
+}
+
+/// Check `proof` places `evt` under `root` (hex).
+pub fn verify_proof(root: &str, evt: &LedgerEvent, proof: &InclusionProof) -> bool {
+    let (Some(root), Ok(leaf)) = (decode_hash(root), leaf_hash(evt)) else {
+        return false;
+    };
+    let Some(index) = proof.lsn.checked_sub(proof.first_lsn) else {
+        return false;
+    };
+    if index >= proof.tree_size {
+        return false;
+    }
+    let mut fnode = index;
+    let mut snode = proof.tree_size - 1;
+    let mut r = leaf;
+    for sibling in &proof.path {
+        let Some(p) = decode_hash(sibling) else {
+            return false;
+        };
+        if snode == 0 {
+            return false;
+        }
+        if fnode & 1 == 1 || fnode == snode {
+            r = node_hash(&p, &r);
+            while fnode & 1 == 0 && fnode != 0 {
+                fnode >>= 1;
+                snode >>= 1;
+            }
+        } else {
+            r = node_hash(&r, &p);
+        }
+        fnode >>= 1;
+        snode >>= 1;
+    }
+    snode == 0 && r == root
+}
+
+fn decode_hash(s: &str) -> Option<Hash> {
+    hex::decode(s).ok()?.try_into().ok()
+}
+
+impl Ledger {
+    /// Merkle root (hex) over every event logged since the ledger began
+    /// keeping a tree; retention does not change it.
+    pub fn merkle_root(&self) -> Result<String, LedgerError> {
+        let tree = self.tree_extent()?;
+        Ok(hex::encode(self.subtree_root(0, tree.size)?))
+    }
+
+    /// Inclusion proof for the event at `event_lsn` against the current root.
+    pub fn prove_inclusion(&self, event_lsn: u64) -> Result<InclusionProof, LedgerError> {
+        let tree = self.tree_extent()?;
+        let pruned = self.pruned_prefix()?.events;
+        if event_lsn < pruned.max(tree.first) {
+            return Err(LedgerError::InvalidArgument(format!(
+                "LSN {} has been pruned by retention",
+                event_lsn
+            )));
+        }
+        let idx = event_lsn - tree.first;
+        if idx >= tree.size {
+            return Err(LedgerError::InvalidArgument(format!(
+                "LSN {} out of range (log holds {} events)",
+                event_lsn,
+                tree.first + tree.size
+            )));
+        }
+        Ok(InclusionProof {
+            lsn: event_lsn,
+            first_lsn: tree.first,
+            tree_size: tree.size,
+            path: self
+                .audit_path(idx, 0, tree.size)?
+                .iter()
+                .map(hex::encode)
+                .collect(),
+        })
+    }
+
+    /// Add `events` as the next leaves, writing every node they complete.
+    pub(crate) fn stage_merkle(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let mut tree = self.tree_extent()?;
+        let mut staged = HashMap::new();
+        for evt in events {
+            if evt.lsn != tree.first + tree.size {
+                return Err(LedgerError::Corruption(format!(
+                    "Merkle tree ends at LSN {} but the next event is {}",
+                    tree.first + tree.size,
+                    evt.lsn
+                )));
+            }
+            self.stage_leaf(batch, &mut staged, tree.size, leaf_hash(evt)?)?;
+            tree.size += 1;
+        }
+        batch.put(TREE_KEY, tree.to_bytes());
+        Ok(())
+    }
+
+    /// Drop nodes that lie wholly before `pruned` and are not the sibling
+    /// of one that doesn't; no root or retained proof reads them.
+    pub(crate) fn stage_prune_merkle(
+        &self,
+        batch: &mut WriteBatch,
+        pruned: u64,
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(MERKLE_CF)?;
+        let p = pruned.saturating_sub(self.tree_extent()?.first);
+        for level in 0..u64::BITS - 1 {
+            let keep_from = (p >> (level + 1)) << 1;
+            if keep_from == 0 {
+                break;
+            }
+            batch.delete_range_cf(cf, node_key(level, 0), node_key(level, keep_from));
+        }
+        Ok(())
+    }
+
+    /// Build the tree over the retained log; used when upgrading ledgers
+    /// that predate it. Pruned events are gone, so it starts after them.
+    pub(crate) fn backfill_merkle(&self) -> Result<(), LedgerError> {
+        let mut tree = Extent {
+            first: self.pruned_prefix()?.events,
+            size: 0,
+        };
+        let mut batch = WriteBatch::default();
+        let mut staged = HashMap::new();
+        for evt in self.log.iter()? {
+            self.stage_leaf(&mut batch, &mut staged, tree.size, leaf_hash(&evt?)?)?;
+            tree.size += 1;
+            if batch.len() >= CHUNK {
+                batch.put(TREE_KEY, tree.to_bytes());
+                self.db.write(std::mem::take(&mut batch))?;
+                staged.clear();
+            }
+        }
+        batch.put(TREE_KEY, tree.to_bytes());
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+
+    /// Add leaves for events that reached the log but not the DB, as after
+    /// a crash between the two.
+    pub(crate) fn catch_up_merkle(&self, next_lsn: u64) -> Result<(), LedgerError> {
+        let tree = self.tree_extent()?;
+        if tree.first + tree.size >= next_lsn {
+            return Ok(());
+        }
+        let events = self
+            .events_since(tree.first + tree.size)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut batch = WriteBatch::default();
+        self.stage_merkle(&mut batch, &events)?;
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+
+    fn tree_extent(&self) -> Result<Extent, LedgerError> {
+        match self.db.get(TREE_KEY)? {
+            Some(raw) => Extent::from_bytes(&raw)
+                .ok_or_else(|| LedgerError::Corruption("corrupt merkle/tree".to_string())),
+            None => Ok(Extent::default()),
+        }
+    }
+
+    /// Stage leaf `index` and each parent it completes. `staged` holds the
+    /// nodes written to `batch` so far, which the DB cannot see yet.
+    fn stage_leaf(
+        &self,
+        batch: &mut WriteBatch,
+        staged: &mut HashMap<(u32, u64), Hash>,
+        index: u64,
+        leaf: Hash,
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(MERKLE_CF)?;
+        let (mut level, mut index, mut hash) = (0, index, leaf);
+        loop {
+            batch.put_cf(cf, node_key(level, index), hash);
+            staged.insert((level, index), hash);
+            if index & 1 == 0 {
+                return Ok(());
+            }
+            let left = match staged.get(&(level, index - 1)) {
+                Some(left) => *left,
+                None => self.node(level, index - 1)?,
+            };
+            hash = node_hash(&left, &hash);
+            level += 1;
+            index >>= 1;
+        }
+    }
+
+    fn node(&self, level: u32, index: u64) -> Result<Hash, LedgerError> {
+        let missing =
+            || LedgerError::Corruption(format!("missing Merkle node {}/{}", level, index));
+        self.db
+            .get_cf(self.cf(MERKLE_CF)?, node_key(level, index))?
+            .ok_or_else(missing)?
+            .try_into()
+            .map_err(|_| missing())
+    }
+
+    /// Root over leaves `lo..hi`. RFC 6962 splits so that every left part
+    /// is a stored perfect subtree.
+    fn subtree_root(&self, lo: u64, hi: u64) -> Result<Hash, LedgerError> {
+        let n = hi - lo;
+        if n == 0 {
+            return Ok(Sha256::digest([]).into());
+        }
+        if n.is_power_of_two() {
+            return self.node(n.trailing_zeros(), lo >> n.trailing_zeros());
+        }
+        let k = split_point(n);
+        Ok(node_hash(
+            &self.subtree_root(lo, lo + k)?,
+            &self.subtree_root(lo + k, hi)?,
+        ))
+    }
+
+    /// Siblings of leaf `m` within `lo..hi`, leaf level first.
+    fn audit_path(&self, m: u64, lo: u64, hi: u64) -> Result<Vec<Hash>, LedgerError> {
+        let n = hi - lo;
+        if n <= 1 {
+            return Ok(Vec::new());
+        }
+        let k = split_point(n);
+        let (mut path, sibling) = if m < lo + k {
+            (
+                self.audit_path(m, lo, lo + k)?,
+                self.subtree_root(lo + k, hi)?,
+            )
+        } else {
+            (
+                self.audit_path(m, lo + k, hi)?,
+                self.subtree_root(lo, lo + k)?,
+            )
+        };
+        path.push(sibling);
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Msd;
+
+    /// Reference RFC 6962 root over leaves held in memory.
+    fn root_of(leaves: &[Hash]) -> Hash {
+        match leaves.len() {
+            0 => Sha256::digest([]).into(),
+            1 => leaves[0],
+            n => {
+                let k = split_point(n as u64) as usize;
+                node_hash(&root_of(&leaves[..k]), &root_of(&leaves[k..]))
+            }
+        }
+    }
+
+    fn audit_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+        let n = leaves.len();
+        if n <= 1 {
+            return Vec::new();
+        }
+        let k = split_point(n as u64) as usize;
+        if m < k {
+            let mut path = audit_path(m, &leaves[..k]);
+            path.push(root_of(&leaves[k..]));
+            path
+        } else {
+            let mut path = audit_path(m - k, &leaves[k..]);
+            path.push(root_of(&leaves[..k]));
+            path
+        }
+    }
+
+    fn event(n: u64) -> LedgerEvent {
+        LedgerEvent {
+            entity_id: n,
+            prime: 2,
+            msd_digits: Msd::from_int(1),
+            via_c: false,
+            centroid_digit: 0,
+            timestamp: 1_700_000_000_000 + n,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn every_leaf_verifies_for_all_tree_sizes() {
+        for size in 1..=9u64 {
+            let events = (0..size).map(event).collect::<Vec<_>>();
+            let leaves = events
+                .iter()
+                .map(|e| leaf_hash(e).unwrap())
+                .collect::<Vec<_>>();
+            let root = hex::encode(root_of(&leaves));
+            for (i, evt) in events.iter().enumerate() {
+                let proof = InclusionProof {
+                    lsn: i as u64,
+                    first_lsn: 0,
+                    tree_size: size,
+                    path: audit_path(i, &leaves).iter().map(hex::encode).collect(),
+                };
+                assert!(verify_proof(&root, evt, &proof), "size {} leaf {}", size, i);
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_event_fails() {
+        let events = (0..5).map(event).collect::<Vec<_>>();
+        let leaves = events
+            .iter()
+            .map(|e| leaf_hash(e).unwrap())
+            .collect::<Vec<_>>();
+        let root = hex::encode(root_of(&leaves));
+        let proof = InclusionProof {
+            lsn: 3,
+            first_lsn: 0,
+            tree_size: 5,
+            path: audit_path(3, &leaves).iter().map(hex::encode).collect(),
+        };
+        let mut forged = events[3].clone();
+        forged.msd_digits = Msd::from_int(2);
+        assert!(!verify_proof(&root, &forged, &proof));
+    }
+
+    #[test]
+    fn stored_nodes_match_the_log() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.merkle_root().unwrap(), hex::encode(root_of(&[])));
+        for (i, target) in [2u8, 4, 6, 1, 2, 4, 6].into_iter().enumerate() {
+            ledger
+                .anchor_batch(i as u64 % 3, &[(2, target), (5, target)])
+                .unwrap();
+            let events = ledger.log.read_all().unwrap();
+            let leaves = events
+                .iter()
+                .map(|e| leaf_hash(e).unwrap())
+                .collect::<Vec<_>>();
+            let root = ledger.merkle_root().unwrap();
+            assert_eq!(root, hex::encode(root_of(&leaves)));
+            for evt in &events {
+                let proof = ledger.prove_inclusion(evt.lsn).unwrap();
+                assert!(verify_proof(&root, evt, &proof), "lsn {}", evt.lsn);
+            }
+        }
+        let root = ledger.merkle_root().unwrap();
+        drop(ledger);
+
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.merkle_root().unwrap(), root);
+        let cf = ledger.cf(MERKLE_CF).unwrap();
+        ledger
+            .db
+            .delete_range_cf(cf, node_key(0, 0), node_key(u64::BITS, 0))
+            .unwrap();
+        ledger.db.delete(TREE_KEY).unwrap();
+        ledger.backfill_merkle().unwrap();
+        assert_eq!(ledger.merkle_root().unwrap(), root);
+    }
+}
@@ -0,0 +1,78 @@
+//! Free-form per-entity metadata (names, tenants, tags)
+//! Stored as JSON in the `metadata` column family. Metadata is not part of
+//! the event log or hash chain; deleting the entity removes it.
+
+use rocksdb::WriteBatch;
+use serde_json::Value;
+
+use crate::scan::RawItem;
+use crate::{Ledger, LedgerError};
+
+pub(crate) const METADATA_CF: &str = "metadata";
+
+pub(crate) type MetadataEntry = (u64, Value);
+
+pub(crate) fn decode_metadata(entity: u64, raw: &[u8]) -> Result<Value, LedgerError> {
+    serde_json::from_slice(raw)
+        .map_err(|e| LedgerError::Corruption(format!("metadata of entity {}: {}", entity, e)))
+}
+
+pub(crate) fn decode_metadata_entry(item: RawItem) -> Result<MetadataEntry, LedgerError> {
+    let (key, value) = item?;
+    let entity = key[..]
+        .try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| LedgerError::Corruption("malformed metadata key".to_string()))?;
+    Ok((entity, serde_json::from_slice(&value)?))
+}
+
+impl Ledger {
+    /// Attach `metadata` to `entity`, replacing what was there.
+    pub fn set_metadata(&self, entity: u64, metadata: &Value) -> Result<(), LedgerError> {
+        self.ensure_writable()?;
+        self.ensure_live(entity)?;
+        let cf = self.cf(METADATA_CF)?;
+        self.db
+            .put_cf(cf, entity.to_be_bytes(), serde_json::to_vec(metadata)?)
+            .map_err(LedgerError::from)
+    }
+
+    pub fn get_metadata(&self, entity: u64) -> Result<Option<Value>, LedgerError> {
+        let cf = self.cf(METADATA_CF)?;
+        self.db
+            .get_cf(cf, entity.to_be_bytes())?
+            .map(|raw| decode_metadata(entity, &raw))
+            .transpose()
+    }
+
+    pub(crate) fn stage_delete_metadata(
+        &self,
+        batch: &mut WriteBatch,
+        entity: u64,
+    ) -> Result<(), LedgerError> {
+        batch.delete_cf(self.cf(METADATA_CF)?, entity.to_be_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn metadata_round_trips_and_goes_with_the_entity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        assert_eq!(ledger.get_metadata(1).unwrap(), None);
+
+        let labels = json!({"name": "acme", "tenant": "t1", "tags": ["gold"]});
+        ledger.set_metadata(1, &labels).unwrap();
+        assert_eq!(ledger.get_metadata(1).unwrap(), Some(labels));
+
+        ledger.delete_entity(1).unwrap();
+        assert_eq!(ledger.get_metadata(1).unwrap(), None);
+        assert!(ledger.set_metadata(1, &json!({})).is_err());
+    }
+}
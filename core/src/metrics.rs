@@ -0,0 +1,338 @@
+//! Ledger metrics: write-path counters plus sampled RocksDB properties
+//! `Ledger::metrics()` returns a snapshot; the `prometheus` feature adds a
+//! collector exporting the same numbers.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Ledger, LedgerError};
+
+/// Counters updated on the write path.
+pub(crate) struct Metrics {
+    started: Instant,
+    batches: AtomicU64,
+    events: AtomicU64,
+    via_c: AtomicU64,
+    denied_transitions: AtomicU64,
+    commit_us_total: AtomicU64,
+    commit_us_max: AtomicU64,
+    max_batch_size: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            started: Instant::now(),
+            batches: AtomicU64::new(0),
+            events: AtomicU64::new(0),
+            via_c: AtomicU64::new(0),
+            denied_transitions: AtomicU64::new(0),
+            commit_us_total: AtomicU64::new(0),
+            commit_us_max: AtomicU64::new(0),
+            max_batch_size: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub(crate) fn record_commit(&self, events: u64, via_c: u64, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.events.fetch_add(events, Ordering::Relaxed);
+        self.via_c.fetch_add(via_c, Ordering::Relaxed);
+        self.commit_us_total.fetch_add(us, Ordering::Relaxed);
+        self.commit_us_max.fetch_max(us, Ordering::Relaxed);
+        self.max_batch_size.fetch_max(events, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_denied(&self) {
+        self.denied_transitions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, rocksdb: RocksDbStats) -> MetricsSnapshot {
+        let uptime = self.started.elapsed().as_secs_f64();
+        let batches = self.batches.load(Ordering::Relaxed);
+        let events = self.events.load(Ordering::Relaxed);
+        let commit_us_total = self.commit_us_total.load(Ordering::Relaxed);
+        let cache_hits = self.cache_hits.load(Ordering::Relaxed);
+        let cache_misses = self.cache_misses.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            uptime_secs: uptime,
+            batches,
+            events,
+            events_per_sec: if uptime > 0.0 {
+                events as f64 / uptime
+            } else {
+                0.0
+            },
+            mean_batch_size: ratio(events, batches),
+            max_batch_size: self.max_batch_size.load(Ordering::Relaxed),
+            via_c: self.via_c.load(Ordering::Relaxed),
+            denied_transitions: self.denied_transitions.load(Ordering::Relaxed),
+            commit_us_total,
+            commit_us_mean: ratio(commit_us_total, batches),
+            commit_us_max: self.commit_us_max.load(Ordering::Relaxed),
+            exponent_cache_hits: cache_hits,
+            exponent_cache_misses: cache_misses,
+            exponent_cache_hit_rate: ratio(cache_hits, cache_hits + cache_misses),
+            rocksdb,
+        }
+    }
+}
+
+fn ratio(num: u64, den: u64) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RocksDbStats {
+    /// Estimated keys across the factors and postings CFs.
+    pub estimate_num_keys: u64,
+    pub live_sst_bytes: u64,
+    pub memtable_bytes: u64,
+    pub pending_compaction_bytes: u64,
+    /// Non-zero while RocksDB is stalling writes.
+    pub write_stopped: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub uptime_secs: f64,
+    pub batches: u64,
+    pub events: u64,
+    pub events_per_sec: f64,
+    pub mean_batch_size: f64,
+    pub max_batch_size: u64,
+    pub via_c: u64,
+    pub denied_transitions: u64,
+    pub commit_us_total: u64,
+    pub commit_us_mean: f64,
+    pub commit_us_max: u64,
+    #[serde(default)]
+    pub exponent_cache_hits: u64,
+    #[serde(default)]
+    pub exponent_cache_misses: u64,
+    #[serde(default)]
+    pub exponent_cache_hit_rate: f64,
+    pub rocksdb: RocksDbStats,
+}
+
+pub(crate) fn rocksdb_stats(db: &rocksdb::DB) -> Result<RocksDbStats, LedgerError> {
+    let prop = |cf_name: &str, name: &str| -> Result<u64, LedgerError> {
+        let cf = db.cf_handle(cf_name).ok_or_else(|| {
+            LedgerError::Corruption(format!("missing column family: {}", cf_name))
+        })?;
+        db.property_int_value_cf(cf, name)
+            .map(|v| v.unwrap_or(0))
+            .map_err(LedgerError::from)
+    };
+    let sum = |name: &str| -> Result<u64, LedgerError> {
+        ["default", "factors", "postings"]
+            .iter()
+            .try_fold(0, |acc, cf| Ok(acc + prop(cf, name)?))
+    };
+    Ok(RocksDbStats {
+        estimate_num_keys: prop("factors", "rocksdb.estimate-num-keys")?
+            + prop("postings", "rocksdb.estimate-num-keys")?,
+        live_sst_bytes: sum("rocksdb.live-sst-files-size")?,
+        memtable_bytes: sum("rocksdb.cur-size-all-mem-tables")?,
+        pending_compaction_bytes: sum("rocksdb.estimate-pending-compaction-bytes")?,
+        write_stopped: prop("default", "rocksdb.is-write-stopped")?,
+    })
+}
+
+impl Ledger {
+    /// Point-in-time metrics since the ledger was opened.
+    pub fn metrics(&self) -> Result<MetricsSnapshot, LedgerError> {
+        Ok(self.metrics.snapshot(rocksdb_stats(&self.db)?))
+    }
+}
+
+#[cfg(feature = "prometheus")]
+mod collector {
+    use std::sync::{Arc, Weak};
+
+    use prometheus::core::{Collector, Desc};
+    use prometheus::proto::MetricFamily;
+    use prometheus::{Gauge, IntCounter, IntGauge, Opts};
+
+    use super::{rocksdb_stats, Metrics, RocksDbStats};
+    use crate::{Ledger, LedgerError};
+
+    /// Prometheus view of a ledger's metrics. Holds the DB weakly, so it
+    /// never keeps a closed ledger alive.
+    pub struct LedgerCollector {
+        metrics: Arc<Metrics>,
+        db: Weak<rocksdb::DB>,
+        batches: IntCounter,
+        events: IntCounter,
+        via_c: IntCounter,
+        denied: IntCounter,
+        commit_seconds: Gauge,
+        max_batch_size: IntGauge,
+        cache_hits: IntCounter,
+        cache_misses: IntCounter,
+        rocks_keys: IntGauge,
+        rocks_sst: IntGauge,
+        rocks_memtable: IntGauge,
+        rocks_pending: IntGauge,
+        rocks_stopped: IntGauge,
+    }
+
+    fn counter(name: &str, help: &str) -> prometheus::Result<IntCounter> {
+        IntCounter::with_opts(Opts::new(name, help).namespace("ledger"))
+    }
+
+    fn gauge(name: &str, help: &str) -> prometheus::Result<IntGauge> {
+        IntGauge::with_opts(Opts::new(name, help).namespace("ledger"))
+    }
+
+    impl LedgerCollector {
+        fn new(ledger: &Ledger) -> prometheus::Result<Self> {
+            Ok(LedgerCollector {
+                metrics: ledger.metrics.clone(),
+                db: Arc::downgrade(&ledger.db),
+                batches: counter("batches_total", "Committed batches")?,
+                events: counter("events_total", "Committed events")?,
+                via_c: counter("via_c_total", "Events routed via the centroid")?,
+                denied: counter("denied_transitions_total", "Rejected transitions")?,
+                commit_seconds: Gauge::with_opts(
+                    Opts::new("commit_seconds_total", "Time spent committing batches")
+                        .namespace("ledger"),
+                )?,
+                max_batch_size: gauge("max_batch_size", "Largest committed batch")?,
+                cache_hits: counter("exponent_cache_hits_total", "Exponent cache hits")?,
+                cache_misses: counter("exponent_cache_misses_total", "Exponent cache misses")?,
+                rocks_keys: gauge("rocksdb_estimate_num_keys", "Estimated keys")?,
+                rocks_sst: gauge("rocksdb_live_sst_bytes", "Live SST bytes")?,
+                rocks_memtable: gauge("rocksdb_memtable_bytes", "Memtable bytes")?,
+                rocks_pending: gauge(
+                    "rocksdb_pending_compaction_bytes",
+                    "Estimated bytes awaiting compaction",
+                )?,
+                rocks_stopped: gauge("rocksdb_write_stopped", "Writes stalled by RocksDB")?,
+            })
+        }
+
+        fn parts(&self) -> [&dyn Collector; 13] {
+            [
+                &self.batches,
+                &self.events,
+                &self.via_c,
+                &self.denied,
+                &self.commit_seconds,
+                &self.max_batch_size,
+                &self.cache_hits,
+                &self.cache_misses,
+                &self.rocks_keys,
+                &self.rocks_sst,
+                &self.rocks_memtable,
+                &self.rocks_pending,
+                &self.rocks_stopped,
+            ]
+        }
+    }
+
+    impl Collector for LedgerCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            self.parts().into_iter().flat_map(|c| c.desc()).collect()
+        }
+
+        fn collect(&self) -> Vec<MetricFamily> {
+            let rocks = self
+                .db
+                .upgrade()
+                .and_then(|db| rocksdb_stats(&db).ok())
+                .unwrap_or_default();
+            let snap = self.metrics.snapshot(RocksDbStats::default());
+            for (c, v) in [
+                (&self.batches, snap.batches),
+                (&self.events, snap.events),
+                (&self.via_c, snap.via_c),
+                (&self.denied, snap.denied_transitions),
+                (&self.cache_hits, snap.exponent_cache_hits),
+                (&self.cache_misses, snap.exponent_cache_misses),
+            ] {
+                c.reset();
+                c.inc_by(v);
+            }
+            self.commit_seconds.set(snap.commit_us_total as f64 / 1e6);
+            for (g, v) in [
+                (&self.max_batch_size, snap.max_batch_size),
+                (&self.rocks_keys, rocks.estimate_num_keys),
+                (&self.rocks_sst, rocks.live_sst_bytes),
+                (&self.rocks_memtable, rocks.memtable_bytes),
+                (&self.rocks_pending, rocks.pending_compaction_bytes),
+                (&self.rocks_stopped, rocks.write_stopped),
+            ] {
+                g.set(v as i64);
+            }
+            self.parts().into_iter().flat_map(|c| c.collect()).collect()
+        }
+    }
+
+    impl Ledger {
+        /// Register this ledger's collectors with `registry`.
+        pub fn register_metrics(&self, registry: &prometheus::Registry) -> Result<(), LedgerError> {
+            let internal = |e: prometheus::Error| LedgerError::Internal(e.to_string());
+            let collector = LedgerCollector::new(self).map_err(internal)?;
+            registry.register(Box::new(collector)).map_err(internal)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_batches_via_c_and_denials() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(1, &[(3, 2)]).unwrap();
+        assert!(ledger.anchor_batch(1, &[(3, 4)]).is_err());
+
+        let m = ledger.metrics().unwrap();
+        assert_eq!(m.batches, 2);
+        assert_eq!(m.events, 3);
+        assert_eq!(m.max_batch_size, 2);
+        assert_eq!(m.denied_transitions, 1);
+        assert!(m.rocksdb.memtable_bytes > 0);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn registers_with_prometheus() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        let registry = prometheus::Registry::new();
+        ledger.register_metrics(&registry).unwrap();
+        let families = registry.gather();
+        let events = families
+            .iter()
+            .find(|f| f.get_name() == "ledger_events_total")
+            .unwrap();
+        assert_eq!(events.get_metric()[0].get_counter().get_value(), 1.0);
+    }
+}
@@ -0,0 +1,143 @@
+//! On-disk format versions, upgraded in place when a ledger is opened
+//! 1: "<entity>:<prime>" string keys (implicit: no marker stored)
+//! 2: fixed-width big-endian binary keys
+//! 3: versioned zigzag-varint exponent values (previously decimal strings)
+//! 4: per-entity centroid digits in the `centroids` column family
+//! 5: per-(entity, prime) change history in the `history` column family
+//! 6: packed quaternion state per entity in the `quat_state` column family
+//! 7: ledger statistics counters under `stats/`
+//! 8: centroid flip counts and last cause alongside each digit
+//! 9: Merkle tree nodes in the `merkle` column family
+
+use rocksdb::{IteratorMode, WriteBatch};
+
+use crate::keys;
+use crate::{Ledger, LedgerError};
+
+pub const FORMAT_VERSION: u8 = 9;
+const FORMAT_KEY: &[u8] = b"format_version";
+/// Rewrites are flushed in chunks so huge ledgers don't build one giant batch.
+pub(crate) const CHUNK: usize = 10_000;
+
+impl Ledger {
+    pub(crate) fn migrate_format(&self) -> Result<(), LedgerError> {
+        let version = self.format_version()?;
+        if version > FORMAT_VERSION {
+            return Err(LedgerError::Corruption(format!(
+                "ledger format {} is newer than supported format {}",
+                version, FORMAT_VERSION
+            )));
+        }
+        if version < 2 {
+            self.rewrite_keys("factors", |a, b| keys::factor_key(a, b as u32))?;
+            self.rewrite_keys("postings", |a, b| keys::posting_key(a as u32, b))?;
+        }
+        if version < 3 {
+            self.rewrite_values("factors")?;
+            self.rewrite_values("postings")?;
+        }
+        // Format 8 only widened the values format 4 introduced.
+        if version < 8 {
+            self.backfill_centroids()?;
+        }
+        if version < 5 {
+            self.backfill_history()?;
+        }
+        if version < 6 {
+            self.backfill_quat_state()?;
+        }
+        if version < 7 {
+            self.backfill_stats()?;
+        }
+        if version < 9 {
+            self.backfill_merkle()?;
+        }
+        if version != FORMAT_VERSION {
+            self.db.put(FORMAT_KEY, [FORMAT_VERSION])?;
+        }
+        Ok(())
+    }
+
+    /// Readers cannot upgrade in place, so they require the current format.
+    pub(crate) fn check_format(&self) -> Result<(), LedgerError> {
+        match self.format_version()? {
+            FORMAT_VERSION => Ok(()),
+            version => Err(LedgerError::InvalidArgument(format!(
+                "ledger format {} differs from supported format {}; open it read-write first",
+                version, FORMAT_VERSION
+            ))),
+        }
+    }
+
+    fn format_version(&self) -> Result<u8, LedgerError> {
+        match self.db.get(FORMAT_KEY)? {
+            Some(v) => v
+                .first()
+                .copied()
+                .ok_or_else(|| LedgerError::Corruption("empty format marker".to_string())),
+            None => Ok(1),
+        }
+    }
+
+    fn rewrite_keys(
+        &self,
+        cf_name: &str,
+        encode: impl Fn(u64, u64) -> [u8; keys::KEY_LEN],
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(cf_name)?;
+        let mut batch = WriteBatch::default();
+        for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+            let (key, value) = item?;
+            if let Some((a, b)) = keys::decode_legacy_key(&key) {
+                batch.delete_cf(cf, &key);
+                batch.put_cf(cf, encode(a, b), &value);
+            }
+            if batch.len() >= CHUNK {
+                self.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+
+    fn rewrite_values(&self, cf_name: &str) -> Result<(), LedgerError> {
+        let cf = self.cf(cf_name)?;
+        let mut batch = WriteBatch::default();
+        for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+            let (key, value) = item?;
+            if let Some(exp) = keys::decode_legacy_exponent(&value) {
+                batch.put_cf(cf, &key, keys::encode_exponent(exp));
+            }
+            if batch.len() >= CHUNK {
+                self.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_keys_and_values_are_rewritten_on_open() {
+        let tmp = tempfile::tempdir().unwrap();
+        {
+            let ledger = Ledger::new(tmp.path()).unwrap();
+            let factors = ledger.cf("factors").unwrap();
+            let postings = ledger.cf("postings").unwrap();
+            ledger.db.put_cf(factors, b"12:5", b"2").unwrap();
+            ledger.db.put_cf(postings, b"5:12", b"2").unwrap();
+            ledger.db.delete(FORMAT_KEY).unwrap();
+        }
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.current_exponent(12, 5).unwrap(), Some(2));
+        let postings = ledger.cf("postings").unwrap();
+        assert!(ledger
+            .db
+            .get_cf(postings, keys::posting_key(5, 12))
+            .unwrap()
+            .is_some());
+        assert_eq!(ledger.format_version().unwrap(), FORMAT_VERSION);
+    }
+}
@@ -1,72 +1,706 @@
-//! Modified-Signed-Digit radix-4 (digits ∈ {-2,-1,0,1,2})
-use rulinalg::vector::Vector;
+//! Signed-digit integers in radix 2, 4 or 8
+//! Radix `R` uses digits in -R/2..=R/2; [`Msd`], the event encoding, is
+//! radix 4 (Modified-Signed-Digit, digits ∈ {-2,-1,0,1,2}). Radix 2 is kept
+//! in non-adjacent form (NAF): no two neighbouring digits are both nonzero.
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use nalgebra::DVector;
+#[cfg(feature = "ndarray")]
+use ndarray::Array1;
+#[cfg(feature = "bigint")]
+use num_bigint::{BigInt, Sign};
+use pyo3::prelude::*;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub type Digit = i8;
-pub struct Msd(Vec<Digit>);
 
-impl Msd {
+/// Little-endian digits in radix `R`, normalized: every digit in range, no
+/// trailing zeros, and NAF when `R` is 2. Radixes 4 and 8 still leave some
+/// values two digit strings (2 is `[2]` or `[-2,1]`), so equality, ordering
+/// and hashing go by value.
+#[derive(Debug, Clone)]
+pub struct SignedDigit<const R: u32>(Vec<Digit>);
+
+/// Radix-4 digits, as stored in `msd_digits`.
+pub type Msd = SignedDigit<4>;
+
+/// Why [`SignedDigit::try_from_digits`] or [`SignedDigit::from_bytes`]
+/// refused a digit string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MsdError {
+    /// No digits at all; zero is `[0]`.
+    Empty,
+    DigitOutOfRange {
+        radix: u32,
+        index: usize,
+        digit: Digit,
+    },
+    /// Digits in range, but not as normalizing would leave them: a
+    /// trailing zero, or adjacent nonzero digits in radix 2.
+    NotNormalized { radix: u32, digits: Vec<Digit> },
+    /// Text that is not of the form printed by `Display`.
+    Parse(String),
+    /// Bytes that don't hold the digit count they start with.
+    Truncated,
+}
+
+impl fmt::Display for MsdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsdError::Empty => write!(f, "no MSD digits"),
+            MsdError::DigitOutOfRange {
+                radix,
+                index,
+                digit,
+            } => write!(
+                f,
+                "digit {} at position {} is outside the radix-{} digit set",
+                digit, index, radix
+            ),
+            MsdError::NotNormalized { radix, digits } => {
+                write!(f, "radix-{} digits {:?} are not normalized", radix, digits)
+            }
+            MsdError::Parse(text) => write!(f, "unparseable MSD {:?}", text),
+            MsdError::Truncated => write!(f, "packed MSD digits do not match their count"),
+        }
+    }
+}
+
+impl std::error::Error for MsdError {}
+
+impl<const R: u32> SignedDigit<R> {
+    /// Digits must fit the 4-bit nibbles of [`SignedDigit::to_bytes`].
+    const RADIX_OK: () = assert!(
+        R == 2 || R == 4 || R == 8,
+        "signed-digit radix must be 2, 4 or 8"
+    );
+
     pub fn from_int(n: i32) -> Self {
-        if n == 0 {
-            return Msd(vec![0]);
+        Self::from_i128(n.into())
+    }
+
+    pub fn from_i64(n: i64) -> Self {
+        Self::from_i128(n.into())
+    }
+
+    /// `from_int` of each of `ns`, in order. Works through blocks of eight
+    /// values one digit position at a time, without data-dependent branches,
+    /// so the per-position step compiles to vector instructions.
+    pub fn from_ints_batch(ns: &[i32]) -> Vec<Self> {
+        let () = Self::RADIX_OK;
+        let bits = R.ilog2();
+        // Enough for 2^31 plus a final carry digit.
+        let positions = 32usize.div_ceil(bits as usize) + 1;
+        let mut out = Vec::with_capacity(ns.len());
+        for block in ns.chunks(LANES) {
+            let mut mag = [0u32; LANES];
+            for (m, n) in mag.iter_mut().zip(block) {
+                *m = n.unsigned_abs();
+            }
+            let mut rows = [[0 as Digit; LANES]; MAX_POSITIONS];
+            for row in &mut rows[..positions] {
+                for (d, m) in row.iter_mut().zip(&mut mag) {
+                    let digit = lane_digit::<R>(*m);
+                    *d = digit as Digit;
+                    *m = (*m >> bits) + u32::from(digit < 0);
+                }
+            }
+            for (lane, &n) in block.iter().enumerate() {
+                let sign = if n < 0 { -1 } else { 1 };
+                let len = rows[..positions]
+                    .iter()
+                    .rposition(|row| row[lane] != 0)
+                    .map_or(1, |i| i + 1);
+                let digits = rows[..len].iter().map(|row| sign * row[lane]).collect();
+                out.push(SignedDigit(digits));
+            }
         }
+        out
+    }
+
+    pub fn from_i128(n: i128) -> Self {
         let mut out = Vec::with_capacity(8);
-        let mut m = n.abs();
+        let mut m = n.unsigned_abs();
         while m != 0 {
-            let rem = (m & 3) as i8;
-            m >>= 2;
-            let digit = if rem > 2 { rem - 4 } else { rem };
-            out.push(digit);
-            if rem > 2 {
-                m += 1;
-            }
+            out.push((m % u128::from(R)) as Digit);
+            m /= u128::from(R);
         }
-        if n < 0 {
-            out.iter_mut().for_each(|d| *d = -*d);
+        Self::from_magnitude(out, n < 0)
+    }
+
+    /// Rebuild from little-endian digits (e.g. read back from the event log).
+    pub fn from_digits(digits: Vec<Digit>) -> Self {
+        let () = Self::RADIX_OK;
+        SignedDigit(normalize::<R>(digits))
+    }
+
+    /// Plain base-`R` digits of a magnitude, then signed and normalized.
+    fn from_magnitude(mut digits: Vec<Digit>, negative: bool) -> Self {
+        if negative {
+            digits.iter_mut().for_each(|d| *d = -*d);
         }
-        Msd(normalize(out))
+        Self::from_digits(digits)
     }
 
-    #[allow(dead_code)]
+    /// The value as an `i32`; panics if it doesn't fit, which values built
+    /// from `i32`s never do, nor do events read back from the log. See
+    /// [`SignedDigit::to_i32`] for digits from elsewhere.
     pub fn to_int(&self) -> i32 {
+        self.to_i32().expect("MSD value out of i32 range")
+    }
+
+    /// The value, or `None` if it overflows an `i32`.
+    pub fn to_i32(&self) -> Option<i32> {
+        self.to_i128().and_then(|n| i32::try_from(n).ok())
+    }
+
+    /// The value, or `None` if it overflows an `i64`.
+    pub fn to_i64(&self) -> Option<i64> {
+        self.to_i128().and_then(|n| i64::try_from(n).ok())
+    }
+
+    /// The value, or `None` if it overflows an `i128`.
+    pub fn to_i128(&self) -> Option<i128> {
+        // Evaluated as high·R^SPLIT + low with both halves carrying the sign
+        // of the value, so in-range values never overflow on the way; a
+        // plain Horner pass would, e.g. for i128::MAX = 2·4^63 − 1.
+        let split = (64 / R.ilog2()) as usize;
+        let eval = |digits: &[Digit]| {
+            digits.iter().rev().try_fold(0i128, |acc, &d| {
+                acc.checked_mul(R.into())?.checked_add(d.into())
+            })
+        };
+        let base = i128::from(R).pow(split as u32);
+        let (low, high) = self.0.split_at(self.0.len().min(split));
+        let (mut low, mut high) = (eval(low)?, eval(high)?);
+        if high > 0 && low < 0 {
+            high -= 1;
+            low += base;
+        } else if high < 0 && low > 0 {
+            high += 1;
+            low -= base;
+        }
+        high.checked_mul(base)?.checked_add(low)
+    }
+
+    /// Digits of any integer, however large.
+    #[cfg(feature = "bigint")]
+    pub fn from_bigint(n: &BigInt) -> Self {
+        let digits = n.magnitude().to_radix_le(R);
+        let digits = digits.into_iter().map(|d| d as Digit).collect();
+        Self::from_magnitude(digits, n.sign() == Sign::Minus)
+    }
+
+    #[cfg(feature = "bigint")]
+    pub fn to_bigint(&self) -> BigInt {
         self.0
+            .iter()
+            .rev()
+            .fold(BigInt::default(), |acc, &d| acc * R + BigInt::from(d))
+    }
+
+    /// The digit count as a LEB128 varint, then the digits two per byte as
+    /// 4-bit two's complement, low nibble first; an odd count leaves the
+    /// last high nibble zero. The form the event log stores; counts below
+    /// 128 take a single byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + self.0.len().div_ceil(2));
+        let mut n = self.0.len();
+        while n >= 0x80 {
+            out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+        out.extend(
+            self.0
+                .chunks(2)
+                .map(|pair| pair.iter().rev().fold(0, |b, &d| b << 4 | nibble(d))),
+        );
+        out
+    }
+
+    /// Inverse of [`SignedDigit::to_bytes`], rejecting any encoding it
+    /// would not have produced.
+    pub fn from_bytes(raw: &[u8]) -> Result<Self, MsdError> {
+        let (len, packed) = read_count(raw).ok_or(MsdError::Truncated)?;
+        if packed.len() != len.div_ceil(2)
+            || (len % 2 == 1 && packed.last().is_some_and(|b| b >> 4 != 0))
+        {
+            return Err(MsdError::Truncated);
+        }
+        let digits = packed
+            .iter()
+            .flat_map(|&b| [b & 0x0f, b >> 4])
+            .take(len)
+            .map(sign_extend)
+            .collect();
+        Self::try_from_digits(digits)
+    }
+
+    /// Validate digits read from outside, e.g. a log being replayed: fails
+    /// unless they are exactly what normalizing would produce. Use
+    /// [`SignedDigit::from_digits`] to normalize them instead.
+    pub fn try_from_digits(digits: Vec<Digit>) -> Result<Self, MsdError> {
+        let half = (R / 2) as Digit;
+        if digits.is_empty() {
+            return Err(MsdError::Empty);
+        }
+        if let Some((index, &digit)) = digits
             .iter()
             .enumerate()
-            .map(|(i, &d)| d as i32 * 4_i32.pow(i as u32))
-            .sum()
+            .find(|(_, d)| !(-half..=half).contains(*d))
+        {
+            return Err(MsdError::DigitOutOfRange {
+                radix: R,
+                index,
+                digit,
+            });
+        }
+        if normalize::<R>(digits.clone()) != digits {
+            return Err(MsdError::NotNormalized { radix: R, digits });
+        }
+        Ok(SignedDigit(digits))
+    }
+
+    /// Nonzero digits: the terms a signed-digit operation actually costs.
+    pub fn weight(&self) -> usize {
+        self.0.iter().filter(|&&d| d != 0).count()
+    }
+
+    /// Digit positions up to the most significant nonzero one; 0 for zero.
+    pub fn span(&self) -> usize {
+        self.0.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1)
+    }
+
+    /// Digits from the least significant up.
+    pub fn digits_lsb(&self) -> impl DoubleEndedIterator<Item = Digit> + ExactSizeIterator + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Digits from the most significant down.
+    pub fn digits_msb(&self) -> impl DoubleEndedIterator<Item = Digit> + ExactSizeIterator + '_ {
+        self.0.iter().rev().copied()
     }
 
-    #[allow(dead_code)]
     pub fn as_slice(&self) -> &[Digit] {
         &self.0
     }
 
-    pub fn as_vector(&self) -> Vector<Digit> {
-        Vector::new(self.0.clone())
+    pub fn to_vec(&self) -> Vec<Digit> {
+        self.0.clone()
+    }
+}
+
+/// Least significant digit first, as stored, with the radix as a
+/// subscript: `[-1,-2]₄` is -9.
+impl<const R: u32> fmt::Display for SignedDigit<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, d) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", d)?;
+        }
+        write!(f, "]{}", subscript(R))
+    }
+}
+
+/// Parses the `Display` form, spaces allowed; the subscript may be left
+/// off but must otherwise match `R`, and the digits must be normalized.
+impl<const R: u32> FromStr for SignedDigit<R> {
+    type Err = MsdError;
+
+    fn from_str(s: &str) -> Result<Self, MsdError> {
+        let unparseable = || MsdError::Parse(s.to_string());
+        let body = s.trim().strip_prefix('[').ok_or_else(unparseable)?;
+        let (list, radix) = body.rsplit_once(']').ok_or_else(unparseable)?;
+        if !radix.is_empty() && radix.chars().ne([subscript(R)]) {
+            return Err(unparseable());
+        }
+        let digits = list
+            .split(',')
+            .map(|d| d.trim().parse::<Digit>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| unparseable())?;
+        SignedDigit::try_from_digits(digits)
+    }
+}
+
+/// `R` as a subscript numeral; radixes are single digits.
+fn subscript(radix: u32) -> char {
+    char::from_u32('₀' as u32 + radix).unwrap_or('?')
+}
+
+impl<const R: u32> From<&SignedDigit<R>> for DVector<Digit> {
+    fn from(msd: &SignedDigit<R>) -> Self {
+        DVector::from_column_slice(&msd.0)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<const R: u32> From<&SignedDigit<R>> for Array1<Digit> {
+    fn from(msd: &SignedDigit<R>) -> Self {
+        Array1::from(msd.to_vec())
+    }
+}
+
+/// Compares from the most significant digit down, stopping once the digits
+/// left can no longer outweigh the difference so far.
+impl<const R: u32> Ord for SignedDigit<R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let digit = |v: &[Digit], i: usize| i32::from(v.get(i).copied().unwrap_or(0));
+        // Digits below position i differ by less than 2·R^i in value.
+        let mut diff = 0i32;
+        for i in (0..self.0.len().max(other.0.len())).rev() {
+            diff = diff * R as i32 + digit(&self.0, i) - digit(&other.0, i);
+            if diff.abs() >= 2 {
+                break;
+            }
+        }
+        diff.cmp(&0)
+    }
+}
+
+impl<const R: u32> PartialOrd for SignedDigit<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const R: u32> PartialEq for SignedDigit<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<const R: u32> Eq for SignedDigit<R> {}
+
+/// Hashes the sign and plain base-`R` digits of the magnitude, which,
+/// unlike the signed digits, are unique to the value.
+impl<const R: u32> Hash for SignedDigit<R> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The top digit of a normalized string carries the value's sign.
+        let sign = self.0.last().map_or(0, |d| d.signum());
+        let mut magnitude = Vec::with_capacity(self.0.len());
+        let mut borrow = 0i16;
+        for &d in &self.0 {
+            let x = i16::from(d * sign) + borrow;
+            let u = x.rem_euclid(R as i16);
+            borrow = (x - u) / R as i16;
+            magnitude.push(u as u8);
+        }
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        sign.hash(state);
+        magnitude.hash(state);
     }
 }
 
-fn normalize(mut v: Vec<Digit>) -> Vec<Digit> {
-    let mut carry = 0i8;
-    for d in v.iter_mut() {
-        let sum = *d + carry;
-        if sum > 2 {
-            *d = sum - 4;
-            carry = 1;
-        } else if sum < -2 {
-            *d = sum + 4;
-            carry = -1;
+impl<const R: u32> Default for SignedDigit<R> {
+    fn default() -> Self {
+        SignedDigit(vec![0])
+    }
+}
+
+/// Python sees the digit list.
+impl<const R: u32> IntoPy<PyObject> for SignedDigit<R> {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        self.0.into_py(py)
+    }
+}
+
+/// The digit list in human-readable formats, so event JSON (and the hashes
+/// taken over it) keep their shape; [`SignedDigit::to_bytes`] otherwise.
+impl<const R: u32> Serialize for SignedDigit<R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            self.0.serialize(serializer)
         } else {
-            *d = sum;
-            carry = 0;
+            serializer.serialize_bytes(&self.to_bytes())
         }
     }
-    if carry != 0 {
-        v.push(carry);
+}
+
+/// Either form is accepted, and must be canonical.
+impl<'de, const R: u32> Deserialize<'de> for SignedDigit<R> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DigitsVisitor<const R: u32>;
+
+        impl<'de, const R: u32> Visitor<'de> for DigitsVisitor<R> {
+            type Value = SignedDigit<R>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "radix-{} signed digits", R)
+            }
+
+            fn visit_bytes<E: de::Error>(self, raw: &[u8]) -> Result<Self::Value, E> {
+                SignedDigit::from_bytes(raw).map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut digits = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(d) = seq.next_element()? {
+                    digits.push(d);
+                }
+                SignedDigit::try_from_digits(digits).map_err(de::Error::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_seq(DigitsVisitor)
+        } else {
+            deserializer.deserialize_bytes(DigitsVisitor)
+        }
     }
-    while v.len() > 1 && v.last() == Some(&0) {
-        v.pop();
+}
+
+/// Digit-wise `a + sign * b`, each position within twice the digit range
+/// before normalizing.
+fn add_digits<const R: u32>(a: &[Digit], b: &[Digit], sign: Digit) -> SignedDigit<R> {
+    let digit = |v: &[Digit], i: usize| v.get(i).copied().unwrap_or(0);
+    let sum = (0..a.len().max(b.len()))
+        .map(|i| digit(a, i) + sign * digit(b, i))
+        .collect();
+    SignedDigit::from_digits(sum)
+}
+
+impl<const R: u32> Add for &SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn add(self, rhs: &SignedDigit<R>) -> SignedDigit<R> {
+        add_digits(&self.0, &rhs.0, 1)
+    }
+}
+
+impl<const R: u32> Sub for &SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn sub(self, rhs: &SignedDigit<R>) -> SignedDigit<R> {
+        add_digits(&self.0, &rhs.0, -1)
+    }
+}
+
+impl<const R: u32> Add for SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn add(self, rhs: SignedDigit<R>) -> SignedDigit<R> {
+        &self + &rhs
+    }
+}
+
+impl<const R: u32> Sub for SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn sub(self, rhs: SignedDigit<R>) -> SignedDigit<R> {
+        &self - &rhs
+    }
+}
+
+/// Shift-and-add: one partial product per digit of `rhs`, each `self`
+/// scaled by a single digit and shifted left by that digit's position.
+impl<const R: u32> Mul for &SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn mul(self, rhs: &SignedDigit<R>) -> SignedDigit<R> {
+        let mut product = SignedDigit(vec![0]);
+        for (shift, &d) in rhs.0.iter().enumerate() {
+            if d == 0 {
+                continue;
+            }
+            let mut partial = vec![0; shift];
+            partial.extend(self.0.iter().map(|&e| e * d));
+            product += &SignedDigit::from_digits(partial);
+        }
+        product
+    }
+}
+
+impl<const R: u32> Mul for SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn mul(self, rhs: SignedDigit<R>) -> SignedDigit<R> {
+        &self * &rhs
+    }
+}
+
+impl<const R: u32> AddAssign<&SignedDigit<R>> for SignedDigit<R> {
+    fn add_assign(&mut self, rhs: &SignedDigit<R>) {
+        *self = &*self + rhs;
+    }
+}
+
+impl<const R: u32> SubAssign<&SignedDigit<R>> for SignedDigit<R> {
+    fn sub_assign(&mut self, rhs: &SignedDigit<R>) {
+        *self = &*self - rhs;
+    }
+}
+
+impl<const R: u32> Neg for SignedDigit<R> {
+    type Output = SignedDigit<R>;
+
+    fn neg(mut self) -> SignedDigit<R> {
+        self.0.iter_mut().for_each(|d| *d = -*d);
+        self
+    }
+}
+
+/// The leading varint of [`SignedDigit::to_bytes`] and the bytes after it;
+/// `None` if it is cut short, padded or absurdly large.
+fn read_count(raw: &[u8]) -> Option<(usize, &[u8])> {
+    let mut n = 0usize;
+    for (i, &b) in raw.iter().enumerate().take(4) {
+        n |= usize::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            // A zero high byte means a longer encoding than needed.
+            return (i == 0 || b != 0).then(|| (n, &raw[i + 1..]));
+        }
+    }
+    None
+}
+
+fn nibble(d: Digit) -> u8 {
+    d as u8 & 0x0f
+}
+
+/// Shift the nibble into the top of an i8 and back to sign-extend it.
+fn sign_extend(n: u8) -> Digit {
+    ((n << 4) as i8) >> 4
+}
+
+/// Values converted side by side in [`SignedDigit::from_ints_batch`].
+const LANES: usize = 8;
+
+/// Digit positions of an `i32` in the smallest radix, NAF's 32 + 1.
+const MAX_POSITIONS: usize = 33;
+
+/// Lowest digit of the magnitude `m`, as [`normalize`] would choose it;
+/// branch-free so a block of lanes vectorizes.
+fn lane_digit<const R: u32>(m: u32) -> i32 {
+    if R == 2 {
+        let r = m & 3;
+        i32::from(r == 1) - i32::from(r == 3)
+    } else {
+        let r = (m & (R - 1)) as i32;
+        r - R as i32 * i32::from(r > R as i32 / 2)
+    }
+}
+
+/// The digit [`normalize`] leaves at a position holding `x` (carry
+/// included), given the raw digit above it: `x` carried into -R/2..=R/2, or
+/// for radix 2 the ±1 that leaves the rest a multiple of 4, so that the next
+/// digit comes out zero (NAF).
+fn settle_digit<const R: u32>(x: i16, next: i16) -> i16 {
+    let (radix, half) = (R as i16, R as i16 / 2);
+    if R == 2 {
+        match (x + 2 * next).rem_euclid(4) {
+            1 => 1,
+            3 => -1,
+            _ => 0,
+        }
+    } else if x > half {
+        x - (x + half - 1) / radix * radix
+    } else if x < -half {
+        x + (half - 1 - x) / radix * radix
+    } else {
+        x
+    }
+}
+
+/// Carry digits of any size into range, least significant first, and drop
+/// trailing zeros.
+fn normalize<const R: u32>(v: Vec<Digit>) -> Vec<Digit> {
+    let mut builder = SignedDigitBuilder::<R>::with_capacity(v.len() + 1);
+    builder.extend(v);
+    builder.finish_digits()
+}
+
+/// Builds a [`SignedDigit`] from digits of any size pushed least significant
+/// first, normalizing as they arrive: each digit is settled as soon as the
+/// carry into it is known, or in radix 2 once the digit above it is.
+#[derive(Debug, Clone, Default)]
+pub struct SignedDigitBuilder<const R: u32> {
+    digits: Vec<Digit>,
+    carry: i16,
+    /// Radix 2: the newest position, carry included, awaiting the next push.
+    held: Option<i16>,
+}
+
+pub type MsdBuilder = SignedDigitBuilder<4>;
+
+impl<const R: u32> SignedDigitBuilder<R> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(digits: usize) -> Self {
+        SignedDigitBuilder {
+            digits: Vec::with_capacity(digits),
+            ..Self::default()
+        }
+    }
+
+    /// Add the next more significant digit.
+    pub fn push(&mut self, digit: Digit) {
+        let digit = i16::from(digit);
+        if R == 2 {
+            if let Some(x) = self.held.take() {
+                self.settle(x, digit);
+            }
+            self.held = Some(digit + std::mem::take(&mut self.carry));
+        } else {
+            self.settle(digit + self.carry, 0);
+        }
+    }
+
+    fn settle(&mut self, x: i16, next: i16) {
+        let digit = settle_digit::<R>(x, next);
+        self.digits.push(digit as Digit);
+        self.carry = (x - digit) / R as i16;
+    }
+
+    pub fn finish(self) -> SignedDigit<R> {
+        let () = SignedDigit::<R>::RADIX_OK;
+        SignedDigit(self.finish_digits())
+    }
+
+    fn finish_digits(mut self) -> Vec<Digit> {
+        if let Some(x) = self.held.take() {
+            self.settle(x, 0);
+        }
+        while self.carry != 0 {
+            self.settle(self.carry, 0);
+        }
+        let mut v = self.digits;
+        while v.len() > 1 && v.last() == Some(&0) {
+            v.pop();
+        }
+        if v.is_empty() {
+            v.push(0);
+        }
+        v
+    }
+}
+
+impl<const R: u32> Extend<Digit> for SignedDigitBuilder<R> {
+    fn extend<I: IntoIterator<Item = Digit>>(&mut self, digits: I) {
+        digits.into_iter().for_each(|d| self.push(d));
+    }
+}
+
+/// Normalizes digits collected least significant first.
+impl<const R: u32> FromIterator<Digit> for SignedDigit<R> {
+    fn from_iter<I: IntoIterator<Item = Digit>>(digits: I) -> Self {
+        let mut builder = SignedDigitBuilder::new();
+        builder.extend(digits);
+        builder.finish()
     }
-    v
 }
 
 #[cfg(test)]
@@ -80,4 +714,299 @@ mod tests {
             assert_eq!(msd.to_int(), n);
         }
     }
+
+    #[test]
+    fn wide_integers_round_trip() {
+        for n in [i32::MIN, i32::MAX] {
+            assert_eq!(Msd::from_int(n).to_int(), n);
+        }
+        for n in [i64::MIN, i64::MAX, 1 << 40, -(3 << 50)] {
+            assert_eq!(Msd::from_i64(n).to_i64(), Some(n));
+        }
+        for n in [i128::MIN, i128::MAX, i128::from(i64::MAX) * 5] {
+            assert_eq!(Msd::from_i128(n).to_i128(), Some(n));
+        }
+        assert_eq!(Msd::from_i128(i128::from(i64::MAX) + 1).to_i64(), None);
+        let product = Msd::from_i64(1 << 40) * Msd::from_i64(-(1 << 41));
+        assert_eq!(product.to_i128(), Some(-(1 << 81)));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigints_round_trip() {
+        let huge = BigInt::from(i128::MAX) * BigInt::from(i128::MIN) + 7u32;
+        for n in [BigInt::default(), BigInt::from(-9), huge.clone(), -huge] {
+            assert_eq!(Msd::from_bigint(&n).to_bigint(), n);
+        }
+        assert_eq!(Msd::from_bigint(&BigInt::from(-9)).as_slice(), [-1, -2]);
+        assert_eq!(Msd::from_bigint(&BigInt::default()).as_slice(), [0]);
+        assert_eq!(
+            Msd::from_i128(i128::MIN).to_bigint(),
+            BigInt::from(i128::MIN)
+        );
+    }
+
+    #[test]
+    fn arithmetic_stays_in_digit_space() {
+        for a in -40..40 {
+            for b in -40..40 {
+                let (x, y) = (Msd::from_int(a), Msd::from_int(b));
+                let sum = &x + &y;
+                assert_eq!(sum.to_int(), a + b);
+                assert!(sum.as_slice().iter().all(|d| (-2..=2).contains(d)));
+                assert_eq!((&x - &y).to_int(), a - b);
+                assert_eq!((-x).to_int(), -a);
+            }
+        }
+        let mut acc = Msd::from_int(0);
+        for delta in [7, -3, 1000, -999] {
+            acc += &Msd::from_int(delta);
+        }
+        assert_eq!(acc.to_int(), 5);
+        acc -= &Msd::from_int(5);
+        assert_eq!(acc.as_slice(), [0]);
+    }
+
+    #[test]
+    fn multiplication_matches_integers() {
+        for a in -70..70 {
+            for b in -70..70 {
+                let product = &Msd::from_int(a) * &Msd::from_int(b);
+                assert_eq!(product.to_int(), a * b, "{} * {}", a, b);
+                assert!(product.as_slice().iter().all(|d| (-2..=2).contains(d)));
+            }
+        }
+        let big = Msd::from_int(3_001) * Msd::from_int(-7_000);
+        assert_eq!(big.to_int(), -21_007_000);
+    }
+
+    #[test]
+    fn other_radixes_share_the_arithmetic() {
+        type Naf = SignedDigit<2>;
+        type Octal = SignedDigit<8>;
+        for a in -60..60 {
+            let naf = Naf::from_int(a);
+            assert_eq!(naf.to_int(), a);
+            assert!(naf.as_slice().windows(2).all(|w| w[0] == 0 || w[1] == 0));
+            let octal = Octal::from_int(a);
+            assert_eq!(octal.to_int(), a);
+            assert!(octal.as_slice().iter().all(|d| (-4..=4).contains(d)));
+            for b in [-37, -1, 0, 5, 44] {
+                let sum = &naf + &Naf::from_int(b);
+                assert_eq!(sum.to_int(), a + b);
+                assert!(sum.as_slice().windows(2).all(|w| w[0] == 0 || w[1] == 0));
+                assert_eq!((&octal * &Octal::from_int(b)).to_int(), a * b);
+                assert_eq!((&octal - &Octal::from_int(b)).to_int(), a - b);
+            }
+        }
+        assert_eq!(Naf::from_int(7).as_slice(), [-1, 0, 0, 1]);
+        assert_eq!(Octal::from_int(-13).as_slice(), [3, -2]);
+        for n in [i128::MIN, i128::MAX] {
+            assert_eq!(Naf::from_i128(n).to_i128(), Some(n));
+            assert_eq!(Octal::from_i128(n).to_i128(), Some(n));
+        }
+    }
+
+    #[test]
+    fn byte_form_is_canonical() {
+        for n in [0, 1, -1, 4, -9, 1000, i32::MIN, i32::MAX] {
+            let bytes = Msd::from_int(n).to_bytes();
+            assert_eq!(
+                bytes.len(),
+                1 + Msd::from_int(n).as_slice().len().div_ceil(2)
+            );
+            assert_eq!(Msd::from_bytes(&bytes).unwrap().to_int(), n);
+            let naf = SignedDigit::<2>::from_int(n).to_bytes();
+            assert_eq!(SignedDigit::<2>::from_bytes(&naf).unwrap().to_int(), n);
+        }
+        assert_eq!(Msd::from_int(0).to_bytes(), [0x01, 0x00]);
+        assert_eq!(Msd::from_int(-9).to_bytes(), [0x02, 0xef]);
+        assert_eq!(Msd::from_bytes(&[]), Err(MsdError::Truncated));
+        assert_eq!(Msd::from_bytes(&[0x00]), Err(MsdError::Empty));
+        assert_eq!(Msd::from_bytes(&[0x03, 0xe1]), Err(MsdError::Truncated));
+        assert_eq!(Msd::from_bytes(&[0x01, 0x10]), Err(MsdError::Truncated));
+        assert!(matches!(
+            Msd::from_bytes(&[0x02, 0x01]),
+            Err(MsdError::NotNormalized { .. })
+        ));
+        assert!(matches!(
+            Msd::from_bytes(&[0x01, 0x03]),
+            Err(MsdError::DigitOutOfRange { digit: 3, .. })
+        ));
+        assert!(SignedDigit::<2>::from_bytes(&[0x02, 0x11]).is_err());
+    }
+
+    #[test]
+    fn serde_keeps_digit_lists_and_validates() {
+        use serde::de::value::{BytesDeserializer, Error};
+
+        let msd = Msd::from_int(-9);
+        assert_eq!(serde_json::to_string(&msd).unwrap(), "[-1,-2]");
+        assert_eq!(serde_json::from_str::<Msd>("[-1,-2]").unwrap(), msd);
+        assert!(serde_json::from_str::<Msd>("[2,0]").is_err());
+        assert!(serde_json::from_str::<Msd>("[3]").is_err());
+        let bytes = msd.to_bytes();
+        let de = BytesDeserializer::<Error>::new(&bytes);
+        assert_eq!(Msd::deserialize(de).unwrap(), msd);
+        assert!(Msd::deserialize(BytesDeserializer::<Error>::new(&[])).is_err());
+    }
+
+    #[test]
+    fn digit_vectors_are_validated() {
+        assert_eq!(Msd::try_from_digits(vec![-1, -2]), Ok(Msd::from_int(-9)));
+        assert_eq!(Msd::try_from_digits(vec![0]), Ok(Msd::from_int(0)));
+        assert_eq!(
+            Msd::try_from_digits(vec![1, 5, 1]),
+            Err(MsdError::DigitOutOfRange {
+                radix: 4,
+                index: 1,
+                digit: 5
+            })
+        );
+        assert!(matches!(
+            Msd::try_from_digits(vec![2, 0]),
+            Err(MsdError::NotNormalized { .. })
+        ));
+        assert!(SignedDigit::<8>::try_from_digits(vec![4, -3]).is_ok());
+        assert!(SignedDigit::<2>::try_from_digits(vec![1, 1]).is_err());
+        assert_eq!(Msd::from_digits(vec![1, 5, 1]).to_int(), 1 + 5 * 4 + 16);
+    }
+
+    #[test]
+    fn text_form_round_trips() {
+        assert_eq!(Msd::from_int(-9).to_string(), "[-1,-2]₄");
+        assert_eq!(SignedDigit::<2>::from_int(7).to_string(), "[-1,0,0,1]₂");
+        for n in [0, 1, -9, 12_345, i32::MIN] {
+            let msd = Msd::from_int(n);
+            assert_eq!(msd.to_string().parse::<Msd>(), Ok(msd));
+        }
+        assert_eq!(" [ -1, -2 ] ".parse::<Msd>(), Ok(Msd::from_int(-9)));
+        assert!(matches!("[1,2]₈".parse::<Msd>(), Err(MsdError::Parse(_))));
+        assert!(matches!("1,2".parse::<Msd>(), Err(MsdError::Parse(_))));
+        assert!(matches!("[]".parse::<Msd>(), Err(MsdError::Parse(_))));
+        assert!(matches!(
+            "[3]".parse::<Msd>(),
+            Err(MsdError::DigitOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn converts_to_linear_algebra_vectors() {
+        let msd = Msd::from_int(-9);
+        assert_eq!(msd.to_vec(), [-1, -2]);
+        assert_eq!(DVector::from(&msd), DVector::from_vec(vec![-1, -2]));
+        #[cfg(feature = "ndarray")]
+        assert_eq!(Array1::from(&msd), ndarray::arr1(&[-1i8, -2]));
+    }
+
+    #[test]
+    fn batch_conversion_matches_scalar() {
+        fn check<const R: u32>(ns: &[i32]) {
+            let batch = SignedDigit::<R>::from_ints_batch(ns);
+            assert_eq!(batch.len(), ns.len());
+            for (msd, &n) in batch.iter().zip(ns) {
+                let scalar = SignedDigit::<R>::from_int(n);
+                assert_eq!(msd.as_slice(), scalar.as_slice(), "radix {} {}", R, n);
+            }
+        }
+        let mut ns: Vec<i32> = (-300..300).collect();
+        ns.extend([
+            i32::MIN,
+            i32::MIN + 1,
+            i32::MAX,
+            i32::MAX - 1,
+            1 << 30,
+            -(1 << 30),
+        ]);
+        ns.extend((0..31).map(|k| (1i32 << k) - 1));
+        check::<2>(&ns);
+        check::<4>(&ns);
+        check::<8>(&ns);
+        check::<4>(&ns[..LANES + 3]);
+        assert!(Msd::from_ints_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn weight_and_span_count_digits() {
+        assert_eq!((Msd::from_int(0).weight(), Msd::from_int(0).span()), (0, 0));
+        let msd = Msd::from_int(1 + 2 * 16);
+        assert_eq!(msd.as_slice(), [1, 0, 2]);
+        assert_eq!((msd.weight(), msd.span()), (2, 3));
+        // 119 = 128 - 8 - 1: three terms where plain binary needs six.
+        let naf = SignedDigit::<2>::from_int(0b0111_0111);
+        assert_eq!((naf.weight(), naf.span()), (3, 8));
+    }
+
+    #[test]
+    fn ordering_and_equality_go_by_value() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::BTreeSet;
+
+        let hash = |m: &Msd| {
+            let mut h = DefaultHasher::new();
+            m.hash(&mut h);
+            h.finish()
+        };
+        let two = Msd::from_int(-1) + Msd::from_int(3);
+        assert_eq!(two.as_slice(), [-2, 1]);
+        assert_eq!(two, Msd::from_int(2));
+        assert_eq!(hash(&two), hash(&Msd::from_int(2)));
+        for a in -70..70 {
+            for b in -70..70 {
+                let (x, y) = (Msd::from_int(a), Msd::from_int(b));
+                assert_eq!(x.cmp(&y), a.cmp(&b), "{} vs {}", a, b);
+                let naf = (SignedDigit::<2>::from_int(a), SignedDigit::<2>::from_int(b));
+                assert_eq!(naf.0.cmp(&naf.1), a.cmp(&b));
+            }
+        }
+        let big = Msd::from_i128(i128::MAX);
+        assert!(big > Msd::from_i128(i128::MAX - 1) && -big.clone() < Msd::from_int(i32::MIN));
+        let sorted: Vec<i32> = [5, -3, 1000, 0, -70_000, 2]
+            .into_iter()
+            .map(Msd::from_int)
+            .collect::<BTreeSet<_>>()
+            .iter()
+            .map(Msd::to_int)
+            .collect();
+        assert_eq!(sorted, [-70_000, -3, 0, 2, 5, 1000]);
+    }
+
+    #[test]
+    fn builder_normalizes_as_digits_arrive() {
+        let raw: Vec<Digit> = vec![3, -7, 0, 2, 5, -1, 1, 4, -4];
+        for len in 0..=raw.len() {
+            let digits = raw[..len].to_vec();
+            let mut four = MsdBuilder::new();
+            let mut naf = SignedDigitBuilder::<2>::new();
+            for &d in &digits {
+                four.push(d);
+                naf.push(d);
+            }
+            let four = four.finish();
+            assert_eq!(four.as_slice(), Msd::from_digits(digits.clone()).as_slice());
+            let naf = naf.finish();
+            let expected = SignedDigit::<2>::from_digits(digits.clone());
+            assert_eq!(naf.as_slice(), expected.as_slice());
+            let octal: SignedDigit<8> = digits.iter().copied().collect();
+            assert_eq!(octal, SignedDigit::<8>::from_digits(digits));
+        }
+        let msd = Msd::from_int(55);
+        assert_eq!(msd.digits_lsb().collect::<Vec<_>>(), [-1, 2, -1, 1]);
+        assert_eq!(msd.digits_msb().collect::<Vec<_>>(), [1, -1, 2, -1]);
+        assert_eq!(msd.digits_lsb().collect::<Msd>(), msd);
+    }
+
+    #[test]
+    fn long_digit_strings_take_a_wider_count() {
+        let big = SignedDigit::<2>::from_i128(i128::MAX);
+        assert!(big.as_slice().len() >= 128);
+        let bytes = big.to_bytes();
+        assert_eq!(bytes[..2], [big.as_slice().len() as u8 | 0x80, 1]);
+        assert_eq!(SignedDigit::<2>::from_bytes(&bytes).unwrap(), big);
+        // The same count, padded with a redundant continuation byte.
+        assert_eq!(
+            Msd::from_bytes(&[0x81, 0x00, 0x01]),
+            Err(MsdError::Truncated)
+        );
+    }
 }
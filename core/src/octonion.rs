@@ -0,0 +1,149 @@
+//! Octonion packing for 8-prime star
+//! All eight exponents [exp₀…exp₇] as one octonion, built by Cayley–Dickson
+//! from the quaternion pair (Ψ₁, Ψ₂): exp₀ is the real unit and exp₁…exp₇
+//! the imaginary units e₁…e₇. Multiplication is not associative, so
+//! `(x * y) * z` and `x * (y * z)` generally differ.
+
+use std::ops::{Mul, Neg};
+
+use nalgebra::Quaternion;
+
+use crate::qp_encode::QuatScalar;
+
+/// `a + b·e₄`, with `a` and `b` as quaternions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OctonionOf<T: QuatScalar> {
+    pub a: Quaternion<T>,
+    pub b: Quaternion<T>,
+}
+
+pub type Octonion = OctonionOf<f32>;
+pub type Octonion64 = OctonionOf<f64>;
+
+impl<T: QuatScalar> OctonionOf<T> {
+    pub fn pack(exponents: &[i32; 8]) -> Self {
+        Self::from_components(exponents.map(T::from_exponent))
+    }
+
+    /// Nearest exponents, in component order.
+    pub fn unpack(&self) -> [i32; 8] {
+        self.components().map(T::to_exponent)
+    }
+
+    /// Real part, then e₁…e₇.
+    pub fn from_components(c: [T; 8]) -> Self {
+        OctonionOf {
+            a: Quaternion::new(c[0], c[1], c[2], c[3]),
+            b: Quaternion::new(c[4], c[5], c[6], c[7]),
+        }
+    }
+
+    pub fn components(&self) -> [T; 8] {
+        let (a, b) = (&self.a, &self.b);
+        [a.w, a.i, a.j, a.k, b.w, b.i, b.j, b.k]
+    }
+
+    pub fn one() -> Self {
+        OctonionOf {
+            a: Quaternion::identity(),
+            b: Quaternion::new(T::zero(), T::zero(), T::zero(), T::zero()),
+        }
+    }
+
+    pub fn norm_squared(&self) -> T {
+        self.a.norm_squared() + self.b.norm_squared()
+    }
+
+    /// Multiplicative: `(x * y).norm() == x.norm() * y.norm()`.
+    pub fn norm(&self) -> T {
+        self.norm_squared().sqrt()
+    }
+
+    pub fn conjugate(&self) -> Self {
+        OctonionOf {
+            a: self.a.conjugate(),
+            b: -self.b,
+        }
+    }
+
+    /// `None` for zero.
+    pub fn try_inverse(&self) -> Option<Self> {
+        let n = self.norm_squared();
+        if n > T::zero() {
+            let c = self.conjugate();
+            Some(OctonionOf {
+                a: c.a / n,
+                b: c.b / n,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: QuatScalar> Mul for OctonionOf<T> {
+    type Output = Self;
+
+    /// `(a, b)(c, d) = (ac − d̄b, da + bc̄)`.
+    fn mul(self, rhs: Self) -> Self {
+        let (a, b, c, d) = (self.a, self.b, rhs.a, rhs.b);
+        OctonionOf {
+            a: a * c - d.conjugate() * b,
+            b: d * a + b * c.conjugate(),
+        }
+    }
+}
+
+impl<T: QuatScalar> Neg for OctonionOf<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        OctonionOf {
+            a: -self.a,
+            b: -self.b,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(n: usize) -> Octonion64 {
+        let mut c = [0.0; 8];
+        c[n] = 1.0;
+        Octonion64::from_components(c)
+    }
+
+    #[test]
+    fn octonion_algebra_laws() {
+        let exponents = [3, -1, 4, 1, -5, 9, 2, -6];
+        let x = Octonion64::pack(&exponents);
+        assert_eq!(x.unpack(), exponents);
+        let y = Octonion64::pack(&[2, 7, -1, 8, 2, -8, 1, 8]);
+
+        let xy = x * y;
+        assert!((xy.norm() - x.norm() * y.norm()).abs() < 1e-9);
+        assert_eq!((x * y).conjugate(), y.conjugate() * x.conjugate());
+        let square = x * x.conjugate();
+        assert_eq!(square.components()[0], x.norm_squared());
+        assert!(square.components()[1..].iter().all(|&c| c == 0.0));
+        let one = x * x.try_inverse().unwrap();
+        assert!(one
+            .components()
+            .iter()
+            .zip(Octonion64::one().components())
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert!(Octonion64::pack(&[0; 8]).try_inverse().is_none());
+
+        for n in 1..8 {
+            assert_eq!(unit(n) * unit(n), -Octonion64::one());
+        }
+        // e₁e₂ = e₃ but (e₁e₂)e₄ = −e₁(e₂e₄).
+        assert_eq!(unit(1) * unit(2), unit(3));
+        assert_eq!(
+            (unit(1) * unit(2)) * unit(4),
+            -(unit(1) * (unit(2) * unit(4)))
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! Tuning knobs for opening a ledger
+//! `LedgerOptions::default()` reproduces what `Ledger::new` has always used.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocksdb::{BlockBasedOptions, Cache, DBCompressionType, Options};
+
+use crate::archive::{ObjectStore, SharedStore};
+use crate::bounds::{BoundsConfig, ExponentBounds};
+use crate::centroid::CentroidSeed;
+use crate::clock::SharedClock;
+use crate::crdt::WriterId;
+use crate::durability::SyncPolicy;
+use crate::encryption::{KeyProvider, LogCipher, SharedKeyProvider};
+use crate::outbox::{EventPublisher, SharedPublisher};
+use crate::registry::Registry;
+use crate::{Clock, LedgerError, RetentionPolicy, DEFAULT_EXPONENT_CACHE_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    None,
+    #[default]
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "snappy" => Ok(Compression::Snappy),
+            "lz4" => Ok(Compression::Lz4),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression {:?}", s)),
+        }
+    }
+}
+
+impl From<Compression> for DBCompressionType {
+    fn from(c: Compression) -> Self {
+        match c {
+            Compression::None => DBCompressionType::None,
+            Compression::Snappy => DBCompressionType::Snappy,
+            Compression::Lz4 => DBCompressionType::Lz4,
+            Compression::Zstd => DBCompressionType::Zstd,
+        }
+    }
+}
+
+/// Compression for one column family; see [`LedgerOptions::cf_compression`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CfCompression {
+    compression: Compression,
+    level: Option<i32>,
+    dict_bytes: u32,
+    train_bytes: u32,
+}
+
+impl CfCompression {
+    pub fn new(compression: Compression) -> Self {
+        CfCompression {
+            compression,
+            level: None,
+            dict_bytes: 0,
+            train_bytes: 0,
+        }
+    }
+
+    /// Codec-specific level; RocksDB's default otherwise.
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Give each SST a shared dictionary of up to `bytes`, which pays off
+    /// on small, repetitive values. With `train_bytes > 0`, zstd trains the
+    /// dictionary on that much sampled data instead of using raw samples.
+    pub fn dictionary(mut self, bytes: u32, train_bytes: u32) -> Self {
+        self.dict_bytes = bytes;
+        self.train_bytes = train_bytes;
+        self
+    }
+
+    fn apply(&self, opts: &mut Options) {
+        opts.set_compression_type(self.compression.into());
+        if self.level.is_some() || self.dict_bytes > 0 {
+            // -14 and 0 are RocksDB's defaults for window bits and strategy.
+            let level = self.level.unwrap_or(DEFAULT_COMPRESSION_LEVEL);
+            opts.set_compression_options(-14, level, 0, clamp(self.dict_bytes));
+        }
+        if self.train_bytes > 0 {
+            opts.set_zstd_max_train_bytes(clamp(self.train_bytes));
+        }
+    }
+}
+
+impl From<Compression> for CfCompression {
+    fn from(compression: Compression) -> Self {
+        CfCompression::new(compression)
+    }
+}
+
+/// RocksDB's "use the codec's default level" sentinel.
+const DEFAULT_COMPRESSION_LEVEL: i32 = 32_767;
+
+fn clamp(bytes: u32) -> i32 {
+    i32::try_from(bytes).unwrap_or(i32::MAX)
+}
+
+/// Builder for RocksDB tuning, passed to [`crate::Ledger::with_options`].
+///
+/// Unset fields keep RocksDB's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct LedgerOptions {
+    block_cache_mb: Option<usize>,
+    compression: Compression,
+    bloom_bits: Option<f64>,
+    write_buffer_mb: Option<usize>,
+    max_background_jobs: Option<i32>,
+    pub(crate) retention: RetentionPolicy,
+    pub(crate) lock_wait: Option<Duration>,
+    pub(crate) clock: SharedClock,
+    exponent_cache_size: Option<usize>,
+    key_provider: Option<SharedKeyProvider>,
+    cf_compression: BTreeMap<String, CfCompression>,
+    pub(crate) sync: SyncPolicy,
+    pub(crate) publisher: Option<SharedPublisher>,
+    pub(crate) archive: Option<SharedStore>,
+    pub(crate) writer_id: Option<WriterId>,
+    pub(crate) record_denials: bool,
+    pub(crate) bounds: BoundsConfig,
+    pub(crate) registry: Option<Registry>,
+    pub(crate) centroid_seed: CentroidSeed,
+}
+
+impl LedgerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size of the block cache shared by all column families.
+    pub fn block_cache_mb(mut self, mb: usize) -> Self {
+        self.block_cache_mb = Some(mb);
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Bloom filter bits per key on the factors/postings tables.
+    pub fn bloom_bits(mut self, bits: u32) -> Self {
+        self.bloom_bits = Some(f64::from(bits));
+        self
+    }
+
+    /// Memtable size per column family before it is flushed.
+    pub fn write_buffer_mb(mut self, mb: usize) -> Self {
+        self.write_buffer_mb = Some(mb);
+        self
+    }
+
+    /// Threads shared by flushes and compactions.
+    pub fn max_background_jobs(mut self, jobs: i32) -> Self {
+        self.max_background_jobs = Some(jobs);
+        self
+    }
+
+    /// Compression for the column family `cf` (`factors`, `postings`,
+    /// `history`, ...), overriding [`LedgerOptions::compression`] there.
+    pub fn cf_compression(mut self, cf: &str, compression: impl Into<CfCompression>) -> Self {
+        self.cf_compression
+            .insert(cf.to_string(), compression.into());
+        self
+    }
+
+    /// When commits are fsynced to the log and the RocksDB WAL; left to the
+    /// OS by default.
+    pub fn sync(mut self, policy: SyncPolicy) -> Self {
+        self.sync = policy;
+        self
+    }
+
+    /// Which sealed log segments to keep; everything by default.
+    pub fn retention(mut self, policy: RetentionPolicy) -> Self {
+        self.retention = policy;
+        self
+    }
+
+    /// Wait up to `timeout` for another writer to release the ledger
+    /// instead of failing immediately.
+    pub fn wait_for_lock(mut self, timeout: Duration) -> Self {
+        self.lock_wait = Some(timeout);
+        self
+    }
+
+    /// Source of event timestamps; the system clock by default.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = SharedClock(clock);
+        self
+    }
+
+    /// Entries in the in-process exponent cache used by the write path;
+    /// 0 disables it. Defaults to [`DEFAULT_EXPONENT_CACHE_SIZE`].
+    pub fn exponent_cache_size(mut self, entries: usize) -> Self {
+        self.exponent_cache_size = Some(entries);
+        self
+    }
+
+    /// Encrypt new log segments at rest with the key from `provider`.
+    /// Segments written earlier stay readable; the key is fetched once,
+    /// on open.
+    pub fn encryption(mut self, provider: Arc<dyn KeyProvider>) -> Self {
+        self.key_provider = Some(SharedKeyProvider(provider));
+        self
+    }
+
+    /// Upload every sealed log segment to `store`, e.g. a
+    /// [`crate::CloudStore`] bucket; retention won't delete a segment
+    /// before it has been archived.
+    pub fn archive_store(mut self, store: Arc<dyn ObjectStore>) -> Self {
+        self.archive = Some(SharedStore(store));
+        self
+    }
+
+    /// Publish every committed event through `publisher`, at least once,
+    /// via the outbox; see [`crate::KafkaPublisher`] and
+    /// [`crate::NatsPublisher`] behind the `kafka` and `nats` features.
+    pub fn publisher(mut self, publisher: Arc<dyn EventPublisher>) -> Self {
+        self.publisher = Some(SharedPublisher(publisher));
+        self
+    }
+
+    /// Keep PN-counter state under `id` so this ledger can merge with
+    /// other writers; see [`crate::Ledger::merge_crdt`].
+    pub fn writer_id(mut self, id: WriterId) -> Self {
+        self.writer_id = Some(id);
+        self
+    }
+
+    /// Record every flow-rule rejection in the `denials` column family;
+    /// see [`crate::Ledger::denials`]. Dry runs are not recorded.
+    pub fn record_denials(mut self, enabled: bool) -> Self {
+        self.record_denials = enabled;
+        self
+    }
+
+    /// Keep every exponent within `bounds` unless [`Self::prime_bounds`]
+    /// overrides it for that prime.
+    pub fn exponent_bounds(mut self, bounds: ExponentBounds) -> Self {
+        self.bounds.default = Some(bounds);
+        self
+    }
+
+    /// Bounds for exponents of `prime` only.
+    pub fn prime_bounds(mut self, prime: u32, bounds: ExponentBounds) -> Self {
+        self.bounds.per_prime.insert(prime, bounds);
+        self
+    }
+
+    /// Map primes to nodes with `registry` instead of the S0 alphabet. Only
+    /// a new ledger takes it; an existing one must have been created with
+    /// the same registry or opening fails. Omit it to use whatever the
+    /// ledger recorded.
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// How a new entity's first centroid digit is picked;
+    /// [`CentroidSeed::Clock`] by default.
+    pub fn centroid_seed(mut self, seed: CentroidSeed) -> Self {
+        self.centroid_seed = seed;
+        self
+    }
+
+    pub(crate) fn log_cipher(&self) -> Result<Option<LogCipher>, LedgerError> {
+        self.key_provider
+            .as_ref()
+            .map(|provider| LogCipher::new(provider.0.as_ref()))
+            .transpose()
+    }
+
+    pub(crate) fn exponent_cache_size_or_default(&self) -> usize {
+        self.exponent_cache_size
+            .unwrap_or(DEFAULT_EXPONENT_CACHE_SIZE)
+    }
+
+    /// DB-wide options.
+    pub(crate) fn db_options(&self) -> Options {
+        let mut opts = Options::default();
+        if let Some(jobs) = self.max_background_jobs {
+            opts.set_max_background_jobs(jobs);
+        }
+        opts
+    }
+
+    /// Options for the column family `cf`; `cache` is shared across families.
+    pub(crate) fn cf_options(&self, cf: &str, cache: Option<&Cache>) -> Options {
+        let mut opts = Options::default();
+        match self.cf_compression.get(cf) {
+            Some(compression) => compression.apply(&mut opts),
+            None => opts.set_compression_type(self.compression.into()),
+        }
+        if let Some(mb) = self.write_buffer_mb {
+            opts.set_write_buffer_size(mb * 1024 * 1024);
+        }
+        let mut table = BlockBasedOptions::default();
+        if let Some(cache) = cache {
+            table.set_block_cache(cache);
+        }
+        if let Some(bits) = self.bloom_bits {
+            table.set_bloom_filter(bits, false);
+        }
+        opts.set_block_based_table_factory(&table);
+        opts
+    }
+
+    /// Fail if a per-family setting names a column family that doesn't exist.
+    pub(crate) fn check_cf_names(&self, known: &[&str]) -> Result<(), LedgerError> {
+        match self
+            .cf_compression
+            .keys()
+            .find(|cf| !known.contains(&cf.as_str()))
+        {
+            Some(cf) => Err(LedgerError::InvalidArgument(format!(
+                "no column family named {:?}",
+                cf
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn block_cache(&self) -> Option<Cache> {
+        self.block_cache_mb
+            .map(|mb| Cache::new_lru_cache(mb * 1024 * 1024))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ledger;
+
+    #[test]
+    fn tuned_ledger_opens_and_reopens() {
+        let tmp = tempfile::tempdir().unwrap();
+        let options = LedgerOptions::new()
+            .block_cache_mb(8)
+            .compression("snappy".parse().unwrap())
+            .bloom_bits(10)
+            .write_buffer_mb(4)
+            .max_background_jobs(2)
+            .cf_compression("factors", Compression::None)
+            .cf_compression(
+                "postings",
+                CfCompression::new(Compression::Zstd)
+                    .level(3)
+                    .dictionary(16 * 1024, 1 << 20),
+            );
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        drop(ledger);
+
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(2));
+        assert!("brotli".parse::<Compression>().is_err());
+        drop(ledger);
+
+        let typo = options.cf_compression("posting", Compression::Lz4);
+        assert!(matches!(
+            Ledger::with_options(tmp.path(), &typo),
+            Err(LedgerError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,356 @@
+//! Transactional outbox feeding an external event bus
+//! With a publisher configured, every commit also writes its events under
+//! `outbox/<lsn>` in the same RocksDB batch. A background thread publishes
+//! them in LSN order and deletes each entry once the broker acknowledges
+//! it, so every committed event is delivered at least once, across crashes.
+//! Payloads are the protobuf `LedgerEvent` of `proto/dualsubstrate/v1`.
+
+use std::fmt;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use prost::Message;
+use rocksdb::{Direction, IteratorMode, WriteBatch, DB};
+
+use crate::{Ledger, LedgerError, LedgerEvent, Msd};
+
+const OUTBOX_PREFIX: &[u8] = b"outbox/";
+
+/// How long the publisher thread waits before retrying after a failure.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delivers encoded events to a broker. `publish` must only return `Ok`
+/// once the broker has durably accepted the message; it may be called again
+/// for the same `lsn` after a failure or restart.
+pub trait EventPublisher: Send + Sync {
+    fn publish(&self, entity: u64, lsn: u64, payload: &[u8]) -> Result<(), LedgerError>;
+}
+
+/// Publisher handle carried by options.
+#[derive(Clone)]
+pub(crate) struct SharedPublisher(pub Arc<dyn EventPublisher>);
+
+impl fmt::Debug for SharedPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventPublisher")
+    }
+}
+
+/// Wire form of an event; mirrors `dualsubstrate.v1.LedgerEvent`.
+#[derive(Clone, PartialEq, Message)]
+struct WireEvent {
+    #[prost(uint64, tag = "1")]
+    entity: u64,
+    #[prost(uint32, tag = "2")]
+    prime: u32,
+    #[prost(sint32, repeated, tag = "3")]
+    msd_digits: Vec<i32>,
+    #[prost(bool, tag = "4")]
+    via_c: bool,
+    #[prost(uint32, tag = "5")]
+    centroid_digit: u32,
+    #[prost(uint64, tag = "6")]
+    timestamp: u64,
+    #[prost(string, tag = "7")]
+    prev_hash: String,
+    #[prost(string, tag = "8")]
+    event_hash: String,
+    #[prost(string, tag = "9")]
+    signature: String,
+    #[prost(bool, tag = "10")]
+    tombstone: bool,
+    #[prost(uint64, tag = "11")]
+    lsn: u64,
+}
+
+/// Protobuf encoding of `evt`, as published.
+pub fn encode_event(evt: &LedgerEvent) -> Vec<u8> {
+    WireEvent {
+        entity: evt.entity_id,
+        prime: evt.prime,
+        msd_digits: evt
+            .msd_digits
+            .as_slice()
+            .iter()
+            .map(|&d| i32::from(d))
+            .collect(),
+        via_c: evt.via_c,
+        centroid_digit: u32::from(evt.centroid_digit),
+        timestamp: evt.timestamp,
+        prev_hash: evt.prev_hash.clone(),
+        event_hash: evt.event_hash.clone(),
+        signature: evt.signature.clone(),
+        tombstone: evt.tombstone,
+        lsn: evt.lsn,
+    }
+    .encode_to_vec()
+}
+
+pub fn decode_event(payload: &[u8]) -> Result<LedgerEvent, LedgerError> {
+    let wire = WireEvent::decode(payload)
+        .map_err(|e| LedgerError::Corruption(format!("undecodable event: {}", e)))?;
+    let msd_digits = wire
+        .msd_digits
+        .into_iter()
+        .map(i8::try_from)
+        .collect::<Result<_, _>>()
+        .map_err(|_| LedgerError::Corruption("MSD digit out of range".to_string()))
+        .and_then(|digits| Ok(Msd::try_from_digits(digits)?))?;
+    if msd_digits.to_i32().is_none() {
+        return Err(LedgerError::Corruption(format!(
+            "MSD delta {} is out of i32 range",
+            msd_digits
+        )));
+    }
+    Ok(LedgerEvent {
+        entity_id: wire.entity,
+        prime: wire.prime,
+        msd_digits,
+        via_c: wire.via_c,
+        centroid_digit: u8::try_from(wire.centroid_digit)?,
+        timestamp: wire.timestamp,
+        prev_hash: wire.prev_hash,
+        event_hash: wire.event_hash,
+        signature: wire.signature,
+        tombstone: wire.tombstone,
+        lsn: wire.lsn,
+    })
+}
+
+fn outbox_key(lsn: u64) -> Vec<u8> {
+    [OUTBOX_PREFIX, &lsn.to_be_bytes()].concat()
+}
+
+/// Background publisher; stopped and joined on drop.
+pub(crate) struct Outbox {
+    wake: Option<Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Outbox {
+    pub(crate) fn start(db: Arc<DB>, publisher: SharedPublisher) -> Self {
+        let (wake, woken) = bounded(1);
+        let worker = thread::spawn(move || run(&db, publisher.0.as_ref(), woken));
+        Outbox {
+            wake: Some(wake),
+            worker: Some(worker),
+        }
+    }
+
+    /// Tell the publisher thread new entries are waiting.
+    pub(crate) fn notify(&self) {
+        if let Some(wake) = &self.wake {
+            let _ = wake.try_send(());
+        }
+    }
+}
+
+impl Drop for Outbox {
+    fn drop(&mut self) {
+        self.wake.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Drain on every wake-up and retry on a timer; one last drain on shutdown.
+fn run(db: &DB, publisher: &dyn EventPublisher, woken: Receiver<()>) {
+    loop {
+        let stopping = matches!(
+            woken.recv_timeout(RETRY_INTERVAL),
+            Err(RecvTimeoutError::Disconnected)
+        );
+        let _ = drain(db, publisher);
+        if stopping {
+            return;
+        }
+    }
+}
+
+/// Publish pending entries oldest first, stopping at the first failure so
+/// delivery order follows LSN order.
+fn drain(db: &DB, publisher: &dyn EventPublisher) -> Result<u64, LedgerError> {
+    let mut published = 0;
+    for entry in db.iterator(IteratorMode::From(OUTBOX_PREFIX, Direction::Forward)) {
+        let (key, payload) = entry?;
+        if !key.starts_with(OUTBOX_PREFIX) {
+            break;
+        }
+        let evt = decode_event(&payload)?;
+        publisher.publish(evt.entity_id, evt.lsn, &payload)?;
+        db.delete(&key)?;
+        published += 1;
+    }
+    Ok(published)
+}
+
+impl Ledger {
+    /// Stage outbox entries for `events` when a publisher is configured.
+    pub(crate) fn stage_outbox(&self, batch: &mut WriteBatch, events: &[LedgerEvent]) {
+        if self.outbox.is_none() {
+            return;
+        }
+        for evt in events {
+            batch.put(outbox_key(evt.lsn), encode_event(evt));
+        }
+    }
+
+    /// Committed events not yet acknowledged by the broker.
+    pub fn outbox_pending(&self) -> Result<u64, LedgerError> {
+        let mut pending = 0;
+        for entry in self
+            .db
+            .iterator(IteratorMode::From(OUTBOX_PREFIX, Direction::Forward))
+        {
+            if !entry?.0.starts_with(OUTBOX_PREFIX) {
+                break;
+            }
+            pending += 1;
+        }
+        Ok(pending)
+    }
+}
+
+/// Kafka producer publishing to one topic, keyed by entity id (decimal) so
+/// an entity's events stay ordered within a partition.
+#[cfg(feature = "kafka")]
+pub struct KafkaPublisher {
+    producer: std::sync::Mutex<kafka::producer::Producer>,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaPublisher {
+    /// Connect to `brokers` (`host:port`); sends wait for all in-sync
+    /// replicas.
+    pub fn connect(brokers: Vec<String>, topic: impl Into<String>) -> Result<Self, LedgerError> {
+        use kafka::producer::{Producer, RequiredAcks};
+        let producer = Producer::from_hosts(brokers)
+            .with_ack_timeout(Duration::from_secs(5))
+            .with_required_acks(RequiredAcks::All)
+            .create()
+            .map_err(|e| LedgerError::Internal(format!("kafka: {}", e)))?;
+        Ok(KafkaPublisher {
+            producer: std::sync::Mutex::new(producer),
+            topic: topic.into(),
+        })
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl EventPublisher for KafkaPublisher {
+    fn publish(&self, entity: u64, _lsn: u64, payload: &[u8]) -> Result<(), LedgerError> {
+        let key = entity.to_string();
+        let record = kafka::producer::Record::from_key_value(&self.topic, key.as_bytes(), payload);
+        self.producer
+            .lock()?
+            .send(&record)
+            .map_err(|e| LedgerError::Internal(format!("kafka: {}", e)))
+    }
+}
+
+/// NATS JetStream publisher. Events go to `<subject>.<entity>` with a
+/// `Nats-Msg-Id` per LSN, so the stream drops redelivered duplicates.
+#[cfg(feature = "nats")]
+pub struct NatsPublisher {
+    runtime: tokio::runtime::Runtime,
+    jetstream: async_nats::jetstream::Context,
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsPublisher {
+    /// Connect to the server at `url`. Call it outside any async runtime;
+    /// the publisher drives its own.
+    pub fn connect(url: &str, subject: impl Into<String>) -> Result<Self, LedgerError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = runtime
+            .block_on(async_nats::connect(url))
+            .map_err(|e| LedgerError::Internal(format!("nats: {}", e)))?;
+        Ok(NatsPublisher {
+            runtime,
+            jetstream: async_nats::jetstream::new(client),
+            subject: subject.into(),
+        })
+    }
+}
+
+#[cfg(feature = "nats")]
+impl EventPublisher for NatsPublisher {
+    fn publish(&self, entity: u64, lsn: u64, payload: &[u8]) -> Result<(), LedgerError> {
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert("Nats-Msg-Id", format!("{}:{}", self.subject, lsn).as_str());
+        let subject = format!("{}.{}", self.subject, entity);
+        self.runtime
+            .block_on(async {
+                self.jetstream
+                    .publish_with_headers(subject, headers, payload.to_vec().into())
+                    .await?
+                    .await
+            })
+            .map(|_| ())
+            .map_err(|e| LedgerError::Internal(format!("nats: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::LedgerOptions;
+
+    /// Fails its first `failures` calls, then records what it is sent.
+    #[derive(Default)]
+    struct Flaky {
+        failures: Mutex<u32>,
+        sent: Mutex<Vec<(u64, u64, LedgerEvent)>>,
+    }
+
+    impl EventPublisher for Flaky {
+        fn publish(&self, entity: u64, lsn: u64, payload: &[u8]) -> Result<(), LedgerError> {
+            let mut failures = self.failures.lock()?;
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(LedgerError::Internal("broker down".to_string()));
+            }
+            self.sent
+                .lock()?
+                .push((entity, lsn, decode_event(payload)?));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn committed_events_are_published_in_order_after_failures() {
+        let tmp = tempfile::tempdir().unwrap();
+        let publisher = Arc::new(Flaky {
+            failures: Mutex::new(1),
+            ..Default::default()
+        });
+        let options = LedgerOptions::new().publisher(publisher.clone());
+        let ledger = Ledger::with_options(tmp.path(), &options).unwrap();
+        let mut events = ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        events.extend(ledger.anchor_batch(2, &[(3, 2)]).unwrap());
+        events.push(ledger.delete_entity(1).unwrap());
+        // Dropping the ledger drains whatever the retry timer hasn't yet.
+        drop(ledger);
+
+        let sent = publisher.sent.lock().unwrap();
+        let keys = sent.iter().map(|(e, l, _)| (*e, *l)).collect::<Vec<_>>();
+        assert_eq!(keys, vec![(1, 0), (1, 1), (2, 2), (1, 3)]);
+        assert!(sent
+            .iter()
+            .map(|s| &s.2.event_hash)
+            .eq(events.iter().map(|e| &e.event_hash)));
+        assert!(sent[3].2.tombstone);
+
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.outbox_pending().unwrap(), 0);
+    }
+}
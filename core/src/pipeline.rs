@@ -0,0 +1,379 @@
+//! Pipelined writer: validation, log append and DB commit run on separate
+//! threads joined by bounded queues, so staging one batch overlaps the I/O
+//! of the batches ahead of it.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+
+use crate::centroid::CentroidDigit;
+use crate::chain;
+use crate::{Ledger, LedgerError, LedgerEvent, StagedBatch, WriterState};
+
+/// Queue depth that keeps every stage busy without buffering much; a
+/// starting point for [`PipelinedWriter::new`].
+pub const DEFAULT_PIPELINE_DEPTH: usize = 64;
+
+type Reply = Sender<Result<Vec<LedgerEvent>, LedgerError>>;
+
+struct Job {
+    entity: u64,
+    commands: Vec<(u32, u8)>,
+    reply: Reply,
+}
+
+/// A validated batch on its way to the log and the DB.
+struct Sealed {
+    seq: u64,
+    staged: StagedBatch,
+    started: Option<Instant>,
+    reply: Reply,
+}
+
+/// What the stages share besides the queues.
+#[derive(Default)]
+struct Progress {
+    /// Sequence number of the last batch written to the DB.
+    committed: AtomicU64,
+    /// Set once a log append or DB write fails; everything behind it was
+    /// validated against state that never landed, so it fails too.
+    failed: AtomicBool,
+}
+
+/// Exponents and centroids staged by batches validated but not yet
+/// committed, tagged with the batch that wrote them.
+#[derive(Default)]
+struct InFlight {
+    exponents: HashMap<(u64, u32), (i32, u64)>,
+    centroids: HashMap<u64, (CentroidDigit, u64)>,
+}
+
+impl InFlight {
+    /// Forget what batches up to `committed` wrote; the DB has it now.
+    fn retire(&mut self, committed: u64) {
+        self.exponents.retain(|_, &mut (_, seq)| seq > committed);
+        self.centroids.retain(|_, &mut (_, seq)| seq > committed);
+    }
+
+    fn seed(&self, staged: &mut StagedBatch, entity: u64, commands: &[(u32, u8)]) {
+        for &(prime, _) in commands {
+            if let Some(&(exp, _)) = self.exponents.get(&(entity, prime)) {
+                staged.prefetched.insert((entity, prime), Some(exp));
+            }
+        }
+        if let Some(&(digit, _)) = self.centroids.get(&entity) {
+            staged.centroids.insert(entity, digit);
+        }
+    }
+
+    fn record(&mut self, seq: u64, staged: &StagedBatch) {
+        for (&key, &exp) in &staged.pending {
+            self.exponents.insert(key, (exp, seq));
+        }
+        for (&entity, &digit) in &staged.centroids {
+            self.centroids.insert(entity, (digit, seq));
+        }
+    }
+}
+
+/// Result of a batch handed to [`PipelinedWriter::submit`].
+pub struct PendingBatch {
+    rx: Receiver<Result<Vec<LedgerEvent>, LedgerError>>,
+}
+
+impl PendingBatch {
+    /// Block until the batch is committed or rejected.
+    pub fn wait(self) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.rx.recv().unwrap_or_else(|_| Err(stopped()))
+    }
+}
+
+/// Writer that validates batch N+1 while batch N is being logged and
+/// committed. Batches commit in submission order with the same semantics
+/// as [`Ledger::anchor_batch`].
+///
+/// The pipeline holds the ledger's write lock until it is finished or
+/// dropped; other writes on the ledger wait for it.
+pub struct PipelinedWriter {
+    jobs: Option<Sender<Job>>,
+    handle: Option<JoinHandle<Result<(), LedgerError>>>,
+}
+
+impl PipelinedWriter {
+    /// Start the pipeline, letting up to `depth` batches queue at each stage.
+    pub fn new(ledger: Arc<Ledger>, depth: usize) -> Result<Self, LedgerError> {
+        ledger.ensure_writable()?;
+        let depth = depth.max(1);
+        let (jobs, rx) = bounded(depth);
+        let (ready_tx, ready) = bounded(1);
+        let handle = thread::Builder::new()
+            .name("ledger-pipeline".to_string())
+            .spawn(move || run(&ledger, rx, depth, ready_tx))?;
+        match ready.recv() {
+            Ok(Ok(())) => Ok(PipelinedWriter {
+                jobs: Some(jobs),
+                handle: Some(handle),
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(stopped()),
+        }
+    }
+
+    /// Queue a batch; blocks only while the validation queue is full.
+    pub fn submit(
+        &self,
+        entity: u64,
+        commands: Vec<(u32, u8)>,
+    ) -> Result<PendingBatch, LedgerError> {
+        let (reply, rx) = bounded(1);
+        let job = Job {
+            entity,
+            commands,
+            reply,
+        };
+        self.jobs
+            .as_ref()
+            .ok_or_else(stopped)?
+            .send(job)
+            .map_err(|_| stopped())?;
+        Ok(PendingBatch { rx })
+    }
+
+    /// Submit a batch and wait for it.
+    pub fn anchor_batch(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.submit(entity, commands.to_vec())?.wait()
+    }
+
+    /// Drain the queues, stop the threads and release the write lock.
+    pub fn finish(mut self) -> Result<(), LedgerError> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), LedgerError> {
+        self.jobs.take();
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| LedgerError::Internal("writer pipeline panicked".to_string()))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PipelinedWriter {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+fn stopped() -> LedgerError {
+    LedgerError::Internal("writer pipeline has stopped".to_string())
+}
+
+fn poisoned() -> LedgerError {
+    LedgerError::Internal("writer pipeline stopped after an earlier write failed".to_string())
+}
+
+/// Body of the pipeline thread: takes the write lock, runs validation here
+/// and the log and commit stages on scoped threads, then hands the writer
+/// state back.
+fn run(
+    ledger: &Ledger,
+    jobs: Receiver<Job>,
+    depth: usize,
+    ready: Sender<Result<(), LedgerError>>,
+) -> Result<(), LedgerError> {
+    let mut writer = match ledger.write_lock.lock() {
+        Ok(writer) => writer,
+        Err(e) => {
+            let _ = ready.send(Err(e.into()));
+            return Ok(());
+        }
+    };
+    let progress = Progress::default();
+    let head = thread::scope(|s| {
+        let (to_log, logs) = bounded(depth);
+        let (to_commit, commits) = bounded(depth);
+        let progress = &progress;
+        s.spawn(move || log_stage(ledger, logs, to_commit, progress));
+        s.spawn(move || commit_stage(ledger, commits, progress));
+        let _ = ready.send(Ok(()));
+        validate_stage(ledger, &writer, jobs, to_log, progress)
+    });
+    if progress.failed.load(Ordering::SeqCst) {
+        // Later batches may have been logged without being applied; take
+        // the chain head and LSN from what actually reached disk.
+        writer.chain_head = chain::chain_head(ledger.log.last_event()?.as_ref());
+        writer.next_lsn = ledger.load_next_lsn()?;
+    } else {
+        *writer = head;
+    }
+    Ok(())
+}
+
+/// Stage each batch against the DB plus everything still in flight, then
+/// pass it on. Returns the writer state after the last batch.
+fn validate_stage(
+    ledger: &Ledger,
+    writer: &WriterState,
+    jobs: Receiver<Job>,
+    out: Sender<Sealed>,
+    progress: &Progress,
+) -> WriterState {
+    let mut head = WriterState {
+        chain_head: writer.chain_head.clone(),
+        next_lsn: writer.next_lsn,
+    };
+    let mut in_flight = InFlight::default();
+    let mut seq = 0;
+    for job in jobs {
+        if progress.failed.load(Ordering::SeqCst) {
+            let _ = job.reply.send(Err(poisoned()));
+            continue;
+        }
+        in_flight.retire(progress.committed.load(Ordering::SeqCst));
+        let staged = match stage_job(ledger, &head, &in_flight, job.entity, &job.commands) {
+            Ok(staged) => staged,
+            Err(e) => {
+                let _ = job.reply.send(Err(e));
+                continue;
+            }
+        };
+        let Some(last) = staged.events.last() else {
+            let _ = job.reply.send(Ok(Vec::new()));
+            continue;
+        };
+        seq += 1;
+        head.chain_head = last.event_hash.clone();
+        head.next_lsn += staged.events.len() as u64;
+        in_flight.record(seq, &staged);
+        let sealed = Sealed {
+            seq,
+            staged,
+            started: None,
+            reply: job.reply,
+        };
+        if let Err(e) = out.send(sealed) {
+            let _ = e.0.reply.send(Err(stopped()));
+        }
+    }
+    head
+}
+
+fn stage_job(
+    ledger: &Ledger,
+    head: &WriterState,
+    in_flight: &InFlight,
+    entity: u64,
+    commands: &[(u32, u8)],
+) -> Result<StagedBatch, LedgerError> {
+    let _span = span!("anchor_batch", entity, commands = commands.len());
+    ledger.ensure_live(entity)?;
+    let mut staged = StagedBatch::new(ledger.clock.now_millis(), commands.len());
+    in_flight.seed(&mut staged, entity, commands);
+    ledger.prefetch(&mut staged, entity, commands)?;
+    for &(prime, target_node) in commands {
+        ledger.stage_command(&mut staged, head, entity, prime, target_node)?;
+    }
+    Ok(staged)
+}
+
+/// Append each batch to the event log. Rotation runs before the append so
+/// a failed rotation leaves the batch unlogged.
+fn log_stage(ledger: &Ledger, batches: Receiver<Sealed>, out: Sender<Sealed>, progress: &Progress) {
+    for mut sealed in batches {
+        if progress.failed.load(Ordering::SeqCst) {
+            let _ = sealed.reply.send(Err(poisoned()));
+            continue;
+        }
+        let started = Instant::now();
+        let logged = ledger
+            .rotate_log()
+            .and_then(|()| ledger.append_events(&sealed.staged.events));
+        if let Err(e) = logged {
+            progress.failed.store(true, Ordering::SeqCst);
+            let _ = sealed.reply.send(Err(e));
+            continue;
+        }
+        sealed.started = Some(started);
+        if let Err(e) = out.send(sealed) {
+            let _ = e.0.reply.send(Err(stopped()));
+        }
+    }
+}
+
+/// Write each logged batch to the DB, then cache and publish its events.
+fn commit_stage(ledger: &Ledger, batches: Receiver<Sealed>, progress: &Progress) {
+    for sealed in batches {
+        if progress.failed.load(Ordering::SeqCst) {
+            let _ = sealed.reply.send(Err(poisoned()));
+            continue;
+        }
+        let StagedBatch {
+            events,
+            mut batch,
+            pending,
+            ..
+        } = sealed.staged;
+        let next_lsn = events.last().map_or(0, |evt| evt.lsn + 1);
+        let applied = ledger
+            .stage_crdt(&mut batch, &events)
+            .and_then(|()| ledger.stage_commit(&mut batch, &events, next_lsn))
+            .and_then(|()| ledger.apply_events(&events, batch));
+        if let Err(e) = applied {
+            progress.failed.store(true, Ordering::SeqCst);
+            let _ = sealed.reply.send(Err(e));
+            continue;
+        }
+        ledger.record_commit(&events, sealed.started.unwrap_or_else(Instant::now));
+        ledger.exponent_cache.put_all(&pending);
+        progress.committed.store(sealed.seq, Ordering::SeqCst);
+        ledger.subscribers.publish(&events);
+        let _ = sealed.reply.send(Ok(events));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipelined_batches_match_sequential_semantics() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Arc::new(Ledger::new(tmp.path()).unwrap());
+        let writer = PipelinedWriter::new(Arc::clone(&ledger), 2).unwrap();
+        let targets = [2u8, 4, 6, 1, 2, 4];
+        let pending = (0..36)
+            .map(|i| {
+                let target = targets[(i / 3) % targets.len()];
+                writer.submit(i as u64 % 3, vec![(2, target)]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let rejected = writer.submit(1, vec![(4, 0)]).unwrap();
+        let mut lsn = 0;
+        for batch in pending {
+            for evt in batch.wait().unwrap() {
+                assert_eq!(evt.lsn, lsn);
+                lsn += 1;
+            }
+        }
+        assert!(matches!(rejected.wait(), Err(LedgerError::UnknownPrime(4))));
+        writer.finish().unwrap();
+
+        assert_eq!(ledger.verify_chain().unwrap(), lsn);
+        assert_eq!(ledger.next_lsn().unwrap(), lsn);
+        // Each entity walks through `targets` twice, ending on 4.
+        assert_eq!(ledger.current_exponents(0, &[2]).unwrap(), vec![Some(4)]);
+        let events = ledger.anchor_batch(0, &[(2, 6)]).unwrap();
+        assert_eq!(events[0].lsn, lsn);
+        assert_eq!(events[0].msd_digits.to_int(), 2);
+    }
+}
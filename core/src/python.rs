@@ -1,63 +1,407 @@
-use nalgebra::{Quaternion, Unit, UnitQuaternion, Vector3};
+use nalgebra::{Unit, UnitQuaternion, Vector3};
+use numpy::ndarray::Array2;
+use numpy::{
+    Element, IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1, PyReadonlyArray2, PyUntypedArray,
+};
+use pyo3::exceptions::{PyOverflowError, PyValueError};
 use pyo3::prelude::*;
 
+use crate::dual_quat::{DualQuat, DualQuatParts};
+use crate::energy::CycleCounter;
 use crate::qp_encode::QpQuat;
+use crate::{Digit, LedgerEvent, Msd, MsdError};
 
+/// A fixed-length vector from Python: a 1-D numpy array, read in place when
+/// its dtype matches, or any sequence. Results go back as the same kind.
+pub struct Fixed<T, const N: usize> {
+    values: [T; N],
+    numpy: bool,
+}
+
+impl<'py, T, const N: usize> FromPyObject<'py> for Fixed<T, N>
+where
+    T: Element + Copy + FromPyObject<'py>,
+{
+    fn extract(ob: &'py PyAny) -> PyResult<Self> {
+        // rust-numpy panics when numpy can't be imported, and nothing can
+        // be an array before numpy is loaded anyway.
+        let numpy = numpy_loaded(ob.py()) && ob.downcast::<PyUntypedArray>().is_ok();
+        let array = if numpy {
+            ob.extract::<PyReadonlyArray1<T>>().ok()
+        } else {
+            None
+        };
+        let values = match array {
+            Some(array) => {
+                let view = array.as_array();
+                let len = view.len();
+                <[T; N]>::try_from(view.to_vec()).map_err(|_| {
+                    PyValueError::new_err(format!("expected {} values, got {}", N, len))
+                })?
+            }
+            None => ob.extract()?,
+        };
+        Ok(Fixed { values, numpy })
+    }
+}
+
+fn numpy_loaded(py: Python<'_>) -> bool {
+    py.import("sys")
+        .and_then(|sys| sys.getattr("modules"))
+        .and_then(|modules| modules.contains("numpy"))
+        .unwrap_or(false)
+}
+
+/// `ob` as an array, with an `ImportError` rather than a panic when numpy
+/// is missing.
+fn array<'py, A: FromPyObject<'py>>(ob: &'py PyAny) -> PyResult<A> {
+    ob.py().import("numpy")?;
+    ob.extract()
+}
+
+fn vector<T: Element + ToPyObject>(py: Python<'_>, values: &[T], numpy: bool) -> PyObject {
+    if numpy {
+        PyArray1::from_slice(py, values).to_object(py)
+    } else {
+        values.to_object(py)
+    }
+}
+
+/// An (N, `cols`) array's rows, or a `ValueError` naming the shape.
+fn rows<T: Element + Copy, const COLS: usize>(
+    array: &PyReadonlyArray2<T>,
+) -> PyResult<Vec<[T; COLS]>> {
+    let view = array.as_array();
+    if view.ncols() != COLS {
+        return Err(PyValueError::new_err(format!(
+            "expected shape (N, {}), got {:?}",
+            COLS,
+            view.shape()
+        )));
+    }
+    Ok(view
+        .rows()
+        .into_iter()
+        .map(|row| std::array::from_fn(|c| row[c]))
+        .collect())
+}
+
+fn matrix<'py, T: Element, const COLS: usize>(
+    py: Python<'py>,
+    rows: impl ExactSizeIterator<Item = [T; COLS]>,
+) -> &'py PyArray2<T> {
+    let n = rows.len();
+    let flat: Vec<T> = rows.flatten().collect();
+    Array2::from_shape_vec((n, COLS), flat)
+        .expect("rows of COLS values")
+        .into_pyarray(py)
+}
+
+type QuatArrays<'py> = (&'py PyArray2<f32>, &'py PyArray2<f32>);
+/// Ψ₁, Ψ₂, then both norms.
+type PackedArrays<'py> = (
+    &'py PyArray2<f32>,
+    &'py PyArray2<f32>,
+    &'py PyArray1<f32>,
+    &'py PyArray1<f32>,
+);
+
+fn quat_arrays<'py>(py: Python<'py>, packed: &[QpQuat]) -> QuatArrays<'py> {
+    let part = |half: usize| {
+        matrix(
+            py,
+            packed.iter().map(move |qp| {
+                let (q1, q2, _, _) = qp.to_parts();
+                [q1, q2][half]
+            }),
+        )
+    };
+    (part(0), part(1))
+}
+
+/// Ψ₁, Ψ₂ rows and norms as packed states; `ValueError` on mismatched shapes.
+fn packed_rows(
+    q1: &PyReadonlyArray2<f32>,
+    q2: &PyReadonlyArray2<f32>,
+    norms: Option<(&PyReadonlyArray1<f32>, &PyReadonlyArray1<f32>)>,
+) -> PyResult<Vec<QpQuat>> {
+    let (q1, q2) = (rows::<f32, 4>(q1)?, rows::<f32, 4>(q2)?);
+    let n = q1.len();
+    let (n1, n2) = match norms {
+        Some((n1, n2)) => (n1.as_array().to_vec(), n2.as_array().to_vec()),
+        None => (vec![1.0; n], vec![1.0; n]),
+    };
+    if q2.len() != n || n1.len() != n || n2.len() != n {
+        return Err(PyValueError::new_err(format!(
+            "row counts differ: {}, {}, {}, {}",
+            n,
+            q2.len(),
+            n1.len(),
+            n2.len()
+        )));
+    }
+    Ok((0..n)
+        .map(|r| QpQuat::from_parts((q1[r], q2[r], n1[r], n2[r])))
+        .collect())
+}
+
+/// Quaternions in these functions are (w, i, j, k). Vectors come back as
+/// numpy arrays when passed as numpy arrays, lists otherwise.
 #[pyfunction]
-pub fn py_pack_quaternion(exps: [i32; 8]) -> PyResult<([f32; 4], [f32; 4], f32, f32)> {
-    let q = QpQuat::pack(&exps);
-    let QpQuat {
-        psi1,
-        psi2,
-        psi1_norm,
-        psi2_norm,
-    } = q;
-    let q1: [f32; 4] = psi1.coords.into();
-    let q2: [f32; 4] = psi2.coords.into();
-    Ok((q1, q2, psi1_norm, psi2_norm))
+pub fn py_pack_quaternion(
+    py: Python<'_>,
+    exps: Fixed<i32, 8>,
+) -> PyResult<(PyObject, PyObject, f32, f32)> {
+    let (q1, q2, n1, n2) = QpQuat::pack(&exps.values).to_parts();
+    Ok((
+        vector(py, &q1, exps.numpy),
+        vector(py, &q2, exps.numpy),
+        n1,
+        n2,
+    ))
 }
 
 #[pyfunction]
 pub fn py_unpack_quaternion(
-    q1: [f32; 4],
-    q2: [f32; 4],
+    py: Python<'_>,
+    q1: Fixed<f32, 4>,
+    q2: Fixed<f32, 4>,
     norm1: f32,
     norm2: f32,
-) -> PyResult<[i32; 8]> {
-    let qp = QpQuat {
-        psi1: Quaternion::new(q1[0], q1[1], q1[2], q1[3]),
-        psi2: Quaternion::new(q2[0], q2[1], q2[2], q2[3]),
-        psi1_norm: norm1,
-        psi2_norm: norm2,
-    };
-    Ok(qp.unpack())
+) -> PyResult<PyObject> {
+    let exps = QpQuat::from_parts((q1.values, q2.values, norm1, norm2)).unpack();
+    Ok(vector(py, &exps, q1.numpy))
 }
 
+/// Norms are untouched by rotation, so only the quaternions go in and out.
 #[pyfunction]
 pub fn py_rotate_quaternion(
-    q1: [f32; 4],
-    q2: [f32; 4],
+    py: Python<'_>,
+    q1: Fixed<f32, 4>,
+    q2: Fixed<f32, 4>,
+    axis: [f32; 3],
+    angle: f32,
+) -> PyResult<(PyObject, PyObject)> {
+    let mut qp = QpQuat::from_parts((q1.values, q2.values, 1.0, 1.0));
+    qp.rotate(axis_rotation(axis, angle).into_inner());
+    let (r1, r2, _, _) = qp.to_parts();
+    Ok((vector(py, &r1, q1.numpy), vector(py, &r2, q1.numpy)))
+}
+
+/// [`py_pack_quaternion`] over an (N, 8) int32 array in one call: Ψ₁ and Ψ₂
+/// as (N, 4) float32 arrays, then both norms as (N,) arrays.
+#[pyfunction]
+pub fn py_pack_quaternion_batch<'py>(
+    py: Python<'py>,
+    exps: &'py PyAny,
+) -> PyResult<PackedArrays<'py>> {
+    let packed = QpQuat::pack_batch(&rows::<i32, 8>(&array(exps)?)?);
+    let (q1, q2) = quat_arrays(py, &packed);
+    let norms = |f: fn(&QpQuat) -> f32| packed.iter().map(f).collect::<Vec<_>>().into_pyarray(py);
+    Ok((q1, q2, norms(|qp| qp.psi1_norm), norms(|qp| qp.psi2_norm)))
+}
+
+/// Inverse of [`py_pack_quaternion_batch`]: an (N, 8) int32 array.
+#[pyfunction]
+pub fn py_unpack_quaternion_batch<'py>(
+    py: Python<'py>,
+    q1: &'py PyAny,
+    q2: &'py PyAny,
+    norm1: &'py PyAny,
+    norm2: &'py PyAny,
+) -> PyResult<&'py PyArray2<i32>> {
+    let norms = (array(norm1)?, array(norm2)?);
+    let packed = packed_rows(&array(q1)?, &array(q2)?, Some((&norms.0, &norms.1)))?;
+    Ok(matrix(py, QpQuat::unpack_batch(&packed).into_iter()))
+}
+
+/// [`py_rotate_quaternion`] of every row of two (N, 4) float32 arrays.
+#[pyfunction]
+pub fn py_rotate_quaternion_batch<'py>(
+    py: Python<'py>,
+    q1: &'py PyAny,
+    q2: &'py PyAny,
+    axis: [f32; 3],
+    angle: f32,
+) -> PyResult<QuatArrays<'py>> {
+    let mut packed = packed_rows(&array(q1)?, &array(q2)?, None)?;
+    let rotation = axis_rotation(axis, angle).into_inner();
+    for qp in &mut packed {
+        qp.rotate(rotation);
+    }
+    Ok(quat_arrays(py, &packed))
+}
+
+/// Rotate about `axis` by `angle`, then translate, with the eight exponents
+/// as a dual quaternion: `real` is exp₀…exp₃ and `dual` exp₄…exp₇.
+#[pyfunction]
+pub fn py_screw_quaternion(
+    real: [f32; 4],
+    dual: [f32; 4],
     axis: [f32; 3],
     angle: f32,
-) -> PyResult<([f32; 4], [f32; 4])> {
-    let axis_vec = Vector3::new(axis[0], axis[1], axis[2]);
-    let rotation = if axis_vec.norm_squared() == 0.0 {
-        Quaternion::identity()
+    translation: [f32; 3],
+) -> PyResult<DualQuatParts> {
+    let motion = DualQuat::motion(axis_rotation(axis, angle), Vector3::from(translation));
+    let mut dq = DualQuat::from_parts((real, dual));
+    dq.transform(&motion);
+    Ok(dq.to_parts())
+}
+
+/// The identity for a zero axis.
+fn axis_rotation(axis: [f32; 3], angle: f32) -> UnitQuaternion<f32> {
+    let axis_vec = Vector3::from(axis);
+    if axis_vec.norm_squared() == 0.0 {
+        UnitQuaternion::identity()
     } else {
         let unit_axis: Unit<Vector3<f32>> = Unit::new_normalize(axis_vec);
-        UnitQuaternion::from_axis_angle(&unit_axis, angle).into_inner()
-    };
-    let mut qp = QpQuat {
-        psi1: Quaternion::new(q1[0], q1[1], q1[2], q1[3]),
-        psi2: Quaternion::new(q2[0], q2[1], q2[2], q2[3]),
-        psi1_norm: 1.0,
-        psi2_norm: 1.0,
-    };
-    qp.rotate(rotation);
-    Ok((qp.psi1.coords.into(), qp.psi2.coords.into()))
+        UnitQuaternion::from_axis_angle(&unit_axis, angle)
+    }
 }
 
 #[pyfunction]
 pub fn py_energy_proxy() -> u64 {
-    QpQuat::energy_proxy()
+    CycleCounter::now()
+}
+
+/// [`crate::bench`] as a JSON string.
+#[pyfunction]
+#[pyo3(signature = (iterations=100_000))]
+pub fn py_bench_packing(iterations: u64) -> PyResult<String> {
+    serde_json::to_string(&crate::bench(iterations))
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn msd_err(e: MsdError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// Radix-4 signed digits for Python, so callers stop decoding digit lists
+/// by hand.
+#[pyclass(name = "Msd")]
+#[derive(Clone)]
+pub struct PyMsd(pub Msd);
+
+#[pymethods]
+impl PyMsd {
+    #[new]
+    fn new(n: i128) -> Self {
+        PyMsd(Msd::from_i128(n))
+    }
+
+    #[staticmethod]
+    fn from_int(n: i128) -> Self {
+        PyMsd(Msd::from_i128(n))
+    }
+
+    /// Little-endian digits; must already be normalized.
+    #[staticmethod]
+    fn from_digits(digits: Vec<Digit>) -> PyResult<Self> {
+        Msd::try_from_digits(digits).map(PyMsd).map_err(msd_err)
+    }
+
+    #[staticmethod]
+    fn from_bytes(raw: &[u8]) -> PyResult<Self> {
+        Msd::from_bytes(raw).map(PyMsd).map_err(msd_err)
+    }
+
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        text.parse().map(PyMsd).map_err(msd_err)
+    }
+
+    fn to_int(&self) -> PyResult<i128> {
+        self.0
+            .to_i128()
+            .ok_or_else(|| PyOverflowError::new_err("MSD value out of i128 range"))
+    }
+
+    #[getter]
+    fn digits(&self) -> Vec<Digit> {
+        self.0.to_vec()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    fn weight(&self) -> usize {
+        self.0.weight()
+    }
+
+    fn span(&self) -> usize {
+        self.0.span()
+    }
+
+    /// The digits as an int8 numpy array; numpy must be installed.
+    fn to_numpy(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let numpy = py.import("numpy")?;
+        Ok(numpy
+            .call_method1("array", (self.0.to_vec(), "int8"))?
+            .into())
+    }
+
+    /// Lets `numpy.asarray(msd)` see the digits.
+    #[pyo3(signature = (dtype=None))]
+    fn __array__(&self, py: Python<'_>, dtype: Option<PyObject>) -> PyResult<PyObject> {
+        let array = self.to_numpy(py)?;
+        match dtype {
+            Some(dtype) => Ok(array.call_method1(py, "astype", (dtype,))?),
+            None => Ok(array),
+        }
+    }
+
+    fn __int__(&self) -> PyResult<i128> {
+        self.to_int()
+    }
+
+    fn __add__(&self, other: &PyMsd) -> PyMsd {
+        PyMsd(&self.0 + &other.0)
+    }
+
+    fn __sub__(&self, other: &PyMsd) -> PyMsd {
+        PyMsd(&self.0 - &other.0)
+    }
+
+    fn __mul__(&self, other: &PyMsd) -> PyMsd {
+        PyMsd(&self.0 * &other.0)
+    }
+
+    fn __neg__(&self) -> PyMsd {
+        PyMsd(-self.0.clone())
+    }
+
+    fn __eq__(&self, other: &PyMsd) -> bool {
+        self.0 == other.0
+    }
+
+    fn __hash__(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Msd({})", self.0)
+    }
+}
+
+#[pymethods]
+impl LedgerEvent {
+    /// `msd_digits` as an [`PyMsd`].
+    #[getter]
+    fn msd(&self) -> PyMsd {
+        PyMsd(self.msd_digits.clone())
+    }
+
+    /// `"anchor"`, `"centroid_flip"` or `"tombstone"`.
+    #[getter(kind)]
+    fn kind_py(&self) -> &'static str {
+        self.kind().as_str()
+    }
 }
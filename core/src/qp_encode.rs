@@ -1,39 +1,218 @@
 //! Quaternion pack/unpack for 8-prime star
 //! Two quaternions Ψ₁, Ψ₂ ←→ 8 exponents [exp₀…exp₇]
+//! Each quaternion is stored unit-length with its own norm; outside Rust
+//! (Python tuples, stored bytes) components are always in (w, i, j, k) order.
+//!
+//! Binary layout of [`QpQuatOf::to_bytes`], stable across releases and
+//! languages; all scalars little-endian IEEE 754, `S` = 4 for `f32`, 8 for
+//! `f64`:
+//!
+//! | offset      | size | field                       |
+//! |-------------|------|-----------------------------|
+//! | 0           | 1    | [`QPQUAT_FORMAT_VERSION`]   |
+//! | 1           | 4·S  | Ψ₁ as w, i, j, k            |
+//! | 1 + 4·S     | 4·S  | Ψ₂ as w, i, j, k            |
+//! | 1 + 8·S     | S    | ‖Ψ₁‖                        |
+//! | 1 + 9·S     | S    | ‖Ψ₂‖                        |
+//!
+//! so 41 bytes for [`QpQuat`]: the 40-byte body plus the version.
 
-use nalgebra::{Quaternion, Vector4};
+use std::fmt;
+use std::hint::black_box;
+use std::marker::PhantomData;
+use std::ops::Mul;
+use std::time::Instant;
+
+use nalgebra::{Quaternion, RealField};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::energy::{CycleCounter, EnergyMeter};
+use crate::octonion::OctonionOf;
+use crate::LedgerError;
+
+/// Leading byte of [`QpQuatOf::to_bytes`].
+pub const QPQUAT_FORMAT_VERSION: u8 = 1;
+/// Ψ₁, Ψ₂, then both norms.
+const FIELDS: usize = 10;
+
+/// Component type of a [`QpQuatOf`]: `f32` for compact, low-power state, or
+/// `f64`, which keeps exponents exact far beyond where `f32` rounding
+/// starts to lose them.
+pub trait QuatScalar: RealField + Copy {
+    const BYTES: usize;
+
+    fn from_exponent(exponent: i32) -> Self;
+
+    /// Nearest exponent, saturating at the `i32` range.
+    fn to_exponent(self) -> i32;
+
+    fn write_le(self, out: &mut Vec<u8>);
+
+    /// From exactly [`QuatScalar::BYTES`] little-endian bytes.
+    fn read_le(raw: &[u8]) -> Self;
+}
+
+impl QuatScalar for f32 {
+    const BYTES: usize = 4;
+
+    fn from_exponent(exponent: i32) -> Self {
+        exponent as f32
+    }
+
+    fn to_exponent(self) -> i32 {
+        self.round() as i32
+    }
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_le(raw: &[u8]) -> Self {
+        f32::from_le_bytes(raw.try_into().expect("4 bytes"))
+    }
+}
+
+impl QuatScalar for f64 {
+    const BYTES: usize = 8;
+
+    fn from_exponent(exponent: i32) -> Self {
+        exponent.into()
+    }
+
+    fn to_exponent(self) -> i32 {
+        self.round() as i32
+    }
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_le(raw: &[u8]) -> Self {
+        f64::from_le_bytes(raw.try_into().expect("8 bytes"))
+    }
+}
+
+/// `q` scaled to unit length, and its norm; the identity if `q` is zero.
+pub(crate) fn unit_and_norm<T: QuatScalar>(q: Quaternion<T>) -> (Quaternion<T>, T) {
+    let norm = q.norm();
+    if norm > T::zero() {
+        (q / norm, norm)
+    } else {
+        (Quaternion::identity(), norm)
+    }
+}
+
+/// `(psi1, psi2, psi1_norm, psi2_norm)` with each quaternion as (w, i, j, k).
+pub type QpQuatParts<T = f32> = ([T; 4], [T; 4], T, T);
 
 /// Paired quaternions representing eight prime exponents.
-pub struct QpQuat {
-    pub psi1: Quaternion<f32>,
-    pub psi2: Quaternion<f32>,
-    pub psi1_norm: f32,
-    pub psi2_norm: f32,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QpQuatOf<T: QuatScalar> {
+    pub psi1: Quaternion<T>,
+    pub psi2: Quaternion<T>,
+    pub psi1_norm: T,
+    pub psi2_norm: T,
 }
 
-impl QpQuat {
-    /// Pack eight `i32` exponents into two unit quaternions.
-    pub fn pack(exponents: &[i32; 8]) -> Self {
-        fn build_quaternion(chunk: &[i32]) -> (Quaternion<f32>, f32) {
-            let v = Vector4::new(
-                chunk[0] as f32,
-                chunk[1] as f32,
-                chunk[2] as f32,
-                chunk[3] as f32,
-            );
-            let mut q = Quaternion::new(v[0], v[1], v[2], v[3]);
-            let norm = q.norm();
-            if norm > 0.0 {
-                q /= norm;
-            } else {
-                q = Quaternion::identity();
+/// Serde shape of a [`QpQuatOf`] in human-readable formats.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "QpQuat")]
+struct NamedParts<T> {
+    psi1: [T; 4],
+    psi2: [T; 4],
+    psi1_norm: T,
+    psi2_norm: T,
+}
+
+/// Named parts in human-readable formats; [`QpQuatOf::to_bytes`] otherwise.
+impl<T: QuatScalar + Serialize> Serialize for QpQuatOf<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let (psi1, psi2, psi1_norm, psi2_norm) = self.to_parts();
+            NamedParts {
+                psi1,
+                psi2,
+                psi1_norm,
+                psi2_norm,
+            }
+            .serialize(serializer)
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+/// Either form is accepted.
+impl<'de, T: QuatScalar + Deserialize<'de>> Deserialize<'de> for QpQuatOf<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QuatVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: QuatScalar + Deserialize<'de>> Visitor<'de> for QuatVisitor<T> {
+            type Value = QpQuatOf<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "packed quaternions")
+            }
+
+            fn visit_bytes<E: de::Error>(self, raw: &[u8]) -> Result<Self::Value, E> {
+                QpQuatOf::from_bytes(raw).map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let p = NamedParts::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(QpQuatOf::from_parts((
+                    p.psi1,
+                    p.psi2,
+                    p.psi1_norm,
+                    p.psi2_norm,
+                )))
             }
-            (q, norm)
         }
 
-        let (psi1, psi1_norm) = build_quaternion(&exponents[0..4]);
-        let (psi2, psi2_norm) = build_quaternion(&exponents[4..8]);
-        QpQuat {
+        let visitor = QuatVisitor(PhantomData);
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_map(visitor)
+        } else {
+            deserializer.deserialize_bytes(visitor)
+        }
+    }
+}
+
+/// Exponents that [`QpQuatOf::pack_checked`] could not pack losslessly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackError {
+    pub exponents: [i32; 8],
+    /// Unpacked minus original, per component; nonzero where rounding lost
+    /// the exponent.
+    pub residuals: [i64; 8],
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "exponents {:?} do not survive packing (residuals {:?})",
+            self.exponents, self.residuals
+        )
+    }
+}
+
+impl std::error::Error for PackError {}
+
+/// Single precision, as stored in `quat_state` and handed to Python.
+pub type QpQuat = QpQuatOf<f32>;
+/// Double precision, for exponents beyond what `f32` holds exactly.
+pub type QpQuat64 = QpQuatOf<f64>;
+
+impl<T: QuatScalar> QpQuatOf<T> {
+    /// Pack eight `i32` exponents into two unit quaternions.
+    pub fn pack(exponents: &[i32; 8]) -> Self {
+        let e = |i: usize| T::from_exponent(exponents[i]);
+        let (psi1, psi1_norm) = unit_and_norm(Quaternion::new(e(0), e(1), e(2), e(3)));
+        let (psi2, psi2_norm) = unit_and_norm(Quaternion::new(e(4), e(5), e(6), e(7)));
+        QpQuatOf {
             psi1,
             psi2,
             psi1_norm,
@@ -41,78 +220,424 @@ impl QpQuat {
         }
     }
 
+    /// Like [`QpQuatOf::pack`], but fails unless unpacking gives back
+    /// exactly `exponents`.
+    pub fn pack_checked(exponents: &[i32; 8]) -> Result<Self, PackError> {
+        let packed = Self::pack(exponents);
+        let unpacked = packed.unpack();
+        if unpacked == *exponents {
+            return Ok(packed);
+        }
+        let mut residuals = [0; 8];
+        for (r, (&u, &e)) in residuals.iter_mut().zip(unpacked.iter().zip(exponents)) {
+            *r = i64::from(u) - i64::from(e);
+        }
+        Err(PackError {
+            exponents: *exponents,
+            residuals,
+        })
+    }
+
     /// Unpack the quaternions back into integer exponents using the stored norms.
     pub fn unpack(&self) -> [i32; 8] {
-        let psi1 = &self.psi1;
-        let psi2 = &self.psi2;
-        [
-            (psi1.w * self.psi1_norm).round() as i32,
-            (psi1.i * self.psi1_norm).round() as i32,
-            (psi1.j * self.psi1_norm).round() as i32,
-            (psi1.k * self.psi1_norm).round() as i32,
-            (psi2.w * self.psi2_norm).round() as i32,
-            (psi2.i * self.psi2_norm).round() as i32,
-            (psi2.j * self.psi2_norm).round() as i32,
-            (psi2.k * self.psi2_norm).round() as i32,
-        ]
+        let (psi1, psi2, n1, n2) = self.to_parts();
+        let [a, b, c, d] = psi1.map(|x| (x * n1).to_exponent());
+        let [e, f, g, h] = psi2.map(|x| (x * n2).to_exponent());
+        [a, b, c, d, e, f, g, h]
     }
 
-    /// Rotate both quaternions by `q` using conjugation (`q * Ψ * q⁻¹`).
-    pub fn rotate(&mut self, q: Quaternion<f32>) {
-        let mut rot = q;
-        let norm = rot.norm();
-        if norm > 0.0 {
-            rot /= norm;
+    pub fn from_parts((psi1, psi2, psi1_norm, psi2_norm): QpQuatParts<T>) -> Self {
+        let quat = |[w, i, j, k]: [T; 4]| Quaternion::new(w, i, j, k);
+        QpQuatOf {
+            psi1: quat(psi1),
+            psi2: quat(psi2),
+            psi1_norm,
+            psi2_norm,
+        }
+    }
+
+    pub fn to_parts(&self) -> QpQuatParts<T> {
+        let parts = |q: &Quaternion<T>| [q.w, q.i, q.j, q.k];
+        (
+            parts(&self.psi1),
+            parts(&self.psi2),
+            self.psi1_norm,
+            self.psi2_norm,
+        )
+    }
+
+    /// [`QPQUAT_FORMAT_VERSION`], then the parts as little-endian scalars;
+    /// see the module docs for the layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (psi1, psi2, n1, n2) = self.to_parts();
+        let mut out = Vec::with_capacity(1 + FIELDS * T::BYTES);
+        out.push(QPQUAT_FORMAT_VERSION);
+        for &f in psi1.iter().chain(&psi2).chain([&n1, &n2]) {
+            f.write_le(&mut out);
+        }
+        out
+    }
+
+    /// Inverse of [`QpQuatOf::to_bytes`]. `f32` values written before the
+    /// format was versioned, the same bytes without the leading version,
+    /// are read too.
+    pub fn from_bytes(raw: &[u8]) -> Result<Self, LedgerError> {
+        let len = FIELDS * T::BYTES;
+        let fields = match raw.split_first() {
+            _ if T::BYTES == 4 && raw.len() == len => raw,
+            Some((&QPQUAT_FORMAT_VERSION, rest)) if rest.len() == len => rest,
+            _ => {
+                return Err(LedgerError::Corruption(format!(
+                    "malformed packed quaternions ({} bytes)",
+                    raw.len()
+                )))
+            }
+        };
+        let f: Vec<T> = fields.chunks_exact(T::BYTES).map(T::read_le).collect();
+        Ok(QpQuatOf::from_parts((
+            [f[0], f[1], f[2], f[3]],
+            [f[4], f[5], f[6], f[7]],
+            f[8],
+            f[9],
+        )))
+    }
+
+    /// Ψ₁ and Ψ₂ scaled back up by their norms.
+    pub fn quaternions(&self) -> (Quaternion<T>, Quaternion<T>) {
+        (self.psi1 * self.psi1_norm, self.psi2 * self.psi2_norm)
+    }
+
+    /// Split each quaternion into unit part and norm, as `pack` does.
+    pub fn from_quaternions(q1: Quaternion<T>, q2: Quaternion<T>) -> Self {
+        let (psi1, psi1_norm) = unit_and_norm(q1);
+        let (psi2, psi2_norm) = unit_and_norm(q2);
+        QpQuatOf {
+            psi1,
+            psi2,
+            psi1_norm,
+            psi2_norm,
+        }
+    }
+
+    /// Norm of all eight components together: `√(‖Ψ₁‖² + ‖Ψ₂‖²)`.
+    pub fn norm(&self) -> T {
+        (self.psi1_norm * self.psi1_norm + self.psi2_norm * self.psi2_norm).sqrt()
+    }
+
+    /// Conjugates Ψ₁ and Ψ₂ separately; norms are unchanged.
+    pub fn conjugate(&self) -> Self {
+        QpQuatOf {
+            psi1: self.psi1.conjugate(),
+            psi2: self.psi2.conjugate(),
+            ..*self
+        }
+    }
+
+    /// Component-wise inverse, so `x * x.try_inverse()` is the identity
+    /// pair; `None` if either quaternion is zero.
+    pub fn try_inverse(&self) -> Option<Self> {
+        if self.psi1_norm > T::zero() && self.psi2_norm > T::zero() {
+            Some(QpQuatOf {
+                psi1: self.psi1.conjugate(),
+                psi2: self.psi2.conjugate(),
+                psi1_norm: T::one() / self.psi1_norm,
+                psi2_norm: T::one() / self.psi2_norm,
+            })
         } else {
-            rot = Quaternion::identity();
+            None
         }
+    }
+
+    /// The pair multiplied as one [`OctonionOf`], so Ψ₂ feeds into Ψ₁ and
+    /// back; `*` multiplies them separately instead.
+    pub fn coupled_mul(&self, rhs: &Self) -> Self {
+        let octonion = |qp: &Self| {
+            let (a, b) = qp.quaternions();
+            OctonionOf { a, b }
+        };
+        let product = octonion(self) * octonion(rhs);
+        Self::from_quaternions(product.a, product.b)
+    }
+
+    /// Rotate both quaternions by `q` using conjugation (`q * Ψ * q⁻¹`).
+    pub fn rotate(&mut self, q: Quaternion<T>) {
+        let (rot, _) = unit_and_norm(q);
         let conj = rot.conjugate();
         self.psi1 = rot * self.psi1 * conj;
         self.psi2 = rot * self.psi2 * conj;
     }
 
-    /// Energy proxy counter (PMCCNTR on ARM NEON, RDTSC on x86_64, wall-clock fallback otherwise).
-    #[cfg(target_arch = "aarch64")]
-    pub fn energy_proxy() -> u64 {
-        let val: u64;
-        unsafe {
-            core::arch::asm!("mrs {0}, pmccntr_el0", out(reg) val);
+    /// [`QpQuatOf::pack`] of each state, bit for bit. States go through in
+    /// blocks of [`LANES`], laid out component by component so each step
+    /// runs across the whole block; the loops are scalar Rust, so whether
+    /// that beats `pack` is up to the optimiser. [`bench`] measures both.
+    pub fn pack_batch(states: &[[i32; 8]]) -> Vec<Self> {
+        let mut out = Vec::with_capacity(states.len());
+        for block in states.chunks(LANES) {
+            // comps[c][lane]: component c in (w, i, j, k, w, i, j, k) order.
+            let mut comps = [[T::zero(); LANES]; 8];
+            for (lane, state) in block.iter().enumerate() {
+                for (c, &e) in state.iter().enumerate() {
+                    comps[c][lane] = T::from_exponent(e);
+                }
+            }
+            let mut norms = [[T::zero(); LANES]; 2];
+            for (half, norm) in norms.iter_mut().enumerate() {
+                let [w, i, j, k] = [0, 1, 2, 3].map(|c| comps[4 * half + c]);
+                for lane in 0..LANES {
+                    // Summed in nalgebra's (i, j, k, w) order to match `pack`.
+                    let mut sq = T::zero();
+                    for x in [i[lane], j[lane], k[lane], w[lane]] {
+                        sq += x * x;
+                    }
+                    norm[lane] = sq.sqrt();
+                }
+            }
+            for (c, row) in comps.iter_mut().enumerate() {
+                let norm = &norms[c / 4];
+                for (x, &n) in row.iter_mut().zip(norm) {
+                    *x = if n > T::zero() { *x / n } else { T::zero() };
+                }
+            }
+            for lane in 0..block.len() {
+                let part = |half: usize| {
+                    let q = [0, 1, 2, 3].map(|c| comps[4 * half + c][lane]);
+                    // A zero chunk packs as the identity, as in `pack`.
+                    if norms[half][lane] > T::zero() {
+                        q
+                    } else {
+                        [T::one(), T::zero(), T::zero(), T::zero()]
+                    }
+                };
+                out.push(Self::from_parts((
+                    part(0),
+                    part(1),
+                    norms[0][lane],
+                    norms[1][lane],
+                )));
+            }
+        }
+        out
+    }
+
+    /// [`QpQuatOf::unpack`] of each, in the same blocked layout as
+    /// [`QpQuatOf::pack_batch`].
+    pub fn unpack_batch(packed: &[Self]) -> Vec<[i32; 8]> {
+        let mut out = Vec::with_capacity(packed.len());
+        for block in packed.chunks(LANES) {
+            let mut scaled = [[T::zero(); LANES]; 8];
+            for (lane, qp) in block.iter().enumerate() {
+                let (psi1, psi2, n1, n2) = qp.to_parts();
+                for c in 0..4 {
+                    scaled[c][lane] = psi1[c];
+                    scaled[c + 4][lane] = psi2[c];
+                }
+                for row in scaled.iter_mut().take(4) {
+                    row[lane] *= n1;
+                }
+                for row in scaled.iter_mut().skip(4) {
+                    row[lane] *= n2;
+                }
+            }
+            out.extend((0..block.len()).map(|lane| scaled.map(|row| row[lane].to_exponent())));
+        }
+        out
+    }
+
+    /// The change taking `prev` to `next`: a left rotation of each
+    /// quaternion plus the change in each norm.
+    pub fn delta(prev: &Self, next: &Self) -> QpDeltaOf<T> {
+        QpDeltaOf {
+            rot1: next.psi1 * prev.psi1.conjugate(),
+            rot2: next.psi2 * prev.psi2.conjugate(),
+            norm1_change: next.psi1_norm - prev.psi1_norm,
+            norm2_change: next.psi2_norm - prev.psi2_norm,
+        }
+    }
+}
+
+/// Hamilton product of Ψ₁ with Ψ₁ and Ψ₂ with Ψ₂; norms multiply.
+impl<T: QuatScalar> Mul for QpQuatOf<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        QpQuatOf {
+            psi1: self.psi1 * rhs.psi1,
+            psi2: self.psi2 * rhs.psi2,
+            psi1_norm: self.psi1_norm * rhs.psi1_norm,
+            psi2_norm: self.psi2_norm * rhs.psi2_norm,
+        }
+    }
+}
+
+/// A state packed in `f32` when that round-trips exactly, promoted to
+/// `f64` otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnyQpQuat {
+    Single(QpQuat),
+    Double(QpQuat64),
+}
+
+impl AnyQpQuat {
+    /// Fails, with the `f64` residuals, only if neither precision gives
+    /// back `exponents` exactly.
+    pub fn pack(exponents: &[i32; 8]) -> Result<Self, PackError> {
+        match QpQuat::pack_checked(exponents) {
+            Ok(single) => Ok(AnyQpQuat::Single(single)),
+            Err(_) => QpQuat64::pack_checked(exponents).map(AnyQpQuat::Double),
+        }
+    }
+
+    pub fn unpack(&self) -> [i32; 8] {
+        match self {
+            AnyQpQuat::Single(qp) => qp.unpack(),
+            AnyQpQuat::Double(qp) => qp.unpack(),
+        }
+    }
+
+    pub fn is_promoted(&self) -> bool {
+        matches!(self, AnyQpQuat::Double(_))
+    }
+
+    /// [`QpQuatOf::to_bytes`] of either precision; the length tells them
+    /// apart.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            AnyQpQuat::Single(qp) => qp.to_bytes(),
+            AnyQpQuat::Double(qp) => qp.to_bytes(),
+        }
+    }
+
+    pub fn from_bytes(raw: &[u8]) -> Result<Self, LedgerError> {
+        if raw.len() == 1 + FIELDS * f64::BYTES {
+            QpQuat64::from_bytes(raw).map(AnyQpQuat::Double)
+        } else {
+            QpQuat::from_bytes(raw).map(AnyQpQuat::Single)
+        }
+    }
+}
+
+/// Difference between two packed states, from [`QpQuatOf::delta`]. Both
+/// quaternions are unit length, so each step is a rotation; the norms
+/// change additively so a zero chunk can grow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QpDeltaOf<T: QuatScalar> {
+    pub rot1: Quaternion<T>,
+    pub rot2: Quaternion<T>,
+    pub norm1_change: T,
+    pub norm2_change: T,
+}
+
+pub type QpDelta = QpDeltaOf<f32>;
+pub type QpDelta64 = QpDeltaOf<f64>;
+
+impl<T: QuatScalar> QpDeltaOf<T> {
+    /// `prev` moved by this delta; equals the `next` it was taken against
+    /// up to float rounding.
+    pub fn apply(&self, prev: &QpQuatOf<T>) -> QpQuatOf<T> {
+        QpQuatOf {
+            psi1: self.rot1 * prev.psi1,
+            psi2: self.rot2 * prev.psi2,
+            psi1_norm: prev.psi1_norm + self.norm1_change,
+            psi2_norm: prev.psi2_norm + self.norm2_change,
+        }
+    }
+
+    /// The delta taking `next` back to `prev`.
+    pub fn inverse(&self) -> Self {
+        QpDeltaOf {
+            rot1: self.rot1.conjugate(),
+            rot2: self.rot2.conjugate(),
+            norm1_change: -self.norm1_change,
+            norm2_change: -self.norm2_change,
         }
-        val
     }
+}
+
+/// States packed side by side in [`QpQuatOf::pack_batch`].
+pub const LANES: usize = 8;
+
+/// Average cost of one operation in a [`bench`] run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OpCost {
+    /// Counts of [`CycleCounter`], in units of [`BenchReport::cycle_source`].
+    pub cycles_per_op: f64,
+    pub ns_per_op: f64,
+}
+
+/// Per-operation cost of [`QpQuat`] pack, unpack and rotate on this host.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BenchReport {
+    pub iterations: u64,
+    pub cycle_source: &'static str,
+    pub pack: OpCost,
+    /// Per state, packed [`LANES`] at a time by [`QpQuatOf::pack_batch`].
+    pub pack_batch: OpCost,
+    pub unpack: OpCost,
+    pub rotate: OpCost,
+}
 
-    #[cfg(all(not(target_arch = "aarch64"), target_arch = "x86_64"))]
-    pub fn energy_proxy() -> u64 {
-        unsafe { std::arch::x86_64::_rdtsc() }
+/// Time `op` over `iterations` runs on the cycle counter and a monotonic
+/// clock.
+fn measure(iterations: u64, mut op: impl FnMut(u64)) -> OpCost {
+    let start = Instant::now();
+    let cycles_start = CycleCounter.read().unwrap_or_default();
+    for n in 0..iterations {
+        op(n);
     }
+    let cycles = CycleCounter
+        .read()
+        .unwrap_or_default()
+        .wrapping_sub(cycles_start);
+    let ns = start.elapsed().as_nanos() as f64;
+    let ops = iterations.max(1) as f64;
+    OpCost {
+        cycles_per_op: cycles as f64 / ops,
+        ns_per_op: ns / ops,
+    }
+}
 
-    #[cfg(all(not(target_arch = "aarch64"), not(target_arch = "x86_64")))]
-    pub fn energy_proxy() -> u64 {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as u64
+/// Run pack, unpack and rotate `iterations` times each over varying
+/// exponents and report what one call costs, for sizing deployments from
+/// on-device numbers. The same states also go through `pack_batch`, so the
+/// two pack costs show what blocking buys on this host.
+pub fn bench(iterations: u64) -> BenchReport {
+    let state = |n: u64| std::array::from_fn(|c| ((n as i32).wrapping_mul(7) - 3 * c as i32) % 41);
+    let packed = QpQuat::pack(&state(1));
+    let rot = Quaternion::new(1.0, 0.5, -0.25, 0.75);
+    let blocks = measure(iterations.div_ceil(LANES as u64), |n| {
+        let block: [[i32; 8]; LANES] = std::array::from_fn(|l| state(n * LANES as u64 + l as u64));
+        black_box(QpQuat::pack_batch(black_box(&block)));
+    });
+    BenchReport {
+        iterations,
+        cycle_source: CycleCounter::source().name(),
+        pack: measure(iterations, |n| {
+            black_box(QpQuat::pack(black_box(&state(n))));
+        }),
+        pack_batch: OpCost {
+            cycles_per_op: blocks.cycles_per_op / LANES as f64,
+            ns_per_op: blocks.ns_per_op / LANES as f64,
+        },
+        unpack: measure(iterations, |_| {
+            black_box(black_box(&packed).unpack());
+        }),
+        rotate: measure(iterations, |_| {
+            let mut qp = black_box(packed);
+            qp.rotate(black_box(rot));
+            black_box(qp);
+        }),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::QpQuat;
-    use nalgebra::Quaternion;
+    use super::*;
+    use crate::octonion::Octonion64;
 
     fn norms_of_exponents(exponents: &[i32; 8]) -> (f32, f32) {
-        let norm_chunk = |chunk: &[i32]| {
-            chunk
-                .iter()
-                .map(|&e| (e * e) as f32)
-                .sum::<f32>()
-                .sqrt()
-        };
-        (
-            norm_chunk(&exponents[0..4]),
-            norm_chunk(&exponents[4..8]),
-        )
+        let norm_chunk = |chunk: &[i32]| chunk.iter().map(|&e| (e * e) as f32).sum::<f32>().sqrt();
+        (norm_chunk(&exponents[0..4]), norm_chunk(&exponents[4..8]))
     }
 
     #[test]
@@ -139,6 +664,152 @@ mod tests {
         assert_eq!(recovered, exponents);
     }
 
+    #[test]
+    fn parts_and_bytes_round_trip_w_first() {
+        let qp = QpQuat::pack(&[3, 0, 0, 4, 0, 0, 0, -2]);
+        let parts = qp.to_parts();
+        assert_eq!(parts.0, [0.6, 0.0, 0.0, 0.8]);
+        assert_eq!(parts.1, [0.0, 0.0, 0.0, -1.0]);
+        assert_eq!(
+            QpQuat::from_parts(parts).unpack(),
+            [3, 0, 0, 4, 0, 0, 0, -2]
+        );
+
+        let bytes = qp.to_bytes();
+        assert_eq!((bytes.len(), bytes[0]), (41, QPQUAT_FORMAT_VERSION));
+        assert_eq!(QpQuat::from_bytes(&bytes).unwrap(), qp);
+        assert_eq!(QpQuat::from_bytes(&bytes[1..]).unwrap(), qp);
+        let mut future = bytes.clone();
+        future[0] = QPQUAT_FORMAT_VERSION + 1;
+        assert!(QpQuat::from_bytes(&future).is_err());
+        assert!(QpQuat::from_bytes(&bytes[..30]).is_err());
+    }
+
+    #[test]
+    fn serde_uses_named_parts_or_fixed_bytes() {
+        use serde::de::value::{BytesDeserializer, Error};
+
+        let qp = QpQuat::pack(&[3, 0, 4, 0, 0, 0, 0, -2]);
+        let json = serde_json::to_string(&qp).unwrap();
+        assert_eq!(
+            json,
+            r#"{"psi1":[0.6,0.0,0.8,0.0],"psi2":[0.0,0.0,0.0,-1.0],"psi1_norm":5.0,"psi2_norm":2.0}"#
+        );
+        assert_eq!(serde_json::from_str::<QpQuat>(&json).unwrap(), qp);
+
+        let bytes = qp.to_bytes();
+        assert_eq!(bytes.len(), 41);
+        assert_eq!(bytes[0], QPQUAT_FORMAT_VERSION);
+        assert_eq!(&bytes[33..37], &5.0f32.to_le_bytes());
+        let de = BytesDeserializer::<Error>::new(&bytes);
+        assert_eq!(QpQuat::deserialize(de).unwrap(), qp);
+        assert!(QpQuat::deserialize(BytesDeserializer::<Error>::new(&bytes[..9])).is_err());
+    }
+
+    #[test]
+    fn f64_keeps_large_exponents_exact() {
+        let exponents = [123_456_789, -7, 1, 0, 3, -98_765_432, 55_555_555, 2];
+        let wide = QpQuat64::pack(&exponents);
+        assert_eq!(wide.unpack(), exponents);
+        assert_ne!(QpQuat::pack(&exponents).unpack(), exponents);
+
+        let bytes = wide.to_bytes();
+        assert_eq!(bytes.len(), 81);
+        assert_eq!(QpQuat64::from_bytes(&bytes).unwrap(), wide);
+        assert!(QpQuat64::from_bytes(&bytes[1..]).is_err());
+        assert!(QpQuat::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn packing_promotes_to_f64_when_f32_loses_exponents() {
+        let small = AnyQpQuat::pack(&[3, 0, 4, 0, 1, -1, 1, -1]).unwrap();
+        assert!(!small.is_promoted());
+        let exponents = [123_456_789, -7, 1, 0, 3, -98_765_432, 55_555_555, 2];
+        let large = AnyQpQuat::pack(&exponents).unwrap();
+        assert!(large.is_promoted());
+        assert_eq!(large.unpack(), exponents);
+        for any in [small, large] {
+            assert_eq!(AnyQpQuat::from_bytes(&any.to_bytes()).unwrap(), any);
+        }
+        let extremes = [i32::MAX, i32::MIN, -1, 1, i32::MIN, 0, i32::MAX, 7];
+        assert_eq!(AnyQpQuat::pack(&extremes).unwrap().unpack(), extremes);
+    }
+
+    #[test]
+    fn checked_packing_reports_residuals() {
+        let small = [7, 0, -1, 2, -3, 5, 11, -13];
+        assert_eq!(QpQuat::pack_checked(&small).unwrap(), QpQuat::pack(&small));
+
+        let large = [123_456_789, -7, 1, 0, 3, -98_765_432, 55_555_555, 2];
+        let err = QpQuat::pack_checked(&large).unwrap_err();
+        assert_eq!(err.exponents, large);
+        let unpacked = QpQuat::pack(&large).unpack();
+        for i in 0..8 {
+            assert_eq!(
+                err.residuals[i],
+                i64::from(unpacked[i]) - i64::from(large[i])
+            );
+        }
+        assert!(err.residuals.iter().any(|&r| r != 0));
+        assert!(QpQuat64::pack_checked(&large).is_ok());
+    }
+
+    #[test]
+    fn batches_match_scalar_packing() {
+        let mut states: Vec<[i32; 8]> = (0..37)
+            .map(|n: i32| std::array::from_fn(|c| (n * 7 - 3 * c as i32) * (n % 5 - 2)))
+            .collect();
+        states.push([0; 8]);
+        states.push([i32::MAX, 0, 0, 0, 0, 0, i32::MIN, 1]);
+        let packed = QpQuat::pack_batch(&states);
+        let scalar: Vec<QpQuat> = states.iter().map(QpQuat::pack).collect();
+        assert_eq!(packed, scalar);
+        let unpacked: Vec<[i32; 8]> = scalar.iter().map(QpQuat::unpack).collect();
+        assert_eq!(QpQuat::unpack_batch(&packed), unpacked);
+        let wide = QpQuat64::pack_batch(&states);
+        assert_eq!(QpQuat64::unpack_batch(&wide), states);
+        assert!(QpQuat::pack_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn deltas_apply_and_invert() {
+        let prev = QpQuat64::pack(&[2, 1, -3, 4, 0, 0, 0, 0]);
+        let next = QpQuat64::pack(&[5, -1, 0, 2, -1, 2, -5, 6]);
+        let delta = QpQuat64::delta(&prev, &next);
+        assert_eq!(delta.apply(&prev).unpack(), next.unpack());
+        assert!((delta.apply(&prev).psi1 - next.psi1).norm() < 1e-12);
+        assert_eq!(delta.inverse().apply(&next).unpack(), prev.unpack());
+        let none = QpQuat64::delta(&next, &next);
+        assert!((none.rot1 - Quaternion::identity()).norm() < 1e-12);
+        assert_eq!((none.norm1_change, none.norm2_change), (0.0, 0.0));
+    }
+
+    #[test]
+    fn pair_algebra_matches_quaternions_and_octonions() {
+        let x = QpQuat64::pack(&[2, 1, -3, 4, -1, 2, -5, 6]);
+        let y = QpQuat64::pack(&[1, 0, 2, -1, 3, 3, 0, 1]);
+        let (x1, x2) = x.quaternions();
+        let (y1, y2) = y.quaternions();
+        let xy = (x * y).quaternions();
+        assert!((xy.0 - x1 * y1).norm() < 1e-9 && (xy.1 - x2 * y2).norm() < 1e-9);
+        assert!((x.norm() - 96f64.sqrt()).abs() < 1e-12);
+        assert_eq!(x.conjugate().quaternions().0, x1.conjugate());
+
+        let one = (x * x.try_inverse().unwrap()).quaternions();
+        assert!((one.0 - Quaternion::identity()).norm() < 1e-12);
+        assert!((one.1 - Quaternion::identity()).norm() < 1e-12);
+        assert!(QpQuat64::pack(&[1, 0, 0, 0, 0, 0, 0, 0])
+            .try_inverse()
+            .is_none());
+
+        let coupled = x.coupled_mul(&y);
+        let oct = Octonion64::pack(&[2, 1, -3, 4, -1, 2, -5, 6])
+            * Octonion64::pack(&[1, 0, 2, -1, 3, 3, 0, 1]);
+        let (c1, c2) = coupled.quaternions();
+        assert!((c1 - oct.a).norm() < 1e-9 && (c2 - oct.b).norm() < 1e-9);
+        assert!((coupled.norm() - x.norm() * y.norm()).abs() < 1e-9);
+    }
+
     #[test]
     fn rotate_preserves_quaternion_norms() {
         let exponents = [2, 1, -3, 4, -1, 2, -5, 6];
@@ -155,4 +826,16 @@ mod tests {
         assert!((norm1 - qp.psi1_norm).abs() < f32::EPSILON);
         assert!((norm2 - qp.psi2_norm).abs() < f32::EPSILON);
     }
+
+    #[test]
+    fn bench_reports_positive_costs() {
+        let report = bench(2_000);
+        assert_eq!(report.iterations, 2_000);
+        assert_eq!(report.cycle_source, CycleCounter::source().name());
+        for cost in [report.pack, report.pack_batch, report.unpack, report.rotate] {
+            assert!(cost.ns_per_op > 0.0 && cost.cycles_per_op > 0.0);
+        }
+        let json = serde_json::to_value(report).unwrap();
+        assert!(json["rotate"]["ns_per_op"].is_number());
+    }
 }
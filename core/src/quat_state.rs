@@ -0,0 +1,267 @@
+//! Packed quaternion state per entity
+//! The `quat_state` column family maps entity (BE) to its eight exponents,
+//! in node order, packed with [`QpQuat`] and stored as
+//! [`QpQuat::to_bytes`]. Primes without an exponent count as 0.
+
+use std::collections::HashMap;
+
+use nalgebra::Quaternion;
+use rocksdb::{IteratorMode, WriteBatch};
+
+use crate::keys;
+use crate::migrate::CHUNK;
+use crate::qp_encode::QpQuat;
+use crate::{node_from_u8, Ledger, LedgerError, LedgerEvent};
+
+pub(crate) const QUAT_STATE_CF: &str = "quat_state";
+
+/// Exponents of one entity indexed by node; `None` where nothing is stored.
+type NodeExponents = [Option<i32>; 8];
+
+fn pack(exponents: &NodeExponents) -> QpQuat {
+    QpQuat::pack(&exponents.map(|e| e.unwrap_or(0)))
+}
+
+impl Ledger {
+    /// `entity`'s exponents as of its last commit, packed into two
+    /// quaternions; `None` if it has no events or was deleted. Values
+    /// written by `bulk_load` are reflected after the next
+    /// [`Ledger::rebuild_projections`].
+    pub fn quaternion_state(&self, entity: u64) -> Result<Option<QpQuat>, LedgerError> {
+        let cf = self.cf(QUAT_STATE_CF)?;
+        self.db
+            .get_cf(cf, entity.to_be_bytes())?
+            .map(|raw| QpQuat::from_bytes(&raw))
+            .transpose()
+    }
+
+    /// `entity`'s current exponents packed as its [`Ledger::quaternion_state`]
+    /// would be, read straight from the factors with one `multi_get`.
+    /// Fails if the entity has been deleted.
+    pub fn pack_entity(&self, entity: u64) -> Result<QpQuat, LedgerError> {
+        Ok(QpQuat::pack(&self.packed_exponents(entity)?))
+    }
+
+    /// Anchor whatever it takes for `entity` to unpack as `state`: one
+    /// command per node whose exponent differs, validated like any
+    /// [`Ledger::anchor_batch`]. Exponents must be node indices.
+    pub fn apply_quat_state(
+        &self,
+        entity: u64,
+        state: &QpQuat,
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let current = self.packed_exponents(entity)?;
+        let mut commands = Vec::new();
+        for (node, (&target, &now)) in state.unpack().iter().zip(&current).enumerate() {
+            if target == now {
+                continue;
+            }
+            let prime = self
+                .registry
+                .node_to_prime(node as u8)
+                .ok_or(LedgerError::InvalidNode(node as u8))?;
+            let target_node = u8::try_from(target).map_err(|_| {
+                LedgerError::InvalidArgument(format!(
+                    "exponent {} for prime {} is not a node",
+                    target, prime
+                ))
+            })?;
+            commands.push((prime, target_node));
+        }
+        self.anchor_batch(entity, &commands)
+    }
+
+    /// Rotate `entity`'s packed state by `q` and apply the result, but only
+    /// if every exponent it moves is a transition `flow_rule` allows
+    /// directly. [`Ledger::apply_quat_state`] would route an even→odd move
+    /// through the centroid; here it fails with
+    /// [`LedgerError::FlowRuleViolation`] and nothing is written.
+    pub fn rotate_legal(
+        &self,
+        entity: u64,
+        q: Quaternion<f32>,
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let stored = self.stored_exponents(entity)?;
+        let mut state = QpQuat::pack(&stored.map(|e| e.unwrap_or(0)));
+        state.rotate(q);
+        for (node, (&target, &now)) in state.unpack().iter().zip(&stored).enumerate() {
+            if target == now.unwrap_or(0) {
+                continue;
+            }
+            // Commands move a prime from its stored node, or its own.
+            let src = u8::try_from(now.unwrap_or(node as i32))
+                .map_err(|_| LedgerError::InvalidNode(node as u8))?;
+            let dst = u8::try_from(target).map_err(|_| {
+                LedgerError::InvalidArgument(format!(
+                    "rotation moves node {} to exponent {}, which is not a node",
+                    node, target
+                ))
+            })?;
+            let nodes = node_from_u8(src).zip(node_from_u8(dst));
+            let (src_node, dst_node) = nodes.ok_or(LedgerError::InvalidNode(src.max(dst)))?;
+            if !flow_rule::transition_allowed(src_node, dst_node) {
+                return Err(LedgerError::FlowRuleViolation { src, dst });
+            }
+        }
+        self.apply_quat_state(entity, &state)
+    }
+
+    /// Exponents in node order with missing ones as 0, as packed.
+    fn packed_exponents(&self, entity: u64) -> Result<[i32; 8], LedgerError> {
+        Ok(self.stored_exponents(entity)?.map(|e| e.unwrap_or(0)))
+    }
+
+    fn stored_exponents(&self, entity: u64) -> Result<NodeExponents, LedgerError> {
+        self.ensure_live(entity)?;
+        let stored = self.current_exponents(entity, self.registry.primes())?;
+        Ok(std::array::from_fn(|node| stored[node]))
+    }
+
+    /// Stage the packed state of every entity `events` touch. Reads the
+    /// stored exponents, so must run before the batch is applied;
+    /// tombstones clear the state.
+    pub(crate) fn stage_quat_state(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let cf = self.cf(QUAT_STATE_CF)?;
+        let mut states: HashMap<u64, Option<NodeExponents>> = HashMap::new();
+        for evt in events {
+            if evt.tombstone {
+                states.insert(evt.entity_id, None);
+                continue;
+            }
+            let mut exps = match states.get(&evt.entity_id) {
+                Some(Some(exps)) => *exps,
+                Some(None) => [None; 8],
+                None => self.node_exponents(evt.entity_id)?,
+            };
+            let node = self.node_index(evt.prime)?;
+            // A missing exponent starts from the prime's node, as in `merge`.
+            let base = exps[node].unwrap_or(node as i32);
+            exps[node] = Some(base + evt.msd_digits.to_int());
+            states.insert(evt.entity_id, Some(exps));
+        }
+        for (entity, exps) in states {
+            match exps {
+                Some(exps) => batch.put_cf(cf, entity.to_be_bytes(), pack(&exps).to_bytes()),
+                None => batch.delete_cf(cf, entity.to_be_bytes()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Recompute the column family from the factors projection; used after
+    /// rebuilding it and when upgrading ledgers that predate it.
+    pub(crate) fn backfill_quat_state(&self) -> Result<(), LedgerError> {
+        let cf = self.cf(QUAT_STATE_CF)?;
+        let mut batch = WriteBatch::default();
+        for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+            batch.delete_cf(cf, item?.0);
+        }
+        let mut current: Option<(u64, NodeExponents)> = None;
+        for item in self
+            .db
+            .iterator_cf(self.cf("factors")?, IteratorMode::Start)
+        {
+            let (key, value) = item?;
+            let (entity, prime) = keys::decode_factor_key(&key)?;
+            if current.is_some_and(|(e, _)| e != entity) {
+                if let Some((e, exps)) = current.take() {
+                    batch.put_cf(cf, e.to_be_bytes(), pack(&exps).to_bytes());
+                }
+            }
+            let (_, exps) = current.get_or_insert((entity, [None; 8]));
+            exps[self.node_index(prime)?] = Some(keys::decode_exponent(&value)?);
+            if batch.len() >= CHUNK {
+                self.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        if let Some((e, exps)) = current {
+            batch.put_cf(cf, e.to_be_bytes(), pack(&exps).to_bytes());
+        }
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+
+    fn node_index(&self, prime: u32) -> Result<usize, LedgerError> {
+        self.registry
+            .prime_to_node(prime)
+            .map(usize::from)
+            .ok_or(LedgerError::UnknownPrime(prime))
+    }
+
+    fn node_exponents(&self, entity: u64) -> Result<NodeExponents, LedgerError> {
+        let mut exps = [None; 8];
+        for entry in self.iter_entity_raw(entity)? {
+            let (_, prime, exp) = entry?;
+            exps[self.node_index(prime)?] = Some(exp);
+        }
+        Ok(exps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_tracks_commits_and_survives_rebuild() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert!(ledger.quaternion_state(1).unwrap().is_none());
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(1, &[(2, 4), (3, 2)]).unwrap();
+        let state = ledger.quaternion_state(1).unwrap().unwrap();
+        assert_eq!(state.unpack(), [4, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(state, QpQuat::pack(&[4, 2, 0, 0, 0, 0, 0, 0]));
+
+        ledger.anchor_batch(2, &[(19, 5)]).unwrap();
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(
+            ledger.quaternion_state(2).unwrap().unwrap().unpack(),
+            [0, 0, 0, 0, 0, 0, 0, 5]
+        );
+        assert_eq!(ledger.quaternion_state(1).unwrap(), Some(state));
+
+        assert_eq!(ledger.pack_entity(1).unwrap(), state);
+        let target = QpQuat::pack(&[4, 2, 0, 0, 0, 0, 0, 5]);
+        let events = ledger.apply_quat_state(1, &target).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(ledger.quaternion_state(1).unwrap(), Some(target));
+        assert!(ledger.apply_quat_state(1, &target).unwrap().is_empty());
+        assert!(ledger
+            .apply_quat_state(1, &QpQuat::pack(&[9, 0, 0, 0, 0, 0, 0, 0]))
+            .is_err());
+
+        ledger.delete_entity(1).unwrap();
+        assert!(ledger.quaternion_state(1).unwrap().is_none());
+        assert!(ledger.pack_entity(1).is_err());
+    }
+
+    #[test]
+    fn legal_rotations_apply_and_bypasses_are_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        // Half a turn about (i + j) swaps the exponents of 3 and 5.
+        let swap = Quaternion::new(0.0, 1.0, 1.0, 0.0);
+        ledger.anchor_batch(1, &[(3, 3), (5, 5)]).unwrap();
+        assert_eq!(ledger.rotate_legal(1, swap).unwrap().len(), 2);
+        assert_eq!(ledger.pack_entity(1).unwrap().unpack()[1..3], [5, 3]);
+
+        // A quarter turn about k sends 3 to node 0, allowed, and 5 from its
+        // own node 2 to 3, an even→odd bypass.
+        ledger.anchor_batch(2, &[(3, 3)]).unwrap();
+        let before = ledger.pack_entity(2).unwrap();
+        let quarter = Quaternion::new(1.0, 0.0, 0.0, 1.0);
+        assert!(matches!(
+            ledger.rotate_legal(2, quarter),
+            Err(LedgerError::FlowRuleViolation { src: 2, dst: 3 })
+        ));
+        assert_eq!(ledger.pack_entity(2).unwrap(), before);
+        assert!(matches!(
+            ledger.rotate_legal(2, Quaternion::new(1.0, 0.0, 0.0, -1.0)),
+            Err(LedgerError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,196 @@
+//! Read-only and secondary opens for query services and dashboards
+//! Neither takes the RocksDB lock, so both can run beside the primary writer.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rocksdb::{Options, DB};
+
+use crate::bounds::BoundsConfig;
+use crate::clock::SharedClock;
+use crate::durability::SyncState;
+use crate::event_log::EventLog;
+use crate::exponent_cache::ExponentCache;
+use crate::registry::{self, Registry};
+use crate::subscription::Subscribers;
+use crate::{
+    cf_descriptors, chain, CentroidSeed, Ledger, LedgerError, LedgerOptions, RetentionPolicy,
+    WriterState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    /// Holds the DB lock; the only mode that may write.
+    Primary,
+    /// Static view of the DB as of open time.
+    ReadOnly,
+    /// Follows the primary; call [`Ledger::catch_up`] to see new writes.
+    Secondary,
+    /// Applies a primary's shipped log; see [`crate::Follower`].
+    Follower,
+}
+
+impl Ledger {
+    /// Open a ledger for reads only, alongside a running primary.
+    pub fn open_read_only<P: AsRef<Path>>(base_path: P) -> Result<Self, LedgerError> {
+        Ledger::open_read_only_with_options(base_path, &LedgerOptions::default())
+    }
+
+    /// [`Ledger::open_read_only`] with the primary's options; only the column
+    /// family layout and encryption key matter to a reader.
+    pub fn open_read_only_with_options<P: AsRef<Path>>(
+        base_path: P,
+        options: &LedgerOptions,
+    ) -> Result<Self, LedgerError> {
+        let base_path = base_path.as_ref();
+        let (registry, _) = registry::resolve(&base_path.join("db"), options.registry)?;
+        let db = DB::open_cf_descriptors_read_only(
+            &Options::default(),
+            base_path.join("db"),
+            cf_descriptors(options, registry)?,
+            false,
+        )?;
+        Ledger::open_reader(db, base_path, AccessMode::ReadOnly, options, registry)
+    }
+
+    /// Open a secondary instance that tails the primary at `base_path`.
+    /// `secondary_path` holds the secondary's own RocksDB info logs.
+    pub fn open_as_secondary<P: AsRef<Path>, Q: AsRef<Path>>(
+        base_path: P,
+        secondary_path: Q,
+    ) -> Result<Self, LedgerError> {
+        Ledger::open_as_secondary_with_options(base_path, secondary_path, &LedgerOptions::default())
+    }
+
+    /// [`Ledger::open_as_secondary`] with the primary's options, as for
+    /// [`Ledger::open_read_only_with_options`].
+    pub fn open_as_secondary_with_options<P: AsRef<Path>, Q: AsRef<Path>>(
+        base_path: P,
+        secondary_path: Q,
+        options: &LedgerOptions,
+    ) -> Result<Self, LedgerError> {
+        let base_path = base_path.as_ref();
+        let mut opts = Options::default();
+        // Secondaries must keep every table file open to follow the primary.
+        opts.set_max_open_files(-1);
+        let (registry, _) = registry::resolve(&base_path.join("db"), options.registry)?;
+        let db = DB::open_cf_descriptors_as_secondary(
+            &opts,
+            base_path.join("db"),
+            secondary_path.as_ref().to_path_buf(),
+            cf_descriptors(options, registry)?,
+        )?;
+        Ledger::open_reader(db, base_path, AccessMode::Secondary, options, registry)
+    }
+
+    /// Pick up writes the primary has made since open or the last catch-up.
+    pub fn catch_up(&self) -> Result<(), LedgerError> {
+        if self.mode != AccessMode::Secondary {
+            return Err(LedgerError::InvalidArgument(
+                "catch_up is only available on secondary ledgers".to_string(),
+            ));
+        }
+        self.db
+            .try_catch_up_with_primary()
+            .map_err(LedgerError::from)
+    }
+
+    pub fn access_mode(&self) -> AccessMode {
+        self.mode
+    }
+
+    pub(crate) fn ensure_writable(&self) -> Result<(), LedgerError> {
+        match self.mode {
+            AccessMode::Primary => Ok(()),
+            mode => Err(LedgerError::ReadOnly(mode)),
+        }
+    }
+
+    fn open_reader(
+        db: DB,
+        base_path: &Path,
+        mode: AccessMode,
+        options: &LedgerOptions,
+        registry: Registry,
+    ) -> Result<Self, LedgerError> {
+        let log = EventLog::open_existing(base_path, options.log_cipher()?)?;
+        let chain_head = chain::chain_head(log.last_event()?.as_ref());
+        let mut ledger = Ledger {
+            db: Arc::new(db),
+            log,
+            signer: None,
+            subscribers: Subscribers::default(),
+            write_lock: Mutex::new(WriterState {
+                chain_head,
+                next_lsn: 0,
+            }),
+            mode,
+            retention: RetentionPolicy::default(),
+            metrics: Arc::default(),
+            exponent_cache: ExponentCache::disabled(),
+            clock: SharedClock::default(),
+            sync: SyncState::default(),
+            writer_id: None,
+            record_denials: false,
+            bounds: BoundsConfig::default(),
+            registry,
+            centroid_seed: CentroidSeed::default(),
+            archive: None,
+            outbox: None,
+            _lock: None,
+        };
+        ledger.check_format()?;
+        ledger.write_lock.get_mut()?.next_lsn = ledger.load_next_lsn()?;
+        ledger
+            .log
+            .reserve_segments_through(ledger.pruned_prefix()?.last_segment);
+        Ok(ledger)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::FileKey;
+
+    #[test]
+    fn readers_open_beside_the_primary() {
+        let tmp = tempfile::tempdir().unwrap();
+        let primary = Ledger::new(tmp.path().join("ledger")).unwrap();
+        primary.anchor_batch(1, &[(2, 2)]).unwrap();
+
+        let reader = Ledger::open_read_only(tmp.path().join("ledger")).unwrap();
+        assert_eq!(reader.current_exponent(1, 2).unwrap(), Some(2));
+        assert!(reader.anchor_batch(1, &[(2, 4)]).is_err());
+        assert!(reader.catch_up().is_err());
+
+        let secondary =
+            Ledger::open_as_secondary(tmp.path().join("ledger"), tmp.path().join("secondary"))
+                .unwrap();
+        primary.anchor_batch(1, &[(2, 4)]).unwrap();
+        secondary.catch_up().unwrap();
+        assert_eq!(secondary.current_exponent(1, 2).unwrap(), Some(4));
+        assert_eq!(secondary.log.read_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn secondaries_tail_an_encrypted_primary() {
+        let tmp = tempfile::tempdir().unwrap();
+        let key_file = tmp.path().join("ledger.key");
+        std::fs::write(&key_file, hex::encode([7u8; 32])).unwrap();
+        let options = LedgerOptions::new().encryption(Arc::new(FileKey::new(&key_file)));
+        let path = tmp.path().join("ledger");
+        let primary = Ledger::with_options(&path, &options).unwrap();
+        primary.anchor_batch(1, &[(2, 2)]).unwrap();
+
+        let secondary =
+            Ledger::open_as_secondary_with_options(&path, tmp.path().join("secondary"), &options)
+                .unwrap();
+        primary.anchor_batch(1, &[(2, 4)]).unwrap();
+        secondary.catch_up().unwrap();
+        assert_eq!(secondary.current_exponent(1, 2).unwrap(), Some(4));
+        assert_eq!(secondary.verify_chain().unwrap(), 2);
+        drop(secondary);
+        assert!(Ledger::open_as_secondary(&path, tmp.path().join("plain")).is_err());
+    }
+}
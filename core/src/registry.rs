@@ -1,28 +1,546 @@
-pub fn prime_to_node(p: u32) -> Option<u8> {
-    match p {
-        2 => Some(0),
-        3 => Some(1),
-        5 => Some(2),
-        7 => Some(3),
-        11 => Some(4),
-        13 => Some(5),
-        17 => Some(6),
-        19 => Some(7),
-        _ => None,
-    }
-}
-
-#[allow(dead_code)]
-pub fn node_to_prime(n: u8) -> Option<u32> {
-    match n {
-        0 => Some(2),
-        1 => Some(3),
-        2 => Some(5),
-        3 => Some(7),
-        4 => Some(11),
-        5 => Some(13),
-        6 => Some(17),
-        7 => Some(19),
-        _ => None,
+//! Prime ↔ node registry
+//! Each of the eight flow-rule nodes is assigned one prime, and anchoring a
+//! prime starts it at that node. [`Registry::default`] is the S0 alphabet
+//! 2..19; deployments with another alphabet build one with
+//! [`Registry::new`] and open the ledger with
+//! [`crate::LedgerOptions::registry`].
+//!
+//! Larger models stack registries into [`Tiers`]: S0 is 2..19, S1 the next
+//! eight primes, and so on, each tier covering the same eight nodes.
+//!
+//! A ledger records its registry in the `meta` column family when it is
+//! created and runs with that one from then on, so a log is never replayed
+//! against a different alphabet. Ledgers that predate the record were built
+//! with S0.
+
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use rocksdb::{Options, DB};
+
+use crate::{Ledger, LedgerError};
+
+pub(crate) const META_CF: &str = "meta";
+const REGISTRY_KEY: &[u8] = b"registry";
+
+/// Nodes in the flow-rule graph, and so primes in a registry.
+pub const NODES: usize = 8;
+
+/// The default alphabet, in node order.
+pub const S0_PRIMES: [u32; NODES] = [2, 3, 5, 7, 11, 13, 17, 19];
+
+/// An ordered prime alphabet: the prime at index `n` belongs to node `n`.
+/// Lookups take a fixed handful of steps either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Registry {
+    primes: [u32; NODES],
+    /// `(prime, node)` sorted by prime, for [`Registry::prime_to_node`].
+    by_prime: [(u32, u8); NODES],
+}
+
+/// Why [`Registry::new`] refused a prime list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// Not exactly [`NODES`] primes.
+    Count {
+        expected: usize,
+        found: usize,
+    },
+    NotPrime {
+        node: u8,
+        value: u32,
+    },
+    /// `prime` was given for both nodes.
+    Duplicate {
+        prime: u32,
+        nodes: (u8, u8),
+    },
+    /// `prime` belongs to both tiers of a [`Tiers`].
+    SharedPrime {
+        prime: u32,
+        tiers: (u8, u8),
+    },
+    /// A [`crate::NodeMap`] element is reducible, not canonical or repeated.
+    NotIrreducible {
+        node: u8,
+        element: String,
+    },
+    /// A [`Tiers`] needs at least one tier and at most `u8::MAX + 1`.
+    TierCount(usize),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Count { expected, found } => {
+                write!(f, "a registry needs {} primes, got {}", expected, found)
+            }
+            RegistryError::NotPrime { node, value } => {
+                write!(f, "{} for node {} is not prime", value, node)
+            }
+            RegistryError::Duplicate {
+                prime,
+                nodes: (a, b),
+            } => write!(
+                f,
+                "prime {} is given for both node {} and node {}",
+                prime, a, b
+            ),
+            RegistryError::SharedPrime {
+                prime,
+                tiers: (a, b),
+            } => write!(f, "prime {} is in both tier {} and tier {}", prime, a, b),
+            RegistryError::NotIrreducible { node, element } => {
+                write!(
+                    f,
+                    "{} for node {} is not a usable irreducible",
+                    element, node
+                )
+            }
+            RegistryError::TierCount(n) => write!(f, "cannot stack {} tiers", n),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<RegistryError> for LedgerError {
+    fn from(e: RegistryError) -> Self {
+        LedgerError::InvalidArgument(e.to_string())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::from_primes(S0_PRIMES)
+    }
+}
+
+impl Registry {
+    /// `primes` in node order; fails unless there are exactly eight
+    /// distinct primes.
+    pub fn new(primes: &[u32]) -> Result<Self, RegistryError> {
+        let primes: [u32; NODES] = primes.try_into().map_err(|_| RegistryError::Count {
+            expected: NODES,
+            found: primes.len(),
+        })?;
+        for (node, &value) in primes.iter().enumerate() {
+            if !is_prime(value) {
+                return Err(RegistryError::NotPrime {
+                    node: node as u8,
+                    value,
+                });
+            }
+            if let Some(first) = primes[..node].iter().position(|&p| p == value) {
+                return Err(RegistryError::Duplicate {
+                    prime: value,
+                    nodes: (first as u8, node as u8),
+                });
+            }
+        }
+        Ok(Registry::from_primes(primes))
+    }
+
+    /// Tier `t` of the standard stack: the eight primes after those of
+    /// tiers `0..t`, so `tier(0)` is S0 and `tier(1)` is 23..53.
+    pub fn tier(t: u8) -> Self {
+        let mut primes = (2..).filter(|&n| is_prime(n)).skip(usize::from(t) * NODES);
+        Registry::from_primes(std::array::from_fn(|_| primes.next().unwrap()))
+    }
+
+    fn from_primes(primes: [u32; NODES]) -> Self {
+        let mut by_prime = std::array::from_fn(|n| (primes[n], n as u8));
+        by_prime.sort_unstable();
+        Registry { primes, by_prime }
+    }
+
+    /// The primes in node order.
+    pub fn primes(&self) -> &[u32; NODES] {
+        &self.primes
+    }
+
+    /// The node indices, `0..8`.
+    pub fn nodes(&self) -> Range<u8> {
+        0..NODES as u8
+    }
+
+    /// `(node, prime)` pairs in node order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u32)> + '_ {
+        self.nodes().zip(self.primes.iter().copied())
+    }
+
+    /// A binary search over the eight primes: three comparisons at most.
+    pub fn prime_to_node(&self, p: u32) -> Option<u8> {
+        self.by_prime
+            .binary_search_by_key(&p, |&(q, _)| q)
+            .ok()
+            .map(|i| self.by_prime[i].1)
+    }
+
+    pub fn node_to_prime(&self, n: u8) -> Option<u32> {
+        self.primes.get(usize::from(n)).copied()
+    }
+
+    /// The primes as big-endian `u32`s, in node order.
+    fn to_bytes(self) -> Vec<u8> {
+        self.primes.iter().flat_map(|p| p.to_be_bytes()).collect()
+    }
+
+    fn from_bytes(raw: &[u8]) -> Result<Self, LedgerError> {
+        let primes: Vec<u32> = raw
+            .chunks(4)
+            .map(|c| c.try_into().map(u32::from_be_bytes))
+            .collect::<Result<_, _>>()
+            .map_err(|_| LedgerError::Corruption("corrupt registry record".to_string()))?;
+        Registry::new(&primes)
+            .map_err(|e| LedgerError::Corruption(format!("stored registry: {}", e)))
+    }
+}
+
+/// Where a prime sits in a [`Tiers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TierNode {
+    pub tier: u8,
+    pub node: u8,
+}
+
+impl TierNode {
+    /// Position in the flattened graph, `tier * 8 + node`.
+    pub fn index(self) -> u16 {
+        u16::from(self.tier) * NODES as u16 + u16::from(self.node)
+    }
+}
+
+/// Registries stacked by tier, coarsest first. No prime may appear in two
+/// tiers, so every prime resolves to a single [`TierNode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tiers {
+    tiers: Vec<Registry>,
+}
+
+impl Tiers {
+    /// The first `count` tiers of the standard stack.
+    pub fn standard(count: u8) -> Result<Self, RegistryError> {
+        Tiers::new((0..count).map(Registry::tier).collect())
+    }
+
+    /// `tiers` in order; fails if it is empty, has more than 256 tiers or
+    /// two tiers share a prime.
+    pub fn new(tiers: Vec<Registry>) -> Result<Self, RegistryError> {
+        if tiers.is_empty() || tiers.len() > usize::from(u8::MAX) + 1 {
+            return Err(RegistryError::TierCount(tiers.len()));
+        }
+        for (t, registry) in tiers.iter().enumerate() {
+            for &prime in registry.primes() {
+                if let Some(first) = tiers[..t]
+                    .iter()
+                    .position(|r| r.prime_to_node(prime).is_some())
+                {
+                    return Err(RegistryError::SharedPrime {
+                        prime,
+                        tiers: (first as u8, t as u8),
+                    });
+                }
+            }
+        }
+        Ok(Tiers { tiers })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiers.len()
+    }
+
+    /// Always false; a `Tiers` has at least one tier.
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    pub fn tier(&self, t: u8) -> Option<&Registry> {
+        self.tiers.get(usize::from(t))
+    }
+
+    /// The tier and node `p` belongs to.
+    pub fn resolve(&self, p: u32) -> Option<TierNode> {
+        self.tiers.iter().enumerate().find_map(|(t, registry)| {
+            registry.prime_to_node(p).map(|node| TierNode {
+                tier: t as u8,
+                node,
+            })
+        })
+    }
+
+    pub fn prime(&self, at: TierNode) -> Option<u32> {
+        self.tier(at.tier)?.node_to_prime(at.node)
+    }
+
+    /// Every `(position, prime)`, tier by tier in node order.
+    pub fn iter(&self) -> impl Iterator<Item = (TierNode, u32)> + '_ {
+        self.tiers.iter().enumerate().flat_map(|(t, registry)| {
+            registry.iter().map(move |(node, prime)| {
+                (
+                    TierNode {
+                        tier: t as u8,
+                        node,
+                    },
+                    prime,
+                )
+            })
+        })
+    }
+}
+
+/// What the DB at `db_path` says about its registry.
+enum Stored {
+    /// No DB yet.
+    New,
+    /// An existing DB that predates the record.
+    Unrecorded,
+    Recorded(Registry),
+}
+
+/// Read before the full open, since the merge operators need the registry.
+fn stored(db_path: &Path) -> Result<Stored, LedgerError> {
+    let opts = Options::default();
+    if !db_path.join("CURRENT").exists() {
+        return Ok(Stored::New);
+    }
+    if !DB::list_cf(&opts, db_path)?.iter().any(|cf| cf == META_CF) {
+        return Ok(Stored::Unrecorded);
+    }
+    let db = DB::open_cf_for_read_only(&opts, db_path, [META_CF], false)?;
+    let cf = db
+        .cf_handle(META_CF)
+        .ok_or_else(|| LedgerError::Corruption(format!("missing column family: {}", META_CF)))?;
+    match db.get_cf(cf, REGISTRY_KEY)? {
+        Some(raw) => Ok(Stored::Recorded(Registry::from_bytes(&raw)?)),
+        None => Ok(Stored::Unrecorded),
+    }
+}
+
+/// The registry to open the DB at `db_path` with, and whether it still has
+/// to be recorded. `requested` must match what is stored.
+pub(crate) fn resolve(
+    db_path: &Path,
+    requested: Option<Registry>,
+) -> Result<(Registry, bool), LedgerError> {
+    let (stored, unrecorded) = match stored(db_path)? {
+        Stored::New => return Ok((requested.unwrap_or_default(), true)),
+        // Whatever built an unrecorded ledger used S0.
+        Stored::Unrecorded => (Registry::default(), true),
+        Stored::Recorded(stored) => (stored, false),
+    };
+    match requested {
+        Some(requested) if requested != stored => Err(LedgerError::RegistryMismatch {
+            stored: stored.primes,
+            requested: requested.primes,
+        }),
+        _ => Ok((stored, unrecorded)),
+    }
+}
+
+impl Ledger {
+    /// The prime alphabet this ledger runs with.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub(crate) fn record_registry(&self) -> Result<(), LedgerError> {
+        self.db
+            .put_cf(self.cf(META_CF)?, REGISTRY_KEY, self.registry.to_bytes())
+            .map_err(LedgerError::from)
+    }
+}
+
+/// Miller–Rabin with bases 2, 7 and 61, which is exact for every `u32`.
+pub(crate) fn is_prime(n: u32) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in [2, 3, 5, 7, 61] {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let n = u64::from(n);
+    let pow = |mut base: u64, mut exp: u64| {
+        let mut acc = 1;
+        base %= n;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc * base % n;
+            }
+            base = base * base % n;
+            exp >>= 1;
+        }
+        acc
+    };
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    [2, 7, 61].into_iter().all(|a| {
+        let mut x = pow(a, d);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        (1..s).any(|_| {
+            x = x * x % n;
+            x == n - 1
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ledger, LedgerOptions};
+
+    #[test]
+    fn custom_registry_maps_and_anchors() {
+        let s0 = Registry::default();
+        assert_eq!(s0.prime_to_node(13), Some(5));
+        assert_eq!(s0.node_to_prime(7), Some(19));
+        assert_eq!((s0.prime_to_node(23), s0.node_to_prime(8)), (None, None));
+
+        let s1 = Registry::new(&[23, 29, 31, 37, 41, 43, 47, 53]).unwrap();
+        assert_eq!(s1.prime_to_node(41), Some(4));
+        // Not in ascending order, so lookups can't lean on it.
+        let shuffled = Registry::new(&[19, 2, 17, 3, 13, 5, 11, 7]).unwrap();
+        for (node, prime) in shuffled.iter() {
+            assert_eq!(shuffled.prime_to_node(prime), Some(node));
+            assert_eq!(shuffled.node_to_prime(node), Some(prime));
+        }
+        assert_eq!(shuffled.prime_to_node(23), None);
+        assert_eq!(shuffled.nodes().len(), shuffled.primes().len());
+        assert_eq!(s0.iter().nth(2), Some((2, 5)));
+        assert_eq!(
+            Registry::new(&S0_PRIMES[..7]),
+            Err(RegistryError::Count {
+                expected: 8,
+                found: 7
+            })
+        );
+        assert_eq!(
+            Registry::new(&[2, 3, 5, 7, 11, 13, 17, 9]),
+            Err(RegistryError::NotPrime { node: 7, value: 9 })
+        );
+        assert_eq!(
+            Registry::new(&[2, 3, 5, 7, 11, 3, 17, 19]),
+            Err(RegistryError::Duplicate {
+                prime: 3,
+                nodes: (1, 5)
+            })
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::with_options(tmp.path(), &LedgerOptions::new().registry(s1)).unwrap();
+        ledger.anchor_batch(1, &[(41, 6), (53, 5)]).unwrap();
+        assert_eq!(ledger.current_exponent(1, 53).unwrap(), Some(5));
+        assert!(matches!(
+            ledger.anchor_batch(1, &[(2, 0)]),
+            Err(LedgerError::UnknownPrime(2))
+        ));
+        assert_eq!(ledger.pack_entity(1).unwrap().unpack()[4], 6);
+    }
+
+    #[test]
+    fn tiers_resolve_across_prime_sets() {
+        assert_eq!(Registry::tier(0), Registry::default());
+        assert_eq!(
+            Registry::tier(2).primes(),
+            &[59, 61, 67, 71, 73, 79, 83, 89]
+        );
+
+        let tiers = Tiers::standard(2).unwrap();
+        let at = tiers.resolve(41).unwrap();
+        assert_eq!(at, TierNode { tier: 1, node: 4 });
+        assert_eq!(at.index(), 12);
+        assert_eq!(tiers.prime(at), Some(41));
+        assert_eq!(tiers.resolve(7), Some(TierNode { tier: 0, node: 3 }));
+        assert_eq!(tiers.resolve(59), None);
+        assert_eq!(tiers.iter().count(), 16);
+        assert!(tiers.iter().map(|(at, _)| at.index()).eq(0..16));
+
+        assert_eq!(Tiers::new(Vec::new()), Err(RegistryError::TierCount(0)));
+        let overlapping = Registry::new(&[23, 29, 31, 37, 41, 43, 47, 2]).unwrap();
+        assert_eq!(
+            Tiers::new(vec![Registry::default(), overlapping]),
+            Err(RegistryError::SharedPrime {
+                prime: 2,
+                tiers: (0, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn miller_rabin_agrees_with_trial_division() {
+        let trial = |n: u32| {
+            n >= 2
+                && (2..n)
+                    .take_while(|d| d * d <= n)
+                    .all(|d| !n.is_multiple_of(d))
+        };
+        assert!((0..20_000).all(|n| is_prime(n) == trial(n)));
+        // Strong pseudoprimes to some of the bases, and the largest u32 prime.
+        for n in [2_047, 3_215_031_751, 4_294_967_291] {
+            assert_eq!(is_prime(n), n == 4_294_967_291);
+        }
+        assert!(!is_prime(u32::MAX));
+    }
+
+    #[test]
+    fn ledgers_that_predate_the_record_are_s0() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        ledger
+            .db
+            .delete_cf(ledger.cf(META_CF).unwrap(), REGISTRY_KEY)
+            .unwrap();
+        drop(ledger);
+
+        let s1 = Registry::new(&[23, 29, 31, 37, 41, 43, 47, 53]).unwrap();
+        assert!(matches!(
+            Ledger::with_options(tmp.path(), &LedgerOptions::new().registry(s1)),
+            Err(LedgerError::RegistryMismatch { stored, requested })
+                if stored == S0_PRIMES && requested == *s1.primes()
+        ));
+        // The refused open recorded nothing, so S0 is still accepted, and
+        // recorded from then on.
+        let s0 = LedgerOptions::new().registry(Registry::default());
+        let ledger = Ledger::with_options(tmp.path(), &s0).unwrap();
+        assert_eq!(*ledger.registry(), Registry::default());
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(2));
+        drop(ledger);
+        assert!(
+            matches!(stored(&tmp.path().join("db")), Ok(Stored::Recorded(r)) if r == Registry::default())
+        );
+    }
+
+    #[test]
+    fn ledger_keeps_the_registry_it_was_created_with() {
+        let tmp = tempfile::tempdir().unwrap();
+        let s1 = Registry::new(&[23, 29, 31, 37, 41, 43, 47, 53]).unwrap();
+        let ledger = Ledger::with_options(tmp.path(), &LedgerOptions::new().registry(s1)).unwrap();
+        ledger.anchor_batch(1, &[(41, 6)]).unwrap();
+        drop(ledger);
+
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(*ledger.registry(), s1);
+        ledger.anchor_batch(1, &[(23, 2)]).unwrap();
+        drop(ledger);
+        let reader = Ledger::open_read_only(tmp.path()).unwrap();
+        assert_eq!(*reader.registry(), s1);
+        assert_eq!(reader.current_exponent(1, 41).unwrap(), Some(6));
+        drop(reader);
+
+        let s0 = LedgerOptions::new().registry(Registry::default());
+        assert!(matches!(
+            Ledger::with_options(tmp.path(), &s0),
+            Err(LedgerError::RegistryMismatch { stored, .. }) if stored == *s1.primes()
+        ));
+        // Rebuilding replays the log with the recorded alphabet.
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.current_exponent(1, 23).unwrap(), Some(2));
     }
 }
@@ -0,0 +1,258 @@
+//! Log-shipping replication to a follower ledger
+//! A follower applies the primary's sealed segments in order, checking
+//! every event's LSN and hash link, so it holds exactly the primary's
+//! history up to the last sealed segment. The active segment is never
+//! shipped: a follower trails the primary by at most one segment.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rocksdb::WriteBatch;
+use serde::Serialize;
+
+use crate::event_log::{list_segments, segment_file_name, segment_number};
+use crate::{chain, AccessMode, Ledger, LedgerError, LedgerEvent, LedgerOptions};
+
+/// Last source segment the follower has fully applied.
+const APPLIED_SEGMENT_KEY: &[u8] = b"replication/segment";
+
+/// Where a follower finds the primary's sealed segments. Implement it over
+/// S3, a message stream or an RPC; [`DirSource`] reads a directory.
+pub trait SegmentSource: Send + Sync {
+    /// Numbers of the sealed segments available, ascending.
+    fn segments(&self) -> Result<Vec<u64>, LedgerError>;
+    /// Raw bytes of sealed segment `n`, exactly as the primary wrote them.
+    fn fetch(&self, n: u64) -> Result<Vec<u8>, LedgerError>;
+}
+
+/// Segments in a local or mounted directory: the primary's own
+/// `segments/`, a synced copy, or a bucket mounted as a filesystem.
+#[derive(Debug, Clone)]
+pub struct DirSource {
+    dir: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DirSource { dir: dir.into() }
+    }
+
+    /// The sealed segments of the primary ledger at `base_path`.
+    pub fn primary<P: AsRef<Path>>(base_path: P) -> Self {
+        DirSource::new(base_path.as_ref().join("segments"))
+    }
+}
+
+impl SegmentSource for DirSource {
+    fn segments(&self) -> Result<Vec<u64>, LedgerError> {
+        Ok(list_segments(&self.dir)?
+            .iter()
+            .filter_map(|p| segment_number(p))
+            .collect())
+    }
+
+    fn fetch(&self, n: u64) -> Result<Vec<u8>, LedgerError> {
+        fs::read(self.dir.join(segment_file_name(n))).map_err(LedgerError::from)
+    }
+}
+
+/// How far a follower trails its source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ReplicationStatus {
+    /// Next LSN the follower expects; everything below it is applied.
+    pub applied_lsn: u64,
+    pub applied_segment: u64,
+    /// Sealed segments in the source not applied yet.
+    pub pending_segments: u64,
+    /// Milliseconds between the last applied event and now; `None` before
+    /// anything has been applied.
+    pub lag_millis: Option<u64>,
+}
+
+/// A warm standby or read replica fed from a [`SegmentSource`]. Reads go
+/// through [`Follower::ledger`]; local writes are refused until
+/// [`Follower::promote`].
+pub struct Follower {
+    ledger: Ledger,
+    source: Box<dyn SegmentSource>,
+    applied_segment: AtomicU64,
+}
+
+impl Follower {
+    pub fn open<P: AsRef<Path>>(
+        base_path: P,
+        source: impl SegmentSource + 'static,
+    ) -> Result<Self, LedgerError> {
+        Follower::open_with_options(base_path, source, &LedgerOptions::default())
+    }
+
+    /// Open (or create) the follower's ledger at `base_path`. Use the
+    /// primary's options: an encrypted primary ships encrypted segments.
+    pub fn open_with_options<P: AsRef<Path>>(
+        base_path: P,
+        source: impl SegmentSource + 'static,
+        options: &LedgerOptions,
+    ) -> Result<Self, LedgerError> {
+        let mut ledger = Ledger::with_options(base_path, options)?;
+        ledger.mode = AccessMode::Follower;
+        let applied_segment = ledger.load_applied_segment()?;
+        Ok(Follower {
+            ledger,
+            source: Box::new(source),
+            applied_segment: AtomicU64::new(applied_segment),
+        })
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Apply every sealed segment the source has beyond the last one
+    /// applied. Returns the number of events applied.
+    pub fn poll(&self) -> Result<u64, LedgerError> {
+        let mut applied = 0;
+        for n in self.source.segments()? {
+            if n <= self.applied_segment.load(Ordering::SeqCst) {
+                continue;
+            }
+            let raw = self.source.fetch(n)?;
+            let events = self
+                .ledger
+                .log
+                .decode_segment(Path::new(&segment_file_name(n)), &raw)?;
+            applied += self.ledger.apply_replicated(&events, n)?;
+            self.applied_segment.fetch_max(n, Ordering::SeqCst);
+        }
+        Ok(applied)
+    }
+
+    pub fn status(&self) -> Result<ReplicationStatus, LedgerError> {
+        let applied_segment = self.applied_segment.load(Ordering::SeqCst);
+        let pending_segments = self
+            .source
+            .segments()?
+            .into_iter()
+            .filter(|&n| n > applied_segment)
+            .count() as u64;
+        let lag_millis = self
+            .ledger
+            .log
+            .last_event()?
+            .map(|evt| self.ledger.clock.now_millis().saturating_sub(evt.timestamp));
+        Ok(ReplicationStatus {
+            applied_lsn: self.ledger.next_lsn()?,
+            applied_segment,
+            pending_segments,
+            lag_millis,
+        })
+    }
+
+    /// Stop following and take writes, e.g. on failover. Poll once more
+    /// first if the old primary's last segments are still reachable.
+    pub fn promote(self) -> Ledger {
+        let mut ledger = self.ledger;
+        ledger.mode = AccessMode::Primary;
+        ledger
+    }
+}
+
+impl Ledger {
+    /// Log and apply events shipped from source segment `segment`, skipping
+    /// any already applied. Every event must carry the next LSN and link to
+    /// the current chain head.
+    fn apply_replicated(&self, events: &[LedgerEvent], segment: u64) -> Result<u64, LedgerError> {
+        let mut writer = self.write_lock.lock()?;
+        let fresh = events
+            .iter()
+            .position(|evt| evt.lsn >= writer.next_lsn)
+            .map_or(&events[..0], |i| &events[i..]);
+        let mut head = writer.chain_head.clone();
+        for (i, evt) in fresh.iter().enumerate() {
+            let expected = writer.next_lsn + i as u64;
+            if evt.lsn != expected {
+                return Err(LedgerError::Verification(format!(
+                    "segment {} has LSN {} where {} was expected",
+                    segment, evt.lsn, expected
+                )));
+            }
+            if evt.prev_hash != head || evt.event_hash != chain::compute_event_hash(evt)? {
+                return Err(LedgerError::Verification(format!(
+                    "event {} in segment {} does not extend the chain",
+                    evt.lsn, segment
+                )));
+            }
+            head = evt.event_hash.clone();
+        }
+
+        let mut batch = WriteBatch::default();
+        batch.put(APPLIED_SEGMENT_KEY, segment.to_be_bytes());
+        if fresh.is_empty() {
+            self.db.write(batch)?;
+            return Ok(0);
+        }
+        self.stage_projections(&mut batch, fresh)?;
+        self.commit(&mut writer, fresh, batch)?;
+        Ok(fresh.len() as u64)
+    }
+
+    fn load_applied_segment(&self) -> Result<u64, LedgerError> {
+        match self.db.get(APPLIED_SEGMENT_KEY)? {
+            Some(raw) => raw
+                .as_slice()
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| LedgerError::Corruption("corrupt replication/segment".to_string())),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follower_applies_sealed_segments_and_promotes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let primary_path = tmp.path().join("primary");
+        let follower_path = tmp.path().join("follower");
+        let primary = Ledger::new(&primary_path).unwrap();
+        primary.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        primary.log.seal().unwrap();
+
+        let follower = Follower::open(&follower_path, DirSource::primary(&primary_path)).unwrap();
+        assert_eq!(follower.status().unwrap().pending_segments, 1);
+        assert_eq!(follower.poll().unwrap(), 2);
+        assert!(matches!(
+            follower.ledger().anchor_batch(9, &[(2, 2)]),
+            Err(LedgerError::ReadOnly(AccessMode::Follower))
+        ));
+
+        primary.anchor_batch(3, &[(2, 4)]).unwrap();
+        primary.delete_entity(1).unwrap();
+        primary.anchor_batch(2, &[(3, 2)]).unwrap();
+        assert_eq!(follower.poll().unwrap(), 0);
+        primary.log.seal().unwrap();
+        assert_eq!(follower.poll().unwrap(), 3);
+        let status = follower.status().unwrap();
+        assert_eq!((status.applied_lsn, status.applied_segment), (5, 2));
+        assert_eq!(status.pending_segments, 0);
+        drop(follower);
+
+        let follower = Follower::open(&follower_path, DirSource::primary(&primary_path)).unwrap();
+        assert_eq!(follower.poll().unwrap(), 0);
+        let replica = follower.ledger();
+        assert_eq!(replica.verify_chain().unwrap(), 5);
+        assert_eq!(
+            replica.merkle_root().unwrap(),
+            primary.merkle_root().unwrap()
+        );
+        assert!(replica.is_deleted(1).unwrap());
+        assert_eq!(replica.current_exponents(2, &[3]).unwrap(), vec![Some(2)]);
+
+        let promoted = follower.promote();
+        promoted.anchor_batch(4, &[(2, 2)]).unwrap();
+        assert_eq!(promoted.verify_chain().unwrap(), 6);
+    }
+}
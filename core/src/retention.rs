@@ -0,0 +1,207 @@
+//! Retention for sealed log segments
+//! Expired segments are deleted or moved to an archive directory; current
+//! exponents live in RocksDB and are never touched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rocksdb::WriteBatch;
+use serde::{Deserialize, Serialize};
+
+use crate::event_log;
+use crate::{Ledger, LedgerError};
+
+const PRUNED_KEY: &[u8] = b"pruned_prefix";
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Which sealed segments to keep. The newest sealed segment and the active
+/// segment are always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    max_age_days: Option<u32>,
+    max_sealed_segments: Option<usize>,
+    archive_dir: Option<PathBuf>,
+}
+
+impl RetentionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop segments whose newest event is older than `days`.
+    pub fn max_age_days(mut self, days: u32) -> Self {
+        self.max_age_days = Some(days);
+        self
+    }
+
+    /// Keep at most `n` sealed segments (minimum 1).
+    pub fn max_sealed_segments(mut self, n: usize) -> Self {
+        self.max_sealed_segments = Some(n);
+        self
+    }
+
+    /// Move expired segments here instead of deleting them.
+    pub fn archive_to<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.archive_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.max_age_days.is_none() && self.max_sealed_segments.is_none()
+    }
+}
+
+/// Events removed from the front of the log, so LSNs and the hash chain
+/// still line up with what remains.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrunedPrefix {
+    pub events: u64,
+    /// `event_hash` of the last pruned event; empty if none were chained.
+    pub last_hash: String,
+    /// Number of the last pruned segment.
+    #[serde(default)]
+    pub last_segment: u64,
+}
+
+impl Ledger {
+    /// Apply the configured retention policy now.
+    /// Returns where each expired segment went (archive path, or its old
+    /// path if it was deleted).
+    pub fn apply_retention(&self) -> Result<Vec<PathBuf>, LedgerError> {
+        self.ensure_writable()?;
+        let _guard = self.write_lock.lock()?;
+        self.prune_segments()
+    }
+
+    /// Caller must hold the write lock.
+    pub(crate) fn prune_segments(&self) -> Result<Vec<PathBuf>, LedgerError> {
+        let policy = &self.retention;
+        if policy.is_unbounded() {
+            return Ok(Vec::new());
+        }
+        let sealed = self.log.sealed_segments()?;
+        let keep_newest = policy.max_sealed_segments.unwrap_or(usize::MAX).max(1);
+        let mut expired = sealed.len().saturating_sub(keep_newest);
+        if let Some(days) = policy.max_age_days {
+            let cutoff = self.clock.now_millis().saturating_sub(days as u64 * DAY_MS);
+            // Only the run of old segments at the front can go; never the newest.
+            for segment in &sealed[expired..sealed.len().saturating_sub(1)] {
+                let newest = self
+                    .log
+                    .read_segment(segment)?
+                    .last()
+                    .map_or(0, |e| e.timestamp);
+                if newest >= cutoff {
+                    break;
+                }
+                expired += 1;
+            }
+        }
+        // Segments still waiting for upload stay on disk.
+        let archived = self.archived_through()?;
+        let expired = sealed[..expired]
+            .iter()
+            .take_while(|p| event_log::segment_number(p).is_some_and(|n| n <= archived))
+            .count();
+        if expired == 0 {
+            return Ok(Vec::new());
+        }
+
+        self.drop_segments(&sealed[..expired], policy.archive_dir.as_deref())
+    }
+
+    /// Remove leading sealed segments and advance the pruned prefix past them.
+    /// Caller must hold the write lock.
+    pub(crate) fn drop_segments(
+        &self,
+        segments: &[PathBuf],
+        archive_dir: Option<&Path>,
+    ) -> Result<Vec<PathBuf>, LedgerError> {
+        let mut prefix = self.pruned_prefix()?;
+        let mut moved = Vec::with_capacity(segments.len());
+        for segment in segments {
+            let events = self.log.read_segment(segment)?;
+            prefix.events += events.len() as u64;
+            if let Some(last) = events.last() {
+                prefix.last_hash = last.event_hash.clone();
+            }
+            if let Some(n) = event_log::segment_number(segment) {
+                prefix.last_segment = prefix.last_segment.max(n);
+                self.log.reserve_segments_through(n);
+            }
+            moved.push(dispose(segment, archive_dir)?);
+        }
+        let mut batch = WriteBatch::default();
+        self.stage_prune_merkle(&mut batch, prefix.events)?;
+        batch.put(PRUNED_KEY, serde_json::to_vec(&prefix)?);
+        self.db.write(batch)?;
+        Ok(moved)
+    }
+
+    pub(crate) fn retention_archive(&self) -> Option<&Path> {
+        self.retention.archive_dir.as_deref()
+    }
+
+    /// What retention has removed so far (zero events if nothing).
+    pub fn pruned_prefix(&self) -> Result<PrunedPrefix, LedgerError> {
+        match self.db.get(PRUNED_KEY)? {
+            Some(raw) => serde_json::from_slice(&raw).map_err(LedgerError::from),
+            None => Ok(PrunedPrefix::default()),
+        }
+    }
+}
+
+fn dispose(segment: &Path, archive_dir: Option<&Path>) -> Result<PathBuf, LedgerError> {
+    let Some(dir) = archive_dir else {
+        fs::remove_file(segment)?;
+        return Ok(segment.to_path_buf());
+    };
+    fs::create_dir_all(dir)?;
+    let name = segment.file_name().ok_or_else(|| {
+        LedgerError::Corruption(format!("invalid segment path {}", segment.display()))
+    })?;
+    let target = dir.join(name);
+    // rename fails across filesystems; fall back to copy + delete.
+    if fs::rename(segment, &target).is_err() {
+        fs::copy(segment, &target)?;
+        fs::remove_file(segment)?;
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LedgerOptions;
+
+    #[test]
+    fn prunes_old_segments_but_keeps_chain_and_exponents() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("archive");
+        let options = LedgerOptions::new().retention(
+            RetentionPolicy::new()
+                .max_sealed_segments(1)
+                .archive_to(&archive),
+        );
+        let ledger = Ledger::with_options(tmp.path().join("ledger"), &options).unwrap();
+        for target in [2, 4, 6] {
+            ledger.anchor_batch(1, &[(2, target)]).unwrap();
+            ledger.log.seal().unwrap();
+        }
+        ledger.anchor_batch(1, &[(2, 7)]).unwrap();
+
+        let moved = ledger.apply_retention().unwrap();
+        assert_eq!(moved.len(), 2);
+        assert!(moved.iter().all(|p| p.starts_with(&archive) && p.exists()));
+        assert_eq!(ledger.log.sealed_segments().unwrap().len(), 1);
+        assert_eq!(ledger.pruned_prefix().unwrap().events, 2);
+
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(7));
+        assert_eq!(ledger.verify_chain().unwrap(), 2);
+        let root = ledger.merkle_root().unwrap();
+        let proof = ledger.prove_inclusion(3).unwrap();
+        let last = ledger.log.last_event().unwrap().unwrap();
+        assert!(crate::verify_proof(&root, &last, &proof));
+        assert!(ledger.prove_inclusion(1).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Audit trail of rotations applied to packed quaternion state
+//! Each rotation is kept unit-length, exactly as [`QpQuatOf::rotate`] used
+//! it, so the log can be composed, inverted and replayed.
+
+use nalgebra::Quaternion;
+
+use crate::qp_encode::{unit_and_norm, QpQuatOf, QuatScalar};
+
+/// Rotations in the order they were applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotationLog<T: QuatScalar> {
+    rotations: Vec<Quaternion<T>>,
+}
+
+impl<T: QuatScalar> Default for RotationLog<T> {
+    fn default() -> Self {
+        RotationLog {
+            rotations: Vec::new(),
+        }
+    }
+}
+
+impl<T: QuatScalar> RotationLog<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// [`QpQuatOf::rotate`] `qp` by `q` and record it.
+    pub fn rotate(&mut self, qp: &mut QpQuatOf<T>, q: Quaternion<T>) {
+        let (rot, _) = unit_and_norm(q);
+        qp.rotate(rot);
+        self.rotations.push(rot);
+    }
+
+    pub fn rotations(&self) -> &[Quaternion<T>] {
+        &self.rotations
+    }
+
+    pub fn len(&self) -> usize {
+        self.rotations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rotations.is_empty()
+    }
+
+    /// The single rotation equal to applying the whole log in order; the
+    /// identity when empty.
+    pub fn net(&self) -> Quaternion<T> {
+        self.rotations
+            .iter()
+            .fold(Quaternion::identity(), |acc, r| r * acc)
+    }
+
+    /// The log that undoes this one: conjugates in reverse order.
+    pub fn inverse(&self) -> Self {
+        RotationLog {
+            rotations: self.rotations.iter().rev().map(|r| r.conjugate()).collect(),
+        }
+    }
+
+    /// Apply every rotation to `qp` in order, without recording.
+    pub fn replay(&self, qp: &mut QpQuatOf<T>) {
+        for &r in &self.rotations {
+            qp.rotate(r);
+        }
+    }
+
+    /// Undo and forget the latest rotation; `None` when empty.
+    pub fn undo(&mut self, qp: &mut QpQuatOf<T>) -> Option<Quaternion<T>> {
+        let last = self.rotations.pop()?;
+        qp.rotate(last.conjugate());
+        Some(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qp_encode::QpQuat64;
+
+    fn close(a: &QpQuat64, b: &QpQuat64) -> bool {
+        (a.psi1 - b.psi1).norm() < 1e-12 && (a.psi2 - b.psi2).norm() < 1e-12
+    }
+
+    #[test]
+    fn log_composes_inverts_and_replays() {
+        let start = QpQuat64::pack(&[2, 1, -3, 4, -1, 2, -5, 6]);
+        let mut qp = start;
+        let mut log = RotationLog::new();
+        log.rotate(&mut qp, Quaternion::new(1.0, 0.5, -0.25, 0.75));
+        log.rotate(&mut qp, Quaternion::new(0.0, 2.0, 0.0, 0.0));
+        log.rotate(&mut qp, Quaternion::new(3.0, 0.0, -1.0, 1.0));
+        assert_eq!(log.len(), 3);
+        assert!((log.rotations()[1].norm() - 1.0).abs() < 1e-12);
+
+        let mut net = start;
+        net.rotate(log.net());
+        assert!(close(&net, &qp));
+        let mut replayed = start;
+        log.replay(&mut replayed);
+        assert_eq!(replayed, qp);
+
+        let mut back = qp;
+        log.inverse().replay(&mut back);
+        assert!(close(&back, &start));
+        assert_eq!(back.unpack(), start.unpack());
+
+        let mut undone = qp;
+        log.undo(&mut undone).unwrap();
+        log.undo(&mut undone).unwrap();
+        log.undo(&mut undone).unwrap();
+        assert!(log.undo(&mut undone).is_none() && log.is_empty());
+        assert!(close(&undone, &start));
+    }
+}
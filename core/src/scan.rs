@@ -0,0 +1,187 @@
+//! Prefix/range iteration over the factors and postings column families
+
+use rocksdb::{Direction, IteratorMode};
+
+use crate::keys;
+use crate::{Ledger, LedgerError};
+
+/// One decoded factors entry: (entity, prime, exponent).
+pub type FactorEntry = (u64, u32, i32);
+
+pub(crate) type RawItem = Result<(Box<[u8]>, Box<[u8]>), rocksdb::Error>;
+
+impl Ledger {
+    /// Every stored exponent, in key order.
+    pub fn iter_factors(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<FactorEntry, LedgerError>> + '_, LedgerError> {
+        let cf = self.cf("factors")?;
+        Ok(self
+            .db
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(decode_entry))
+    }
+
+    /// Exponents of a single entity, via a prefix seek.
+    /// Fails if the entity has been deleted.
+    pub fn iter_entity(
+        &self,
+        entity: u64,
+    ) -> Result<impl Iterator<Item = Result<FactorEntry, LedgerError>> + '_, LedgerError> {
+        self.ensure_live(entity)?;
+        self.iter_entity_raw(entity)
+    }
+
+    /// Up to `limit` entity ids holding at least one exponent, ascending,
+    /// starting after `cursor`. Page by passing the last id returned; each
+    /// entity costs one seek, however many primes it has.
+    pub fn list_entities(
+        &self,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<u64>, LedgerError> {
+        let cf = self.cf("factors")?;
+        let mut next = match cursor {
+            Some(id) => match id.checked_add(1) {
+                Some(next) => next,
+                None => return Ok(Vec::new()),
+            },
+            None => 0,
+        };
+        let mut entities = Vec::new();
+        let mut iter = self.db.raw_iterator_cf(cf);
+        while entities.len() < limit {
+            iter.seek(keys::entity_prefix(next));
+            let Some(key) = iter.key() else {
+                break;
+            };
+            let (entity, _) = keys::decode_factor_key(key)?;
+            entities.push(entity);
+            match entity.checked_add(1) {
+                Some(n) => next = n,
+                None => break,
+            }
+        }
+        iter.status()?;
+        Ok(entities)
+    }
+
+    /// Up to `limit` (entity, exponent) pairs holding `prime`, ascending by
+    /// entity and starting after `cursor`; read from the postings index.
+    pub fn entities_for_prime(
+        &self,
+        prime: u32,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<(u64, i32)>, LedgerError> {
+        let cf = self.cf("postings")?;
+        let Some(from) = postings_start(prime, cursor) else {
+            return Ok(Vec::new());
+        };
+        let iter = self
+            .db
+            .iterator_cf(cf, IteratorMode::From(&from, Direction::Forward));
+        postings_page(iter, prime, limit)
+    }
+
+    pub(crate) fn iter_entity_raw(
+        &self,
+        entity: u64,
+    ) -> Result<impl Iterator<Item = Result<FactorEntry, LedgerError>> + '_, LedgerError> {
+        let cf = self.cf("factors")?;
+        let prefix = keys::entity_prefix(entity);
+        let iter = self
+            .db
+            .iterator_cf(cf, IteratorMode::From(&prefix, Direction::Forward));
+        Ok(entity_entries(iter, entity))
+    }
+}
+
+/// Decoded entries of `entity` from a factors iterator positioned at its
+/// prefix.
+pub(crate) fn entity_entries(
+    iter: impl Iterator<Item = RawItem>,
+    entity: u64,
+) -> impl Iterator<Item = Result<FactorEntry, LedgerError>> {
+    let prefix = keys::entity_prefix(entity);
+    iter.take_while(move |item| match item {
+        Ok((key, _)) => key.starts_with(&prefix),
+        Err(_) => true,
+    })
+    .map(decode_entry)
+}
+
+pub(crate) fn decode_entry(item: RawItem) -> Result<FactorEntry, LedgerError> {
+    let (key, value) = item?;
+    let (entity, prime) = keys::decode_factor_key(&key)?;
+    Ok((entity, prime, keys::decode_exponent(&value)?))
+}
+
+/// First postings key of `prime` after `cursor`; `None` if nothing can follow.
+pub(crate) fn postings_start(prime: u32, cursor: Option<u64>) -> Option<[u8; keys::KEY_LEN]> {
+    let start = match cursor {
+        Some(id) => id.checked_add(1)?,
+        None => 0,
+    };
+    Some(keys::posting_key(prime, start))
+}
+
+/// Up to `limit` (entity, exponent) pairs of `prime` from a postings
+/// iterator positioned at [`postings_start`].
+pub(crate) fn postings_page(
+    iter: impl Iterator<Item = RawItem>,
+    prime: u32,
+    limit: usize,
+) -> Result<Vec<(u64, i32)>, LedgerError> {
+    let mut entities = Vec::new();
+    for item in iter {
+        if entities.len() >= limit {
+            break;
+        }
+        let (key, value) = item?;
+        let (p, entity) = keys::decode_posting_key(&key)?;
+        if p != prime {
+            break;
+        }
+        entities.push((entity, keys::decode_exponent(&value)?));
+    }
+    Ok(entities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_scan_does_not_bleed_into_longer_ids() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(12, &[(2, 4)]).unwrap();
+
+        let one = ledger
+            .iter_entity(1)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(one, vec![(1, 2, 2), (1, 5, 0)]);
+        assert_eq!(
+            ledger.current_exponents(1, &[5, 3, 2]).unwrap(),
+            vec![Some(0), None, Some(2)]
+        );
+        assert_eq!(ledger.iter_factors().unwrap().count(), 3);
+
+        ledger.anchor_batch(5, &[(2, 2)]).unwrap();
+        assert_eq!(ledger.list_entities(None, 2).unwrap(), vec![1, 5]);
+        assert_eq!(ledger.list_entities(Some(5), 10).unwrap(), vec![12]);
+        assert!(ledger.list_entities(Some(12), 10).unwrap().is_empty());
+        assert_eq!(
+            ledger.entities_for_prime(2, None, 2).unwrap(),
+            vec![(1, 2), (5, 2)]
+        );
+        assert_eq!(
+            ledger.entities_for_prime(2, Some(5), 10).unwrap(),
+            vec![(12, 4)]
+        );
+    }
+}
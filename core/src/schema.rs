@@ -0,0 +1,165 @@
+//! Schema versions of event log records
+//! 1: `LedgerEvent` JSON as is (implicit: no `v` stored)
+//! 2: MSD digits nibble-packed into `msd`
+//! Every record written carries its version in `v`; readers up-convert older
+//! records on the fly, and [`Ledger::migrate_log`] rewrites them in place.
+
+use serde_json::{Map, Value};
+
+use crate::msd::{Digit, Msd};
+use crate::{Ledger, LedgerError};
+
+pub const EVENT_SCHEMA_VERSION: u32 = 2;
+
+type Record = Map<String, Value>;
+
+pub(crate) fn parse_record(json: &str) -> Result<Record, String> {
+    match serde_json::from_str(json).map_err(|e| e.to_string())? {
+        Value::Object(record) => Ok(record),
+        _ => Err("record is not a JSON object".to_string()),
+    }
+}
+
+pub(crate) fn record_version(record: &Record) -> u32 {
+    match record.get("v").and_then(Value::as_u64) {
+        Some(v) => u32::try_from(v).unwrap_or(u32::MAX),
+        // Packed records written before versions were stamped.
+        None if record.contains_key("msd") => 2,
+        None => 1,
+    }
+}
+
+/// Rewrite `record` from version `from` to version `to`, one step at a
+/// time in either direction.
+pub(crate) fn convert(record: &mut Record, from: u32, to: u32) -> Result<(), String> {
+    check_version(from)?;
+    check_version(to)?;
+    let mut at = from;
+    while at < to {
+        upgrade(record, at)?;
+        at += 1;
+    }
+    while at > to {
+        downgrade(record, at)?;
+        at -= 1;
+    }
+    if to == 1 {
+        record.remove("v");
+    } else {
+        record.insert("v".to_string(), Value::from(to));
+    }
+    Ok(())
+}
+
+fn check_version(v: u32) -> Result<(), String> {
+    if (1..=EVENT_SCHEMA_VERSION).contains(&v) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown schema version {} (supported: 1 to {})",
+            v, EVENT_SCHEMA_VERSION
+        ))
+    }
+}
+
+/// Step from `from` to `from + 1`.
+fn upgrade(record: &mut Record, from: u32) -> Result<(), String> {
+    match from {
+        1 => {
+            let digits: Vec<Digit> = match record.remove("msd_digits") {
+                Some(raw) => serde_json::from_value(raw).map_err(|e| e.to_string())?,
+                None => Vec::new(),
+            };
+            let packed = Msd::try_from_digits(digits)
+                .map_err(|e| e.to_string())?
+                .to_bytes();
+            record.insert("msd".to_string(), Value::from(hex::encode(packed)));
+        }
+        _ => unreachable!("no upgrade from schema version {}", from),
+    }
+    Ok(())
+}
+
+/// Step from `from` to `from - 1`.
+fn downgrade(record: &mut Record, from: u32) -> Result<(), String> {
+    match from {
+        2 => {
+            let packed = record.remove("msd").unwrap_or(Value::Null);
+            let digits = packed
+                .as_str()
+                .and_then(|s| hex::decode(s).ok())
+                .and_then(|raw| Msd::from_bytes(&raw).ok())
+                .map(|msd| msd.to_vec())
+                .ok_or_else(|| format!("malformed packed MSD digits {}", packed))?;
+            record.insert("msd_digits".to_string(), Value::from(digits));
+        }
+        _ => unreachable!("no downgrade from schema version {}", from),
+    }
+    Ok(())
+}
+
+impl Ledger {
+    /// Rewrite every log record at schema version `from` to version `to`,
+    /// e.g. to upgrade historical segments or to hand the log to an older
+    /// reader. Records already at `to` are kept; any other version fails
+    /// the migration, leaving segments before it converted. New appends
+    /// always use [`EVENT_SCHEMA_VERSION`]. Event hashes do not depend on
+    /// the record form. Returns the number of records rewritten.
+    pub fn migrate_log(&self, from: u32, to: u32) -> Result<u64, LedgerError> {
+        self.ensure_writable()?;
+        check_version(from)
+            .and(check_version(to))
+            .map_err(LedgerError::InvalidArgument)?;
+        let _guard = self.write_lock.lock()?;
+        let mut migrated = 0;
+        for segment in self.log.all_segments()? {
+            migrated += self.log.rewrite_segment(&segment, |json| {
+                let mut record = parse_record(json)?;
+                match record_version(&record) {
+                    v if v == to => Ok(None),
+                    v if v == from => {
+                        convert(&mut record, from, to)?;
+                        Ok(Some(Value::Object(record).to_string()))
+                    }
+                    v => Err(format!("record is at schema version {}, not {}", v, from)),
+                }
+            })?;
+        }
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_migrates_down_and_back_up_without_breaking_the_chain() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.log.seal().unwrap();
+        ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+        let log = tmp.path().join("event.log");
+        assert!(std::fs::read_to_string(&log).unwrap().contains("\"v\":2"));
+
+        assert_eq!(ledger.migrate_log(2, 1).unwrap(), 3);
+        let legacy = std::fs::read_to_string(&log).unwrap();
+        assert!(legacy.contains("\"msd_digits\":[0,1]") && !legacy.contains("\"v\""));
+        assert_eq!(ledger.verify_chain().unwrap(), 3);
+        // Old and new records mix freely.
+        ledger.anchor_batch(3, &[(3, 2)]).unwrap();
+        assert_eq!(ledger.verify_chain().unwrap(), 4);
+
+        assert_eq!(ledger.migrate_log(1, 2).unwrap(), 3);
+        assert_eq!(
+            ledger.log.read_all().unwrap()[1].msd_digits.as_slice(),
+            [-2]
+        );
+        assert_eq!(ledger.verify_chain().unwrap(), 4);
+        assert!(matches!(
+            ledger.migrate_log(1, 3),
+            Err(LedgerError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,237 @@
+//! Entity-sharded ledger over several RocksDB instances
+//! Shard `i` is a complete ledger in `<base>/shard-<i>`. An entity always
+//! lands on the same shard, so its events, versions and exponents never
+//! span ledgers; each shard keeps its own log, hash chain and LSNs.
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use crate::scan::FactorEntry;
+use crate::{Ledger, LedgerError, LedgerEvent, LedgerOptions};
+
+/// Records the shard count, which must never change for a given layout.
+const SHARDS_FILE: &str = "shards";
+
+pub struct ShardedLedger {
+    shards: Vec<Ledger>,
+}
+
+/// Stable 64-bit mix (the splitmix64 finalizer), so sequential ids spread
+/// evenly and routing never depends on the std hasher.
+fn mix(entity: u64) -> u64 {
+    let mut z = entity.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl ShardedLedger {
+    pub fn open<P: AsRef<Path>>(base_path: P, shards: usize) -> Result<Self, LedgerError> {
+        ShardedLedger::open_with_options(base_path, shards, &LedgerOptions::default())
+    }
+
+    /// Open (or create) `shards` ledgers under `base_path`, each with
+    /// `options`. Reopening with a different count fails rather than
+    /// misrouting existing entities.
+    pub fn open_with_options<P: AsRef<Path>>(
+        base_path: P,
+        shards: usize,
+        options: &LedgerOptions,
+    ) -> Result<Self, LedgerError> {
+        let base_path = base_path.as_ref();
+        if shards == 0 {
+            return Err(LedgerError::InvalidArgument(
+                "a sharded ledger needs at least one shard".to_string(),
+            ));
+        }
+        fs::create_dir_all(base_path)?;
+        let count_file = base_path.join(SHARDS_FILE);
+        match fs::read_to_string(&count_file) {
+            Ok(text) => {
+                let stored: usize = text.trim().parse().map_err(|_| {
+                    LedgerError::Corruption(format!("malformed {}", count_file.display()))
+                })?;
+                if stored != shards {
+                    return Err(LedgerError::InvalidArgument(format!(
+                        "{} was created with {} shards, not {}",
+                        base_path.display(),
+                        stored,
+                        shards
+                    )));
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                fs::write(&count_file, format!("{}\n", shards))?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let shards = (0..shards)
+            .map(|i| Ledger::with_options(base_path.join(format!("shard-{}", i)), options))
+            .collect::<Result<_, _>>()?;
+        Ok(ShardedLedger { shards })
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard that owns `entity`.
+    pub fn shard_of(&self, entity: u64) -> usize {
+        (mix(entity) % self.shards.len() as u64) as usize
+    }
+
+    pub fn shard(&self, index: usize) -> Option<&Ledger> {
+        self.shards.get(index)
+    }
+
+    fn owner(&self, entity: u64) -> &Ledger {
+        &self.shards[self.shard_of(entity)]
+    }
+
+    pub fn anchor_batch(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.owner(entity).anchor_batch(entity, commands)
+    }
+
+    /// Anchor many batches, one thread per shard involved. Batches for the
+    /// same shard commit in the order given; results come back in input
+    /// order, and one batch failing doesn't stop the others.
+    pub fn anchor_many(
+        &self,
+        batches: &[(u64, Vec<(u32, u8)>)],
+    ) -> Vec<Result<Vec<LedgerEvent>, LedgerError>> {
+        let mut routed = vec![Vec::new(); self.shards.len()];
+        for (i, (entity, _)) in batches.iter().enumerate() {
+            routed[self.shard_of(*entity)].push(i);
+        }
+        let mut results = batches.iter().map(|_| None).collect::<Vec<_>>();
+        thread::scope(|s| {
+            let workers = routed
+                .iter()
+                .zip(&self.shards)
+                .filter(|(indices, _)| !indices.is_empty())
+                .map(|(indices, shard)| {
+                    s.spawn(move || {
+                        indices
+                            .iter()
+                            .map(|&i| {
+                                let (entity, commands) = &batches[i];
+                                (i, shard.anchor_batch(*entity, commands))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            for worker in workers {
+                let done = worker.join().unwrap_or_default();
+                for (i, result) in done {
+                    results[i] = Some(result);
+                }
+            }
+        });
+        results
+            .into_iter()
+            .map(|r| {
+                r.unwrap_or_else(|| Err(LedgerError::Internal("shard worker panicked".to_string())))
+            })
+            .collect()
+    }
+
+    pub fn current_exponents(
+        &self,
+        entity: u64,
+        primes: &[u32],
+    ) -> Result<Vec<Option<i32>>, LedgerError> {
+        self.owner(entity).current_exponents(entity, primes)
+    }
+
+    /// Exponents of one entity; fails if it has been deleted.
+    pub fn entity_factors(&self, entity: u64) -> Result<Vec<FactorEntry>, LedgerError> {
+        self.owner(entity).iter_entity(entity)?.collect()
+    }
+
+    pub fn delete_entity(&self, entity: u64) -> Result<LedgerEvent, LedgerError> {
+        self.owner(entity).delete_entity(entity)
+    }
+
+    /// [`Ledger::list_entities`] across every shard, merged into one
+    /// ascending page.
+    pub fn list_entities(
+        &self,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<u64>, LedgerError> {
+        let mut merged = Vec::new();
+        for shard in &self.shards {
+            merged.extend(shard.list_entities(cursor, limit)?);
+        }
+        merged.sort_unstable();
+        merged.truncate(limit);
+        Ok(merged)
+    }
+
+    /// [`Ledger::entities_for_prime`] across every shard, merged into one
+    /// page ascending by entity.
+    pub fn entities_for_prime(
+        &self,
+        prime: u32,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<(u64, i32)>, LedgerError> {
+        let mut merged = Vec::new();
+        for shard in &self.shards {
+            merged.extend(shard.entities_for_prime(prime, cursor, limit)?);
+        }
+        merged.sort_unstable_by_key(|&(entity, _)| entity);
+        merged.truncate(limit);
+        Ok(merged)
+    }
+
+    /// Verify every shard's hash chain; returns the total event count.
+    pub fn verify_chains(&self) -> Result<u64, LedgerError> {
+        self.shards
+            .iter()
+            .try_fold(0, |total, shard| Ok(total + shard.verify_chain()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities_route_to_one_shard_and_queries_merge() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = ShardedLedger::open(tmp.path(), 4).unwrap();
+        let batches = (0..40)
+            .map(|entity| (entity, vec![(2, 2)]))
+            .chain([(3, vec![(2, 4)]), (5, vec![(4, 0)])])
+            .collect::<Vec<_>>();
+        let results = ledger.anchor_many(&batches);
+        assert!(results[..41].iter().all(|r| r.is_ok()));
+        assert!(matches!(results[41], Err(LedgerError::UnknownPrime(4))));
+        assert!((0..4).all(|i| ledger.shard(i).unwrap().next_lsn().unwrap() > 0));
+        assert_eq!(ledger.verify_chains().unwrap(), 41);
+
+        assert_eq!(ledger.current_exponents(3, &[2]).unwrap(), vec![Some(4)]);
+        let owner = ledger.shard(ledger.shard_of(3)).unwrap();
+        assert_eq!(owner.current_exponents(3, &[2]).unwrap(), vec![Some(4)]);
+        assert_eq!(
+            ledger.list_entities(Some(9), 5).unwrap(),
+            vec![10, 11, 12, 13, 14]
+        );
+        assert_eq!(
+            ledger.entities_for_prime(2, Some(1), 3).unwrap(),
+            vec![(2, 2), (3, 4), (4, 2)]
+        );
+        drop(ledger);
+
+        assert!(ShardedLedger::open(tmp.path(), 8).is_err());
+        let reopened = ShardedLedger::open(tmp.path(), 4).unwrap();
+        assert_eq!(reopened.entity_factors(3).unwrap(), vec![(3, 2, 4)]);
+    }
+}
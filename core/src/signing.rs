@@ -0,0 +1,117 @@
+//! Ed25519 signatures over event hashes
+//! The signature covers the 32-byte `event_hash`, which itself excludes the
+//! signature field, so signing never disturbs the hash chain.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+use crate::chain::compute_event_hash;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+pub fn sign_event(evt: &mut LedgerEvent, key: &SigningKey) -> Result<(), LedgerError> {
+    let digest = hex::decode(&evt.event_hash)
+        .map_err(|e| LedgerError::Corruption(format!("event hash: {}", e)))?;
+    evt.signature = hex::encode(key.sign(&digest).to_bytes());
+    Ok(())
+}
+
+/// Standalone check: the event hash matches its content and the signature
+/// was produced by `public_key`.
+pub fn verify_event_signature(
+    evt: &LedgerEvent,
+    public_key: &VerifyingKey,
+) -> Result<(), LedgerError> {
+    if evt.signature.is_empty() {
+        return Err(LedgerError::Verification("event is unsigned".to_string()));
+    }
+    if compute_event_hash(evt)? != evt.event_hash {
+        return Err(LedgerError::Verification(
+            "event hash does not match content".to_string(),
+        ));
+    }
+    let verification = |e: String| LedgerError::Verification(e);
+    let digest = hex::decode(&evt.event_hash).map_err(|e| verification(e.to_string()))?;
+    let sig_bytes: [u8; 64] = hex::decode(&evt.signature)
+        .map_err(|e| verification(e.to_string()))?
+        .try_into()
+        .map_err(|_| verification("signature must be 64 bytes".to_string()))?;
+    public_key
+        .verify(&digest, &Signature::from_bytes(&sig_bytes))
+        .map_err(|e| verification(e.to_string()))
+}
+
+pub fn verifying_key_from_bytes(bytes: &[u8]) -> Result<VerifyingKey, LedgerError> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| LedgerError::InvalidArgument("public key must be 32 bytes".to_string()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| LedgerError::InvalidArgument(e.to_string()))
+}
+
+pub fn signing_key_from_bytes(bytes: &[u8]) -> Result<SigningKey, LedgerError> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| LedgerError::InvalidArgument("secret key must be 32 bytes".to_string()))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+impl Ledger {
+    /// Sign every subsequently committed event with `key`.
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signer = Some(key);
+    }
+
+    /// Verify the signature on every event in the log; returns how many
+    /// signed events were checked. Events from before signing was turned on
+    /// may be unsigned, but once one event is signed every later one must
+    /// be, so stripping signatures off a tail fails too.
+    pub fn verify_signatures(&self, public_key: &VerifyingKey) -> Result<u64, LedgerError> {
+        let mut checked = 0;
+        for evt in self.log.read_all()? {
+            if evt.signature.is_empty() && checked == 0 {
+                continue;
+            }
+            verify_event_signature(&evt, public_key)
+                .map_err(|e| LedgerError::Verification(format!("event {}: {}", evt.lsn, e)))?;
+            checked += 1;
+        }
+        Ok(checked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_events_verify_and_reject_other_keys() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::new(tmp.path()).unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        ledger.set_signing_key(key.clone());
+
+        let events = ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        assert!(verify_event_signature(&events[0], &key.verifying_key()).is_ok());
+        assert_eq!(ledger.verify_signatures(&key.verifying_key()).unwrap(), 1);
+        assert_eq!(ledger.verify_chain().unwrap(), 1);
+
+        let other = SigningKey::from_bytes(&[9u8; 32]);
+        assert!(verify_event_signature(&events[0], &other.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn unsigned_events_after_a_signed_one_fail() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::new(tmp.path()).unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        ledger.set_signing_key(key.clone());
+        ledger.anchor_batch(1, &[(2, 4)]).unwrap();
+        assert_eq!(ledger.verify_signatures(&key.verifying_key()).unwrap(), 1);
+
+        ledger.signer = None;
+        let events = ledger.anchor_batch(1, &[(2, 6)]).unwrap();
+        let err = ledger.verify_signatures(&key.verifying_key()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("event {}: event is unsigned", events[0].lsn)));
+    }
+}
@@ -0,0 +1,195 @@
+//! Point-in-time reads pinned to a RocksDB snapshot
+//! Every read through a [`LedgerSnapshot`] sees the ledger as of
+//! [`Ledger::snapshot`], however many batches commit in the meantime.
+
+use rocksdb::{Direction, IteratorMode, Snapshot};
+
+use crate::keys;
+use crate::lsn::NEXT_LSN_KEY;
+use crate::metadata::{self, MetadataEntry, METADATA_CF};
+use crate::scan::{self, FactorEntry};
+use crate::tombstone::tombstone_key;
+use crate::versioning;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+/// A consistent read view of a ledger; see [`Ledger::snapshot`].
+pub struct LedgerSnapshot<'a> {
+    ledger: &'a Ledger,
+    snapshot: Snapshot<'a>,
+    /// First LSN committed after the snapshot was taken; `None` if nothing
+    /// was committed since the ledger started assigning LSNs.
+    next_lsn: Option<u64>,
+}
+
+impl Ledger {
+    /// Pin the current state for multi-key reads. Batches committed while
+    /// the snapshot is held are not visible through it. RocksDB keeps the
+    /// pinned versions alive, so hold it only as long as the reads need.
+    pub fn snapshot(&self) -> Result<LedgerSnapshot<'_>, LedgerError> {
+        let snapshot = self.db.snapshot();
+        // Stored in the same batch as the events, so it matches the view.
+        let next_lsn = snapshot
+            .get(NEXT_LSN_KEY)?
+            .map(|raw| {
+                raw.as_slice()
+                    .try_into()
+                    .map(u64::from_be_bytes)
+                    .map_err(|_| LedgerError::Corruption("corrupt next_lsn".to_string()))
+            })
+            .transpose()?;
+        Ok(LedgerSnapshot {
+            ledger: self,
+            snapshot,
+            next_lsn,
+        })
+    }
+}
+
+impl LedgerSnapshot<'_> {
+    /// LSN of the latest event visible; `None` before the first.
+    pub fn last_lsn(&self) -> Option<u64> {
+        self.next_lsn.and_then(|next| next.checked_sub(1))
+    }
+
+    pub fn is_deleted(&self, entity: u64) -> Result<bool, LedgerError> {
+        Ok(self.snapshot.get(tombstone_key(entity))?.is_some())
+    }
+
+    /// See [`Ledger::entity_version`].
+    pub fn entity_version(&self, entity: u64) -> Result<u64, LedgerError> {
+        versioning::decode_version(entity, self.snapshot.get(versioning::version_key(entity))?)
+    }
+
+    /// See [`Ledger::current_exponents`].
+    pub fn current_exponents(
+        &self,
+        entity: u64,
+        primes: &[u32],
+    ) -> Result<Vec<Option<i32>>, LedgerError> {
+        let cf = self.ledger.cf("factors")?;
+        self.snapshot
+            .multi_get_cf(primes.iter().map(|&p| (cf, keys::factor_key(entity, p))))
+            .into_iter()
+            .map(|value| match value? {
+                Some(raw) => keys::decode_exponent(&raw).map(Some),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    /// See [`Ledger::get_metadata`].
+    pub fn get_metadata(&self, entity: u64) -> Result<Option<serde_json::Value>, LedgerError> {
+        let cf = self.ledger.cf(METADATA_CF)?;
+        self.snapshot
+            .get_cf(cf, entity.to_be_bytes())?
+            .map(|raw| metadata::decode_metadata(entity, &raw))
+            .transpose()
+    }
+
+    /// See [`Ledger::iter_factors`].
+    pub fn iter_factors(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<FactorEntry, LedgerError>> + '_, LedgerError> {
+        let cf = self.ledger.cf("factors")?;
+        Ok(self
+            .snapshot
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(scan::decode_entry))
+    }
+
+    /// See [`Ledger::iter_entity`]; fails if the entity had been deleted.
+    pub fn iter_entity(
+        &self,
+        entity: u64,
+    ) -> Result<impl Iterator<Item = Result<FactorEntry, LedgerError>> + '_, LedgerError> {
+        if self.is_deleted(entity)? {
+            return Err(LedgerError::EntityGone(entity));
+        }
+        let cf = self.ledger.cf("factors")?;
+        let prefix = keys::entity_prefix(entity);
+        let iter = self
+            .snapshot
+            .iterator_cf(cf, IteratorMode::From(&prefix, Direction::Forward));
+        Ok(scan::entity_entries(iter, entity))
+    }
+
+    /// See [`Ledger::entities_for_prime`].
+    pub fn entities_for_prime(
+        &self,
+        prime: u32,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<(u64, i32)>, LedgerError> {
+        let cf = self.ledger.cf("postings")?;
+        let Some(from) = scan::postings_start(prime, cursor) else {
+            return Ok(Vec::new());
+        };
+        let iter = self
+            .snapshot
+            .iterator_cf(cf, IteratorMode::From(&from, Direction::Forward));
+        scan::postings_page(iter, prime, limit)
+    }
+
+    /// Retained events up to [`LedgerSnapshot::last_lsn`], oldest first.
+    /// The log is append-only, so it is cut at the LSN rather than pinned.
+    pub fn events(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<LedgerEvent, LedgerError>>, LedgerError> {
+        let next_lsn = self.next_lsn;
+        Ok(self
+            .ledger
+            .log
+            .iter()?
+            .take_while(move |evt| match (evt, next_lsn) {
+                (Ok(evt), Some(next)) => evt.lsn < next,
+                _ => true,
+            }))
+    }
+
+    /// Every entity's metadata in entity order, for exports.
+    pub(crate) fn iter_metadata(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<MetadataEntry, LedgerError>> + '_, LedgerError> {
+        let cf = self.ledger.cf(METADATA_CF)?;
+        Ok(self
+            .snapshot
+            .iterator_cf(cf, IteratorMode::Start)
+            .map(metadata::decode_metadata_entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_reads_ignore_later_commits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+
+        let snapshot = ledger.snapshot().unwrap();
+        ledger.anchor_batch(1, &[(2, 6), (3, 2)]).unwrap();
+        ledger.anchor_batch(3, &[(2, 2)]).unwrap();
+        ledger.delete_entity(2).unwrap();
+
+        assert_eq!(snapshot.last_lsn(), Some(2));
+        assert_eq!(
+            snapshot.current_exponents(1, &[2, 3]).unwrap(),
+            vec![Some(2), None]
+        );
+        assert_eq!(snapshot.entity_version(1).unwrap(), 2);
+        assert_eq!(snapshot.iter_factors().unwrap().count(), 3);
+        assert_eq!(
+            snapshot.entities_for_prime(2, None, 10).unwrap(),
+            vec![(1, 2), (2, 4)]
+        );
+        assert!(!snapshot.is_deleted(2).unwrap());
+        assert_eq!(snapshot.iter_entity(2).unwrap().count(), 1);
+        assert_eq!(snapshot.events().unwrap().count(), 3);
+
+        assert!(ledger.snapshot().unwrap().iter_entity(2).is_err());
+        assert_eq!(ledger.snapshot().unwrap().last_lsn(), Some(6));
+    }
+}
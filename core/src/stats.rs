@@ -0,0 +1,187 @@
+//! Ledger-wide statistics from counters maintained at commit time
+//! Counters live under `stats/` in the default column family and are
+//! written in the same batch as the events they count, so reading them
+//! never scans the data.
+
+use std::collections::{BTreeMap, HashSet};
+
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+use serde::Serialize;
+
+use crate::lsn::NEXT_LSN_KEY;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+const ENTITIES_KEY: &[u8] = b"stats/entities";
+const VIA_C_KEY: &[u8] = b"stats/via_c";
+const PRIME_PREFIX: &[u8] = b"stats/prime/";
+
+fn prime_key(prime: u32) -> Vec<u8> {
+    [PRIME_PREFIX, &prime.to_be_bytes()].concat()
+}
+
+fn decode_counter(raw: &[u8]) -> Result<u64, LedgerError> {
+    raw.try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| LedgerError::Corruption("corrupt stats counter".to_string()))
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LedgerStats {
+    /// Entities with at least one event that have not been deleted.
+    pub entities: u64,
+    /// Exponent events logged per prime; tombstones are not counted.
+    pub events_per_prime: BTreeMap<u32, u64>,
+    /// Events that crossed the centroid.
+    pub via_c_crossings: u64,
+    /// Bytes of log segments on disk, sealed and active.
+    pub log_bytes: u64,
+    /// LSN of the latest committed event; `None` before the first.
+    pub last_lsn: Option<u64>,
+}
+
+/// Counter changes made by one batch.
+#[derive(Default)]
+struct StatsDelta {
+    entities: i64,
+    per_prime: BTreeMap<u32, u64>,
+    via_c: u64,
+}
+
+impl StatsDelta {
+    fn count(&mut self, evt: &LedgerEvent) {
+        if evt.tombstone {
+            self.entities -= 1;
+        } else {
+            *self.per_prime.entry(evt.prime).or_default() += 1;
+        }
+        self.via_c += u64::from(evt.via_c);
+    }
+}
+
+impl Ledger {
+    /// Current statistics. Counters come from one consistent DB snapshot;
+    /// only `log_bytes` looks at the filesystem. Events in segments pruned
+    /// before an upgrade to counters are not included.
+    pub fn stats(&self) -> Result<LedgerStats, LedgerError> {
+        let snapshot = self.db.snapshot();
+        let counter = |key: &[u8]| -> Result<u64, LedgerError> {
+            snapshot.get(key)?.map_or(Ok(0), |raw| decode_counter(&raw))
+        };
+        let mut events_per_prime = BTreeMap::new();
+        let iter = snapshot.iterator(IteratorMode::From(PRIME_PREFIX, Direction::Forward));
+        for item in iter {
+            let (key, value) = item?;
+            let Some(prime) = key.strip_prefix(PRIME_PREFIX) else {
+                break;
+            };
+            let prime = prime
+                .try_into()
+                .map(u32::from_be_bytes)
+                .map_err(|_| LedgerError::Corruption("corrupt stats key".to_string()))?;
+            events_per_prime.insert(prime, decode_counter(&value)?);
+        }
+        let mut log_bytes = 0;
+        for segment in self.log.all_segments()? {
+            log_bytes += std::fs::metadata(segment)?.len();
+        }
+        Ok(LedgerStats {
+            entities: counter(ENTITIES_KEY)?,
+            events_per_prime,
+            via_c_crossings: counter(VIA_C_KEY)?,
+            log_bytes,
+            last_lsn: counter(NEXT_LSN_KEY)?.checked_sub(1),
+        })
+    }
+
+    /// Stage the counter updates for `events`. Reads stored versions and
+    /// counters, so must run before the batch is applied.
+    pub(crate) fn stage_stats(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let mut delta = StatsDelta::default();
+        let mut seen = HashSet::new();
+        for evt in events {
+            if seen.insert(evt.entity_id) && self.entity_version(evt.entity_id)? == 0 {
+                delta.entities += 1;
+            }
+            delta.count(evt);
+        }
+        self.stage_delta(batch, &delta)
+    }
+
+    /// Recount from the stored versions and the retained log; used when
+    /// upgrading ledgers that predate the counters.
+    pub(crate) fn backfill_stats(&self) -> Result<(), LedgerError> {
+        let mut delta = StatsDelta::default();
+        for entity in self.all_versions()?.into_keys() {
+            if !self.is_deleted(entity)? {
+                delta.entities += 1;
+            }
+        }
+        for evt in self.log.iter()? {
+            let evt = evt?;
+            if evt.tombstone {
+                continue;
+            }
+            delta.count(&evt);
+        }
+        let mut batch = WriteBatch::default();
+        self.stage_delta(&mut batch, &delta)?;
+        self.db.write(batch).map_err(LedgerError::from)
+    }
+
+    fn stage_delta(&self, batch: &mut WriteBatch, delta: &StatsDelta) -> Result<(), LedgerError> {
+        let stored = |key: &[u8]| -> Result<u64, LedgerError> {
+            self.db.get(key)?.map_or(Ok(0), |raw| decode_counter(&raw))
+        };
+        if delta.entities != 0 {
+            let entities = stored(ENTITIES_KEY)?.saturating_add_signed(delta.entities);
+            batch.put(ENTITIES_KEY, entities.to_be_bytes());
+        }
+        if delta.via_c != 0 {
+            batch.put(VIA_C_KEY, (stored(VIA_C_KEY)? + delta.via_c).to_be_bytes());
+        }
+        for (&prime, &n) in &delta.per_prime {
+            let key = prime_key(prime);
+            batch.put(&key, (stored(&key)? + n).to_be_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_follow_commits_and_match_a_backfill() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.stats().unwrap().last_lsn, None);
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(2, &[(2, 4), (3, 2)]).unwrap();
+        ledger.anchor_batch(3, &[(2, 1)]).unwrap();
+        ledger.delete_entity(2).unwrap();
+
+        let stats = ledger.stats().unwrap();
+        assert_eq!(stats.entities, 2);
+        assert_eq!(
+            stats.events_per_prime,
+            BTreeMap::from([(2, 3), (3, 1), (5, 1)])
+        );
+        assert_eq!(stats.via_c_crossings, 1);
+        assert_eq!(stats.last_lsn, Some(5));
+        assert!(stats.log_bytes > 0);
+
+        for key in [ENTITIES_KEY, VIA_C_KEY] {
+            ledger.db.delete(key).unwrap();
+        }
+        for prime in [2, 3, 5] {
+            ledger.db.delete(prime_key(prime)).unwrap();
+        }
+        ledger.backfill_stats().unwrap();
+        assert_eq!(ledger.stats().unwrap(), stats);
+    }
+}
@@ -0,0 +1,197 @@
+//! Streaming anchor of command files
+//! Commands are parsed line by line from NDJSON (`{"entity": 1, "prime": 2,
+//! "target_node": 0}`) or CSV (`entity,prime,target_node`, header optional)
+//! and committed in batches of bounded size, so input of any length runs in
+//! constant memory.
+
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::{Ledger, LedgerError, StagedBatch};
+
+/// Commands per commit unless the caller picks another size.
+pub const DEFAULT_STREAM_BATCH: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandFormat {
+    Ndjson,
+    Csv,
+}
+
+impl FromStr for CommandFormat {
+    type Err = LedgerError;
+
+    fn from_str(s: &str) -> Result<Self, LedgerError> {
+        match s.to_ascii_lowercase().as_str() {
+            "ndjson" | "jsonl" => Ok(CommandFormat::Ndjson),
+            "csv" => Ok(CommandFormat::Csv),
+            _ => Err(LedgerError::InvalidArgument(format!(
+                "unknown command format {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Running totals, reported after every committed batch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamProgress {
+    /// Input lines covered by committed batches; resume after this many.
+    pub lines: u64,
+    pub commands: u64,
+    /// Events written; no-op commands produce none.
+    pub events: u64,
+    pub batches: u64,
+}
+
+#[derive(Deserialize)]
+struct CommandRow {
+    entity: u64,
+    prime: u32,
+    target_node: u8,
+}
+
+type Command = (u64, u32, u8);
+
+/// The command on line `n` (0-based), or `None` for blank lines and a CSV
+/// header.
+fn parse_line(format: CommandFormat, n: usize, line: &str) -> Result<Option<Command>, LedgerError> {
+    let line = line.trim();
+    let invalid = |e: String| LedgerError::InvalidArgument(format!("line {}: {}", n + 1, e));
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match format {
+        CommandFormat::Ndjson => {
+            let row: CommandRow = serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+            Ok(Some((row.entity, row.prime, row.target_node)))
+        }
+        CommandFormat::Csv => {
+            if n == 0 && line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return Ok(None);
+            }
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let [entity, prime, node] = fields[..] else {
+                return Err(invalid(format!(
+                    "expected 3 fields, found {}",
+                    fields.len()
+                )));
+            };
+            let field = |s: &str| invalid(format!("malformed field {:?}", s));
+            Ok(Some((
+                entity.parse().map_err(|_| field(entity))?,
+                prime.parse().map_err(|_| field(prime))?,
+                node.parse().map_err(|_| field(node))?,
+            )))
+        }
+    }
+}
+
+impl Ledger {
+    /// Anchor every command in `reader`, committing each `batch_size`
+    /// commands (which may span entities) as one batch and calling
+    /// `progress` after each commit. The first bad line or rejected command
+    /// stops the stream; batches before it stay committed and the last
+    /// progress report says how many lines they covered.
+    pub fn anchor_stream<R: BufRead>(
+        &self,
+        reader: R,
+        format: CommandFormat,
+        batch_size: usize,
+        mut progress: impl FnMut(&StreamProgress),
+    ) -> Result<StreamProgress, LedgerError> {
+        self.ensure_writable()?;
+        let batch_size = batch_size.max(1);
+        let mut done = StreamProgress::default();
+        let mut pending = Vec::with_capacity(batch_size);
+        let mut lines = 0;
+        for (n, line) in reader.lines().enumerate() {
+            if let Some(cmd) = parse_line(format, n, &line?)? {
+                pending.push(cmd);
+            }
+            lines += 1;
+            if pending.len() == batch_size {
+                self.commit_stream_batch(&pending, lines, &mut done)?;
+                progress(&done);
+                pending.clear();
+                lines = 0;
+            }
+        }
+        if lines > 0 {
+            self.commit_stream_batch(&pending, lines, &mut done)?;
+            progress(&done);
+        }
+        Ok(done)
+    }
+
+    fn commit_stream_batch(
+        &self,
+        commands: &[Command],
+        lines: u64,
+        done: &mut StreamProgress,
+    ) -> Result<(), LedgerError> {
+        let _span = span!("anchor_stream_batch", commands = commands.len());
+        let mut writer = self.write_lock.lock()?;
+        let mut staged = StagedBatch::new(self.clock.now_millis(), commands.len());
+        let mut live = HashSet::new();
+        for &(entity, prime, target_node) in commands {
+            if live.insert(entity) {
+                self.ensure_live(entity)?;
+            }
+            self.stage_command(&mut staged, &writer, entity, prime, target_node)?;
+        }
+        let (events, _) = self.commit_staged(&mut writer, staged)?;
+        done.lines += lines;
+        done.commands += commands.len() as u64;
+        done.events += events.len() as u64;
+        done.batches += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_commit_in_bounded_batches_and_stop_at_bad_input() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        let csv = "entity,prime,target_node\n1,2,2\n1,5,0\n\n2,2,4\n3,3,2\n";
+        let mut reports = Vec::new();
+        let done = ledger
+            .anchor_stream(csv.as_bytes(), CommandFormat::Csv, 2, |p| reports.push(*p))
+            .unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].lines, 3);
+        assert_eq!(
+            done,
+            StreamProgress {
+                lines: 6,
+                commands: 4,
+                events: 4,
+                batches: 2,
+            }
+        );
+        assert_eq!(ledger.current_exponents(2, &[2]).unwrap(), vec![Some(4)]);
+
+        let ndjson = concat!(
+            r#"{"entity": 4, "prime": 2, "target_node": 2}"#,
+            "\n",
+            r#"{"entity": 4, "prime": 3, "target_node": 4}"#,
+            "\n",
+        );
+        let err = ledger
+            .anchor_stream(ndjson.as_bytes(), "ndjson".parse().unwrap(), 10, |_| {})
+            .unwrap_err();
+        assert!(matches!(err, LedgerError::FlowRuleViolation { .. }));
+        assert_eq!(ledger.current_exponents(4, &[2]).unwrap(), vec![None]);
+        assert!(matches!(
+            ledger.anchor_stream("1,2\n".as_bytes(), CommandFormat::Csv, 10, |_| {}),
+            Err(LedgerError::InvalidArgument(_))
+        ));
+    }
+}
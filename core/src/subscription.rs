@@ -0,0 +1,83 @@
+//! Fan-out of committed events to in-process subscribers
+
+use std::sync::Mutex;
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::{Ledger, LedgerEvent};
+
+#[derive(Default)]
+pub struct Subscribers {
+    senders: Mutex<Vec<Sender<LedgerEvent>>>,
+}
+
+impl Subscribers {
+    pub fn add(&self) -> Receiver<LedgerEvent> {
+        let (tx, rx) = unbounded();
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.push(tx);
+        }
+        rx
+    }
+
+    /// Deliver events in commit order; subscribers whose receiver has been
+    /// dropped are pruned.
+    pub fn publish(&self, events: &[LedgerEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.retain(|tx| events.iter().all(|evt| tx.send(evt.clone()).is_ok()));
+        }
+    }
+}
+
+impl Ledger {
+    /// Receive every event committed from now on. Dropping the receiver
+    /// unsubscribes.
+    pub fn subscribe(&self) -> Receiver<LedgerEvent> {
+        self.subscribers.add()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventKind;
+
+    #[test]
+    fn subscribers_see_committed_events_in_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        let rx = ledger.subscribe();
+        let dropped = ledger.subscribe();
+        drop(dropped);
+
+        let events = ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        let received = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].event_hash, events[0].event_hash);
+        assert_eq!(received[1].event_hash, events[1].event_hash);
+        assert_eq!(ledger.subscribers.senders.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn subscribers_can_count_centroid_flips() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        let rx = ledger.subscribe();
+        // 0 → 1 and 4 → 5 cross C; 2 → 4 does not.
+        ledger.anchor_batch(1, &[(2, 1), (5, 4), (11, 5)]).unwrap();
+        ledger.delete_entity(1).unwrap();
+        let kinds = rx.try_iter().map(|e| e.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::CentroidFlip,
+                EventKind::Anchor,
+                EventKind::CentroidFlip,
+                EventKind::Tombstone
+            ]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Per-batch summaries, so callers need not re-derive them from the events
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+/// What one [`Ledger::anchor_batch_with_summary`] call committed.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// Events committed, one per command that changed an exponent.
+    pub events: u64,
+    /// Commands that left their exponent where it was.
+    pub no_ops: u64,
+    /// Events that crossed the centroid.
+    pub via_c: u64,
+    /// Net exponent change per prime across the batch.
+    pub net_delta: BTreeMap<u32, i64>,
+    /// Bytes appended to the event log.
+    pub log_bytes: u64,
+    /// Wall time of the call, validation included.
+    pub elapsed_us: u64,
+}
+
+impl BatchSummary {
+    pub(crate) fn new(
+        commands: usize,
+        events: &[LedgerEvent],
+        log_bytes: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let mut net_delta = BTreeMap::new();
+        for evt in events {
+            let delta = evt.msd_digits.to_int();
+            *net_delta.entry(evt.prime).or_default() += i64::from(delta);
+        }
+        BatchSummary {
+            events: events.len() as u64,
+            no_ops: (commands - events.len()) as u64,
+            via_c: events.iter().filter(|evt| evt.via_c).count() as u64,
+            net_delta,
+            log_bytes,
+            elapsed_us: elapsed.as_micros() as u64,
+        }
+    }
+}
+
+impl Ledger {
+    /// [`Ledger::anchor_batch`], also returning a [`BatchSummary`].
+    pub fn anchor_batch_with_summary(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+    ) -> Result<(Vec<LedgerEvent>, BatchSummary), LedgerError> {
+        self.anchor(entity, commands, None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_matches_the_committed_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2)]).unwrap();
+        let log_len = || {
+            std::fs::metadata(tmp.path().join("event.log"))
+                .unwrap()
+                .len()
+        };
+        let before = log_len();
+
+        let (events, summary) = ledger
+            .anchor_batch_with_summary(1, &[(2, 4), (2, 1), (3, 1), (5, 0)])
+            .unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(summary.events, 3);
+        assert_eq!(summary.no_ops, 1);
+        assert_eq!(summary.via_c, 1);
+        assert_eq!(summary.net_delta, BTreeMap::from([(2, -1), (5, -2)]));
+        assert_eq!(summary.log_bytes, log_len() - before);
+    }
+}
@@ -0,0 +1,115 @@
+//! Entity deletion
+//! A tombstone event is logged, every factor/posting key of the entity is
+//! removed, and later reads or writes for it report the entity as gone.
+
+use rocksdb::WriteBatch;
+
+use crate::keys;
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+const TOMBSTONE_PREFIX: &[u8] = b"tombstone/";
+
+pub(crate) fn tombstone_key(entity: u64) -> Vec<u8> {
+    [TOMBSTONE_PREFIX, &entity.to_be_bytes()].concat()
+}
+
+impl Ledger {
+    /// Delete `entity` and log a tombstone event for it.
+    pub fn delete_entity(&self, entity: u64) -> Result<LedgerEvent, LedgerError> {
+        self.ensure_writable()?;
+        let mut writer = self.write_lock.lock()?;
+        self.ensure_live(entity)?;
+
+        let ts = self.clock.now_millis();
+        let mut evt = LedgerEvent {
+            entity_id: entity,
+            centroid_digit: self.centroid_seed.digit(
+                self.clock.as_clock(),
+                entity,
+                writer.next_lsn,
+                ts,
+            ),
+            timestamp: ts,
+            tombstone: true,
+            lsn: writer.next_lsn,
+            ..Default::default()
+        };
+        self.seal(&mut evt, &writer.chain_head)?;
+
+        let mut batch = WriteBatch::default();
+        self.stage_tombstone(&mut batch, entity, &[])?;
+        let events = [evt];
+        self.commit(&mut writer, &events, batch)?;
+        let [evt] = events;
+        Ok(evt)
+    }
+
+    pub fn is_deleted(&self, entity: u64) -> Result<bool, LedgerError> {
+        self.db
+            .get(tombstone_key(entity))
+            .map(|v| v.is_some())
+            .map_err(LedgerError::from)
+    }
+
+    pub(crate) fn ensure_live(&self, entity: u64) -> Result<(), LedgerError> {
+        if self.is_deleted(entity)? {
+            return Err(LedgerError::EntityGone(entity));
+        }
+        Ok(())
+    }
+
+    /// Stage removal of every key for `entity`. `staged_primes` covers
+    /// keys only written earlier in the same batch, which a DB scan can't see.
+    pub(crate) fn stage_tombstone(
+        &self,
+        batch: &mut WriteBatch,
+        entity: u64,
+        staged_primes: &[u32],
+    ) -> Result<(), LedgerError> {
+        let factors_cf = self.cf("factors")?;
+        let postings_cf = self.cf("postings")?;
+        let mut primes = staged_primes.to_vec();
+        for entry in self.iter_entity_raw(entity)? {
+            primes.push(entry?.1);
+        }
+        primes.sort_unstable();
+        primes.dedup();
+        for prime in primes {
+            batch.delete_cf(factors_cf, keys::factor_key(entity, prime));
+            batch.delete_cf(postings_cf, keys::posting_key(prime, entity));
+        }
+        self.stage_delete_metadata(batch, entity)?;
+        self.stage_delete_crdt(batch, entity)?;
+        batch.put(tombstone_key(entity), []);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_entity_is_gone_and_survives_replay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 2), (5, 0)]).unwrap();
+        ledger.anchor_batch(2, &[(2, 4)]).unwrap();
+
+        let evt = ledger.delete_entity(1).unwrap();
+        assert!(evt.tombstone);
+        assert_eq!(ledger.verify_chain().unwrap(), 4);
+        assert!(ledger.iter_entity(1).is_err());
+        assert!(ledger.anchor_batch(1, &[(2, 4)]).is_err());
+        assert!(ledger.delete_entity(1).is_err());
+        assert_eq!(ledger.iter_factors().unwrap().count(), 1);
+
+        // Full replay applies the tombstone too.
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.iter_factors().unwrap().count(), 1);
+        ledger.compact().unwrap();
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.iter_factors().unwrap().count(), 1);
+        assert!(ledger.is_deleted(1).unwrap());
+    }
+}
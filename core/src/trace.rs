@@ -0,0 +1,17 @@
+//! Tracing spans, compiled out unless the `tracing` feature is enabled
+//! `let _span = span!("name", field = value);` keeps the span open for the
+//! rest of the scope.
+
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($($args:tt)*) => {
+        tracing::debug_span!($($args)*).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($args:tt)*) => {
+        ()
+    };
+}
@@ -0,0 +1,79 @@
+//! Atomic transfers of exponent between two entities
+//! Both legs are validated like `anchor_batch` commands and committed in one
+//! WriteBatch, so either both entities move or neither does.
+
+use crate::{Ledger, LedgerError, LedgerEvent, StagedBatch};
+
+impl Ledger {
+    /// Move `amount` of `prime`'s exponent from `from_entity` to
+    /// `to_entity`. Returns the two logged events, debit first.
+    pub fn transfer(
+        &self,
+        from_entity: u64,
+        to_entity: u64,
+        prime: u32,
+        amount: i32,
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        let _span = span!("transfer", from_entity, to_entity, prime, amount);
+        if from_entity == to_entity {
+            return Err(LedgerError::InvalidArgument(
+                "transfer needs two distinct entities".to_string(),
+            ));
+        }
+        if amount <= 0 {
+            return Err(LedgerError::InvalidArgument(format!(
+                "transfer amount must be positive, got {}",
+                amount
+            )));
+        }
+        self.ensure_writable()?;
+        self.ensure_live(from_entity)?;
+        self.ensure_live(to_entity)?;
+        let mut writer = self.write_lock.lock()?;
+        let node = self
+            .registry
+            .prime_to_node(prime)
+            .ok_or(LedgerError::UnknownPrime(prime))?;
+
+        let mut staged = StagedBatch::new(self.clock.now_millis(), 2);
+        for (entity, delta) in [(from_entity, -amount), (to_entity, amount)] {
+            let current = self.current_exponent(entity, prime)?.unwrap_or(node as i32);
+            let target = current
+                .checked_add(delta)
+                .and_then(|exp| u8::try_from(exp).ok())
+                .ok_or_else(|| {
+                    LedgerError::InvalidArgument(format!(
+                        "transfer would take entity {} prime {} outside the node range",
+                        entity, prime
+                    ))
+                })?;
+            self.stage_command(&mut staged, &writer, entity, prime, target)?;
+        }
+        self.commit_staged(&mut writer, staged)
+            .map(|(events, _)| events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_moves_both_sides_or_neither() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        ledger.anchor_batch(1, &[(2, 4)]).unwrap();
+
+        let events = ledger.transfer(1, 2, 2, 2).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(2));
+        assert_eq!(ledger.current_exponent(2, 2).unwrap(), Some(2));
+        assert_eq!(ledger.verify_chain().unwrap(), 3);
+
+        // The debit would go negative: nothing is written.
+        assert!(ledger.transfer(1, 2, 2, 3).is_err());
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(2));
+        assert_eq!(ledger.current_exponent(2, 2).unwrap(), Some(2));
+        assert_eq!(ledger.entity_version(2).unwrap(), 1);
+    }
+}
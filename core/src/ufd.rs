@@ -0,0 +1,331 @@
+//! Registries over other unique factorisation domains
+//! [`crate::Registry`] assigns rational primes to nodes; [`NodeMap`] does the
+//! same for any [`Factor`], such as [`GaussianPrime`] for ℤ[i] ledgers. A
+//! [`Factorization`] holds exponents keyed by factor and multiplies by adding
+//! them, whatever the domain.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Div, Mul};
+
+use crate::registry::{is_prime, Registry, RegistryError, NODES};
+
+/// An irreducible element of some UFD, in a canonical form so that
+/// associates compare equal.
+pub trait Factor: Copy + Eq + Hash + Debug {
+    /// Bytes in [`Factor::to_bytes`].
+    const WIDTH: usize;
+
+    /// Whether `self` is irreducible and in canonical form.
+    fn is_irreducible(&self) -> bool;
+
+    /// The field norm, `|self|²` for ℤ[i] and `self` for ℤ.
+    fn norm(&self) -> u64;
+
+    /// Big-endian, exactly [`Factor::WIDTH`] bytes.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    fn from_bytes(raw: &[u8]) -> Option<Self>;
+}
+
+impl Factor for u32 {
+    const WIDTH: usize = 4;
+
+    fn is_irreducible(&self) -> bool {
+        is_prime(*self)
+    }
+
+    fn norm(&self) -> u64 {
+        u64::from(*self)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        raw.try_into().ok().map(u32::from_be_bytes)
+    }
+}
+
+/// `a + b·i`. Canonical forms have `a > 0` and `b >= 0`; the other three
+/// associates are rotations of it by a unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GaussianPrime {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl GaussianPrime {
+    pub fn new(a: i32, b: i32) -> Self {
+        GaussianPrime { a, b }
+    }
+}
+
+impl Factor for GaussianPrime {
+    const WIDTH: usize = 8;
+
+    /// Either the norm is a rational prime, or the element is a rational
+    /// prime `p ≡ 3 (mod 4)` on the real axis.
+    fn is_irreducible(&self) -> bool {
+        if self.a <= 0 || self.b < 0 {
+            return false;
+        }
+        if self.b == 0 {
+            return self.a % 4 == 3 && is_prime(self.a as u32);
+        }
+        u32::try_from(self.norm()).is_ok_and(is_prime)
+    }
+
+    fn norm(&self) -> u64 {
+        let (a, b) = (i64::from(self.a), i64::from(self.b));
+        (a * a + b * b) as u64
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.a.to_be_bytes().to_vec();
+        out.extend_from_slice(&self.b.to_be_bytes());
+        out
+    }
+
+    fn from_bytes(raw: &[u8]) -> Option<Self> {
+        let raw: &[u8; 8] = raw.try_into().ok()?;
+        let (a, b) = raw.split_at(4);
+        Some(GaussianPrime {
+            a: i32::from_be_bytes(a.try_into().ok()?),
+            b: i32::from_be_bytes(b.try_into().ok()?),
+        })
+    }
+}
+
+/// Eight distinct irreducibles in node order, generic over the domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeMap<F: Factor> {
+    factors: [F; NODES],
+}
+
+impl<F: Factor> NodeMap<F> {
+    /// Fails unless `factors` holds exactly eight distinct irreducibles.
+    pub fn new(factors: &[F]) -> Result<Self, RegistryError> {
+        let factors: [F; NODES] = factors.try_into().map_err(|_| RegistryError::Count {
+            expected: NODES,
+            found: factors.len(),
+        })?;
+        for (node, factor) in factors.iter().enumerate() {
+            if !factor.is_irreducible() {
+                return Err(RegistryError::NotIrreducible {
+                    node: node as u8,
+                    element: format!("{:?}", factor),
+                });
+            }
+            if factors[..node].contains(factor) {
+                return Err(RegistryError::NotIrreducible {
+                    node: node as u8,
+                    element: format!("{:?} (repeated)", factor),
+                });
+            }
+        }
+        Ok(NodeMap { factors })
+    }
+
+    pub fn factors(&self) -> &[F; NODES] {
+        &self.factors
+    }
+
+    pub fn factor_to_node(&self, f: F) -> Option<u8> {
+        self.factors.iter().position(|&g| g == f).map(|n| n as u8)
+    }
+
+    pub fn node_to_factor(&self, n: u8) -> Option<F> {
+        self.factors.get(usize::from(n)).copied()
+    }
+
+    /// `(node, factor)` pairs in node order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, F)> + '_ {
+        (0..NODES as u8).zip(self.factors.iter().copied())
+    }
+
+    /// The factors' [`Factor::to_bytes`], concatenated in node order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.factors.iter().flat_map(Factor::to_bytes).collect()
+    }
+
+    pub fn from_bytes(raw: &[u8]) -> Option<Self> {
+        if raw.len() != F::WIDTH * NODES {
+            return None;
+        }
+        let factors: Vec<F> = raw
+            .chunks(F::WIDTH)
+            .map(F::from_bytes)
+            .collect::<Option<_>>()?;
+        NodeMap::new(&factors).ok()
+    }
+
+    /// Exponents of `value` by node; factors not in the map are dropped.
+    pub fn exponents(&self, value: &Factorization<F>) -> [i64; NODES] {
+        self.factors.map(|f| value.exponent(f))
+    }
+}
+
+impl From<Registry> for NodeMap<u32> {
+    fn from(registry: Registry) -> Self {
+        NodeMap {
+            factors: *registry.primes(),
+        }
+    }
+}
+
+/// A product of irreducibles with integer exponents; negative exponents
+/// make it a fraction. Units are dropped, so equality is up to associates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Factorization<F: Factor> {
+    exponents: HashMap<F, i64>,
+}
+
+impl<F: Factor> Factorization<F> {
+    pub fn one() -> Self {
+        Factorization {
+            exponents: HashMap::new(),
+        }
+    }
+
+    pub fn exponent(&self, f: F) -> i64 {
+        self.exponents.get(&f).copied().unwrap_or(0)
+    }
+
+    /// Multiply by `f^exp`.
+    pub fn mul_factor(&mut self, f: F, exp: i64) {
+        let e = self.exponents.entry(f).or_insert(0);
+        *e += exp;
+        if *e == 0 {
+            self.exponents.remove(&f);
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Factorization {
+            exponents: self.exponents.iter().map(|(&f, &e)| (f, -e)).collect(),
+        }
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.exponents.is_empty()
+    }
+
+    /// The norm of the product, or `None` for a fraction or on overflow.
+    pub fn norm(&self) -> Option<u128> {
+        self.exponents.iter().try_fold(1u128, |acc, (f, &e)| {
+            let e = u32::try_from(e).ok()?;
+            acc.checked_mul(u128::from(f.norm()).checked_pow(e)?)
+        })
+    }
+
+    /// `(factor, exponent)` pairs sorted by factor bytes, each factor
+    /// followed by its exponent as a big-endian `i64`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(Vec<u8>, i64)> = self
+            .exponents
+            .iter()
+            .map(|(f, &e)| (f.to_bytes(), e))
+            .collect();
+        entries.sort_unstable();
+        entries
+            .into_iter()
+            .flat_map(|(f, e)| f.into_iter().chain(e.to_be_bytes()))
+            .collect()
+    }
+
+    pub fn from_bytes(raw: &[u8]) -> Option<Self> {
+        let width = F::WIDTH + 8;
+        if !raw.len().is_multiple_of(width) {
+            return None;
+        }
+        let mut out = Factorization::one();
+        for entry in raw.chunks(width) {
+            let (f, e) = entry.split_at(F::WIDTH);
+            out.mul_factor(F::from_bytes(f)?, i64::from_be_bytes(e.try_into().ok()?));
+        }
+        Some(out)
+    }
+}
+
+impl<F: Factor> Mul for &Factorization<F> {
+    type Output = Factorization<F>;
+
+    fn mul(self, rhs: Self) -> Factorization<F> {
+        let mut out = self.clone();
+        for (&f, &e) in &rhs.exponents {
+            out.mul_factor(f, e);
+        }
+        out
+    }
+}
+
+impl<F: Factor> Div for &Factorization<F> {
+    type Output = Factorization<F>;
+
+    fn div(self, rhs: Self) -> Factorization<F> {
+        self * &rhs.inverse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn g(a: i32, b: i32) -> GaussianPrime {
+        GaussianPrime::new(a, b)
+    }
+
+    #[test]
+    fn gaussian_registry_round_trips_and_multiplies() {
+        assert!(g(1, 1).is_irreducible());
+        assert!(g(3, 0).is_irreducible());
+        assert!(!g(5, 0).is_irreducible()); // (2 + i)(2 − i)
+        assert!(!g(-1, 1).is_irreducible()); // an associate of 1 + i
+        assert!(!g(3, 3).is_irreducible());
+
+        let primes = [
+            g(1, 1),
+            g(2, 1),
+            g(1, 2),
+            g(3, 0),
+            g(3, 2),
+            g(2, 3),
+            g(4, 1),
+            g(7, 0),
+        ];
+        let map = NodeMap::new(&primes).unwrap();
+        assert_eq!(map.factor_to_node(g(3, 2)), Some(4));
+        assert_eq!(NodeMap::from_bytes(&map.to_bytes()), Some(map));
+        assert!(matches!(
+            NodeMap::new(&[g(1, 1); 8]),
+            Err(RegistryError::NotIrreducible { node: 1, .. })
+        ));
+
+        // (1 + i)² · (2 + i) / (2 + i) = (1 + i)², of norm 4.
+        let mut x = Factorization::one();
+        x.mul_factor(g(1, 1), 2);
+        x.mul_factor(g(2, 1), 1);
+        let mut y = Factorization::one();
+        y.mul_factor(g(2, 1), 1);
+        let q = &x / &y;
+        assert_eq!(q.norm(), Some(4));
+        assert_eq!(map.exponents(&q), [2, 0, 0, 0, 0, 0, 0, 0]);
+        assert!((&q / &q).is_one());
+        assert_eq!(y.inverse().norm(), None);
+        assert_eq!(Factorization::from_bytes(&x.to_bytes()), Some(x));
+    }
+
+    #[test]
+    fn rational_registry_is_a_node_map() {
+        let map = NodeMap::from(Registry::default());
+        assert_eq!(map.node_to_factor(3), Some(7));
+        let mut x = Factorization::one();
+        x.mul_factor(3u32, 2);
+        x.mul_factor(5, 1);
+        assert_eq!(x.norm(), Some(45));
+        assert_eq!(map.exponents(&x)[1..3], [2, 1]);
+    }
+}
@@ -0,0 +1,150 @@
+//! Per-entity versions for optimistic concurrency
+//! An entity's version is the number of events logged for it, so replay
+//! reconstructs it exactly.
+
+use std::collections::{BTreeMap, HashMap};
+
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+
+use crate::{Ledger, LedgerError, LedgerEvent};
+
+const VERSION_PREFIX: &[u8] = b"version/";
+
+pub(crate) fn version_key(entity: u64) -> Vec<u8> {
+    [VERSION_PREFIX, &entity.to_be_bytes()].concat()
+}
+
+pub(crate) fn decode_version(entity: u64, raw: Option<Vec<u8>>) -> Result<u64, LedgerError> {
+    match raw {
+        Some(raw) => raw
+            .as_slice()
+            .try_into()
+            .map(u64::from_be_bytes)
+            .map_err(|_| LedgerError::Corruption(format!("corrupt version for entity {}", entity))),
+        None => Ok(0),
+    }
+}
+
+impl Ledger {
+    /// Current version of `entity`; 0 if nothing was ever logged for it.
+    pub fn entity_version(&self, entity: u64) -> Result<u64, LedgerError> {
+        decode_version(entity, self.db.get(version_key(entity))?)
+    }
+
+    /// Compare-and-set form of [`Ledger::anchor_batch`]: fails without
+    /// writing anything unless the entity is still at `expected_version`.
+    pub fn anchor_batch_if(
+        &self,
+        entity: u64,
+        commands: &[(u32, u8)],
+        expected_version: u64,
+    ) -> Result<Vec<LedgerEvent>, LedgerError> {
+        self.anchor(entity, commands, Some(expected_version), None)
+            .map(|(events, _)| events)
+    }
+
+    pub(crate) fn check_version(
+        &self,
+        entity: u64,
+        expected: Option<u64>,
+    ) -> Result<(), LedgerError> {
+        let Some(expected) = expected else {
+            return Ok(());
+        };
+        let found = self.entity_version(entity)?;
+        if found != expected {
+            return Err(LedgerError::VersionConflict {
+                entity,
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    /// Stage version bumps for `events`, on top of what is stored.
+    pub(crate) fn stage_versions(
+        &self,
+        batch: &mut WriteBatch,
+        events: &[LedgerEvent],
+    ) -> Result<(), LedgerError> {
+        let mut counts: HashMap<u64, u64> = HashMap::new();
+        for evt in events {
+            *counts.entry(evt.entity_id).or_default() += 1;
+        }
+        for (entity, n) in counts {
+            let version = self.entity_version(entity)? + n;
+            batch.put(version_key(entity), version.to_be_bytes());
+        }
+        Ok(())
+    }
+
+    /// Every stored entity version, for snapshots.
+    pub(crate) fn all_versions(&self) -> Result<BTreeMap<u64, u64>, LedgerError> {
+        let mut versions = BTreeMap::new();
+        let iter = self
+            .db
+            .iterator(IteratorMode::From(VERSION_PREFIX, Direction::Forward));
+        for item in iter {
+            let (key, value) = item?;
+            let Some(id) = key.strip_prefix(VERSION_PREFIX) else {
+                break;
+            };
+            let entity = id
+                .try_into()
+                .map(u64::from_be_bytes)
+                .map_err(|_| LedgerError::Corruption("corrupt version key".to_string()))?;
+            let version = value[..].try_into().map(u64::from_be_bytes).map_err(|_| {
+                LedgerError::Corruption(format!("corrupt version for entity {}", entity))
+            })?;
+            versions.insert(entity, version);
+        }
+        Ok(versions)
+    }
+
+    /// Replace every stored version with `versions`.
+    pub(crate) fn stage_reset_versions(
+        &self,
+        batch: &mut WriteBatch,
+        versions: &BTreeMap<u64, u64>,
+    ) -> Result<(), LedgerError> {
+        for entity in self.all_versions()?.keys() {
+            batch.delete(version_key(*entity));
+        }
+        for (entity, version) in versions {
+            batch.put(version_key(*entity), version.to_be_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_expected_version_conflicts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ledger = Ledger::new(tmp.path()).unwrap();
+        assert_eq!(ledger.entity_version(1).unwrap(), 0);
+        ledger.anchor_batch_if(1, &[(2, 2), (5, 0)], 0).unwrap();
+        assert_eq!(ledger.entity_version(1).unwrap(), 2);
+
+        let err = ledger.anchor_batch_if(1, &[(2, 4)], 0).unwrap_err();
+        assert!(matches!(
+            err,
+            LedgerError::VersionConflict {
+                expected: 0,
+                found: 2,
+                ..
+            }
+        ));
+        assert_eq!(ledger.current_exponent(1, 2).unwrap(), Some(2));
+
+        ledger.anchor_batch_if(1, &[(2, 4)], 2).unwrap();
+        ledger.compact().unwrap();
+        ledger.anchor_batch(1, &[(2, 6)]).unwrap();
+        ledger.rebuild_projections().unwrap();
+        assert_eq!(ledger.entity_version(1).unwrap(), 4);
+    }
+}
@@ -0,0 +1,254 @@
+//! `dsctl`: operator commands for a ledger directory
+//! Read-only commands open the ledger beside a running server; the others
+//! need the writer lock, so stop the server first.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use ledger::{ExportData, ExportFormat, Ledger};
+use serde_json::json;
+
+#[derive(Parser)]
+#[command(name = "dsctl", about = "Inspect and maintain a dual-substrate ledger")]
+struct Cli {
+    /// Ledger directory (the one holding `db/` and the event log).
+    #[arg(long, env = "LEDGER_PATH", default_value = "ledger")]
+    ledger: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print an entity's exponents, version, centroid and metadata.
+    Inspect { entity: u64 },
+    /// Verify the hash chain of the whole log.
+    ValidateLog,
+    /// Compare the factors/postings projections with a replay of the log.
+    Fsck,
+    /// Rebuild the factors/postings projections from snapshot plus log.
+    Replay,
+    /// Rewrite log records from one event schema version to another.
+    MigrateLog {
+        #[arg(long)]
+        from: u32,
+        #[arg(long, default_value_t = ledger::EVENT_SCHEMA_VERSION)]
+        to: u32,
+    },
+    /// Write factors, events or metadata as CSV, JSONL or Parquet.
+    Export {
+        #[arg(long, default_value = "factors")]
+        data: ExportData,
+        #[arg(long, default_value = "jsonl")]
+        format: ExportFormat,
+        /// Output file; stdout when omitted.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Fold the log into a new state snapshot.
+    Compact,
+    /// Write a consistent backup into a new directory.
+    Backup { dir: PathBuf },
+    /// Print log position, retention, registry, ledger and RocksDB statistics.
+    Stats,
+    /// Print recorded flow-rule denials, one JSON object per line.
+    Denials {
+        /// Start after this denial id.
+        #[arg(long)]
+        after: Option<u64>,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// Time quaternion pack, unpack and rotate on this host; needs no ledger.
+    Bench {
+        #[arg(long, default_value_t = 100_000)]
+        iterations: u64,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli, &mut io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("dsctl: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli, out: &mut (dyn Write + Send)) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Inspect { entity } => {
+            let ledger = Ledger::open_read_only(&cli.ledger)?;
+            let report = if ledger.is_deleted(entity)? {
+                json!({ "entity": entity, "deleted": true })
+            } else {
+                let factors = ledger
+                    .iter_entity(entity)?
+                    .map(|entry| {
+                        entry.map(|(_, prime, exp)| json!({ "prime": prime, "exponent": exp }))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                json!({
+                    "entity": entity,
+                    "deleted": false,
+                    "version": ledger.entity_version(entity)?,
+                    "centroid": ledger.centroid(entity)?,
+                    "factors": factors,
+                    "metadata": ledger.get_metadata(entity)?,
+                })
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+        }
+        Command::ValidateLog => {
+            let ledger = Ledger::open_read_only(&cli.ledger)?;
+            let events = ledger.verify_chain()?;
+            writeln!(
+                out,
+                "ok: {} events, merkle root {}",
+                events,
+                ledger.merkle_root()?
+            )?;
+        }
+        Command::Fsck => {
+            let ledger = Ledger::open_read_only(&cli.ledger)?;
+            let report = ledger.fsck()?;
+            writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+            if !report.is_clean() {
+                return Err(format!(
+                    "{} divergent keys; run `dsctl replay` to rebuild",
+                    report.divergences.len()
+                )
+                .into());
+            }
+        }
+        Command::Replay => {
+            let ledger = Ledger::new(&cli.ledger)?;
+            ledger.rebuild_projections()?;
+            writeln!(
+                out,
+                "rebuilt projections through LSN {}",
+                ledger.next_lsn()?
+            )?;
+        }
+        Command::MigrateLog { from, to } => {
+            let ledger = Ledger::new(&cli.ledger)?;
+            let migrated = ledger.migrate_log(from, to)?;
+            writeln!(
+                out,
+                "migrated {} records from schema {} to {}",
+                migrated, from, to
+            )?;
+        }
+        Command::Export {
+            data,
+            format,
+            out: path,
+        } => {
+            let ledger = Ledger::open_read_only(&cli.ledger)?;
+            let rows = match path {
+                Some(path) => ledger.export(data, format, BufWriter::new(File::create(path)?))?,
+                None => ledger.export(data, format, &mut *out)?,
+            };
+            eprintln!("exported {} rows", rows);
+        }
+        Command::Compact => {
+            let ledger = Ledger::new(&cli.ledger)?;
+            let snapshot = ledger.compact()?;
+            writeln!(
+                out,
+                "snapshot at LSN {} with {} exponents",
+                snapshot.lsn,
+                snapshot.exponents.len()
+            )?;
+        }
+        Command::Backup { dir } => {
+            let ledger = Ledger::new(&cli.ledger)?;
+            let manifest = ledger.create_checkpoint(&dir)?;
+            writeln!(
+                out,
+                "backed up {} segments to {}",
+                manifest.segments.len(),
+                dir.display()
+            )?;
+        }
+        Command::Stats => {
+            let ledger = Ledger::open_read_only(&cli.ledger)?;
+            let stats = json!({
+                "next_lsn": ledger.next_lsn()?,
+                "primes": ledger.registry().primes(),
+                "ledger": ledger.stats()?,
+                "pruned": ledger.pruned_prefix()?,
+                "snapshot_lsn": ledger.latest_snapshot()?.map(|s| s.lsn),
+                "rocksdb": ledger.metrics()?.rocksdb,
+            });
+            writeln!(out, "{}", serde_json::to_string_pretty(&stats)?)?;
+        }
+        Command::Denials { after, limit } => {
+            let ledger = Ledger::open_read_only(&cli.ledger)?;
+            for denial in ledger.denials(after, limit)? {
+                writeln!(out, "{}", serde_json::to_string(&denial)?)?;
+            }
+        }
+        Command::Bench { iterations } => {
+            let report = ledger::bench(iterations);
+            writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dsctl(ledger: &std::path::Path, args: &[&str]) -> String {
+        let mut argv = vec!["dsctl", "--ledger", ledger.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        let mut out = Vec::new();
+        run(Cli::parse_from(argv), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn commands_run_against_a_ledger_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("ledger");
+        Ledger::new(&path)
+            .unwrap()
+            .anchor_batch(1, &[(2, 2), (5, 0)])
+            .unwrap();
+
+        let inspect: serde_json::Value =
+            serde_json::from_str(&dsctl(&path, &["inspect", "1"])).unwrap();
+        assert_eq!(inspect["version"], 2);
+        assert_eq!(inspect["factors"][0], json!({ "prime": 2, "exponent": 2 }));
+        assert!(dsctl(&path, &["validate-log"]).starts_with("ok: 2 events"));
+        let fsck: serde_json::Value = serde_json::from_str(&dsctl(&path, &["fsck"])).unwrap();
+        assert_eq!(fsck["divergences"], json!([]));
+        assert_eq!(
+            dsctl(&path, &["export", "--format", "csv"]),
+            "entity_id,prime,exponent\n1,2,2\n1,5,0\n"
+        );
+        assert!(dsctl(&path, &["compact"]).starts_with("snapshot at LSN 2"));
+        dsctl(&path, &["replay"]);
+        let stats: serde_json::Value = serde_json::from_str(&dsctl(&path, &["stats"])).unwrap();
+        assert_eq!(stats["next_lsn"], 2);
+        assert_eq!(stats["snapshot_lsn"], 2);
+        assert_eq!(stats["primes"], json!(ledger::S0_PRIMES));
+        assert!(dsctl(&path, &["denials"]).is_empty());
+        dsctl(
+            &path,
+            &["backup", tmp.path().join("backup").to_str().unwrap()],
+        );
+        assert!(tmp.path().join("backup/db").exists());
+        let bench: serde_json::Value =
+            serde_json::from_str(&dsctl(&path, &["bench", "--iterations", "100"])).unwrap();
+        assert_eq!(bench["iterations"], 100);
+    }
+}
@@ -0,0 +1,12 @@
+//! Compile the ledger service protos with protox, so building needs no
+//! system `protoc`.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let proto = "../proto/dualsubstrate/v1/ledger_core.proto";
+    println!("cargo:rerun-if-changed={}", proto);
+    let descriptors = protox::compile([proto], ["../proto"])?;
+    tonic_build::configure()
+        .build_client(false)
+        .compile_fds(descriptors)?;
+    Ok(())
+}
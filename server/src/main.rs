@@ -0,0 +1,39 @@
+//! gRPC server over the core ledger; the HTTP gateway forwards to it.
+//! Usage: dualsubstrate-server [LEDGER_PATH] [ADDR]; both may also come
+//! from the environment variables of the same names.
+
+use std::env;
+use std::error::Error;
+use std::net::SocketAddr;
+
+use ledger::AsyncLedger;
+use tonic::transport::Server;
+
+mod service;
+
+/// The gateway's default `UPSTREAM_GRPC` points here.
+const DEFAULT_ADDR: &str = "0.0.0.0:50051";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
+        .or_else(|| env::var("LEDGER_PATH").ok())
+        .unwrap_or_else(|| "ledger".to_string());
+    let addr: SocketAddr = args
+        .next()
+        .or_else(|| env::var("ADDR").ok())
+        .unwrap_or_else(|| DEFAULT_ADDR.to_string())
+        .parse()?;
+
+    let ledger = AsyncLedger::open(&path).await?;
+    eprintln!("serving ledger {} on {}", path, addr);
+    Server::builder()
+        .add_service(service::server(ledger))
+        .serve_with_shutdown(addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
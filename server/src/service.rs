@@ -0,0 +1,326 @@
+//! `LedgerService` RPCs mapped onto `AsyncLedger`
+//! Writes go through the blocking pool; factor and postings reads are
+//! cheap prefix seeks and run inline.
+
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use ledger::{AsyncLedger, EventKind, LedgerError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+pub mod pb {
+    tonic::include_proto!("dualsubstrate.v1");
+}
+
+use pb::ledger_service_server::{LedgerService, LedgerServiceServer};
+
+/// `EntitiesForPrime` page size when the request leaves `limit` at 0.
+const DEFAULT_PAGE: usize = 1_000;
+const MAX_PAGE: usize = 10_000;
+/// Events buffered per watcher before the stream applies backpressure.
+const WATCH_BUFFER: usize = 1_024;
+/// How often an idle watcher checks whether its client has gone.
+const WATCH_POLL: Duration = Duration::from_millis(500);
+
+pub struct LedgerGrpc {
+    ledger: AsyncLedger,
+}
+
+pub fn server(ledger: AsyncLedger) -> LedgerServiceServer<LedgerGrpc> {
+    LedgerServiceServer::new(LedgerGrpc { ledger })
+}
+
+fn status(e: LedgerError) -> Status {
+    let message = e.to_string();
+    match e {
+        LedgerError::FlowRuleViolation { .. }
+        | LedgerError::UnknownPrime(_)
+        | LedgerError::InvalidNode(_)
+        | LedgerError::InvalidArgument(_) => Status::invalid_argument(message),
+        LedgerError::ExponentOutOfBounds { .. } => Status::out_of_range(message),
+        LedgerError::VersionConflict { .. } => Status::aborted(message),
+        LedgerError::EntityGone(_) => Status::not_found(message),
+        LedgerError::ReadOnly(_) | LedgerError::RegistryMismatch { .. } => {
+            Status::failed_precondition(message)
+        }
+        LedgerError::AlreadyLocked { .. } => Status::unavailable(message),
+        LedgerError::Corruption(_) | LedgerError::Verification(_) => Status::data_loss(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// Commands of an `AnchorBatchRequest`, rejecting nodes outside 0..=7.
+#[allow(clippy::result_large_err)]
+fn batch_commands(request: &pb::AnchorBatchRequest) -> Result<Vec<(u32, u8)>, Status> {
+    request
+        .commands
+        .iter()
+        .map(|cmd| match u8::try_from(cmd.target_node) {
+            Ok(node) if node <= 7 => Ok((cmd.prime, node)),
+            _ => Err(Status::invalid_argument(format!(
+                "Invalid node {}",
+                cmd.target_node
+            ))),
+        })
+        .collect()
+}
+
+fn to_proto(evt: ledger::LedgerEvent) -> pb::LedgerEvent {
+    let kind = match evt.kind() {
+        EventKind::Anchor => pb::ledger_event::Kind::Anchor,
+        EventKind::CentroidFlip => pb::ledger_event::Kind::CentroidFlip,
+        EventKind::Tombstone => pb::ledger_event::Kind::Tombstone,
+    };
+    pb::LedgerEvent {
+        entity: evt.entity_id,
+        prime: evt.prime,
+        msd_digits: evt
+            .msd_digits
+            .as_slice()
+            .iter()
+            .map(|&d| i32::from(d))
+            .collect(),
+        via_c: evt.via_c,
+        centroid_digit: evt.centroid_digit.into(),
+        timestamp: evt.timestamp,
+        prev_hash: evt.prev_hash,
+        event_hash: evt.event_hash,
+        signature: evt.signature,
+        tombstone: evt.tombstone,
+        lsn: evt.lsn,
+        kind: kind.into(),
+    }
+}
+
+#[tonic::async_trait]
+impl LedgerService for LedgerGrpc {
+    async fn anchor_batch(
+        &self,
+        request: Request<pb::AnchorBatchRequest>,
+    ) -> Result<Response<pb::AnchorBatchResponse>, Status> {
+        let request = request.into_inner();
+        let events = self
+            .ledger
+            .anchor_batch(request.entity, batch_commands(&request)?)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(pb::AnchorBatchResponse {
+            events: events.into_iter().map(to_proto).collect(),
+        }))
+    }
+
+    async fn validate_batch(
+        &self,
+        request: Request<pb::AnchorBatchRequest>,
+    ) -> Result<Response<pb::AnchorBatchResponse>, Status> {
+        let request = request.into_inner();
+        let events = self
+            .ledger
+            .validate_batch(request.entity, batch_commands(&request)?)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(pb::AnchorBatchResponse {
+            events: events.into_iter().map(to_proto).collect(),
+        }))
+    }
+
+    async fn get_factors(
+        &self,
+        request: Request<pb::GetFactorsRequest>,
+    ) -> Result<Response<pb::GetFactorsResponse>, Status> {
+        let entity = request.into_inner().entity;
+        let factors = self
+            .ledger
+            .ledger()
+            .iter_entity(entity)
+            .map_err(status)?
+            .map(|entry| entry.map(|(_, prime, exponent)| pb::Factor { prime, exponent }))
+            .collect::<Result<_, _>>()
+            .map_err(status)?;
+        Ok(Response::new(pb::GetFactorsResponse { factors }))
+    }
+
+    async fn entities_for_prime(
+        &self,
+        request: Request<pb::EntitiesForPrimeRequest>,
+    ) -> Result<Response<pb::EntitiesForPrimeResponse>, Status> {
+        let request = request.into_inner();
+        let limit = match request.limit as usize {
+            0 => DEFAULT_PAGE,
+            n => n.min(MAX_PAGE),
+        };
+        let entities = self
+            .ledger
+            .ledger()
+            .entities_for_prime(request.prime, request.cursor, limit)
+            .map_err(status)?
+            .into_iter()
+            .map(|(entity, exponent)| pb::EntityExponent { entity, exponent })
+            .collect();
+        Ok(Response::new(pb::EntitiesForPrimeResponse { entities }))
+    }
+
+    type WatchEventsStream = ReceiverStream<Result<pb::LedgerEvent, Status>>;
+
+    async fn watch_events(
+        &self,
+        request: Request<pb::WatchEventsRequest>,
+    ) -> Result<Response<Self::WatchEventsStream>, Status> {
+        let from_lsn = request.into_inner().from_lsn;
+        // Subscribe before replaying so nothing committed in between is lost.
+        let live = self.ledger.subscribe();
+        let ledger = self.ledger.clone();
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+        tokio::task::spawn_blocking(move || forward_events(&ledger, from_lsn, live, tx));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Feed a watcher: retained events from `from_lsn` first, then live ones
+/// not already replayed. Returns once the client goes away.
+fn forward_events(
+    ledger: &AsyncLedger,
+    from_lsn: Option<u64>,
+    live: Receiver<ledger::LedgerEvent>,
+    tx: mpsc::Sender<Result<pb::LedgerEvent, Status>>,
+) {
+    let mut next_lsn = 0;
+    if let Some(lsn) = from_lsn {
+        let replay = match ledger.ledger().events_since(lsn) {
+            Ok(replay) => replay,
+            Err(e) => {
+                let _ = tx.blocking_send(Err(status(e)));
+                return;
+            }
+        };
+        for evt in replay {
+            let item = match evt {
+                Ok(evt) => {
+                    next_lsn = evt.lsn + 1;
+                    Ok(to_proto(evt))
+                }
+                Err(e) => Err(status(e)),
+            };
+            let failed = item.is_err();
+            if tx.blocking_send(item).is_err() || failed {
+                return;
+            }
+        }
+    }
+    while !tx.is_closed() {
+        match live.recv_timeout(WATCH_POLL) {
+            Ok(evt) if evt.lsn < next_lsn => {}
+            Ok(evt) => {
+                if tx.blocking_send(Ok(to_proto(evt))).is_err() {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rpcs_round_trip_through_the_ledger() {
+        let tmp = tempfile::tempdir().unwrap();
+        let service = LedgerGrpc {
+            ledger: AsyncLedger::open(tmp.path()).await.unwrap(),
+        };
+        let anchor = |entity, commands: Vec<(u32, u32)>| pb::AnchorBatchRequest {
+            entity,
+            commands: commands
+                .into_iter()
+                .map(|(prime, target_node)| pb::AnchorCommand { prime, target_node })
+                .collect(),
+        };
+        let preview = service
+            .validate_batch(Request::new(anchor(1, vec![(2, 2), (5, 0)])))
+            .await
+            .unwrap()
+            .into_inner();
+        let first = service
+            .anchor_batch(Request::new(anchor(1, vec![(2, 2), (5, 0)])))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(first.events.len(), 2);
+        // Timestamps, and so hashes, follow the wall clock.
+        let shape = |events: &[pb::LedgerEvent]| {
+            events
+                .iter()
+                .map(|e| (e.lsn, e.prime, e.msd_digits.clone(), e.via_c))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shape(&preview.events), shape(&first.events));
+        for evt in &first.events {
+            let flip = evt.kind() == pb::ledger_event::Kind::CentroidFlip;
+            assert_eq!(flip, evt.via_c);
+        }
+        let err = service
+            .anchor_batch(Request::new(anchor(1, vec![(4, 0)])))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let mut watch = service
+            .watch_events(Request::new(pb::WatchEventsRequest { from_lsn: Some(1) }))
+            .await
+            .unwrap()
+            .into_inner();
+        service
+            .anchor_batch(Request::new(anchor(7, vec![(2, 4)])))
+            .await
+            .unwrap();
+        let lsns = [
+            watch.next().await.unwrap().unwrap(),
+            watch.next().await.unwrap().unwrap(),
+        ]
+        .map(|evt| evt.lsn);
+        assert_eq!(lsns, [1, 2]);
+
+        let factors = service
+            .get_factors(Request::new(pb::GetFactorsRequest { entity: 1 }))
+            .await
+            .unwrap()
+            .into_inner()
+            .factors;
+        assert_eq!(
+            factors,
+            vec![
+                pb::Factor {
+                    prime: 2,
+                    exponent: 2
+                },
+                pb::Factor {
+                    prime: 5,
+                    exponent: 0
+                },
+            ]
+        );
+        let holders = service
+            .entities_for_prime(Request::new(pb::EntitiesForPrimeRequest {
+                prime: 2,
+                cursor: Some(1),
+                limit: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .entities;
+        assert_eq!(
+            holders,
+            vec![pb::EntityExponent {
+                entity: 7,
+                exponent: 4
+            }]
+        );
+    }
+}